@@ -1,6 +1,18 @@
 complete -c orthanc -n "__fish_use_subcommand" -s s -l server -d 'Orthanc server address' -r
 complete -c orthanc -n "__fish_use_subcommand" -s u -l username -d 'Orthanc username' -r
 complete -c orthanc -n "__fish_use_subcommand" -s p -l password -d 'Orthanc password' -r
+complete -c orthanc -n "__fish_use_subcommand" -l token -d 'Orthanc bearer token, sent instead of the username and password' -r
+complete -c orthanc -n "__fish_use_subcommand" -s H -l header -d 'Custom HTTP header to send with every request. Can be used multiple times. Example: X-Api-Key:secret' -r
+complete -c orthanc -n "__fish_use_subcommand" -l profile -d 'Configuration profile to take the server address and the credentials from' -r
+complete -c orthanc -n "__fish_use_subcommand" -l ca-cert -d 'PEM-encoded CA certificate to verify the server certificate with' -r
+complete -c orthanc -n "__fish_use_subcommand" -l client-cert -d 'PEM-encoded client certificate (for mutual TLS)' -r
+complete -c orthanc -n "__fish_use_subcommand" -l client-key -d 'PEM-encoded private key of the client certificate' -r
+complete -c orthanc -n "__fish_use_subcommand" -l client-pkcs12 -d 'PKCS#12 archive with the client certificate and its private key, instead of --client-cert and --client-key' -r
+complete -c orthanc -n "__fish_use_subcommand" -l client-pkcs12-password -d 'Password of the PKCS#12 archive' -r
+complete -c orthanc -n "__fish_use_subcommand" -l timeout -d 'Request timeout in seconds [default: 600]' -r
+complete -c orthanc -n "__fish_use_subcommand" -l proxy -d 'Proxy URL for all requests' -r
+complete -c orthanc -n "__fish_use_subcommand" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_use_subcommand" -l insecure -d 'Don\'t verify the server certificate'
 complete -c orthanc -n "__fish_use_subcommand" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_use_subcommand" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_use_subcommand" -f -a "patient" -d 'Patient-level commands'
@@ -8,7 +20,14 @@ complete -c orthanc -n "__fish_use_subcommand" -f -a "study" -d 'Study-level com
 complete -c orthanc -n "__fish_use_subcommand" -f -a "series" -d 'Series-level commands'
 complete -c orthanc -n "__fish_use_subcommand" -f -a "instance" -d 'Instance-level commands'
 complete -c orthanc -n "__fish_use_subcommand" -f -a "modality" -d 'Modality-level commands'
+complete -c orthanc -n "__fish_use_subcommand" -f -a "tree" -d 'Show the hierarchy of patients, studies, series and instances, either of the whole server or of a single patient, study or series'
+complete -c orthanc -n "__fish_use_subcommand" -f -a "delete" -d 'Delete many patients, studies, series or instances at once: either those matching a search query, or those with the IDs listed in a file'
+complete -c orthanc -n "__fish_use_subcommand" -f -a "download" -d 'Download many patients, studies or series at once, in parallel: either those matching a search query, or those with the IDs listed in a file'
+complete -c orthanc -n "__fish_use_subcommand" -f -a "peer" -d 'Peer-level commands'
+complete -c orthanc -n "__fish_use_subcommand" -f -a "job" -d 'Job-level commands'
+complete -c orthanc -n "__fish_use_subcommand" -f -a "config" -d 'Configuration profiles'
 complete -c orthanc -n "__fish_use_subcommand" -f -a "help" -d 'Prints this message or the help of the given subcommand(s)'
+complete -c orthanc -n "__fish_seen_subcommand_from patient" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
 complete -c orthanc -n "__fish_seen_subcommand_from patient" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from patient" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from patient" -f -a "list" -d 'List all patients'
@@ -19,43 +38,74 @@ complete -c orthanc -n "__fish_seen_subcommand_from patient" -f -a "anonymize" -
 complete -c orthanc -n "__fish_seen_subcommand_from patient" -f -a "modify" -d 'Modify patient'
 complete -c orthanc -n "__fish_seen_subcommand_from patient" -f -a "download" -d 'Download patient'
 complete -c orthanc -n "__fish_seen_subcommand_from patient" -f -a "delete" -d 'Delete patient'
-complete -c orthanc -n "__fish_seen_subcommand_from list" -s c -l columns -d 'Display only the columns specified. Space-separated values. Example: ID PatientName' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s c -l columns -d 'Display only the columns specified. Space-separated values. Any DICOM tag (keyword or gggg,eeee) can be used as a column. Example: ID PatientName' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -l limit -d 'Display at most this number of entries' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -l offset -d 'Skip this number of entries' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -l sort-by -d 'Sort by a column, in ascending order, or in descending order when followed by :desc. Example: StudyDate:desc' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
 complete -c orthanc -n "__fish_seen_subcommand_from list" -s n -l no-header -d 'Don\'t display table header'
 complete -c orthanc -n "__fish_seen_subcommand_from list" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from list" -s V -l version -d 'Prints version information'
-complete -c orthanc -n "__fish_seen_subcommand_from show" -d 'Patient ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from show" -d 'Patient ID, or - to read IDs from the standard input' -r
+complete -c orthanc -n "__fish_seen_subcommand_from show" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
 complete -c orthanc -n "__fish_seen_subcommand_from show" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from show" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from list-studies" -d 'Patient ID' -r
-complete -c orthanc -n "__fish_seen_subcommand_from list-studies" -s c -l columns -d 'Display only the columns specified. Space-separated values. Example: ID AccessionNumber StudyDate' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list-studies" -s c -l columns -d 'Display only the columns specified. Space-separated values. Any DICOM tag (keyword or gggg,eeee) can be used as a column. Example: ID AccessionNumber StudyDate' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list-studies" -l limit -d 'Display at most this number of entries' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list-studies" -l offset -d 'Skip this number of entries' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list-studies" -l sort-by -d 'Sort by a column, in ascending order, or in descending order when followed by :desc. Example: StudyDate:desc' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list-studies" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
 complete -c orthanc -n "__fish_seen_subcommand_from list-studies" -s n -l no-header -d 'Don\'t display table header'
 complete -c orthanc -n "__fish_seen_subcommand_from list-studies" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from list-studies" -s V -l version -d 'Prints version information'
-complete -c orthanc -n "__fish_seen_subcommand_from search" -s q -l query -d 'Search query terms. Space-separted pairs TagName=TagValue. Wildcards are allowed. Example: PatientSex=F PatientName=*Sanchez*' -r
-complete -c orthanc -n "__fish_seen_subcommand_from search" -s c -l columns -d 'Display only the columns specified. Space-separated values. Example: ID PatientName' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -s q -l query -d 'Search query terms. Space-separated pairs TagName=TagValue. Wildcards are allowed, dates and times can be ranges (e.g. StudyDate=20200101-20201231), a repeated tag matches any of its values. Example: PatientSex=F PatientName=*Sanchez*' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -s l -l level -d 'Level of the entities to list. The query is matched at the level of the command, and either the descendants or the ancestors of the matching entities are listed' -r -f -a "patient study series instance"
+complete -c orthanc -n "__fish_seen_subcommand_from search" -s c -l columns -d 'Display only the columns specified. Space-separated values. Any DICOM tag (keyword or gggg,eeee) can be used as a column. Example: ID PatientName' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l limit -d 'Display at most this number of entries' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l offset -d 'Skip this number of entries' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l sort-by -d 'Sort by a column, in ascending order, or in descending order when followed by :desc. Example: StudyDate:desc' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l case-sensitive -d 'Match person names (e.g. PatientName) case-sensitively'
 complete -c orthanc -n "__fish_seen_subcommand_from search" -s n -l no-header -d 'Don\'t display table header'
 complete -c orthanc -n "__fish_seen_subcommand_from search" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from search" -s V -l version -d 'Prints version information'
-complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -d 'Patient ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -d 'Patient ID, or - to read IDs from the standard input' -r
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s r -l replace -d 'DICOM tags that should be replaced with the values specified. Space-separted pairs TagName=TagValue. Example: PatientName=REMOVED AccessionNumber=42' -r
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s k -l keep -d 'DICOM tags that should be kept intact. Space-separated tag names. Example: PatientSex PatientBirthDate' -r
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s P -l anonymization-profile -d 'Anonymization profiles to apply on top of the basic profile of DICOM PS3.15. retain-dates, retain-device-identity and retain-uids keep the corresponding tags, clean-descriptions replaces free-text descriptions with CLEANED' -r -f -a "basic retain-dates retain-device-identity retain-uids clean-descriptions"
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -l dicom-version -d 'Version of DICOM PS3.15 to anonymize according to. Defaults to the latest version, that Orthanc supports' -r -f -a "2008 2017c 2021b"
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s c -l config -d 'Anonymization configuration file' -r
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -l mapping-file -d 'Append the original and the new PatientID, PatientName, StudyInstanceUID and AccessionNumber of every anonymized entity, with a timestamp, to this CSV file' -r
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -l pseudonym-salt-file -d 'File with the secret salt to derive deterministic pseudonyms of PatientID and PatientName from, so that a patient always gets the same pseudonym. The salt can also be set with ORC_PSEUDONYM_SALT' -r
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s p -l keep-private-tags -d 'Keep private tags intact'
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -l print-config -d 'Print the anonymization configuration, that would be sent to Orthanc, instead of anonymizing'
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -l async -d 'Run as an Orthanc job and print the job ID instead of waiting for the result'
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s V -l version -d 'Prints version information'
-complete -c orthanc -n "__fish_seen_subcommand_from modify" -d 'Patient ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -d 'Patient ID, or - to read IDs from the standard input' -r
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s r -l replace -d 'DICOM tags that should be replaced with the values specified. Space-separted pairs TagName=TagValue. Example: PatientName=REMOVED AccessionNumber=42' -r
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s m -l remove -d 'DICOM tags that should be removed. Space-separated tag names. Example: PatientSex PatientBirthDate' -r
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s c -l config -d 'Modification configuration file' -r
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -l async -d 'Run as an Orthanc job and print the job ID instead of waiting for the result'
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s V -l version -d 'Prints version information'
-complete -c orthanc -n "__fish_seen_subcommand_from download" -d 'Patient ID' -r
-complete -c orthanc -n "__fish_seen_subcommand_from download" -s o -l output -d 'Output file path' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -d 'Patient ID, or - to read IDs from the standard input' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s o -l output -d 'Output file path (- for the standard output), or directory if the IDs are read from the standard input' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s x -l extract -d 'Extract the archive into a directory, as it is downloaded' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s t -l path-template -d 'Path of the extracted files, relative to the directory, where {Tag} is replaced with the value of a DICOM tag. Example: {PatientID}/{StudyDate}_{StudyDescription}/{SeriesNumber}/{InstanceNumber}.dcm' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s m -l media -d 'Download DICOM media with a DICOMDIR'
 complete -c orthanc -n "__fish_seen_subcommand_from download" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from download" -s V -l version -d 'Prints version information'
-complete -c orthanc -n "__fish_seen_subcommand_from delete" -d 'Patient ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -d 'Patient ID, or - to read IDs from the standard input' -r
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -s y -l yes -d 'Confirm the deletion of the IDs read from the standard input. Required with -, as the deletion can not be confirmed interactively'
 complete -c orthanc -n "__fish_seen_subcommand_from delete" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from delete" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from study" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
 complete -c orthanc -n "__fish_seen_subcommand_from study" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from study" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from study" -f -a "list" -d 'List all studies'
@@ -66,43 +116,74 @@ complete -c orthanc -n "__fish_seen_subcommand_from study" -f -a "anonymize" -d
 complete -c orthanc -n "__fish_seen_subcommand_from study" -f -a "modify" -d 'Modify study'
 complete -c orthanc -n "__fish_seen_subcommand_from study" -f -a "download" -d 'Download study'
 complete -c orthanc -n "__fish_seen_subcommand_from study" -f -a "delete" -d 'Delete study'
-complete -c orthanc -n "__fish_seen_subcommand_from list" -s c -l columns -d 'Display only the columns specified. Space-separated values. Example: ID AccessionNumber StudyDate' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s c -l columns -d 'Display only the columns specified. Space-separated values. Any DICOM tag (keyword or gggg,eeee) can be used as a column. Example: ID AccessionNumber StudyDate' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -l limit -d 'Display at most this number of entries' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -l offset -d 'Skip this number of entries' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -l sort-by -d 'Sort by a column, in ascending order, or in descending order when followed by :desc. Example: StudyDate:desc' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
 complete -c orthanc -n "__fish_seen_subcommand_from list" -s n -l no-header -d 'Don\'t display table header'
 complete -c orthanc -n "__fish_seen_subcommand_from list" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from list" -s V -l version -d 'Prints version information'
-complete -c orthanc -n "__fish_seen_subcommand_from show" -d 'Study ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from show" -d 'Study ID, or - to read IDs from the standard input' -r
+complete -c orthanc -n "__fish_seen_subcommand_from show" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
 complete -c orthanc -n "__fish_seen_subcommand_from show" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from show" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from list-series" -d 'Study ID' -r
-complete -c orthanc -n "__fish_seen_subcommand_from list-series" -s c -l columns -d 'Display only the columns specified. Space-separated values. Example: ID Modality BodyPartExamined' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list-series" -s c -l columns -d 'Display only the columns specified. Space-separated values. Any DICOM tag (keyword or gggg,eeee) can be used as a column. Example: ID Modality BodyPartExamined' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list-series" -l limit -d 'Display at most this number of entries' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list-series" -l offset -d 'Skip this number of entries' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list-series" -l sort-by -d 'Sort by a column, in ascending order, or in descending order when followed by :desc. Example: StudyDate:desc' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list-series" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
 complete -c orthanc -n "__fish_seen_subcommand_from list-series" -s n -l no-header -d 'Don\'t display table header'
 complete -c orthanc -n "__fish_seen_subcommand_from list-series" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from list-series" -s V -l version -d 'Prints version information'
-complete -c orthanc -n "__fish_seen_subcommand_from search" -s q -l query -d 'Search query terms. Space-separted pairs TagName=TagValue. Wildcards are allowed. Example: StudyDescription=*BRAIN* StudyDate=20200101' -r
-complete -c orthanc -n "__fish_seen_subcommand_from search" -s c -l columns -d 'Display only the columns specified. Space-separated values. Example: ID AccessionNumber StudyDate' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -s q -l query -d 'Search query terms. Space-separated pairs TagName=TagValue. Wildcards are allowed, dates and times can be ranges (e.g. StudyDate=20200101-20201231), a repeated tag matches any of its values. Example: StudyDescription=*BRAIN* StudyDate=20200101' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -s l -l level -d 'Level of the entities to list. The query is matched at the level of the command, and either the descendants or the ancestors of the matching entities are listed' -r -f -a "patient study series instance"
+complete -c orthanc -n "__fish_seen_subcommand_from search" -s c -l columns -d 'Display only the columns specified. Space-separated values. Any DICOM tag (keyword or gggg,eeee) can be used as a column. Example: ID AccessionNumber StudyDate' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l limit -d 'Display at most this number of entries' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l offset -d 'Skip this number of entries' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l sort-by -d 'Sort by a column, in ascending order, or in descending order when followed by :desc. Example: StudyDate:desc' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l case-sensitive -d 'Match person names (e.g. PatientName) case-sensitively'
 complete -c orthanc -n "__fish_seen_subcommand_from search" -s n -l no-header -d 'Don\'t display table header'
 complete -c orthanc -n "__fish_seen_subcommand_from search" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from search" -s V -l version -d 'Prints version information'
-complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -d 'Study ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -d 'Study ID, or - to read IDs from the standard input' -r
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s r -l replace -d 'DICOM tags that should be replaced with the values specified. Space-separted pairs TagName=TagValue. Example: PatientName=REMOVED AccessionNumber=42' -r
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s k -l keep -d 'DICOM tags that should be kept intact. Space-separated tag names. Example: PatientSex PatientBirthDate' -r
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s P -l anonymization-profile -d 'Anonymization profiles to apply on top of the basic profile of DICOM PS3.15. retain-dates, retain-device-identity and retain-uids keep the corresponding tags, clean-descriptions replaces free-text descriptions with CLEANED' -r -f -a "basic retain-dates retain-device-identity retain-uids clean-descriptions"
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -l dicom-version -d 'Version of DICOM PS3.15 to anonymize according to. Defaults to the latest version, that Orthanc supports' -r -f -a "2008 2017c 2021b"
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s c -l config -d 'Anonymization configuration file' -r
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -l mapping-file -d 'Append the original and the new PatientID, PatientName, StudyInstanceUID and AccessionNumber of every anonymized entity, with a timestamp, to this CSV file' -r
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -l pseudonym-salt-file -d 'File with the secret salt to derive deterministic pseudonyms of PatientID and PatientName from, so that a patient always gets the same pseudonym. The salt can also be set with ORC_PSEUDONYM_SALT' -r
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s p -l keep-private-tags -d 'Keep private tags intact'
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -l print-config -d 'Print the anonymization configuration, that would be sent to Orthanc, instead of anonymizing'
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -l async -d 'Run as an Orthanc job and print the job ID instead of waiting for the result'
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s V -l version -d 'Prints version information'
-complete -c orthanc -n "__fish_seen_subcommand_from modify" -d 'Study ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -d 'Study ID, or - to read IDs from the standard input' -r
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s r -l replace -d 'DICOM tags that should be replaced with the values specified. Space-separted pairs TagName=TagValue. Example: PatientName=REMOVED AccessionNumber=42' -r
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s m -l remove -d 'DICOM tags that should be removed. Space-separated tag names. Example: PatientSex PatientBirthDate' -r
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s c -l config -d 'Modification configuration file' -r
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -l async -d 'Run as an Orthanc job and print the job ID instead of waiting for the result'
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s V -l version -d 'Prints version information'
-complete -c orthanc -n "__fish_seen_subcommand_from download" -s o -l output -d 'Output file path' -r
-complete -c orthanc -n "__fish_seen_subcommand_from download" -d 'Study ID'
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s o -l output -d 'Output file path (- for the standard output), or directory if the IDs are read from the standard input' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s x -l extract -d 'Extract the archive into a directory, as it is downloaded' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s t -l path-template -d 'Path of the extracted files, relative to the directory, where {Tag} is replaced with the value of a DICOM tag. Example: {PatientID}/{StudyDate}_{StudyDescription}/{SeriesNumber}/{InstanceNumber}.dcm' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from download" -d 'Study ID, or - to read IDs from the standard input'
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s m -l media -d 'Download DICOM media with a DICOMDIR'
 complete -c orthanc -n "__fish_seen_subcommand_from download" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from download" -s V -l version -d 'Prints version information'
-complete -c orthanc -n "__fish_seen_subcommand_from delete" -d 'Study ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -d 'Study ID, or - to read IDs from the standard input' -r
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -s y -l yes -d 'Confirm the deletion of the IDs read from the standard input. Required with -, as the deletion can not be confirmed interactively'
 complete -c orthanc -n "__fish_seen_subcommand_from delete" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from delete" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from series" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
 complete -c orthanc -n "__fish_seen_subcommand_from series" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from series" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from series" -f -a "list" -d 'List all series'
@@ -113,43 +194,74 @@ complete -c orthanc -n "__fish_seen_subcommand_from series" -f -a "anonymize" -d
 complete -c orthanc -n "__fish_seen_subcommand_from series" -f -a "modify" -d 'Modify series'
 complete -c orthanc -n "__fish_seen_subcommand_from series" -f -a "download" -d 'Download series'
 complete -c orthanc -n "__fish_seen_subcommand_from series" -f -a "delete" -d 'Delete series'
-complete -c orthanc -n "__fish_seen_subcommand_from list" -s c -l columns -d 'Display only the columns specified. Space-separated values. Example: ID Modality BodyPartExamined' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s c -l columns -d 'Display only the columns specified. Space-separated values. Any DICOM tag (keyword or gggg,eeee) can be used as a column. Example: ID Modality BodyPartExamined' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -l limit -d 'Display at most this number of entries' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -l offset -d 'Skip this number of entries' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -l sort-by -d 'Sort by a column, in ascending order, or in descending order when followed by :desc. Example: StudyDate:desc' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
 complete -c orthanc -n "__fish_seen_subcommand_from list" -s n -l no-header -d 'Don\'t display table header'
 complete -c orthanc -n "__fish_seen_subcommand_from list" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from list" -s V -l version -d 'Prints version information'
-complete -c orthanc -n "__fish_seen_subcommand_from show" -d 'Series ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from show" -d 'Series ID, or - to read IDs from the standard input' -r
+complete -c orthanc -n "__fish_seen_subcommand_from show" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
 complete -c orthanc -n "__fish_seen_subcommand_from show" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from show" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from list-instances" -d 'Series ID' -r
-complete -c orthanc -n "__fish_seen_subcommand_from list-instances" -s c -l columns -d 'Display only the columns specified. Space-separated values. Example: ID InstanceCreationDate' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list-instances" -s c -l columns -d 'Display only the columns specified. Space-separated values. Any DICOM tag (keyword or gggg,eeee) can be used as a column. Example: ID InstanceCreationDate' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list-instances" -l limit -d 'Display at most this number of entries' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list-instances" -l offset -d 'Skip this number of entries' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list-instances" -l sort-by -d 'Sort by a column, in ascending order, or in descending order when followed by :desc. Example: StudyDate:desc' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list-instances" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
 complete -c orthanc -n "__fish_seen_subcommand_from list-instances" -s n -l no-header -d 'Don\'t display table header'
 complete -c orthanc -n "__fish_seen_subcommand_from list-instances" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from list-instances" -s V -l version -d 'Prints version information'
-complete -c orthanc -n "__fish_seen_subcommand_from search" -s q -l query -d 'Search query terms. Space-separted pairs TagName=TagValue. Wildcards are allowed. Example: SeriesDescription=*BRAIN* SeriesDate=20200101' -r
-complete -c orthanc -n "__fish_seen_subcommand_from search" -s c -l columns -d 'Display only the columns specified. Space-separated values. Example: ID Modality BodyPartExamined' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -s q -l query -d 'Search query terms. Space-separated pairs TagName=TagValue. Wildcards are allowed, dates and times can be ranges (e.g. StudyDate=20200101-20201231), a repeated tag matches any of its values. Example: SeriesDescription=*BRAIN* SeriesDate=20200101' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -s l -l level -d 'Level of the entities to list. The query is matched at the level of the command, and either the descendants or the ancestors of the matching entities are listed' -r -f -a "patient study series instance"
+complete -c orthanc -n "__fish_seen_subcommand_from search" -s c -l columns -d 'Display only the columns specified. Space-separated values. Any DICOM tag (keyword or gggg,eeee) can be used as a column. Example: ID Modality BodyPartExamined' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l limit -d 'Display at most this number of entries' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l offset -d 'Skip this number of entries' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l sort-by -d 'Sort by a column, in ascending order, or in descending order when followed by :desc. Example: StudyDate:desc' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l case-sensitive -d 'Match person names (e.g. PatientName) case-sensitively'
 complete -c orthanc -n "__fish_seen_subcommand_from search" -s n -l no-header -d 'Don\'t display table header'
 complete -c orthanc -n "__fish_seen_subcommand_from search" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from search" -s V -l version -d 'Prints version information'
-complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -d 'Series ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -d 'Series ID, or - to read IDs from the standard input' -r
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s r -l replace -d 'DICOM tags that should be replaced with the values specified. Space-separted pairs TagName=TagValue. Example: PatientName=REMOVED AccessionNumber=42' -r
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s k -l keep -d 'DICOM tags that should be kept intact. Space-separated tag names. Example: PatientSex PatientBirthDate' -r
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s P -l anonymization-profile -d 'Anonymization profiles to apply on top of the basic profile of DICOM PS3.15. retain-dates, retain-device-identity and retain-uids keep the corresponding tags, clean-descriptions replaces free-text descriptions with CLEANED' -r -f -a "basic retain-dates retain-device-identity retain-uids clean-descriptions"
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -l dicom-version -d 'Version of DICOM PS3.15 to anonymize according to. Defaults to the latest version, that Orthanc supports' -r -f -a "2008 2017c 2021b"
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s c -l config -d 'Anonymization configuration file' -r
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -l mapping-file -d 'Append the original and the new PatientID, PatientName, StudyInstanceUID and AccessionNumber of every anonymized entity, with a timestamp, to this CSV file' -r
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -l pseudonym-salt-file -d 'File with the secret salt to derive deterministic pseudonyms of PatientID and PatientName from, so that a patient always gets the same pseudonym. The salt can also be set with ORC_PSEUDONYM_SALT' -r
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s p -l keep-private-tags -d 'Keep private tags intact'
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -l print-config -d 'Print the anonymization configuration, that would be sent to Orthanc, instead of anonymizing'
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -l async -d 'Run as an Orthanc job and print the job ID instead of waiting for the result'
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s V -l version -d 'Prints version information'
-complete -c orthanc -n "__fish_seen_subcommand_from modify" -d 'Series ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -d 'Series ID, or - to read IDs from the standard input' -r
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s r -l replace -d 'DICOM tags that should be replaced with the values specified. Space-separted pairs TagName=TagValue. Example: PatientName=REMOVED AccessionNumber=42' -r
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s m -l remove -d 'DICOM tags that should be removed. Space-separated tag names. Example: PatientSex PatientBirthDate' -r
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s c -l config -d 'Modification configuration file' -r
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -l async -d 'Run as an Orthanc job and print the job ID instead of waiting for the result'
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s V -l version -d 'Prints version information'
-complete -c orthanc -n "__fish_seen_subcommand_from download" -d 'Series ID' -r
-complete -c orthanc -n "__fish_seen_subcommand_from download" -s o -l output -d 'Output file path' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -d 'Series ID, or - to read IDs from the standard input' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s o -l output -d 'Output file path (- for the standard output), or directory if the IDs are read from the standard input' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s x -l extract -d 'Extract the archive into a directory, as it is downloaded' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s t -l path-template -d 'Path of the extracted files, relative to the directory, where {Tag} is replaced with the value of a DICOM tag. Example: {PatientID}/{StudyDate}_{StudyDescription}/{SeriesNumber}/{InstanceNumber}.dcm' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s m -l media -d 'Download DICOM media with a DICOMDIR'
 complete -c orthanc -n "__fish_seen_subcommand_from download" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from download" -s V -l version -d 'Prints version information'
-complete -c orthanc -n "__fish_seen_subcommand_from delete" -d 'Series ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -d 'Series ID, or - to read IDs from the standard input' -r
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -s y -l yes -d 'Confirm the deletion of the IDs read from the standard input. Required with -, as the deletion can not be confirmed interactively'
 complete -c orthanc -n "__fish_seen_subcommand_from delete" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from delete" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from instance" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
 complete -c orthanc -n "__fish_seen_subcommand_from instance" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from instance" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from instance" -f -a "list" -d 'List all instances'
@@ -160,80 +272,319 @@ complete -c orthanc -n "__fish_seen_subcommand_from instance" -f -a "anonymize"
 complete -c orthanc -n "__fish_seen_subcommand_from instance" -f -a "modify" -d 'Modify instance'
 complete -c orthanc -n "__fish_seen_subcommand_from instance" -f -a "download" -d 'Download instance'
 complete -c orthanc -n "__fish_seen_subcommand_from instance" -f -a "delete" -d 'Delete instance'
-complete -c orthanc -n "__fish_seen_subcommand_from list" -s c -l columns -d 'Display only the columns specified. Space-separated values. Example: ID InstanceCreationDate' -r
+complete -c orthanc -n "__fish_seen_subcommand_from instance" -f -a "upload" -d 'Upload DICOM files, directories or ZIP archives'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s c -l columns -d 'Display only the columns specified. Space-separated values. Any DICOM tag (keyword or gggg,eeee) can be used as a column. Example: ID InstanceCreationDate' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -l limit -d 'Display at most this number of entries' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -l offset -d 'Skip this number of entries' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -l sort-by -d 'Sort by a column, in ascending order, or in descending order when followed by :desc. Example: StudyDate:desc' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
 complete -c orthanc -n "__fish_seen_subcommand_from list" -s n -l no-header -d 'Don\'t display table header'
 complete -c orthanc -n "__fish_seen_subcommand_from list" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from list" -s V -l version -d 'Prints version information'
-complete -c orthanc -n "__fish_seen_subcommand_from show" -d 'Instance ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from show" -d 'Instance ID, or - to read IDs from the standard input' -r
+complete -c orthanc -n "__fish_seen_subcommand_from show" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
 complete -c orthanc -n "__fish_seen_subcommand_from show" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from show" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from tags" -d 'Instance ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from tags" -s g -l group -d 'Show only the tags of the groups specified. Space-separated values. Example: 0010 0020' -r
+complete -c orthanc -n "__fish_seen_subcommand_from tags" -s t -l tag -d 'Show only the tags specified, either by keyword or as gggg,eeee. Space-separated values. Example: PatientName 0020,000d' -r
+complete -c orthanc -n "__fish_seen_subcommand_from tags" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from tags" -s m -l meta -d 'Include the file meta information (group 0002)'
 complete -c orthanc -n "__fish_seen_subcommand_from tags" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from tags" -s V -l version -d 'Prints version information'
-complete -c orthanc -n "__fish_seen_subcommand_from search" -s q -l query -d 'Search query terms. Space-separted pairs TagName=TagValue. Wildcards are allowed. Example: InstanceNumber=42 InstanceCreationTime=174242' -r
-complete -c orthanc -n "__fish_seen_subcommand_from search" -s c -l columns -d 'Display only the columns specified. Space-separated values. Example: ID InstanceCreationDate' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -s q -l query -d 'Search query terms. Space-separated pairs TagName=TagValue. Wildcards are allowed, dates and times can be ranges (e.g. StudyDate=20200101-20201231), a repeated tag matches any of its values. Example: InstanceNumber=42 InstanceCreationTime=174242' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -s l -l level -d 'Level of the entities to list. The query is matched at the level of the command, and either the descendants or the ancestors of the matching entities are listed' -r -f -a "patient study series instance"
+complete -c orthanc -n "__fish_seen_subcommand_from search" -s c -l columns -d 'Display only the columns specified. Space-separated values. Any DICOM tag (keyword or gggg,eeee) can be used as a column. Example: ID InstanceCreationDate' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l limit -d 'Display at most this number of entries' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l offset -d 'Skip this number of entries' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l sort-by -d 'Sort by a column, in ascending order, or in descending order when followed by :desc. Example: StudyDate:desc' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l case-sensitive -d 'Match person names (e.g. PatientName) case-sensitively'
 complete -c orthanc -n "__fish_seen_subcommand_from search" -s n -l no-header -d 'Don\'t display table header'
 complete -c orthanc -n "__fish_seen_subcommand_from search" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from search" -s V -l version -d 'Prints version information'
-complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -d 'Instance ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -d 'Instance ID, or - to read IDs from the standard input' -r
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s r -l replace -d 'DICOM tags that should be replaced with the values specified. Space-separted pairs TagName=TagValue. Example: PatientName=REMOVED AccessionNumber=42' -r
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s k -l keep -d 'DICOM tags that should be kept intact. Space-separated tag names. Example: PatientSex PatientBirthDate' -r
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s P -l anonymization-profile -d 'Anonymization profiles to apply on top of the basic profile of DICOM PS3.15. retain-dates, retain-device-identity and retain-uids keep the corresponding tags, clean-descriptions replaces free-text descriptions with CLEANED' -r -f -a "basic retain-dates retain-device-identity retain-uids clean-descriptions"
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -l dicom-version -d 'Version of DICOM PS3.15 to anonymize according to. Defaults to the latest version, that Orthanc supports' -r -f -a "2008 2017c 2021b"
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s c -l config -d 'Anonymization configuration file' -r
-complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s o -l output -d 'Output file path' -r
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s o -l output -d 'Output file path (- for the standard output), or directory if the IDs are read from the standard input' -r
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s p -l keep-private-tags -d 'Keep private tags intact'
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -l print-config -d 'Print the anonymization configuration, that would be sent to Orthanc, instead of anonymizing'
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s V -l version -d 'Prints version information'
-complete -c orthanc -n "__fish_seen_subcommand_from modify" -d 'Instance ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -d 'Instance ID, or - to read IDs from the standard input' -r
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s r -l replace -d 'DICOM tags that should be replaced with the values specified. Space-separted pairs TagName=TagValue. Example: PatientName=REMOVED AccessionNumber=42' -r
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s m -l remove -d 'DICOM tags that should be removed. Space-separated tag names. Example: PatientSex PatientBirthDate' -r
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s c -l config -d 'Modification configuration file' -r
-complete -c orthanc -n "__fish_seen_subcommand_from modify" -s o -l output -d 'Output file path' -r
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -s o -l output -d 'Output file path (- for the standard output), or directory if the IDs are read from the standard input' -r
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s V -l version -d 'Prints version information'
-complete -c orthanc -n "__fish_seen_subcommand_from download" -d 'Instance ID' -r
-complete -c orthanc -n "__fish_seen_subcommand_from download" -s o -l output -d 'Output file path' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -d 'Instance ID, or - to read IDs from the standard input' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s o -l output -d 'Output file path (- for the standard output), or directory if the IDs are read from the standard input' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
 complete -c orthanc -n "__fish_seen_subcommand_from download" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from download" -s V -l version -d 'Prints version information'
-complete -c orthanc -n "__fish_seen_subcommand_from delete" -d 'Instance ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -d 'Instance ID, or - to read IDs from the standard input' -r
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -s y -l yes -d 'Confirm the deletion of the IDs read from the standard input. Required with -, as the deletion can not be confirmed interactively'
 complete -c orthanc -n "__fish_seen_subcommand_from delete" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from delete" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from upload" -d 'Path to a DICOM file, a directory (traversed recursively) or a ZIP archive of DICOM files' -r
+complete -c orthanc -n "__fish_seen_subcommand_from upload" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from upload" -s n -l no-header -d 'Don\'t display table header'
+complete -c orthanc -n "__fish_seen_subcommand_from upload" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from upload" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from modality" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
 complete -c orthanc -n "__fish_seen_subcommand_from modality" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from modality" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from modality" -f -a "list" -d 'List all modalities'
 complete -c orthanc -n "__fish_seen_subcommand_from modality" -f -a "show" -d 'Show modality details'
 complete -c orthanc -n "__fish_seen_subcommand_from modality" -f -a "create" -d 'Create a modality'
-complete -c orthanc -n "__fish_seen_subcommand_from modality" -f -a "modify" -d 'Modify a modality'
+complete -c orthanc -n "__fish_seen_subcommand_from modality" -f -a "modify" -d 'Modify a modality. Settings, that are not specified, are kept as is'
 complete -c orthanc -n "__fish_seen_subcommand_from modality" -f -a "echo" -d 'Send a C-ECHO request to a modality'
+complete -c orthanc -n "__fish_seen_subcommand_from modality" -f -a "query" -d 'Send a C-FIND request to a modality. The query ID is printed to stderr, so that the answers can be retrieved later on'
+complete -c orthanc -n "__fish_seen_subcommand_from modality" -f -a "retrieve" -d 'Retrieve a study or a series from a modality with C-MOVE or C-GET, either by its UIDs or as the answers to a query'
 complete -c orthanc -n "__fish_seen_subcommand_from modality" -f -a "store" -d 'Send a C-STORE request to a modality'
 complete -c orthanc -n "__fish_seen_subcommand_from modality" -f -a "delete" -d 'Delete modality'
 complete -c orthanc -n "__fish_seen_subcommand_from list" -s c -l columns -d 'Display only the columns specified. Space-separated values. Example: Name Manufacturer' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
 complete -c orthanc -n "__fish_seen_subcommand_from list" -s n -l no-header -d 'Don\'t display table header'
 complete -c orthanc -n "__fish_seen_subcommand_from list" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from list" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from show" -d 'Modality name' -r
+complete -c orthanc -n "__fish_seen_subcommand_from show" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
 complete -c orthanc -n "__fish_seen_subcommand_from show" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from show" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from create" -d 'Modality name' -r
 complete -c orthanc -n "__fish_seen_subcommand_from create" -s a -l aet -d 'Modality AET' -r
 complete -c orthanc -n "__fish_seen_subcommand_from create" -s h -l host -d 'Modality host' -r
 complete -c orthanc -n "__fish_seen_subcommand_from create" -s p -l port -d 'Modality port' -r
+complete -c orthanc -n "__fish_seen_subcommand_from create" -s m -l manufacturer -d 'Modality manufacturer, e.g. Generic, GE, StoreScp' -r
+complete -c orthanc -n "__fish_seen_subcommand_from create" -l allow -d 'Allow DIMSE services. Space-separated values. Example: C-FIND C-MOVE' -r -f -a "C-ECHO C-FIND C-GET C-MOVE C-STORE N-ACTION N-EVENT-REPORT"
+complete -c orthanc -n "__fish_seen_subcommand_from create" -l deny -d 'Deny DIMSE services. Space-separated values. Example: C-GET C-STORE' -r -f -a "C-ECHO C-FIND C-GET C-MOVE C-STORE N-ACTION N-EVENT-REPORT"
+complete -c orthanc -n "__fish_seen_subcommand_from create" -l transcoding -d 'Allow transcoding of the instances sent to the modality' -r -f -a "true false"
+complete -c orthanc -n "__fish_seen_subcommand_from create" -l local-aet -d 'AET used by Orthanc when talking to the modality' -r
+complete -c orthanc -n "__fish_seen_subcommand_from create" -l timeout -d 'DIMSE timeout in seconds' -r
+complete -c orthanc -n "__fish_seen_subcommand_from create" -l tls -d 'Use DICOM TLS' -r -f -a "true false"
+complete -c orthanc -n "__fish_seen_subcommand_from create" -s f -l from-file -d 'Create the modalities defined in a YAML file, that maps modality names to their settings' -r
+complete -c orthanc -n "__fish_seen_subcommand_from create" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
 complete -c orthanc -n "__fish_seen_subcommand_from create" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from create" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -d 'Modality name' -r
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s a -l aet -d 'Modality AET' -r
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s h -l host -d 'Modality host' -r
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s p -l port -d 'Modality port' -r
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -s m -l manufacturer -d 'Modality manufacturer, e.g. Generic, GE, StoreScp' -r
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -l allow -d 'Allow DIMSE services. Space-separated values. Example: C-FIND C-MOVE' -r -f -a "C-ECHO C-FIND C-GET C-MOVE C-STORE N-ACTION N-EVENT-REPORT"
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -l deny -d 'Deny DIMSE services. Space-separated values. Example: C-GET C-STORE' -r -f -a "C-ECHO C-FIND C-GET C-MOVE C-STORE N-ACTION N-EVENT-REPORT"
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -l transcoding -d 'Allow transcoding of the instances sent to the modality' -r -f -a "true false"
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -l local-aet -d 'AET used by Orthanc when talking to the modality' -r
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -l timeout -d 'DIMSE timeout in seconds' -r
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -l tls -d 'Use DICOM TLS' -r -f -a "true false"
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from echo" -d 'Modality name' -r
+complete -c orthanc -n "__fish_seen_subcommand_from echo" -s t -l timeout -d 'C-ECHO timeout in seconds' -r
+complete -c orthanc -n "__fish_seen_subcommand_from echo" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
 complete -c orthanc -n "__fish_seen_subcommand_from echo" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from echo" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from query" -d 'Modality name' -r
+complete -c orthanc -n "__fish_seen_subcommand_from query" -d 'Query terms. Space-separated pairs TagName=TagValue. Wildcards are allowed, dates and times can be ranges (e.g. StudyDate=20200101-20201231), a repeated tag matches any of its values. Example: PatientID=12345 StudyDate=20210101-' -r
+complete -c orthanc -n "__fish_seen_subcommand_from query" -s l -l level -d 'Query level' -r -f -a "patient study series instance"
+complete -c orthanc -n "__fish_seen_subcommand_from query" -s c -l columns -d 'Display only the columns specified. Space-separated values. Any DICOM tag (keyword or gggg,eeee) can be used as a column. Example: Index PatientName' -r
+complete -c orthanc -n "__fish_seen_subcommand_from query" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from query" -s n -l no-header -d 'Don\'t display table header'
+complete -c orthanc -n "__fish_seen_subcommand_from query" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from query" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from retrieve" -d 'Modality name' -r
+complete -c orthanc -n "__fish_seen_subcommand_from retrieve" -l study -d 'StudyInstanceUID of the study to retrieve' -r
+complete -c orthanc -n "__fish_seen_subcommand_from retrieve" -l series -d 'SeriesInstanceUID of the series to retrieve. Requires --study' -r
+complete -c orthanc -n "__fish_seen_subcommand_from retrieve" -l query -d 'ID of a query, as printed by `modality query`' -r
+complete -c orthanc -n "__fish_seen_subcommand_from retrieve" -l index -d 'Retrieve only the answer to the query with this index' -r
+complete -c orthanc -n "__fish_seen_subcommand_from retrieve" -s t -l target -d 'AET to move the resources to, instead of Orthanc' -r
+complete -c orthanc -n "__fish_seen_subcommand_from retrieve" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from retrieve" -l get -d 'Retrieve with C-GET instead of C-MOVE'
+complete -c orthanc -n "__fish_seen_subcommand_from retrieve" -l async -d 'Run as an Orthanc job and print the job ID instead of waiting for the result'
+complete -c orthanc -n "__fish_seen_subcommand_from retrieve" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from retrieve" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from store" -d 'Modality name' -r
-complete -c orthanc -n "__fish_seen_subcommand_from store" -s e -l entity-ids -d 'Entity IDs' -r
+complete -c orthanc -n "__fish_seen_subcommand_from store" -s e -l entity-ids -d 'Entity IDs, or - to read IDs from the standard input' -r
+complete -c orthanc -n "__fish_seen_subcommand_from store" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from store" -l async -d 'Run as an Orthanc job and print the job ID instead of waiting for the result'
 complete -c orthanc -n "__fish_seen_subcommand_from store" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from store" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from delete" -d 'Modality name' -r
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
 complete -c orthanc -n "__fish_seen_subcommand_from delete" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from delete" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from tree" -l patient -d 'Show the hierarchy of this patient' -r
+complete -c orthanc -n "__fish_seen_subcommand_from tree" -l study -d 'Show the hierarchy of this study' -r
+complete -c orthanc -n "__fish_seen_subcommand_from tree" -l series -d 'Show the hierarchy of this series' -r
+complete -c orthanc -n "__fish_seen_subcommand_from tree" -s d -l max-depth -d 'Number of levels to show. For the whole server, 1 shows only patients, 2 shows patients and their studies and so on' -r
+complete -c orthanc -n "__fish_seen_subcommand_from tree" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from tree" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from tree" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -s l -l level -d 'Level of the entities to delete' -r -f -a "patient study series instance"
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -s q -l query -d 'Delete the entities matching these search query terms, as in search commands. Example: StudyDescription=Test* StudyDate=-20200101' -r
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -l ids-from -d 'Delete the entities with the IDs listed in this file, one per line. With -, the IDs are read from the standard input' -r
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -l case-sensitive -d 'Match person names (e.g. PatientName) case-sensitively'
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -l dry-run -d 'Only list the entities, that would be deleted'
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -s y -l yes -d 'Delete without asking for confirmation'
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s l -l level -d 'Level of the entities to download' -r -f -a "patient study series"
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s q -l query -d 'Download the entities matching these search query terms, as in search commands. Example: StudyDescription=Test* StudyDate=-20200101' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -l ids-from -d 'Download the entities with the IDs listed in this file, one per line. With -, the IDs are read from the standard input' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s o -l output-dir -d 'Directory to download the archives to' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s t -l name-template -d 'Name the archives after this template, relative to the output directory. {ID} is replaced with the ID of the entity, and {Tag} with the value of the DICOM tag (a keyword or gggg,eeee). Example: {PatientID}/{StudyDate}_{StudyDescription}.zip' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s p -l parallel -d 'Number of entities to download at the same time' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s r -l retries -d 'Number of times to retry a download, that failed because of a server or a network error. The delay between the retries starts at one second and doubles with every retry' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from download" -l case-sensitive -d 'Match person names (e.g. PatientName) case-sensitively'
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s m -l media -d 'Download DICOM media (with a DICOMDIR) instead of plain archives'
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from peer" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from peer" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from peer" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from peer" -f -a "list" -d 'List all peers'
+complete -c orthanc -n "__fish_seen_subcommand_from peer" -f -a "show" -d 'Show peer details'
+complete -c orthanc -n "__fish_seen_subcommand_from peer" -f -a "create" -d 'Create a peer'
+complete -c orthanc -n "__fish_seen_subcommand_from peer" -f -a "modify" -d 'Modify a peer. Settings, that are not specified, are kept as is, except for the password and the HTTP headers, which Orthanc does not disclose'
+complete -c orthanc -n "__fish_seen_subcommand_from peer" -f -a "system" -d 'Show system information of a peer'
+complete -c orthanc -n "__fish_seen_subcommand_from peer" -f -a "store" -d 'Send entities to a peer'
+complete -c orthanc -n "__fish_seen_subcommand_from peer" -f -a "delete" -d 'Delete peer'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s c -l columns -d 'Display only the columns specified. Space-separated values. Example: Name URL' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s n -l no-header -d 'Don\'t display table header'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from show" -d 'Peer name' -r
+complete -c orthanc -n "__fish_seen_subcommand_from show" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from show" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from show" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from create" -d 'Peer name' -r
+complete -c orthanc -n "__fish_seen_subcommand_from create" -l url -d 'URL of the peer\'s REST API' -r
+complete -c orthanc -n "__fish_seen_subcommand_from create" -s u -l username -d 'Username on the peer' -r
+complete -c orthanc -n "__fish_seen_subcommand_from create" -s p -l password -d 'Password on the peer' -r
+complete -c orthanc -n "__fish_seen_subcommand_from create" -s H -l header -d 'HTTP header to send to the peer. Can be used multiple times' -r
+complete -c orthanc -n "__fish_seen_subcommand_from create" -l certificate-file -d 'Client certificate (PEM) to authenticate to the peer with' -r
+complete -c orthanc -n "__fish_seen_subcommand_from create" -l certificate-key-file -d 'Private key (PEM) of the client certificate' -r
+complete -c orthanc -n "__fish_seen_subcommand_from create" -l certificate-key-password -d 'Password of the private key' -r
+complete -c orthanc -n "__fish_seen_subcommand_from create" -l timeout -d 'HTTP timeout in seconds' -r
+complete -c orthanc -n "__fish_seen_subcommand_from create" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from create" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from create" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -d 'Peer name' -r
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -l url -d 'URL of the peer\'s REST API' -r
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -s u -l username -d 'Username on the peer' -r
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -s p -l password -d 'Password on the peer' -r
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -s H -l header -d 'HTTP header to send to the peer. Can be used multiple times' -r
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -l certificate-file -d 'Client certificate (PEM) to authenticate to the peer with' -r
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -l certificate-key-file -d 'Private key (PEM) of the client certificate' -r
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -l certificate-key-password -d 'Password of the private key' -r
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -l timeout -d 'HTTP timeout in seconds' -r
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from system" -d 'Peer name' -r
+complete -c orthanc -n "__fish_seen_subcommand_from system" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from system" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from system" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from store" -d 'Peer name' -r
+complete -c orthanc -n "__fish_seen_subcommand_from store" -s e -l entity-ids -d 'Entity IDs, or - to read IDs from the standard input' -r
+complete -c orthanc -n "__fish_seen_subcommand_from store" -l transcode -d 'Transcode the instances to this transfer syntax before sending them. Example: 1.2.840.10008.1.2.4.70' -r
+complete -c orthanc -n "__fish_seen_subcommand_from store" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from store" -l compress -d 'Compress the HTTP requests sent to the peer'
+complete -c orthanc -n "__fish_seen_subcommand_from store" -l async -d 'Run as an Orthanc job and print the job ID instead of waiting for the result'
+complete -c orthanc -n "__fish_seen_subcommand_from store" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from store" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -d 'Peer name' -r
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from job" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from job" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from job" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from job" -f -a "list" -d 'List all jobs'
+complete -c orthanc -n "__fish_seen_subcommand_from job" -f -a "show" -d 'Show job details'
+complete -c orthanc -n "__fish_seen_subcommand_from job" -f -a "wait" -d 'Wait for a job to complete, showing its progress'
+complete -c orthanc -n "__fish_seen_subcommand_from job" -f -a "cancel" -d 'Cancel job'
+complete -c orthanc -n "__fish_seen_subcommand_from job" -f -a "pause" -d 'Pause job'
+complete -c orthanc -n "__fish_seen_subcommand_from job" -f -a "resume" -d 'Resume a paused job'
+complete -c orthanc -n "__fish_seen_subcommand_from job" -f -a "resubmit" -d 'Resubmit a failed or canceled job'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s c -l columns -d 'Display only the columns specified. Space-separated values. Example: ID State' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s n -l no-header -d 'Don\'t display table header'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from show" -d 'Job ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from show" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from show" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from show" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from wait" -d 'Job ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from wait" -s i -l interval -d 'Polling interval in seconds' -r
+complete -c orthanc -n "__fish_seen_subcommand_from wait" -s t -l timeout -d 'Give up, if the job does not complete in time' -r
+complete -c orthanc -n "__fish_seen_subcommand_from wait" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from wait" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from wait" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from cancel" -d 'Job ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from cancel" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from cancel" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from cancel" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from pause" -d 'Job ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from pause" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from pause" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from pause" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from resume" -d 'Job ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from resume" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from resume" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from resume" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from resubmit" -d 'Job ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from resubmit" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from resubmit" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from resubmit" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from config" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from config" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from config" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from config" -f -a "list" -d 'List all profiles'
+complete -c orthanc -n "__fish_seen_subcommand_from config" -f -a "show" -d 'Show profile details'
+complete -c orthanc -n "__fish_seen_subcommand_from config" -f -a "add" -d 'Add a profile, or replace an existing one with the same name'
+complete -c orthanc -n "__fish_seen_subcommand_from config" -f -a "remove" -d 'Remove profile'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s n -l no-header -d 'Don\'t display table header'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from show" -d 'Profile name' -r
+complete -c orthanc -n "__fish_seen_subcommand_from show" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from show" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from show" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from add" -d 'Profile name' -r
+complete -c orthanc -n "__fish_seen_subcommand_from add" -s s -l server -d 'Orthanc server address' -r
+complete -c orthanc -n "__fish_seen_subcommand_from add" -s u -l username -d 'Orthanc username' -r
+complete -c orthanc -n "__fish_seen_subcommand_from add" -s H -l header -d 'Custom HTTP header to send with every request. Can be used multiple times. Example: X-Api-Key:secret' -r
+complete -c orthanc -n "__fish_seen_subcommand_from add" -l ca-cert -d 'PEM-encoded CA certificate to verify the server certificate with' -r
+complete -c orthanc -n "__fish_seen_subcommand_from add" -l client-cert -d 'PEM-encoded client certificate (for mutual TLS)' -r
+complete -c orthanc -n "__fish_seen_subcommand_from add" -l client-key -d 'PEM-encoded private key of the client certificate' -r
+complete -c orthanc -n "__fish_seen_subcommand_from add" -l client-pkcs12 -d 'PKCS#12 archive with the client certificate and its private key, instead of --client-cert and --client-key' -r
+complete -c orthanc -n "__fish_seen_subcommand_from add" -l timeout -d 'Request timeout in seconds' -r
+complete -c orthanc -n "__fish_seen_subcommand_from add" -l proxy -d 'Proxy URL for all requests' -r
+complete -c orthanc -n "__fish_seen_subcommand_from add" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from add" -s p -l password -d 'Ask for the Orthanc password. It is read from the standard input, if that is not a terminal'
+complete -c orthanc -n "__fish_seen_subcommand_from add" -l token -d 'Ask for the Orthanc bearer token. It is read from the standard input, if that is not a terminal (after the password)'
+complete -c orthanc -n "__fish_seen_subcommand_from add" -l client-pkcs12-password -d 'Ask for the password of the PKCS#12 archive. It is read from the standard input, if that is not a terminal (after the token)'
+complete -c orthanc -n "__fish_seen_subcommand_from add" -l insecure -d 'Don\'t verify the server certificate'
+complete -c orthanc -n "__fish_seen_subcommand_from add" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from add" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from remove" -d 'Profile name' -r
+complete -c orthanc -n "__fish_seen_subcommand_from remove" -s F -l format -d 'Output format' -r -f -a "table json yaml csv tsv"
+complete -c orthanc -n "__fish_seen_subcommand_from remove" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from remove" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from help" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from help" -s V -l version -d 'Prints version information'
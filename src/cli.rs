@@ -1,5 +1,21 @@
 use clap::{crate_authors, crate_description, crate_version, App, Arg};
 
+/// Validates an option value, that must be a positive integer (e.g. `--limit`)
+fn positive_integer(v: &str) -> Result<(), &'static str> {
+    match v.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(()),
+        _ => Err("must be a positive integer"),
+    }
+}
+
+/// Validates an option value, that must be a non-negative integer (e.g. `--offset`)
+fn non_negative_integer(v: &str) -> Result<(), &'static str> {
+    match v.parse::<usize>() {
+        Ok(_) => Ok(()),
+        _ => Err("must be a non-negative integer"),
+    }
+}
+
 pub fn build_cli() -> App<'static> {
     App::new("orthanc-cli")
         .bin_name("orthanc")
@@ -34,6 +50,116 @@ pub fn build_cli() -> App<'static> {
                 .long("password")
                 .value_name("PASSWORD"),
         )
+        .arg(
+            Arg::new("token")
+                .display_order(3)
+                .about("Orthanc bearer token, sent instead of the username and password")
+                .takes_value(true)
+                .long("token")
+                .value_name("TOKEN"),
+        )
+        .arg(
+            Arg::new("header")
+                .display_order(4)
+                .about(concat!(
+                    "Custom HTTP header to send with every request. ",
+                    "Can be used multiple times. Example: X-Api-Key:secret",
+                ))
+                .takes_value(true)
+                .short('H')
+                .long("header")
+                .multiple_occurrences(true)
+                .value_name("NAME:VALUE"),
+        )
+        .arg(
+            Arg::new("profile")
+                .display_order(5)
+                .about("Configuration profile to take the server address and the credentials from")
+                .takes_value(true)
+                .long("profile")
+                .value_name("PROFILE"),
+        )
+        .arg(
+            Arg::new("ca_cert")
+                .display_order(6)
+                .about("PEM-encoded CA certificate to verify the server certificate with")
+                .takes_value(true)
+                .long("ca-cert")
+                .value_name("PATH"),
+        )
+        .arg(
+            Arg::new("client_cert")
+                .display_order(7)
+                .about("PEM-encoded client certificate (for mutual TLS)")
+                .takes_value(true)
+                .long("client-cert")
+                .requires("client_key")
+                .value_name("PATH"),
+        )
+        .arg(
+            Arg::new("client_key")
+                .display_order(8)
+                .about("PEM-encoded private key of the client certificate")
+                .takes_value(true)
+                .long("client-key")
+                .requires("client_cert")
+                .value_name("PATH"),
+        )
+        .arg(
+            Arg::new("client_pkcs12")
+                .display_order(9)
+                .about(concat!(
+                    "PKCS#12 archive with the client certificate and its private key, ",
+                    "instead of --client-cert and --client-key",
+                ))
+                .takes_value(true)
+                .long("client-pkcs12")
+                .conflicts_with_all(&["client_cert", "client_key"])
+                .value_name("PATH"),
+        )
+        .arg(
+            Arg::new("client_pkcs12_password")
+                .display_order(10)
+                .about("Password of the PKCS#12 archive")
+                .takes_value(true)
+                .long("client-pkcs12-password")
+                .value_name("PASSWORD"),
+        )
+        .arg(
+            Arg::new("insecure")
+                .display_order(11)
+                .about("Don't verify the server certificate")
+                .long("insecure"),
+        )
+        .arg(
+            Arg::new("timeout")
+                .display_order(12)
+                .about("Request timeout in seconds [default: 600]")
+                .takes_value(true)
+                .long("timeout")
+                .validator(|v| v.parse::<u64>())
+                .value_name("SECONDS"),
+        )
+        .arg(
+            Arg::new("proxy")
+                .display_order(13)
+                .about("Proxy URL for all requests")
+                .takes_value(true)
+                .long("proxy")
+                .value_name("URL"),
+        )
+        .arg(
+            Arg::new("format")
+                .display_order(14)
+                .about("Output format")
+                .takes_value(true)
+                .short('F')
+                .long("format")
+                .global(true)
+                .possible_values(&["table", "json", "yaml", "csv", "tsv"])
+                .default_value("table")
+                .value_name("FORMAT"),
+        )
         .subcommand(
             App::new("patient")
                 .setting(clap::AppSettings::SubcommandRequiredElseHelp)
@@ -53,7 +179,8 @@ pub fn build_cli() -> App<'static> {
                             Arg::new("columns")
                                 .about(
                                     concat!(
-                                        "Display only the columns specified. Space-separated values. ",
+                                        "Display only the columns specified. Space-separated values. Any DICOM tag ",
+                                        "(keyword or gggg,eeee) can be used as a column. ",
                                         "Example: ID PatientName",
                                     )
                                 )
@@ -64,6 +191,32 @@ pub fn build_cli() -> App<'static> {
                                 .multiple_values(true)
                                 .value_name("COLUMNS"),
                         )
+                        .arg(
+                            Arg::new("limit")
+                                .about("Display at most this number of entries")
+                                .takes_value(true)
+                                .long("limit")
+                                .validator(positive_integer)
+                                .value_name("NUMBER"),
+                        )
+                        .arg(
+                            Arg::new("offset")
+                                .about("Skip this number of entries")
+                                .takes_value(true)
+                                .long("offset")
+                                .validator(non_negative_integer)
+                                .value_name("NUMBER"),
+                        )
+                        .arg(
+                            Arg::new("sort_by")
+                                .about(concat!(
+                                    "Sort by a column, in ascending order, or in descending order when followed by :desc. ",
+                                    "Example: StudyDate:desc",
+                                ))
+                                .takes_value(true)
+                                .long("sort-by")
+                                .value_name("COLUMN[:desc]"),
+                        )
                 )
                 .subcommand(
                     App::new("show")
@@ -71,7 +224,10 @@ pub fn build_cli() -> App<'static> {
                         .about("Show patient details")
                         .arg(
                             Arg::new("id")
-                                .about("Patient ID")
+                                .about(concat!(
+                                    "Patient ID, ",
+                                    "or - to read IDs from the standard input",
+                                ))
                                 .required(true)
                                 .value_name("ID"),
                         ),
@@ -96,7 +252,8 @@ pub fn build_cli() -> App<'static> {
                             Arg::new("columns")
                                 .about(
                                     concat!(
-                                        "Display only the columns specified. Space-separated values. ",
+                                        "Display only the columns specified. Space-separated values. Any DICOM tag ",
+                                        "(keyword or gggg,eeee) can be used as a column. ",
                                         "Example: ID AccessionNumber StudyDate",
                                     )
                                 )
@@ -107,6 +264,32 @@ pub fn build_cli() -> App<'static> {
                                 .multiple_values(true)
                                 .value_name("COLUMNS"),
                         )
+                        .arg(
+                            Arg::new("limit")
+                                .about("Display at most this number of entries")
+                                .takes_value(true)
+                                .long("limit")
+                                .validator(positive_integer)
+                                .value_name("NUMBER"),
+                        )
+                        .arg(
+                            Arg::new("offset")
+                                .about("Skip this number of entries")
+                                .takes_value(true)
+                                .long("offset")
+                                .validator(non_negative_integer)
+                                .value_name("NUMBER"),
+                        )
+                        .arg(
+                            Arg::new("sort_by")
+                                .about(concat!(
+                                    "Sort by a column, in ascending order, or in descending order when followed by :desc. ",
+                                    "Example: StudyDate:desc",
+                                ))
+                                .takes_value(true)
+                                .long("sort-by")
+                                .value_name("COLUMN[:desc]"),
+                        )
                 )
                 .subcommand(
                     App::new("search")
@@ -115,8 +298,9 @@ pub fn build_cli() -> App<'static> {
                         .arg(
                             Arg::new("query")
                                 .about(concat!(
-                                    "Search query terms. Space-separted pairs TagName=TagValue. ",
-                                    "Wildcards are allowed. Example: PatientSex=F PatientName=*Sanchez*",
+                                    "Search query terms. Space-separated pairs TagName=TagValue. Wildcards are allowed, ",
+                                    "dates and times can be ranges (e.g. StudyDate=20200101-20201231), ",
+                                    "a repeated tag matches any of its values. Example: PatientSex=F PatientName=*Sanchez*",
                                 ))
                                 .required(true)
                                 .takes_value(true)
@@ -126,6 +310,24 @@ pub fn build_cli() -> App<'static> {
                                 .multiple_values(true)
                                 .value_name("QUERY"),
                         )
+                        .arg(
+                            Arg::new("case_sensitive")
+                                .about("Match person names (e.g. PatientName) case-sensitively")
+                                .long("case-sensitive"),
+                        )
+                        .arg(
+                            Arg::new("level")
+                                .about(concat!(
+                                    "Level of the entities to list. The query is matched at the level of the command, ",
+                                    "and either the descendants or the ancestors of the matching entities are listed",
+                                ))
+                                .takes_value(true)
+                                .short('l')
+                                .long("level")
+                                .possible_values(&["patient", "study", "series", "instance"])
+                                .default_value("patient")
+                                .value_name("LEVEL"),
+                        )
                         .arg(
                             Arg::new("no_header")
                                 .about("Don't display table header")
@@ -136,7 +338,8 @@ pub fn build_cli() -> App<'static> {
                             Arg::new("columns")
                                 .about(
                                     concat!(
-                                        "Display only the columns specified. Space-separated values. ",
+                                        "Display only the columns specified. Space-separated values. Any DICOM tag ",
+                                        "(keyword or gggg,eeee) can be used as a column. ",
                                         "Example: ID PatientName",
                                     )
                                 )
@@ -147,6 +350,32 @@ pub fn build_cli() -> App<'static> {
                                 .multiple_values(true)
                                 .value_name("COLUMNS"),
                         )
+                        .arg(
+                            Arg::new("limit")
+                                .about("Display at most this number of entries")
+                                .takes_value(true)
+                                .long("limit")
+                                .validator(positive_integer)
+                                .value_name("NUMBER"),
+                        )
+                        .arg(
+                            Arg::new("offset")
+                                .about("Skip this number of entries")
+                                .takes_value(true)
+                                .long("offset")
+                                .validator(non_negative_integer)
+                                .value_name("NUMBER"),
+                        )
+                        .arg(
+                            Arg::new("sort_by")
+                                .about(concat!(
+                                    "Sort by a column, in ascending order, or in descending order when followed by :desc. ",
+                                    "Example: StudyDate:desc",
+                                ))
+                                .takes_value(true)
+                                .long("sort-by")
+                                .value_name("COLUMN[:desc]"),
+                        )
                 )
                 .subcommand(
                     App::new("anonymize")
@@ -154,7 +383,10 @@ pub fn build_cli() -> App<'static> {
                         .about("Anonymize patient")
                         .arg(
                             Arg::new("id")
-                                .about("Patient ID")
+                                .about(concat!(
+                                    "Patient ID, ",
+                                    "or - to read IDs from the standard input",
+                                ))
                                 .required(true)
                                 .value_name("ID"),
                         )
@@ -195,6 +427,50 @@ pub fn build_cli() -> App<'static> {
                                 .short('p')
                                 .long("keep-private-tags"),
                         )
+                        .arg(
+                            Arg::new("anonymization_profile")
+                                .about(concat!(
+                                    "Anonymization profiles to apply on top of the basic profile of ",
+                                    "DICOM PS3.15. retain-dates, retain-device-identity and ",
+                                    "retain-uids keep the corresponding tags, clean-descriptions ",
+                                    "replaces free-text descriptions with CLEANED",
+                                ))
+                                .conflicts_with("config")
+                                .takes_value(true)
+                                .short('P')
+                                .long("anonymization-profile")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .case_insensitive(true)
+                                .possible_values(&[
+                                    "basic",
+                                    "retain-dates",
+                                    "retain-device-identity",
+                                    "retain-uids",
+                                    "clean-descriptions",
+                                ])
+                                .value_name("PROFILE"),
+                        )
+                        .arg(
+                            Arg::new("dicom_version")
+                                .about(concat!(
+                                    "Version of DICOM PS3.15 to anonymize according to. ",
+                                    "Defaults to the latest version, that Orthanc supports",
+                                ))
+                                .conflicts_with("config")
+                                .takes_value(true)
+                                .long("dicom-version")
+                                .possible_values(&["2008", "2017c", "2021b"])
+                                .value_name("VERSION"),
+                        )
+                        .arg(
+                            Arg::new("print_config")
+                                .about(concat!(
+                                    "Print the anonymization configuration, that would be sent to ",
+                                    "Orthanc, instead of anonymizing",
+                                ))
+                                .long("print-config"),
+                        )
                         .arg(
                             Arg::new("config")
                                 .about("Anonymization configuration file")
@@ -202,6 +478,37 @@ pub fn build_cli() -> App<'static> {
                                 .short('c')
                                 .long("config")
                                 .value_name("CONFIG"),
+                        )
+                        .arg(
+                            Arg::new("async")
+                                .about(concat!(
+                                    "Run as an Orthanc job and print the job ID ",
+                                    "instead of waiting for the result",
+                                ))
+                                .long("async"),
+                        )
+                        .arg(
+                            Arg::new("mapping_file")
+                                .about(concat!(
+                                    "Append the original and the new PatientID, PatientName, ",
+                                    "StudyInstanceUID and AccessionNumber of every anonymized entity, ",
+                                    "with a timestamp, to this CSV file",
+                                ))
+                                .takes_value(true)
+                                .long("mapping-file")
+                                .conflicts_with("async")
+                                .value_name("FILE"),
+                        )
+                        .arg(
+                            Arg::new("pseudonym_salt_file")
+                                .about(concat!(
+                                    "File with the secret salt to derive deterministic pseudonyms of ",
+                                    "PatientID and PatientName from, so that a patient always gets the ",
+                                    "same pseudonym. The salt can also be set with ORC_PSEUDONYM_SALT",
+                                ))
+                                .takes_value(true)
+                                .long("pseudonym-salt-file")
+                                .value_name("FILE"),
                         ),
                 )
                 .subcommand(
@@ -210,7 +517,10 @@ pub fn build_cli() -> App<'static> {
                         .about("Modify patient")
                         .arg(
                             Arg::new("id")
-                                .about("Patient ID")
+                                .about(concat!(
+                                    "Patient ID, ",
+                                    "or - to read IDs from the standard input",
+                                ))
                                 .required(true)
                                 .value_name("ID"),
                         )
@@ -222,7 +532,7 @@ pub fn build_cli() -> App<'static> {
                                     "Example: PatientName=REMOVED AccessionNumber=42",
                                 ))
                                 .conflicts_with("config")
-                                .required_unless_present_any(&["remove", "config"])
+                                .required_unless_present_any(["remove", "config"])
                                 .takes_value(true)
                                 .short('r')
                                 .long("replace")
@@ -238,7 +548,7 @@ pub fn build_cli() -> App<'static> {
                                     "Example: PatientSex PatientBirthDate",
                                 ))
                                 .conflicts_with("config")
-                                .required_unless_present_any(&["replace", "config"])
+                                .required_unless_present_any(["replace", "config"])
                                 .takes_value(true)
                                 .short('m')
                                 .long("remove")
@@ -249,11 +559,19 @@ pub fn build_cli() -> App<'static> {
                         .arg(
                             Arg::new("config")
                                 .about("Modification configuration file")
-                                .required_unless_present_any(&["remove", "replace"])
+                                .required_unless_present_any(["remove", "replace"])
                                 .takes_value(true)
                                 .short('c')
                                 .long("config")
                                 .value_name("CONFIG"),
+                        )
+                        .arg(
+                            Arg::new("async")
+                                .about(concat!(
+                                    "Run as an Orthanc job and print the job ID ",
+                                    "instead of waiting for the result",
+                                ))
+                                .long("async"),
                         ),
                 )
                 .subcommand(
@@ -262,18 +580,54 @@ pub fn build_cli() -> App<'static> {
                         .about("Download patient")
                         .arg(
                             Arg::new("id")
-                                .about("Patient ID")
+                                .about(concat!(
+                                    "Patient ID, ",
+                                    "or - to read IDs from the standard input",
+                                ))
                                 .required(true)
                                 .value_name("ID"),
                         )
                         .arg(
                             Arg::new("output")
-                                .about("Output file path")
+                                .about(concat!(
+                                    "Output file path (- for the standard output), ",
+                                    "or directory if the IDs are read from the standard input",
+                                ))
                                 .takes_value(true)
                                 .short('o')
                                 .long("output")
-                                .required(true)
+                                .required_unless_present("extract")
+                                .conflicts_with("extract")
                                 .value_name("OUTPUT"),
+                        )
+                        .arg(
+                            Arg::new("extract")
+                                .about("Extract the archive into a directory, as it is downloaded")
+                                .takes_value(true)
+                                .short('x')
+                                .long("extract")
+                                .value_name("DIR"),
+                        )
+                        .arg(
+                            Arg::new("path_template")
+                                .about(concat!(
+                                    "Path of the extracted files, relative to the directory, ",
+                                    "where {Tag} is replaced with the value of a DICOM tag. ",
+                                    "Example: {PatientID}/{StudyDate}_{StudyDescription}/",
+                                    "{SeriesNumber}/{InstanceNumber}.dcm",
+                                ))
+                                .takes_value(true)
+                                .short('t')
+                                .long("path-template")
+                                .requires("extract")
+                                .conflicts_with("media")
+                                .value_name("TEMPLATE"),
+                        )
+                        .arg(
+                            Arg::new("media")
+                                .about("Download DICOM media with a DICOMDIR")
+                                .short('m')
+                                .long("media"),
                         ),
                 )
                 .subcommand(
@@ -282,9 +636,21 @@ pub fn build_cli() -> App<'static> {
                         .about("Delete patient")
                         .arg(
                             Arg::new("id")
-                                .about("Patient ID")
+                                .about(concat!(
+                                    "Patient ID, ",
+                                    "or - to read IDs from the standard input",
+                                ))
                                 .required(true)
                                 .value_name("ID"),
+                        )
+                        .arg(
+                            Arg::new("yes")
+                                .about(concat!(
+                                    "Confirm the deletion of the IDs read from the standard input. ",
+                                    "Required with -, as the deletion can not be confirmed interactively",
+                                ))
+                                .short('y')
+                                .long("yes"),
                         ),
                 ),
         )
@@ -307,7 +673,8 @@ pub fn build_cli() -> App<'static> {
                             Arg::new("columns")
                                 .about(
                                     concat!(
-                                        "Display only the columns specified. Space-separated values. ",
+                                        "Display only the columns specified. Space-separated values. Any DICOM tag ",
+                                        "(keyword or gggg,eeee) can be used as a column. ",
                                         "Example: ID AccessionNumber StudyDate",
                                     )
                                 )
@@ -318,6 +685,32 @@ pub fn build_cli() -> App<'static> {
                                 .multiple_values(true)
                                 .value_name("COLUMNS"),
                         )
+                        .arg(
+                            Arg::new("limit")
+                                .about("Display at most this number of entries")
+                                .takes_value(true)
+                                .long("limit")
+                                .validator(positive_integer)
+                                .value_name("NUMBER"),
+                        )
+                        .arg(
+                            Arg::new("offset")
+                                .about("Skip this number of entries")
+                                .takes_value(true)
+                                .long("offset")
+                                .validator(non_negative_integer)
+                                .value_name("NUMBER"),
+                        )
+                        .arg(
+                            Arg::new("sort_by")
+                                .about(concat!(
+                                    "Sort by a column, in ascending order, or in descending order when followed by :desc. ",
+                                    "Example: StudyDate:desc",
+                                ))
+                                .takes_value(true)
+                                .long("sort-by")
+                                .value_name("COLUMN[:desc]"),
+                        )
                     )
                 .subcommand(
                     App::new("show")
@@ -325,7 +718,10 @@ pub fn build_cli() -> App<'static> {
                         .about("Show study details")
                         .arg(
                             Arg::new("id")
-                                .about("Study ID")
+                                .about(concat!(
+                                    "Study ID, ",
+                                    "or - to read IDs from the standard input",
+                                ))
                                 .required(true)
                                 .value_name("ID"),
                         ),
@@ -350,7 +746,8 @@ pub fn build_cli() -> App<'static> {
                             Arg::new("columns")
                                 .about(
                                     concat!(
-                                        "Display only the columns specified. Space-separated values. ",
+                                        "Display only the columns specified. Space-separated values. Any DICOM tag ",
+                                        "(keyword or gggg,eeee) can be used as a column. ",
                                         "Example: ID Modality BodyPartExamined",
                                     )
                                 )
@@ -361,6 +758,32 @@ pub fn build_cli() -> App<'static> {
                                 .multiple_values(true)
                                 .value_name("COLUMNS"),
                         )
+                        .arg(
+                            Arg::new("limit")
+                                .about("Display at most this number of entries")
+                                .takes_value(true)
+                                .long("limit")
+                                .validator(positive_integer)
+                                .value_name("NUMBER"),
+                        )
+                        .arg(
+                            Arg::new("offset")
+                                .about("Skip this number of entries")
+                                .takes_value(true)
+                                .long("offset")
+                                .validator(non_negative_integer)
+                                .value_name("NUMBER"),
+                        )
+                        .arg(
+                            Arg::new("sort_by")
+                                .about(concat!(
+                                    "Sort by a column, in ascending order, or in descending order when followed by :desc. ",
+                                    "Example: StudyDate:desc",
+                                ))
+                                .takes_value(true)
+                                .long("sort-by")
+                                .value_name("COLUMN[:desc]"),
+                        )
                 )
                 .subcommand(
                     App::new("search")
@@ -369,8 +792,9 @@ pub fn build_cli() -> App<'static> {
                         .arg(
                             Arg::new("query")
                                 .about(concat!(
-                                    "Search query terms. Space-separted pairs TagName=TagValue. ",
-                                    "Wildcards are allowed. Example: StudyDescription=*BRAIN* StudyDate=20200101",
+                                    "Search query terms. Space-separated pairs TagName=TagValue. Wildcards are allowed, ",
+                                    "dates and times can be ranges (e.g. StudyDate=20200101-20201231), ",
+                                    "a repeated tag matches any of its values. Example: StudyDescription=*BRAIN* StudyDate=20200101",
                                 ))
                                 .required(true)
                                 .takes_value(true)
@@ -380,6 +804,24 @@ pub fn build_cli() -> App<'static> {
                                 .multiple_values(true)
                                 .value_name("QUERY"),
                         )
+                        .arg(
+                            Arg::new("case_sensitive")
+                                .about("Match person names (e.g. PatientName) case-sensitively")
+                                .long("case-sensitive"),
+                        )
+                        .arg(
+                            Arg::new("level")
+                                .about(concat!(
+                                    "Level of the entities to list. The query is matched at the level of the command, ",
+                                    "and either the descendants or the ancestors of the matching entities are listed",
+                                ))
+                                .takes_value(true)
+                                .short('l')
+                                .long("level")
+                                .possible_values(&["patient", "study", "series", "instance"])
+                                .default_value("study")
+                                .value_name("LEVEL"),
+                        )
                         .arg(
                             Arg::new("no_header")
                                 .about("Don't display table header")
@@ -390,7 +832,8 @@ pub fn build_cli() -> App<'static> {
                             Arg::new("columns")
                                 .about(
                                     concat!(
-                                        "Display only the columns specified. Space-separated values. ",
+                                        "Display only the columns specified. Space-separated values. Any DICOM tag ",
+                                        "(keyword or gggg,eeee) can be used as a column. ",
                                         "Example: ID AccessionNumber StudyDate",
                                     )
                                 )
@@ -401,6 +844,32 @@ pub fn build_cli() -> App<'static> {
                                 .multiple_values(true)
                                 .value_name("COLUMNS"),
                         )
+                        .arg(
+                            Arg::new("limit")
+                                .about("Display at most this number of entries")
+                                .takes_value(true)
+                                .long("limit")
+                                .validator(positive_integer)
+                                .value_name("NUMBER"),
+                        )
+                        .arg(
+                            Arg::new("offset")
+                                .about("Skip this number of entries")
+                                .takes_value(true)
+                                .long("offset")
+                                .validator(non_negative_integer)
+                                .value_name("NUMBER"),
+                        )
+                        .arg(
+                            Arg::new("sort_by")
+                                .about(concat!(
+                                    "Sort by a column, in ascending order, or in descending order when followed by :desc. ",
+                                    "Example: StudyDate:desc",
+                                ))
+                                .takes_value(true)
+                                .long("sort-by")
+                                .value_name("COLUMN[:desc]"),
+                        )
                 )
                 .subcommand(
                     App::new("anonymize")
@@ -408,7 +877,10 @@ pub fn build_cli() -> App<'static> {
                         .about("Anonymize study")
                         .arg(
                             Arg::new("id")
-                                .about("Study ID")
+                                .about(concat!(
+                                    "Study ID, ",
+                                    "or - to read IDs from the standard input",
+                                ))
                                 .required(true)
                                 .value_name("ID"),
                         )
@@ -449,6 +921,50 @@ pub fn build_cli() -> App<'static> {
                                 .short('p')
                                 .long("keep-private-tags"),
                         )
+                        .arg(
+                            Arg::new("anonymization_profile")
+                                .about(concat!(
+                                    "Anonymization profiles to apply on top of the basic profile of ",
+                                    "DICOM PS3.15. retain-dates, retain-device-identity and ",
+                                    "retain-uids keep the corresponding tags, clean-descriptions ",
+                                    "replaces free-text descriptions with CLEANED",
+                                ))
+                                .conflicts_with("config")
+                                .takes_value(true)
+                                .short('P')
+                                .long("anonymization-profile")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .case_insensitive(true)
+                                .possible_values(&[
+                                    "basic",
+                                    "retain-dates",
+                                    "retain-device-identity",
+                                    "retain-uids",
+                                    "clean-descriptions",
+                                ])
+                                .value_name("PROFILE"),
+                        )
+                        .arg(
+                            Arg::new("dicom_version")
+                                .about(concat!(
+                                    "Version of DICOM PS3.15 to anonymize according to. ",
+                                    "Defaults to the latest version, that Orthanc supports",
+                                ))
+                                .conflicts_with("config")
+                                .takes_value(true)
+                                .long("dicom-version")
+                                .possible_values(&["2008", "2017c", "2021b"])
+                                .value_name("VERSION"),
+                        )
+                        .arg(
+                            Arg::new("print_config")
+                                .about(concat!(
+                                    "Print the anonymization configuration, that would be sent to ",
+                                    "Orthanc, instead of anonymizing",
+                                ))
+                                .long("print-config"),
+                        )
                         .arg(
                             Arg::new("config")
                                 .about("Anonymization configuration file")
@@ -456,6 +972,37 @@ pub fn build_cli() -> App<'static> {
                                 .short('c')
                                 .long("config")
                                 .value_name("CONFIG"),
+                        )
+                        .arg(
+                            Arg::new("async")
+                                .about(concat!(
+                                    "Run as an Orthanc job and print the job ID ",
+                                    "instead of waiting for the result",
+                                ))
+                                .long("async"),
+                        )
+                        .arg(
+                            Arg::new("mapping_file")
+                                .about(concat!(
+                                    "Append the original and the new PatientID, PatientName, ",
+                                    "StudyInstanceUID and AccessionNumber of every anonymized entity, ",
+                                    "with a timestamp, to this CSV file",
+                                ))
+                                .takes_value(true)
+                                .long("mapping-file")
+                                .conflicts_with("async")
+                                .value_name("FILE"),
+                        )
+                        .arg(
+                            Arg::new("pseudonym_salt_file")
+                                .about(concat!(
+                                    "File with the secret salt to derive deterministic pseudonyms of ",
+                                    "PatientID and PatientName from, so that a patient always gets the ",
+                                    "same pseudonym. The salt can also be set with ORC_PSEUDONYM_SALT",
+                                ))
+                                .takes_value(true)
+                                .long("pseudonym-salt-file")
+                                .value_name("FILE"),
                         ),
                 )
                 .subcommand(
@@ -464,7 +1011,10 @@ pub fn build_cli() -> App<'static> {
                         .about("Modify study")
                         .arg(
                             Arg::new("id")
-                                .about("Study ID")
+                                .about(concat!(
+                                    "Study ID, ",
+                                    "or - to read IDs from the standard input",
+                                ))
                                 .required(true)
                                 .value_name("ID"),
                         )
@@ -476,7 +1026,7 @@ pub fn build_cli() -> App<'static> {
                                     "Example: PatientName=REMOVED AccessionNumber=42",
                                 ))
                                 .conflicts_with("config")
-                                .required_unless_present_any(&["remove", "config"])
+                                .required_unless_present_any(["remove", "config"])
                                 .takes_value(true)
                                 .short('r')
                                 .long("replace")
@@ -492,7 +1042,7 @@ pub fn build_cli() -> App<'static> {
                                     "Example: PatientSex PatientBirthDate",
                                 ))
                                 .conflicts_with("config")
-                                .required_unless_present_any(&["replace", "config"])
+                                .required_unless_present_any(["replace", "config"])
                                 .takes_value(true)
                                 .short('m')
                                 .long("remove")
@@ -503,37 +1053,97 @@ pub fn build_cli() -> App<'static> {
                         .arg(
                             Arg::new("config")
                                 .about("Modification configuration file")
-                                .required_unless_present_any(&["remove", "replace"])
+                                .required_unless_present_any(["remove", "replace"])
                                 .takes_value(true)
                                 .short('c')
                                 .long("config")
                                 .value_name("CONFIG"),
+                        )
+                        .arg(
+                            Arg::new("async")
+                                .about(concat!(
+                                    "Run as an Orthanc job and print the job ID ",
+                                    "instead of waiting for the result",
+                                ))
+                                .long("async"),
                         ),
                 )
                 .subcommand(
                     App::new("download")
                         .display_order(6)
                         .about("Download study")
-                        .arg(Arg::new("id").about("Study ID").required(true))
+                        .arg(
+                            Arg::new("id")
+                                .about(concat!(
+                                    "Study ID, ",
+                                    "or - to read IDs from the standard input",
+                                ))
+                                .required(true),
+                        )
                         .arg(
                             Arg::new("output")
-                                .about("Output file path")
+                                .about(concat!(
+                                    "Output file path (- for the standard output), ",
+                                    "or directory if the IDs are read from the standard input",
+                                ))
                                 .takes_value(true)
                                 .short('o')
                                 .long("output")
-                                .required(true)
+                                .required_unless_present("extract")
+                                .conflicts_with("extract")
                                 .value_name("OUTPUT"),
-                        ),
-                )
-                .subcommand(
-                    App::new("delete")
-                        .display_order(7)
-                        .about("Delete study")
+                        )
                         .arg(
-                            Arg::new("id")
-                                .about("Study ID")
+                            Arg::new("extract")
+                                .about("Extract the archive into a directory, as it is downloaded")
+                                .takes_value(true)
+                                .short('x')
+                                .long("extract")
+                                .value_name("DIR"),
+                        )
+                        .arg(
+                            Arg::new("path_template")
+                                .about(concat!(
+                                    "Path of the extracted files, relative to the directory, ",
+                                    "where {Tag} is replaced with the value of a DICOM tag. ",
+                                    "Example: {PatientID}/{StudyDate}_{StudyDescription}/",
+                                    "{SeriesNumber}/{InstanceNumber}.dcm",
+                                ))
+                                .takes_value(true)
+                                .short('t')
+                                .long("path-template")
+                                .requires("extract")
+                                .conflicts_with("media")
+                                .value_name("TEMPLATE"),
+                        )
+                        .arg(
+                            Arg::new("media")
+                                .about("Download DICOM media with a DICOMDIR")
+                                .short('m')
+                                .long("media"),
+                        ),
+                )
+                .subcommand(
+                    App::new("delete")
+                        .display_order(7)
+                        .about("Delete study")
+                        .arg(
+                            Arg::new("id")
+                                .about(concat!(
+                                    "Study ID, ",
+                                    "or - to read IDs from the standard input",
+                                ))
                                 .required(true)
                                 .value_name("ID"),
+                        )
+                        .arg(
+                            Arg::new("yes")
+                                .about(concat!(
+                                    "Confirm the deletion of the IDs read from the standard input. ",
+                                    "Required with -, as the deletion can not be confirmed interactively",
+                                ))
+                                .short('y')
+                                .long("yes"),
                         ),
                 ),
         )
@@ -556,7 +1166,8 @@ pub fn build_cli() -> App<'static> {
                         Arg::new("columns")
                             .about(
                                 concat!(
-                                    "Display only the columns specified. Space-separated values. ",
+                                    "Display only the columns specified. Space-separated values. Any DICOM tag ",
+                                    "(keyword or gggg,eeee) can be used as a column. ",
                                     "Example: ID Modality BodyPartExamined",
                                 )
                             )
@@ -567,6 +1178,32 @@ pub fn build_cli() -> App<'static> {
                             .multiple_values(true)
                             .value_name("COLUMNS"),
                     )
+                    .arg(
+                        Arg::new("limit")
+                            .about("Display at most this number of entries")
+                            .takes_value(true)
+                            .long("limit")
+                            .validator(positive_integer)
+                            .value_name("NUMBER"),
+                    )
+                    .arg(
+                        Arg::new("offset")
+                            .about("Skip this number of entries")
+                            .takes_value(true)
+                            .long("offset")
+                            .validator(non_negative_integer)
+                            .value_name("NUMBER"),
+                    )
+                    .arg(
+                        Arg::new("sort_by")
+                            .about(concat!(
+                                "Sort by a column, in ascending order, or in descending order when followed by :desc. ",
+                                "Example: StudyDate:desc",
+                            ))
+                            .takes_value(true)
+                            .long("sort-by")
+                            .value_name("COLUMN[:desc]"),
+                    )
                 )
                 .subcommand(
                     App::new("show")
@@ -574,7 +1211,10 @@ pub fn build_cli() -> App<'static> {
                         .about("Show series details")
                         .arg(
                             Arg::new("id")
-                                .about("Series ID")
+                                .about(concat!(
+                                    "Series ID, ",
+                                    "or - to read IDs from the standard input",
+                                ))
                                 .required(true)
                                 .value_name("ID"),
                         ),
@@ -599,7 +1239,8 @@ pub fn build_cli() -> App<'static> {
                             Arg::new("columns")
                                 .about(
                                     concat!(
-                                        "Display only the columns specified. Space-separated values. ",
+                                        "Display only the columns specified. Space-separated values. Any DICOM tag ",
+                                        "(keyword or gggg,eeee) can be used as a column. ",
                                         "Example: ID InstanceCreationDate",
                                     )
                                 )
@@ -610,6 +1251,32 @@ pub fn build_cli() -> App<'static> {
                                 .multiple_values(true)
                                 .value_name("COLUMNS"),
                         )
+                        .arg(
+                            Arg::new("limit")
+                                .about("Display at most this number of entries")
+                                .takes_value(true)
+                                .long("limit")
+                                .validator(positive_integer)
+                                .value_name("NUMBER"),
+                        )
+                        .arg(
+                            Arg::new("offset")
+                                .about("Skip this number of entries")
+                                .takes_value(true)
+                                .long("offset")
+                                .validator(non_negative_integer)
+                                .value_name("NUMBER"),
+                        )
+                        .arg(
+                            Arg::new("sort_by")
+                                .about(concat!(
+                                    "Sort by a column, in ascending order, or in descending order when followed by :desc. ",
+                                    "Example: StudyDate:desc",
+                                ))
+                                .takes_value(true)
+                                .long("sort-by")
+                                .value_name("COLUMN[:desc]"),
+                        )
                 )
                 .subcommand(
                     App::new("search")
@@ -618,8 +1285,9 @@ pub fn build_cli() -> App<'static> {
                         .arg(
                             Arg::new("query")
                                 .about(concat!(
-                                    "Search query terms. Space-separted pairs TagName=TagValue. ",
-                                    "Wildcards are allowed. Example: SeriesDescription=*BRAIN* SeriesDate=20200101",
+                                    "Search query terms. Space-separated pairs TagName=TagValue. Wildcards are allowed, ",
+                                    "dates and times can be ranges (e.g. StudyDate=20200101-20201231), ",
+                                    "a repeated tag matches any of its values. Example: SeriesDescription=*BRAIN* SeriesDate=20200101",
                                 ))
                                 .required(true)
                                 .takes_value(true)
@@ -629,6 +1297,24 @@ pub fn build_cli() -> App<'static> {
                                 .multiple_values(true)
                                 .value_name("QUERY"),
                         )
+                        .arg(
+                            Arg::new("case_sensitive")
+                                .about("Match person names (e.g. PatientName) case-sensitively")
+                                .long("case-sensitive"),
+                        )
+                        .arg(
+                            Arg::new("level")
+                                .about(concat!(
+                                    "Level of the entities to list. The query is matched at the level of the command, ",
+                                    "and either the descendants or the ancestors of the matching entities are listed",
+                                ))
+                                .takes_value(true)
+                                .short('l')
+                                .long("level")
+                                .possible_values(&["patient", "study", "series", "instance"])
+                                .default_value("series")
+                                .value_name("LEVEL"),
+                        )
                         .arg(
                             Arg::new("no_header")
                                 .about("Don't display table header")
@@ -639,7 +1325,8 @@ pub fn build_cli() -> App<'static> {
                             Arg::new("columns")
                                 .about(
                                     concat!(
-                                        "Display only the columns specified. Space-separated values. ",
+                                        "Display only the columns specified. Space-separated values. Any DICOM tag ",
+                                        "(keyword or gggg,eeee) can be used as a column. ",
                                         "Example: ID Modality BodyPartExamined",
                                     )
                                 )
@@ -650,6 +1337,32 @@ pub fn build_cli() -> App<'static> {
                                 .multiple_values(true)
                                 .value_name("COLUMNS"),
                         )
+                        .arg(
+                            Arg::new("limit")
+                                .about("Display at most this number of entries")
+                                .takes_value(true)
+                                .long("limit")
+                                .validator(positive_integer)
+                                .value_name("NUMBER"),
+                        )
+                        .arg(
+                            Arg::new("offset")
+                                .about("Skip this number of entries")
+                                .takes_value(true)
+                                .long("offset")
+                                .validator(non_negative_integer)
+                                .value_name("NUMBER"),
+                        )
+                        .arg(
+                            Arg::new("sort_by")
+                                .about(concat!(
+                                    "Sort by a column, in ascending order, or in descending order when followed by :desc. ",
+                                    "Example: StudyDate:desc",
+                                ))
+                                .takes_value(true)
+                                .long("sort-by")
+                                .value_name("COLUMN[:desc]"),
+                        )
                 )
                 .subcommand(
                     App::new("anonymize")
@@ -657,7 +1370,10 @@ pub fn build_cli() -> App<'static> {
                         .about("Anonymize series")
                         .arg(
                             Arg::new("id")
-                                .about("Series ID")
+                                .about(concat!(
+                                    "Series ID, ",
+                                    "or - to read IDs from the standard input",
+                                ))
                                 .required(true)
                                 .value_name("ID"),
                         )
@@ -698,6 +1414,50 @@ pub fn build_cli() -> App<'static> {
                                 .short('p')
                                 .long("keep-private-tags"),
                         )
+                        .arg(
+                            Arg::new("anonymization_profile")
+                                .about(concat!(
+                                    "Anonymization profiles to apply on top of the basic profile of ",
+                                    "DICOM PS3.15. retain-dates, retain-device-identity and ",
+                                    "retain-uids keep the corresponding tags, clean-descriptions ",
+                                    "replaces free-text descriptions with CLEANED",
+                                ))
+                                .conflicts_with("config")
+                                .takes_value(true)
+                                .short('P')
+                                .long("anonymization-profile")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .case_insensitive(true)
+                                .possible_values(&[
+                                    "basic",
+                                    "retain-dates",
+                                    "retain-device-identity",
+                                    "retain-uids",
+                                    "clean-descriptions",
+                                ])
+                                .value_name("PROFILE"),
+                        )
+                        .arg(
+                            Arg::new("dicom_version")
+                                .about(concat!(
+                                    "Version of DICOM PS3.15 to anonymize according to. ",
+                                    "Defaults to the latest version, that Orthanc supports",
+                                ))
+                                .conflicts_with("config")
+                                .takes_value(true)
+                                .long("dicom-version")
+                                .possible_values(&["2008", "2017c", "2021b"])
+                                .value_name("VERSION"),
+                        )
+                        .arg(
+                            Arg::new("print_config")
+                                .about(concat!(
+                                    "Print the anonymization configuration, that would be sent to ",
+                                    "Orthanc, instead of anonymizing",
+                                ))
+                                .long("print-config"),
+                        )
                         .arg(
                             Arg::new("config")
                                 .about("Anonymization configuration file")
@@ -705,6 +1465,37 @@ pub fn build_cli() -> App<'static> {
                                 .short('c')
                                 .long("config")
                                 .value_name("CONFIG"),
+                        )
+                        .arg(
+                            Arg::new("async")
+                                .about(concat!(
+                                    "Run as an Orthanc job and print the job ID ",
+                                    "instead of waiting for the result",
+                                ))
+                                .long("async"),
+                        )
+                        .arg(
+                            Arg::new("mapping_file")
+                                .about(concat!(
+                                    "Append the original and the new PatientID, PatientName, ",
+                                    "StudyInstanceUID and AccessionNumber of every anonymized entity, ",
+                                    "with a timestamp, to this CSV file",
+                                ))
+                                .takes_value(true)
+                                .long("mapping-file")
+                                .conflicts_with("async")
+                                .value_name("FILE"),
+                        )
+                        .arg(
+                            Arg::new("pseudonym_salt_file")
+                                .about(concat!(
+                                    "File with the secret salt to derive deterministic pseudonyms of ",
+                                    "PatientID and PatientName from, so that a patient always gets the ",
+                                    "same pseudonym. The salt can also be set with ORC_PSEUDONYM_SALT",
+                                ))
+                                .takes_value(true)
+                                .long("pseudonym-salt-file")
+                                .value_name("FILE"),
                         ),
                 )
                 .subcommand(
@@ -713,7 +1504,10 @@ pub fn build_cli() -> App<'static> {
                         .about("Modify series")
                         .arg(
                             Arg::new("id")
-                                .about("Series ID")
+                                .about(concat!(
+                                    "Series ID, ",
+                                    "or - to read IDs from the standard input",
+                                ))
                                 .required(true)
                                 .value_name("ID"),
                         )
@@ -725,7 +1519,7 @@ pub fn build_cli() -> App<'static> {
                                     "Example: PatientName=REMOVED AccessionNumber=42",
                                 ))
                                 .conflicts_with("config")
-                                .required_unless_present_any(&["remove", "config"])
+                                .required_unless_present_any(["remove", "config"])
                                 .takes_value(true)
                                 .short('r')
                                 .long("replace")
@@ -741,7 +1535,7 @@ pub fn build_cli() -> App<'static> {
                                     "Example: PatientSex PatientBirthDate",
                                 ))
                                 .conflicts_with("config")
-                                .required_unless_present_any(&["replace", "config"])
+                                .required_unless_present_any(["replace", "config"])
                                 .takes_value(true)
                                 .short('m')
                                 .long("remove")
@@ -752,11 +1546,19 @@ pub fn build_cli() -> App<'static> {
                         .arg(
                             Arg::new("config")
                                 .about("Modification configuration file")
-                                .required_unless_present_any(&["remove", "replace"])
+                                .required_unless_present_any(["remove", "replace"])
                                 .takes_value(true)
                                 .short('c')
                                 .long("config")
                                 .value_name("CONFIG"),
+                        )
+                        .arg(
+                            Arg::new("async")
+                                .about(concat!(
+                                    "Run as an Orthanc job and print the job ID ",
+                                    "instead of waiting for the result",
+                                ))
+                                .long("async"),
                         ),
                 )
                 .subcommand(
@@ -765,18 +1567,54 @@ pub fn build_cli() -> App<'static> {
                         .about("Download series")
                         .arg(
                             Arg::new("id")
-                                .about("Series ID")
+                                .about(concat!(
+                                    "Series ID, ",
+                                    "or - to read IDs from the standard input",
+                                ))
                                 .required(true)
                                 .value_name("ID"),
                         )
                         .arg(
                             Arg::new("output")
-                                .about("Output file path")
+                                .about(concat!(
+                                    "Output file path (- for the standard output), ",
+                                    "or directory if the IDs are read from the standard input",
+                                ))
                                 .takes_value(true)
                                 .short('o')
                                 .long("output")
-                                .required(true)
+                                .required_unless_present("extract")
+                                .conflicts_with("extract")
                                 .value_name("OUTPUT"),
+                        )
+                        .arg(
+                            Arg::new("extract")
+                                .about("Extract the archive into a directory, as it is downloaded")
+                                .takes_value(true)
+                                .short('x')
+                                .long("extract")
+                                .value_name("DIR"),
+                        )
+                        .arg(
+                            Arg::new("path_template")
+                                .about(concat!(
+                                    "Path of the extracted files, relative to the directory, ",
+                                    "where {Tag} is replaced with the value of a DICOM tag. ",
+                                    "Example: {PatientID}/{StudyDate}_{StudyDescription}/",
+                                    "{SeriesNumber}/{InstanceNumber}.dcm",
+                                ))
+                                .takes_value(true)
+                                .short('t')
+                                .long("path-template")
+                                .requires("extract")
+                                .conflicts_with("media")
+                                .value_name("TEMPLATE"),
+                        )
+                        .arg(
+                            Arg::new("media")
+                                .about("Download DICOM media with a DICOMDIR")
+                                .short('m')
+                                .long("media"),
                         ),
                 )
                 .subcommand(
@@ -785,9 +1623,21 @@ pub fn build_cli() -> App<'static> {
                         .about("Delete series")
                         .arg(
                             Arg::new("id")
-                                .about("Series ID")
+                                .about(concat!(
+                                    "Series ID, ",
+                                    "or - to read IDs from the standard input",
+                                ))
                                 .required(true)
                                 .value_name("ID"),
+                        )
+                        .arg(
+                            Arg::new("yes")
+                                .about(concat!(
+                                    "Confirm the deletion of the IDs read from the standard input. ",
+                                    "Required with -, as the deletion can not be confirmed interactively",
+                                ))
+                                .short('y')
+                                .long("yes"),
                         ),
                 ),
         )
@@ -810,7 +1660,8 @@ pub fn build_cli() -> App<'static> {
                             Arg::new("columns")
                                 .about(
                                     concat!(
-                                        "Display only the columns specified. Space-separated values. ",
+                                        "Display only the columns specified. Space-separated values. Any DICOM tag ",
+                                        "(keyword or gggg,eeee) can be used as a column. ",
                                         "Example: ID InstanceCreationDate",
                                     )
                                 )
@@ -821,6 +1672,32 @@ pub fn build_cli() -> App<'static> {
                                 .multiple_values(true)
                                 .value_name("COLUMNS"),
                         )
+                        .arg(
+                            Arg::new("limit")
+                                .about("Display at most this number of entries")
+                                .takes_value(true)
+                                .long("limit")
+                                .validator(positive_integer)
+                                .value_name("NUMBER"),
+                        )
+                        .arg(
+                            Arg::new("offset")
+                                .about("Skip this number of entries")
+                                .takes_value(true)
+                                .long("offset")
+                                .validator(non_negative_integer)
+                                .value_name("NUMBER"),
+                        )
+                        .arg(
+                            Arg::new("sort_by")
+                                .about(concat!(
+                                    "Sort by a column, in ascending order, or in descending order when followed by :desc. ",
+                                    "Example: StudyDate:desc",
+                                ))
+                                .takes_value(true)
+                                .long("sort-by")
+                                .value_name("COLUMN[:desc]"),
+                        )
                 )
                 .subcommand(
                     App::new("show")
@@ -828,7 +1705,10 @@ pub fn build_cli() -> App<'static> {
                         .about("Show instance details")
                         .arg(
                             Arg::new("id")
-                                .about("Instance ID")
+                                .about(concat!(
+                                    "Instance ID, ",
+                                    "or - to read IDs from the standard input",
+                                ))
                                 .required(true)
                                 .value_name("ID"),
                         ),
@@ -842,6 +1722,39 @@ pub fn build_cli() -> App<'static> {
                                 .about("Instance ID")
                                 .required(true)
                                 .value_name("ID"),
+                        )
+                        .arg(
+                            Arg::new("group")
+                                .about(concat!(
+                                    "Show only the tags of the groups specified. ",
+                                    "Space-separated values. Example: 0010 0020",
+                                ))
+                                .takes_value(true)
+                                .short('g')
+                                .long("group")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .value_name("GROUP"),
+                        )
+                        .arg(
+                            Arg::new("tag")
+                                .about(concat!(
+                                    "Show only the tags specified, either by keyword or as gggg,eeee. ",
+                                    "Space-separated values. Example: PatientName 0020,000d",
+                                ))
+                                .takes_value(true)
+                                .short('t')
+                                .long("tag")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .value_name("TAG"),
+                        )
+                        .arg(
+                            Arg::new("meta")
+                                .about("Include the file meta information (group 0002)")
+                                .takes_value(false)
+                                .short('m')
+                                .long("meta"),
                         ),
                 )
                 .subcommand(
@@ -851,8 +1764,9 @@ pub fn build_cli() -> App<'static> {
                         .arg(
                             Arg::new("query")
                                 .about(concat!(
-                                    "Search query terms. Space-separted pairs TagName=TagValue. ",
-                                    "Wildcards are allowed. Example: InstanceNumber=42 InstanceCreationTime=174242",
+                                    "Search query terms. Space-separated pairs TagName=TagValue. Wildcards are allowed, ",
+                                    "dates and times can be ranges (e.g. StudyDate=20200101-20201231), ",
+                                    "a repeated tag matches any of its values. Example: InstanceNumber=42 InstanceCreationTime=174242",
                                 ))
                                 .required(true)
                                 .takes_value(true)
@@ -862,6 +1776,24 @@ pub fn build_cli() -> App<'static> {
                                 .multiple_values(true)
                                 .value_name("QUERY"),
                         )
+                        .arg(
+                            Arg::new("case_sensitive")
+                                .about("Match person names (e.g. PatientName) case-sensitively")
+                                .long("case-sensitive"),
+                        )
+                        .arg(
+                            Arg::new("level")
+                                .about(concat!(
+                                    "Level of the entities to list. The query is matched at the level of the command, ",
+                                    "and either the descendants or the ancestors of the matching entities are listed",
+                                ))
+                                .takes_value(true)
+                                .short('l')
+                                .long("level")
+                                .possible_values(&["patient", "study", "series", "instance"])
+                                .default_value("instance")
+                                .value_name("LEVEL"),
+                        )
                         .arg(
                             Arg::new("no_header")
                                 .about("Don't display table header")
@@ -872,7 +1804,8 @@ pub fn build_cli() -> App<'static> {
                             Arg::new("columns")
                                 .about(
                                     concat!(
-                                        "Display only the columns specified. Space-separated values. ",
+                                        "Display only the columns specified. Space-separated values. Any DICOM tag ",
+                                        "(keyword or gggg,eeee) can be used as a column. ",
                                         "Example: ID InstanceCreationDate",
                                     )
                                 )
@@ -883,6 +1816,32 @@ pub fn build_cli() -> App<'static> {
                                 .multiple_values(true)
                                 .value_name("COLUMNS"),
                         )
+                        .arg(
+                            Arg::new("limit")
+                                .about("Display at most this number of entries")
+                                .takes_value(true)
+                                .long("limit")
+                                .validator(positive_integer)
+                                .value_name("NUMBER"),
+                        )
+                        .arg(
+                            Arg::new("offset")
+                                .about("Skip this number of entries")
+                                .takes_value(true)
+                                .long("offset")
+                                .validator(non_negative_integer)
+                                .value_name("NUMBER"),
+                        )
+                        .arg(
+                            Arg::new("sort_by")
+                                .about(concat!(
+                                    "Sort by a column, in ascending order, or in descending order when followed by :desc. ",
+                                    "Example: StudyDate:desc",
+                                ))
+                                .takes_value(true)
+                                .long("sort-by")
+                                .value_name("COLUMN[:desc]"),
+                        )
                 )
                 .subcommand(
                     App::new("anonymize")
@@ -890,7 +1849,10 @@ pub fn build_cli() -> App<'static> {
                         .about("Anonymize instance")
                         .arg(
                             Arg::new("id")
-                                .about("Instance ID")
+                                .about(concat!(
+                                    "Instance ID, ",
+                                    "or - to read IDs from the standard input",
+                                ))
                                 .required(true)
                                 .value_name("ID"),
                         )
@@ -931,6 +1893,50 @@ pub fn build_cli() -> App<'static> {
                                 .short('p')
                                 .long("keep-private-tags"),
                         )
+                        .arg(
+                            Arg::new("anonymization_profile")
+                                .about(concat!(
+                                    "Anonymization profiles to apply on top of the basic profile of ",
+                                    "DICOM PS3.15. retain-dates, retain-device-identity and ",
+                                    "retain-uids keep the corresponding tags, clean-descriptions ",
+                                    "replaces free-text descriptions with CLEANED",
+                                ))
+                                .conflicts_with("config")
+                                .takes_value(true)
+                                .short('P')
+                                .long("anonymization-profile")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .case_insensitive(true)
+                                .possible_values(&[
+                                    "basic",
+                                    "retain-dates",
+                                    "retain-device-identity",
+                                    "retain-uids",
+                                    "clean-descriptions",
+                                ])
+                                .value_name("PROFILE"),
+                        )
+                        .arg(
+                            Arg::new("dicom_version")
+                                .about(concat!(
+                                    "Version of DICOM PS3.15 to anonymize according to. ",
+                                    "Defaults to the latest version, that Orthanc supports",
+                                ))
+                                .conflicts_with("config")
+                                .takes_value(true)
+                                .long("dicom-version")
+                                .possible_values(&["2008", "2017c", "2021b"])
+                                .value_name("VERSION"),
+                        )
+                        .arg(
+                            Arg::new("print_config")
+                                .about(concat!(
+                                    "Print the anonymization configuration, that would be sent to ",
+                                    "Orthanc, instead of anonymizing",
+                                ))
+                                .long("print-config"),
+                        )
                         .arg(
                             Arg::new("config")
                                 .about("Anonymization configuration file")
@@ -941,7 +1947,10 @@ pub fn build_cli() -> App<'static> {
                         )
                         .arg(
                             Arg::new("output")
-                                .about("Output file path")
+                                .about(concat!(
+                                    "Output file path (- for the standard output), ",
+                                    "or directory if the IDs are read from the standard input",
+                                ))
                                 .takes_value(true)
                                 .short('o')
                                 .long("output")
@@ -955,7 +1964,10 @@ pub fn build_cli() -> App<'static> {
                         .about("Modify instance")
                         .arg(
                             Arg::new("id")
-                                .about("Instance ID")
+                                .about(concat!(
+                                    "Instance ID, ",
+                                    "or - to read IDs from the standard input",
+                                ))
                                 .required(true)
                                 .value_name("ID"),
                         )
@@ -967,7 +1979,7 @@ pub fn build_cli() -> App<'static> {
                                     "Example: PatientName=REMOVED AccessionNumber=42",
                                 ))
                                 .conflicts_with("config")
-                                .required_unless_present_any(&["remove", "config"])
+                                .required_unless_present_any(["remove", "config"])
                                 .takes_value(true)
                                 .short('r')
                                 .long("replace")
@@ -983,7 +1995,7 @@ pub fn build_cli() -> App<'static> {
                                     "Example: PatientSex PatientBirthDate",
                                 ))
                                 .conflicts_with("config")
-                                .required_unless_present_any(&["replace", "config"])
+                                .required_unless_present_any(["replace", "config"])
                                 .takes_value(true)
                                 .short('m')
                                 .long("remove")
@@ -994,7 +2006,7 @@ pub fn build_cli() -> App<'static> {
                         .arg(
                             Arg::new("config")
                                 .about("Modification configuration file")
-                                .required_unless_present_any(&["remove", "replace"])
+                                .required_unless_present_any(["remove", "replace"])
                                 .takes_value(true)
                                 .short('c')
                                 .long("config")
@@ -1002,7 +2014,10 @@ pub fn build_cli() -> App<'static> {
                         )
                         .arg(
                             Arg::new("output")
-                                .about("Output file path")
+                                .about(concat!(
+                                    "Output file path (- for the standard output), ",
+                                    "or directory if the IDs are read from the standard input",
+                                ))
                                 .takes_value(true)
                                 .short('o')
                                 .long("output")
@@ -1016,13 +2031,19 @@ pub fn build_cli() -> App<'static> {
                         .about("Download instance")
                         .arg(
                             Arg::new("id")
-                                .about("Instance ID")
+                                .about(concat!(
+                                    "Instance ID, ",
+                                    "or - to read IDs from the standard input",
+                                ))
                                 .required(true)
                                 .value_name("ID"),
                         )
                         .arg(
                             Arg::new("output")
-                                .about("Output file path")
+                                .about(concat!(
+                                    "Output file path (- for the standard output), ",
+                                    "or directory if the IDs are read from the standard input",
+                                ))
                                 .takes_value(true)
                                 .short('o')
                                 .long("output")
@@ -1036,9 +2057,43 @@ pub fn build_cli() -> App<'static> {
                         .about("Delete instance")
                         .arg(
                             Arg::new("id")
-                                .about("Instance ID")
+                                .about(concat!(
+                                    "Instance ID, ",
+                                    "or - to read IDs from the standard input",
+                                ))
                                 .required(true)
                                 .value_name("ID"),
+                        )
+                        .arg(
+                            Arg::new("yes")
+                                .about(concat!(
+                                    "Confirm the deletion of the IDs read from the standard input. ",
+                                    "Required with -, as the deletion can not be confirmed interactively",
+                                ))
+                                .short('y')
+                                .long("yes"),
+                        ),
+                )
+                .subcommand(
+                    App::new("upload")
+                        .display_order(8)
+                        .about("Upload DICOM files, directories or ZIP archives")
+                        .arg(
+                            Arg::new("path")
+                                .about(concat!(
+                                    "Path to a DICOM file, a directory (traversed recursively) ",
+                                    "or a ZIP archive of DICOM files",
+                                ))
+                                .required(true)
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .value_name("PATH"),
+                        )
+                        .arg(
+                            Arg::new("no_header")
+                                .about("Don't display table header")
+                                .short('n')
+                                .long("no-header"),
                         ),
                 ),
         )
@@ -1090,7 +2145,7 @@ pub fn build_cli() -> App<'static> {
                         .arg(
                             Arg::new("name")
                                 .about("Modality name")
-                                .required(true)
+                                .required_unless_present("from_file")
                                 .value_name("NAME"),
                         )
                         .arg(
@@ -1099,7 +2154,7 @@ pub fn build_cli() -> App<'static> {
                                 .takes_value(true)
                                 .short('a')
                                 .long("aet")
-                                .required(true)
+                                .required_unless_present("from_file")
                                 .value_name("AET"),
                         )
                         .arg(
@@ -1108,7 +2163,7 @@ pub fn build_cli() -> App<'static> {
                                 .takes_value(true)
                                 .short('h')
                                 .long("host")
-                                .required(true)
+                                .required_unless_present("from_file")
                                 .value_name("HOST"),
                         )
                         .arg(
@@ -1117,28 +2172,127 @@ pub fn build_cli() -> App<'static> {
                                 .takes_value(true)
                                 .short('p')
                                 .long("port")
-                                .required(true)
+                                .required_unless_present("from_file")
+                                .validator(|v| v.parse::<i32>())
                                 .value_name("PORT"),
-                        ),
-                )
-                .subcommand(
-                    App::new("modify")
-                        .display_order(3)
-                        .about("Modify a modality")
-                        .arg(
-                            Arg::new("name")
-                                .about("Modality name")
-                                .required(true)
-                                .value_name("ID"),
                         )
                         .arg(
-                            Arg::new("aet")
-                                .about("Modality AET")
+                            Arg::new("manufacturer")
+                                .about("Modality manufacturer, e.g. Generic, GE, StoreScp")
                                 .takes_value(true)
-                                .short('a')
-                                .long("aet")
-                                .required(true)
-                                .value_name("AET"),
+                                .short('m')
+                                .long("manufacturer")
+                                .value_name("MANUFACTURER"),
+                        )
+                        .arg(
+                            Arg::new("allow")
+                                .about("Allow DIMSE services. Space-separated values. Example: C-FIND C-MOVE")
+                                .takes_value(true)
+                                .long("allow")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .possible_values(&[
+                                    "C-ECHO",
+                                    "C-FIND",
+                                    "C-GET",
+                                    "C-MOVE",
+                                    "C-STORE",
+                                    "N-ACTION",
+                                    "N-EVENT-REPORT",
+                                ])
+                                .value_name("SERVICES"),
+                        )
+                        .arg(
+                            Arg::new("deny")
+                                .about("Deny DIMSE services. Space-separated values. Example: C-GET C-STORE")
+                                .takes_value(true)
+                                .long("deny")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .possible_values(&[
+                                    "C-ECHO",
+                                    "C-FIND",
+                                    "C-GET",
+                                    "C-MOVE",
+                                    "C-STORE",
+                                    "N-ACTION",
+                                    "N-EVENT-REPORT",
+                                ])
+                                .value_name("SERVICES"),
+                        )
+                        .arg(
+                            Arg::new("transcoding")
+                                .about("Allow transcoding of the instances sent to the modality")
+                                .takes_value(true)
+                                .long("transcoding")
+                                .possible_values(&["true", "false"])
+                                .value_name("BOOL"),
+                        )
+                        .arg(
+                            Arg::new("local_aet")
+                                .about("AET used by Orthanc when talking to the modality")
+                                .takes_value(true)
+                                .long("local-aet")
+                                .value_name("AET"),
+                        )
+                        .arg(
+                            Arg::new("timeout")
+                                .about("DIMSE timeout in seconds")
+                                .takes_value(true)
+                                .long("timeout")
+                                .validator(|v| v.parse::<u32>())
+                                .value_name("SECONDS"),
+                        )
+                        .arg(
+                            Arg::new("tls")
+                                .about("Use DICOM TLS")
+                                .takes_value(true)
+                                .long("tls")
+                                .possible_values(&["true", "false"])
+                                .value_name("BOOL"),
+                        )
+                        .arg(
+                            Arg::new("from_file")
+                                .about(concat!(
+                                    "Create the modalities defined in a YAML file, ",
+                                    "that maps modality names to their settings"
+                                ))
+                                .takes_value(true)
+                                .short('f')
+                                .long("from-file")
+                                .conflicts_with_all(&[
+                                    "name",
+                                    "aet",
+                                    "host",
+                                    "port",
+                                    "manufacturer",
+                                    "allow",
+                                    "deny",
+                                    "transcoding",
+                                    "local_aet",
+                                    "timeout",
+                                    "tls",
+                                ])
+                                .value_name("FILE"),
+                        ),
+                )
+                .subcommand(
+                    App::new("modify")
+                        .display_order(3)
+                        .about("Modify a modality. Settings, that are not specified, are kept as is")
+                        .arg(
+                            Arg::new("name")
+                                .about("Modality name")
+                                .required(true)
+                                .value_name("NAME"),
+                        )
+                        .arg(
+                            Arg::new("aet")
+                                .about("Modality AET")
+                                .takes_value(true)
+                                .short('a')
+                                .long("aet")
+                                .value_name("AET"),
                         )
                         .arg(
                             Arg::new("host")
@@ -1146,7 +2300,6 @@ pub fn build_cli() -> App<'static> {
                                 .takes_value(true)
                                 .short('h')
                                 .long("host")
-                                .required(true)
                                 .value_name("HOST"),
                         )
                         .arg(
@@ -1155,8 +2308,83 @@ pub fn build_cli() -> App<'static> {
                                 .takes_value(true)
                                 .short('p')
                                 .long("port")
-                                .required(true)
+                                .validator(|v| v.parse::<i32>())
                                 .value_name("PORT"),
+                        )
+                        .arg(
+                            Arg::new("manufacturer")
+                                .about("Modality manufacturer, e.g. Generic, GE, StoreScp")
+                                .takes_value(true)
+                                .short('m')
+                                .long("manufacturer")
+                                .value_name("MANUFACTURER"),
+                        )
+                        .arg(
+                            Arg::new("allow")
+                                .about("Allow DIMSE services. Space-separated values. Example: C-FIND C-MOVE")
+                                .takes_value(true)
+                                .long("allow")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .possible_values(&[
+                                    "C-ECHO",
+                                    "C-FIND",
+                                    "C-GET",
+                                    "C-MOVE",
+                                    "C-STORE",
+                                    "N-ACTION",
+                                    "N-EVENT-REPORT",
+                                ])
+                                .value_name("SERVICES"),
+                        )
+                        .arg(
+                            Arg::new("deny")
+                                .about("Deny DIMSE services. Space-separated values. Example: C-GET C-STORE")
+                                .takes_value(true)
+                                .long("deny")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .possible_values(&[
+                                    "C-ECHO",
+                                    "C-FIND",
+                                    "C-GET",
+                                    "C-MOVE",
+                                    "C-STORE",
+                                    "N-ACTION",
+                                    "N-EVENT-REPORT",
+                                ])
+                                .value_name("SERVICES"),
+                        )
+                        .arg(
+                            Arg::new("transcoding")
+                                .about("Allow transcoding of the instances sent to the modality")
+                                .takes_value(true)
+                                .long("transcoding")
+                                .possible_values(&["true", "false"])
+                                .value_name("BOOL"),
+                        )
+                        .arg(
+                            Arg::new("local_aet")
+                                .about("AET used by Orthanc when talking to the modality")
+                                .takes_value(true)
+                                .long("local-aet")
+                                .value_name("AET"),
+                        )
+                        .arg(
+                            Arg::new("timeout")
+                                .about("DIMSE timeout in seconds")
+                                .takes_value(true)
+                                .long("timeout")
+                                .validator(|v| v.parse::<u32>())
+                                .value_name("SECONDS"),
+                        )
+                        .arg(
+                            Arg::new("tls")
+                                .about("Use DICOM TLS")
+                                .takes_value(true)
+                                .long("tls")
+                                .possible_values(&["true", "false"])
+                                .value_name("BOOL"),
                         ),
                 )
                 .subcommand(
@@ -1168,11 +2396,146 @@ pub fn build_cli() -> App<'static> {
                                 .about("Modality name")
                                 .required(true)
                                 .value_name("NAME"),
+                        )
+                        .arg(
+                            Arg::new("timeout")
+                                .about("C-ECHO timeout in seconds")
+                                .takes_value(true)
+                                .short('t')
+                                .long("timeout")
+                                .validator(|v| v.parse::<u32>())
+                                .value_name("TIMEOUT"),
                         ),
                 )
                 .subcommand(
-                    App::new("store")
+                    App::new("query")
                         .display_order(5)
+                        .about(concat!(
+                            "Send a C-FIND request to a modality. The query ID is printed to stderr, ",
+                            "so that the answers can be retrieved later on",
+                        ))
+                        .arg(
+                            Arg::new("name")
+                                .about("Modality name")
+                                .required(true)
+                                .value_name("NAME"),
+                        )
+                        .arg(
+                            Arg::new("query")
+                                .about(concat!(
+                                    "Query terms. Space-separated pairs TagName=TagValue. Wildcards are allowed, ",
+                                    "dates and times can be ranges (e.g. StudyDate=20200101-20201231), ",
+                                    "a repeated tag matches any of its values. Example: PatientID=12345 StudyDate=20210101-",
+                                ))
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .value_name("QUERY"),
+                        )
+                        .arg(
+                            Arg::new("level")
+                                .about("Query level")
+                                .takes_value(true)
+                                .short('l')
+                                .long("level")
+                                .possible_values(&["patient", "study", "series", "instance"])
+                                .default_value("study")
+                                .value_name("LEVEL"),
+                        )
+                        .arg(
+                            Arg::new("no_header")
+                                .about("Don't display table header")
+                                .short('n')
+                                .long("no-header"),
+                        )
+                        .arg(
+                            Arg::new("columns")
+                                .about(
+                                    concat!(
+                                        "Display only the columns specified. Space-separated values. Any DICOM tag ",
+                                        "(keyword or gggg,eeee) can be used as a column. ",
+                                        "Example: Index PatientName",
+                                    )
+                                )
+                                .takes_value(true)
+                                .short('c')
+                                .long("columns")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .value_name("COLUMNS"),
+                        ),
+                )
+                .subcommand(
+                    App::new("retrieve")
+                        .display_order(6)
+                        .about(concat!(
+                            "Retrieve a study or a series from a modality with C-MOVE or C-GET, ",
+                            "either by its UIDs or as the answers to a query",
+                        ))
+                        .arg(
+                            Arg::new("name")
+                                .about("Modality name")
+                                .required(true)
+                                .value_name("NAME"),
+                        )
+                        .arg(
+                            Arg::new("study")
+                                .about("StudyInstanceUID of the study to retrieve")
+                                .takes_value(true)
+                                .long("study")
+                                .required_unless_present("query")
+                                .conflicts_with("query")
+                                .value_name("UID"),
+                        )
+                        .arg(
+                            Arg::new("series")
+                                .about("SeriesInstanceUID of the series to retrieve. Requires --study")
+                                .takes_value(true)
+                                .long("series")
+                                .requires("study")
+                                .value_name("UID"),
+                        )
+                        .arg(
+                            Arg::new("query")
+                                .about("ID of a query, as printed by `modality query`")
+                                .takes_value(true)
+                                .long("query")
+                                .value_name("ID"),
+                        )
+                        .arg(
+                            Arg::new("index")
+                                .about("Retrieve only the answer to the query with this index")
+                                .takes_value(true)
+                                .long("index")
+                                .requires("query")
+                                .validator(|v| v.parse::<usize>())
+                                .value_name("INDEX"),
+                        )
+                        .arg(
+                            Arg::new("target")
+                                .about("AET to move the resources to, instead of Orthanc")
+                                .takes_value(true)
+                                .short('t')
+                                .long("target")
+                                .conflicts_with("get")
+                                .value_name("AET"),
+                        )
+                        .arg(
+                            Arg::new("get")
+                                .about("Retrieve with C-GET instead of C-MOVE")
+                                .long("get"),
+                        )
+                        .arg(
+                            Arg::new("async")
+                                .about(concat!(
+                                    "Run as an Orthanc job and print the job ID ",
+                                    "instead of waiting for the result",
+                                ))
+                                .long("async"),
+                        ),
+                )
+                .subcommand(
+                    App::new("store")
+                        .display_order(7)
                         .about("Send a C-STORE request to a modality")
                         .arg(
                             Arg::new("name")
@@ -1182,7 +2545,10 @@ pub fn build_cli() -> App<'static> {
                         )
                         .arg(
                             Arg::new("ids")
-                                .about("Entity IDs")
+                                .about(concat!(
+                                    "Entity IDs, ",
+                                    "or - to read IDs from the standard input",
+                                ))
                                 .takes_value(true)
                                 .short('e')
                                 .long("entity-ids")
@@ -1190,11 +2556,19 @@ pub fn build_cli() -> App<'static> {
                                 .multiple_occurrences(true)
                                 .multiple_values(true)
                                 .value_name("IDS"),
+                        )
+                        .arg(
+                            Arg::new("async")
+                                .about(concat!(
+                                    "Run as an Orthanc job and print the job ID ",
+                                    "instead of waiting for the result",
+                                ))
+                                .long("async"),
                         ),
                 )
                 .subcommand(
                     App::new("delete")
-                        .display_order(6)
+                        .display_order(8)
                         .about("Delete modality")
                         .arg(
                             Arg::new("name")
@@ -1204,6 +2578,749 @@ pub fn build_cli() -> App<'static> {
                         ),
                 ),
         )
+        .subcommand(
+            App::new("tree")
+                .display_order(4)
+                .about(concat!(
+                    "Show the hierarchy of patients, studies, series and instances, ",
+                    "either of the whole server or of a single patient, study or series",
+                ))
+                .arg(
+                    Arg::new("patient")
+                        .about("Show the hierarchy of this patient")
+                        .takes_value(true)
+                        .long("patient")
+                        .conflicts_with_all(&["study", "series"])
+                        .value_name("ID"),
+                )
+                .arg(
+                    Arg::new("study")
+                        .about("Show the hierarchy of this study")
+                        .takes_value(true)
+                        .long("study")
+                        .conflicts_with("series")
+                        .value_name("ID"),
+                )
+                .arg(
+                    Arg::new("series")
+                        .about("Show the hierarchy of this series")
+                        .takes_value(true)
+                        .long("series")
+                        .value_name("ID"),
+                )
+                .arg(
+                    Arg::new("max_depth")
+                        .about(concat!(
+                            "Number of levels to show. For the whole server, 1 shows only patients, ",
+                            "2 shows patients and their studies and so on",
+                        ))
+                        .takes_value(true)
+                        .short('d')
+                        .long("max-depth")
+                        .validator(positive_integer)
+                        .value_name("DEPTH"),
+                ),
+        )
+        .subcommand(
+            App::new("delete")
+                .display_order(5)
+                .about(concat!(
+                    "Delete many patients, studies, series or instances at once: either those matching a ",
+                    "search query, or those with the IDs listed in a file",
+                ))
+                .arg(
+                    Arg::new("level")
+                        .about("Level of the entities to delete")
+                        .required(true)
+                        .takes_value(true)
+                        .short('l')
+                        .long("level")
+                        .possible_values(&["patient", "study", "series", "instance"])
+                        .value_name("LEVEL"),
+                )
+                .arg(
+                    Arg::new("query")
+                        .about(concat!(
+                            "Delete the entities matching these search query terms, as in search commands. ",
+                            "Example: StudyDescription=Test* StudyDate=-20200101",
+                        ))
+                        .takes_value(true)
+                        .short('q')
+                        .long("query")
+                        .multiple_occurrences(true)
+                        .multiple_values(true)
+                        .required_unless_present("ids_from")
+                        .conflicts_with("ids_from")
+                        .value_name("QUERY"),
+                )
+                .arg(
+                    Arg::new("ids_from")
+                        .about(concat!(
+                            "Delete the entities with the IDs listed in this file, one per line. ",
+                            "With -, the IDs are read from the standard input",
+                        ))
+                        .takes_value(true)
+                        .long("ids-from")
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new("case_sensitive")
+                        .about("Match person names (e.g. PatientName) case-sensitively")
+                        .long("case-sensitive")
+                        .requires("query"),
+                )
+                .arg(
+                    Arg::new("dry_run")
+                        .about("Only list the entities, that would be deleted")
+                        .long("dry-run"),
+                )
+                .arg(
+                    Arg::new("yes")
+                        .about("Delete without asking for confirmation")
+                        .short('y')
+                        .long("yes")
+                        .conflicts_with("dry_run"),
+                ),
+        )
+        .subcommand(
+            App::new("download")
+                .display_order(5)
+                .about(concat!(
+                    "Download many patients, studies or series at once, in parallel: either those ",
+                    "matching a search query, or those with the IDs listed in a file",
+                ))
+                .arg(
+                    Arg::new("level")
+                        .about("Level of the entities to download")
+                        .required(true)
+                        .takes_value(true)
+                        .short('l')
+                        .long("level")
+                        .possible_values(&["patient", "study", "series"])
+                        .value_name("LEVEL"),
+                )
+                .arg(
+                    Arg::new("query")
+                        .about(concat!(
+                            "Download the entities matching these search query terms, as in search ",
+                            "commands. Example: StudyDescription=Test* StudyDate=-20200101",
+                        ))
+                        .takes_value(true)
+                        .short('q')
+                        .long("query")
+                        .multiple_occurrences(true)
+                        .multiple_values(true)
+                        .required_unless_present("ids_from")
+                        .conflicts_with("ids_from")
+                        .value_name("QUERY"),
+                )
+                .arg(
+                    Arg::new("ids_from")
+                        .about(concat!(
+                            "Download the entities with the IDs listed in this file, one per line. ",
+                            "With -, the IDs are read from the standard input",
+                        ))
+                        .takes_value(true)
+                        .long("ids-from")
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new("case_sensitive")
+                        .about("Match person names (e.g. PatientName) case-sensitively")
+                        .long("case-sensitive")
+                        .requires("query"),
+                )
+                .arg(
+                    Arg::new("output_dir")
+                        .about("Directory to download the archives to")
+                        .required(true)
+                        .takes_value(true)
+                        .short('o')
+                        .long("output-dir")
+                        .value_name("DIR"),
+                )
+                .arg(
+                    Arg::new("name_template")
+                        .about(concat!(
+                            "Name the archives after this template, relative to the output directory. ",
+                            "{ID} is replaced with the ID of the entity, and {Tag} with the value of the ",
+                            "DICOM tag (a keyword or gggg,eeee). ",
+                            "Example: {PatientID}/{StudyDate}_{StudyDescription}.zip",
+                        ))
+                        .takes_value(true)
+                        .short('t')
+                        .long("name-template")
+                        .default_value("{ID}.zip")
+                        .value_name("TEMPLATE"),
+                )
+                .arg(
+                    Arg::new("media")
+                        .about("Download DICOM media (with a DICOMDIR) instead of plain archives")
+                        .short('m')
+                        .long("media"),
+                )
+                .arg(
+                    Arg::new("parallel")
+                        .about("Number of entities to download at the same time")
+                        .takes_value(true)
+                        .short('p')
+                        .long("parallel")
+                        .default_value("1")
+                        .validator(positive_integer)
+                        .value_name("N"),
+                )
+                .arg(
+                    Arg::new("retries")
+                        .about(concat!(
+                            "Number of times to retry a download, that failed because of a server or a ",
+                            "network error. The delay between the retries starts at one second and ",
+                            "doubles with every retry",
+                        ))
+                        .takes_value(true)
+                        .short('r')
+                        .long("retries")
+                        .default_value("3")
+                        .validator(|v| v.parse::<u32>())
+                        .value_name("N"),
+                ),
+        )
+        .subcommand(
+            App::new("peer")
+                .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                .display_order(6)
+                .about("Peer-level commands")
+                .subcommand(
+                    App::new("list")
+                        .display_order(0)
+                        .about("List all peers")
+                        .arg(
+                            Arg::new("no_header")
+                                .about("Don't display table header")
+                                .short('n')
+                                .long("no-header"),
+                        )
+                        .arg(
+                            Arg::new("columns")
+                                .about(
+                                    concat!(
+                                        "Display only the columns specified. Space-separated values. ",
+                                        "Example: Name URL",
+                                    )
+                                )
+                                .takes_value(true)
+                                .short('c')
+                                .long("columns")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .value_name("COLUMNS"),
+                        )
+                )
+                .subcommand(
+                    App::new("show")
+                        .display_order(1)
+                        .about("Show peer details")
+                        .arg(
+                            Arg::new("name")
+                                .about("Peer name")
+                                .required(true)
+                                .value_name("NAME"),
+                        ),
+                )
+                .subcommand(
+                    App::new("create")
+                        .display_order(2)
+                        .about("Create a peer")
+                        .arg(
+                            Arg::new("name")
+                                .about("Peer name")
+                                .required(true)
+                                .value_name("NAME"),
+                        )
+                        .arg(
+                            Arg::new("url")
+                                .about("URL of the peer's REST API")
+                                .takes_value(true)
+                                .long("url")
+                                .required(true)
+                                .value_name("URL"),
+                        )
+                        .arg(
+                            Arg::new("username")
+                                .about("Username on the peer")
+                                .takes_value(true)
+                                .short('u')
+                                .long("username")
+                                .value_name("USERNAME"),
+                        )
+                        .arg(
+                            Arg::new("password")
+                                .about("Password on the peer")
+                                .takes_value(true)
+                                .short('p')
+                                .long("password")
+                                .value_name("PASSWORD"),
+                        )
+                        .arg(
+                            Arg::new("header")
+                                .about("HTTP header to send to the peer. Can be used multiple times")
+                                .takes_value(true)
+                                .short('H')
+                                .long("header")
+                                .multiple_occurrences(true)
+                                .value_name("NAME:VALUE"),
+                        )
+                        .arg(
+                            Arg::new("certificate_file")
+                                .about("Client certificate (PEM) to authenticate to the peer with")
+                                .takes_value(true)
+                                .long("certificate-file")
+                                .value_name("PATH"),
+                        )
+                        .arg(
+                            Arg::new("certificate_key_file")
+                                .about("Private key (PEM) of the client certificate")
+                                .takes_value(true)
+                                .long("certificate-key-file")
+                                .value_name("PATH"),
+                        )
+                        .arg(
+                            Arg::new("certificate_key_password")
+                                .about("Password of the private key")
+                                .takes_value(true)
+                                .long("certificate-key-password")
+                                .value_name("PASSWORD"),
+                        )
+                        .arg(
+                            Arg::new("timeout")
+                                .about("HTTP timeout in seconds")
+                                .takes_value(true)
+                                .long("timeout")
+                                .validator(|v| v.parse::<u32>())
+                                .value_name("SECONDS"),
+                        ),
+                )
+                .subcommand(
+                    App::new("modify")
+                        .display_order(3)
+                        .about(concat!(
+                            "Modify a peer. Settings, that are not specified, are kept as is, ",
+                            "except for the password and the HTTP headers, which Orthanc does not disclose",
+                        ))
+                        .arg(
+                            Arg::new("name")
+                                .about("Peer name")
+                                .required(true)
+                                .value_name("NAME"),
+                        )
+                        .arg(
+                            Arg::new("url")
+                                .about("URL of the peer's REST API")
+                                .takes_value(true)
+                                .long("url")
+                                .value_name("URL"),
+                        )
+                        .arg(
+                            Arg::new("username")
+                                .about("Username on the peer")
+                                .takes_value(true)
+                                .short('u')
+                                .long("username")
+                                .value_name("USERNAME"),
+                        )
+                        .arg(
+                            Arg::new("password")
+                                .about("Password on the peer")
+                                .takes_value(true)
+                                .short('p')
+                                .long("password")
+                                .value_name("PASSWORD"),
+                        )
+                        .arg(
+                            Arg::new("header")
+                                .about("HTTP header to send to the peer. Can be used multiple times")
+                                .takes_value(true)
+                                .short('H')
+                                .long("header")
+                                .multiple_occurrences(true)
+                                .value_name("NAME:VALUE"),
+                        )
+                        .arg(
+                            Arg::new("certificate_file")
+                                .about("Client certificate (PEM) to authenticate to the peer with")
+                                .takes_value(true)
+                                .long("certificate-file")
+                                .value_name("PATH"),
+                        )
+                        .arg(
+                            Arg::new("certificate_key_file")
+                                .about("Private key (PEM) of the client certificate")
+                                .takes_value(true)
+                                .long("certificate-key-file")
+                                .value_name("PATH"),
+                        )
+                        .arg(
+                            Arg::new("certificate_key_password")
+                                .about("Password of the private key")
+                                .takes_value(true)
+                                .long("certificate-key-password")
+                                .value_name("PASSWORD"),
+                        )
+                        .arg(
+                            Arg::new("timeout")
+                                .about("HTTP timeout in seconds")
+                                .takes_value(true)
+                                .long("timeout")
+                                .validator(|v| v.parse::<u32>())
+                                .value_name("SECONDS"),
+                        ),
+                )
+                .subcommand(
+                    App::new("system")
+                        .display_order(4)
+                        .about("Show system information of a peer")
+                        .arg(
+                            Arg::new("name")
+                                .about("Peer name")
+                                .required(true)
+                                .value_name("NAME"),
+                        ),
+                )
+                .subcommand(
+                    App::new("store")
+                        .display_order(5)
+                        .about("Send entities to a peer")
+                        .arg(
+                            Arg::new("name")
+                                .about("Peer name")
+                                .required(true)
+                                .value_name("NAME"),
+                        )
+                        .arg(
+                            Arg::new("ids")
+                                .about(concat!(
+                                    "Entity IDs, ",
+                                    "or - to read IDs from the standard input",
+                                ))
+                                .takes_value(true)
+                                .short('e')
+                                .long("entity-ids")
+                                .required(true)
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .value_name("IDS"),
+                        )
+                        .arg(
+                            Arg::new("transcode")
+                                .about(concat!(
+                                    "Transcode the instances to this transfer syntax before sending them. ",
+                                    "Example: 1.2.840.10008.1.2.4.70",
+                                ))
+                                .takes_value(true)
+                                .long("transcode")
+                                .value_name("TRANSFER_SYNTAX_UID"),
+                        )
+                        .arg(
+                            Arg::new("compress")
+                                .about("Compress the HTTP requests sent to the peer")
+                                .long("compress"),
+                        )
+                        .arg(
+                            Arg::new("async")
+                                .about(concat!(
+                                    "Run as an Orthanc job and print the job ID ",
+                                    "instead of waiting for the result",
+                                ))
+                                .long("async"),
+                        ),
+                )
+                .subcommand(
+                    App::new("delete")
+                        .display_order(6)
+                        .about("Delete peer")
+                        .arg(
+                            Arg::new("name")
+                                .about("Peer name")
+                                .required(true)
+                                .value_name("NAME"),
+                        ),
+                ),
+        )
+        .subcommand(
+            App::new("job")
+                .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                .display_order(7)
+                .about("Job-level commands")
+                .subcommand(
+                    App::new("list")
+                        .display_order(0)
+                        .about("List all jobs")
+                        .arg(
+                            Arg::new("no_header")
+                                .about("Don't display table header")
+                                .short('n')
+                                .long("no-header"),
+                        )
+                        .arg(
+                            Arg::new("columns")
+                                .about(
+                                    concat!(
+                                        "Display only the columns specified. Space-separated values. ",
+                                        "Example: ID State",
+                                    )
+                                )
+                                .takes_value(true)
+                                .short('c')
+                                .long("columns")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .value_name("COLUMNS"),
+                        )
+                )
+                .subcommand(
+                    App::new("show")
+                        .display_order(1)
+                        .about("Show job details")
+                        .arg(
+                            Arg::new("id")
+                                .about("Job ID")
+                                .required(true)
+                                .value_name("ID"),
+                        ),
+                )
+                .subcommand(
+                    App::new("wait")
+                        .display_order(2)
+                        .about("Wait for a job to complete, showing its progress")
+                        .arg(
+                            Arg::new("id")
+                                .about("Job ID")
+                                .required(true)
+                                .value_name("ID"),
+                        )
+                        .arg(
+                            Arg::new("interval")
+                                .about("Polling interval in seconds")
+                                .takes_value(true)
+                                .short('i')
+                                .long("interval")
+                                .default_value("1")
+                                .validator(|v| v.parse::<u64>())
+                                .value_name("SECONDS"),
+                        )
+                        .arg(
+                            Arg::new("job_timeout")
+                                .about("Give up, if the job does not complete in time")
+                                .takes_value(true)
+                                .short('t')
+                                .long("timeout")
+                                .validator(|v| v.parse::<u64>())
+                                .value_name("SECONDS"),
+                        ),
+                )
+                .subcommand(
+                    App::new("cancel")
+                        .display_order(3)
+                        .about("Cancel job")
+                        .arg(
+                            Arg::new("id")
+                                .about("Job ID")
+                                .required(true)
+                                .value_name("ID"),
+                        ),
+                )
+                .subcommand(
+                    App::new("pause")
+                        .display_order(4)
+                        .about("Pause job")
+                        .arg(
+                            Arg::new("id")
+                                .about("Job ID")
+                                .required(true)
+                                .value_name("ID"),
+                        ),
+                )
+                .subcommand(
+                    App::new("resume")
+                        .display_order(5)
+                        .about("Resume a paused job")
+                        .arg(
+                            Arg::new("id")
+                                .about("Job ID")
+                                .required(true)
+                                .value_name("ID"),
+                        ),
+                )
+                .subcommand(
+                    App::new("resubmit")
+                        .display_order(6)
+                        .about("Resubmit a failed or canceled job")
+                        .arg(
+                            Arg::new("id")
+                                .about("Job ID")
+                                .required(true)
+                                .value_name("ID"),
+                        ),
+                ),
+        )
+        .subcommand(
+            App::new("config")
+                .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                .display_order(8)
+                .about("Configuration profiles")
+                .subcommand(
+                    App::new("list")
+                        .display_order(0)
+                        .about("List all profiles")
+                        .arg(
+                            Arg::new("no_header")
+                                .about("Don't display table header")
+                                .short('n')
+                                .long("no-header"),
+                        ),
+                )
+                .subcommand(
+                    App::new("show")
+                        .display_order(1)
+                        .about("Show profile details")
+                        .arg(
+                            Arg::new("name")
+                                .about("Profile name")
+                                .required(true)
+                                .value_name("NAME"),
+                        ),
+                )
+                .subcommand(
+                    App::new("add")
+                        .display_order(2)
+                        .about("Add a profile, or replace an existing one with the same name")
+                        .arg(
+                            Arg::new("name")
+                                .about("Profile name")
+                                .required(true)
+                                .value_name("NAME"),
+                        )
+                        .arg(
+                            Arg::new("server")
+                                .about("Orthanc server address")
+                                .takes_value(true)
+                                .short('s')
+                                .long("server")
+                                .required(true)
+                                .value_name("SERVER"),
+                        )
+                        .arg(
+                            Arg::new("username")
+                                .about("Orthanc username")
+                                .takes_value(true)
+                                .short('u')
+                                .long("username")
+                                .value_name("USERNAME"),
+                        )
+                        .arg(
+                            Arg::new("password")
+                                .about(concat!(
+                                    "Ask for the Orthanc password. It is read from the standard ",
+                                    "input, if that is not a terminal",
+                                ))
+                                .short('p')
+                                .long("password"),
+                        )
+                        .arg(
+                            Arg::new("token")
+                                .about(concat!(
+                                    "Ask for the Orthanc bearer token. It is read from the standard ",
+                                    "input, if that is not a terminal (after the password)",
+                                ))
+                                .long("token"),
+                        )
+                        .arg(
+                            Arg::new("header")
+                                .about(concat!(
+                                    "Custom HTTP header to send with every request. ",
+                                    "Can be used multiple times. Example: X-Api-Key:secret",
+                                ))
+                                .takes_value(true)
+                                .short('H')
+                                .long("header")
+                                .multiple_occurrences(true)
+                                .value_name("NAME:VALUE"),
+                        )
+                        .arg(
+                            Arg::new("ca_cert")
+                                .about("PEM-encoded CA certificate to verify the server certificate with")
+                                .takes_value(true)
+                                .long("ca-cert")
+                                .value_name("PATH"),
+                        )
+                        .arg(
+                            Arg::new("client_cert")
+                                .about("PEM-encoded client certificate (for mutual TLS)")
+                                .takes_value(true)
+                                .long("client-cert")
+                                .requires("client_key")
+                                .value_name("PATH"),
+                        )
+                        .arg(
+                            Arg::new("client_key")
+                                .about("PEM-encoded private key of the client certificate")
+                                .takes_value(true)
+                                .long("client-key")
+                                .requires("client_cert")
+                                .value_name("PATH"),
+                        )
+                        .arg(
+                            Arg::new("client_pkcs12")
+                                .about(concat!(
+                                    "PKCS#12 archive with the client certificate and its private key, ",
+                                    "instead of --client-cert and --client-key",
+                                ))
+                                .takes_value(true)
+                                .long("client-pkcs12")
+                                .conflicts_with_all(&["client_cert", "client_key"])
+                                .value_name("PATH"),
+                        )
+                        .arg(
+                            Arg::new("client_pkcs12_password")
+                                .about(concat!(
+                                    "Ask for the password of the PKCS#12 archive. It is read from the ",
+                                    "standard input, if that is not a terminal (after the token)",
+                                ))
+                                .long("client-pkcs12-password")
+                                .requires("client_pkcs12"),
+                        )
+                        .arg(
+                            Arg::new("insecure")
+                                .about("Don't verify the server certificate")
+                                .long("insecure"),
+                        )
+                        .arg(
+                            Arg::new("timeout")
+                                .about("Request timeout in seconds")
+                                .takes_value(true)
+                                .long("timeout")
+                                .validator(|v| v.parse::<u64>())
+                                .value_name("SECONDS"),
+                        )
+                        .arg(
+                            Arg::new("proxy")
+                                .about("Proxy URL for all requests")
+                                .takes_value(true)
+                                .long("proxy")
+                                .value_name("URL"),
+                        ),
+                )
+                .subcommand(
+                    App::new("remove")
+                        .display_order(3)
+                        .about("Remove profile")
+                        .arg(
+                            Arg::new("name")
+                                .about("Profile name")
+                                .required(true)
+                                .value_name("NAME"),
+                        ),
+                ),
+        )
 }
 
 #[cfg(test)]
@@ -1218,19 +3335,19 @@ mod tests {
         let mut w = Vec::new();
         let mut app = build_cli();
 
-        write!(&mut w, "========== {} ==========\n", app.get_name()).unwrap();
+        writeln!(&mut w, "========== {} ==========", app.get_name()).unwrap();
         app.write_help(&mut w).unwrap();
-        write!(&mut w, "\n").unwrap();
+        writeln!(&mut w).unwrap();
 
         for sc in app.get_subcommands_mut() {
-            write!(&mut w, "========== {} ==========\n", sc.get_name()).unwrap();
+            writeln!(&mut w, "========== {} ==========", sc.get_name()).unwrap();
             sc.write_help(&mut w).unwrap();
-            write!(&mut w, "\n").unwrap();
+            writeln!(&mut w).unwrap();
 
             for nested_sc in sc.get_subcommands_mut() {
-                write!(&mut w, "========== {} ==========\n", nested_sc.get_name()).unwrap();
+                writeln!(&mut w, "========== {} ==========", nested_sc.get_name()).unwrap();
                 nested_sc.write_help(&mut w).unwrap();
-                write!(&mut w, "\n").unwrap();
+                writeln!(&mut w).unwrap();
             }
         }
 
@@ -1246,4 +3363,45 @@ mod tests {
             include_str!("../tests/data/all_help.stdout").trim(),
         );
     }
+
+    #[test]
+    fn test_format_option() {
+        let format = |args: Vec<&str>| {
+            build_cli()
+                .try_get_matches_from(args)
+                .unwrap()
+                .value_of("format")
+                .map(String::from)
+        };
+        assert_eq!(format(vec!["orthanc", "patient", "list"]).unwrap(), "table");
+        assert_eq!(
+            format(vec!["orthanc", "-F", "json", "patient", "list"]).unwrap(),
+            "json"
+        );
+        assert_eq!(
+            format(vec!["orthanc", "patient", "list", "-F", "csv"]).unwrap(),
+            "csv"
+        );
+        assert_eq!(
+            format(vec!["orthanc", "study", "show", "foo", "--format", "yaml"]).unwrap(),
+            "yaml"
+        );
+        // -o remains the output path of the commands, that write files
+        let matches = build_cli()
+            .try_get_matches_from(vec![
+                "orthanc",
+                "study",
+                "download",
+                "foo",
+                "-o",
+                "study.zip",
+                "-F",
+                "json",
+            ])
+            .unwrap();
+        assert_eq!(matches.value_of("format").unwrap(), "json");
+        let (_, study) = matches.subcommand().unwrap();
+        let (_, download) = study.subcommand().unwrap();
+        assert_eq!(download.value_of("output").unwrap(), "study.zip");
+    }
 }
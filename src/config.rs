@@ -0,0 +1,297 @@
+use crate::models::*;
+use crate::{CliError, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs::{DirBuilder, OpenOptions};
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+pub const PROFILES_HEADER: [&str; 3] = ["Name", "Server", "Username"];
+
+/// The configuration file, holding named connection profiles
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct Config {
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// Connection settings of a single Orthanc server
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct Profile {
+    pub server: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Custom headers as `Name:Value`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub headers: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_cert: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_cert: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_pkcs12: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_pkcs12_password: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub insecure: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+}
+
+/// The path of the configuration file: `ORC_CONFIG`, if set, otherwise
+/// `$XDG_CONFIG_HOME/orthanc-cli/config.yaml` or `~/.config/orthanc-cli/config.yaml`
+pub fn config_path() -> Result<PathBuf> {
+    if let Ok(p) = env::var("ORC_CONFIG") {
+        return Ok(PathBuf::from(p));
+    }
+    let config_dir = match env::var("XDG_CONFIG_HOME") {
+        Ok(d) if !d.is_empty() => PathBuf::from(d),
+        _ => match env::var("HOME").or_else(|_| env::var("USERPROFILE")) {
+            Ok(h) => PathBuf::from(h).join(".config"),
+            Err(e) => {
+                return Err(CliError::new(
+                    "Command error",
+                    Some("Could not determine the location of the configuration file"),
+                    Some(&format!("{}. Set ORC_CONFIG to the path of the file", e)),
+                ))
+            }
+        },
+    };
+    Ok(config_dir.join("orthanc-cli").join("config.yaml"))
+}
+
+impl Config {
+    /// Reads the configuration file. A missing file is treated as an empty configuration
+    pub fn load(path: &Path) -> Result<Config> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let config: Config = serde_yaml::from_slice(&fs::read(path)?)?;
+        Ok(config)
+    }
+
+    /// Writes the configuration file, creating its directory if needed. As the file holds
+    /// credentials, only its owner may read it (and the directory, if it is created)
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            let mut builder = DirBuilder::new();
+            builder.recursive(true);
+            #[cfg(unix)]
+            builder.mode(0o700);
+            builder.create(dir)?;
+        }
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let mut file = options.open(path)?;
+        // The mode only applies to a new file, not to one, that existed already
+        #[cfg(unix)]
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        file.write_all(serde_yaml::to_string(self)?.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn profile(&self, name: &str) -> Result<&Profile> {
+        self.profiles
+            .get(name)
+            .ok_or_else(|| profile_not_found_error(name))
+    }
+
+    pub fn list_profiles(&self) -> EntityList {
+        let rows = self
+            .profiles
+            .iter()
+            .map(|(name, p)| {
+                let mut row = Row::new();
+                row.insert("Name".to_string(), Value::from(name.as_str()));
+                row.insert("Server".to_string(), Value::from(p.server.as_str()));
+                row.insert("Username".to_string(), Value::from(p.username.clone()));
+                row
+            })
+            .collect();
+        EntityList {
+            columns: PROFILES_HEADER.iter().map(ToString::to_string).collect(),
+            rows,
+        }
+    }
+
+    /// Profile details. The password is masked
+    pub fn show_profile(&self, name: &str) -> Result<EntityDetails> {
+        let p = self.profile(name)?;
+        let mut fields = Row::new();
+        fields.insert("Name".to_string(), Value::from(name));
+        fields.insert("Server".to_string(), Value::from(p.server.as_str()));
+        fields.insert("Username".to_string(), Value::from(p.username.clone()));
+        fields.insert(
+            "Password".to_string(),
+            Value::from(p.password.as_ref().map(|_| "********")),
+        );
+        Ok(EntityDetails { fields })
+    }
+
+    /// Adds a profile, replacing an existing one with the same name
+    pub fn add_profile(&mut self, name: &str, profile: Profile) {
+        self.profiles.insert(name.to_string(), profile);
+    }
+
+    pub fn remove_profile(&mut self, name: &str) -> Result<()> {
+        self.profiles
+            .remove(name)
+            .map(|_| ())
+            .ok_or_else(|| profile_not_found_error(name))
+    }
+}
+
+/// Loads the profile selected by `--profile` or `ORC_PROFILE`, if any
+pub fn get_profile(cmd_option: Option<&str>) -> Result<Option<Profile>> {
+    let name = match cmd_option {
+        Some(n) => n.to_string(),
+        None => match env::var("ORC_PROFILE") {
+            Ok(n) => n,
+            Err(_) => return Ok(None),
+        },
+    };
+    let mut config = Config::load(&config_path()?)?;
+    match config.profiles.remove(&name) {
+        Some(p) => Ok(Some(p)),
+        None => Err(profile_not_found_error(&name)),
+    }
+}
+
+fn profile_not_found_error(name: &str) -> CliError {
+    CliError::new(
+        "Command error",
+        Some(&format!("Profile not found: {}", name)),
+        Some("See `orthanc config list` for the available profiles"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn profile(server: &str, username: Option<&str>) -> Profile {
+        Profile {
+            server: server.to_string(),
+            username: username.map(String::from),
+            password: username.map(|_| "secret".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_load_missing_file() {
+        let dir = TempDir::new().unwrap();
+        assert_eq!(
+            Config::load(&dir.path().join("config.yaml")).unwrap(),
+            Config::default()
+        );
+    }
+
+    #[test]
+    fn test_save_load() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("orthanc-cli").join("config.yaml");
+        let mut config = Config::default();
+        config.add_profile("dev", profile("http://localhost:8042", None));
+        config.add_profile("pacs", profile("https://pacs", Some("rick")));
+        config.save(&path).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            concat!(
+                "---\n",
+                "profiles:\n",
+                "  dev:\n",
+                "    server: \"http://localhost:8042\"\n",
+                "  pacs:\n",
+                "    server: \"https://pacs\"\n",
+                "    username: rick\n",
+                "    password: secret\n",
+            )
+        );
+        assert_eq!(Config::load(&path).unwrap(), config);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_save_permissions() {
+        let dir = TempDir::new().unwrap();
+        let config_dir = dir.path().join("orthanc-cli");
+        let path = config_dir.join("config.yaml");
+        let mode = |p: &Path| fs::metadata(p).unwrap().permissions().mode() & 0o777;
+        let config = Config::default();
+        config.save(&path).unwrap();
+        assert_eq!(mode(&config_dir), 0o700);
+        assert_eq!(mode(&path), 0o600);
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        config.save(&path).unwrap();
+        assert_eq!(mode(&path), 0o600);
+    }
+
+    #[test]
+    fn test_load_invalid_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.yaml");
+        fs::write(&path, "profiles:\n  dev:\n    username: rick\n").unwrap();
+        assert!(Config::load(&path).is_err());
+    }
+
+    #[test]
+    fn test_list_show_profiles() {
+        let mut config = Config::default();
+        config.add_profile("pacs", profile("https://pacs", Some("rick")));
+        config.add_profile("dev", profile("http://localhost:8042", None));
+        assert_eq!(
+            serde_json::to_value(config.list_profiles()).unwrap(),
+            json!([
+                {"Name": "dev", "Server": "http://localhost:8042", "Username": null},
+                {"Name": "pacs", "Server": "https://pacs", "Username": "rick"},
+            ])
+        );
+        assert_eq!(
+            serde_json::to_value(config.show_profile("pacs").unwrap()).unwrap(),
+            json!({
+                "Name": "pacs",
+                "Server": "https://pacs",
+                "Username": "rick",
+                "Password": "********"
+            })
+        );
+        assert_eq!(
+            config.show_profile("foo").unwrap_err(),
+            profile_not_found_error("foo")
+        );
+    }
+
+    #[test]
+    fn test_remove_profile() {
+        let mut config = Config::default();
+        config.add_profile("dev", profile("http://localhost:8042", None));
+        config.remove_profile("dev").unwrap();
+        assert_eq!(config, Config::default());
+        assert_eq!(
+            config.remove_profile("dev").unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Profile not found: dev"),
+                Some("See `orthanc config list` for the available profiles"),
+            )
+        );
+    }
+}
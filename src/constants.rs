@@ -1,6 +1,10 @@
+use std::time::Duration;
+
 pub const TABLE_PRESET: &str = "     --            ";
 pub const ID_COLUMN_WIDTH: u16 = 46;
 pub const ABSENT_DICOM_TAG_PLACEHOLDER: &str = "undefined";
+pub const DICOM_PREAMBLE_LENGTH: usize = 128;
+pub const UPLOAD_FAILED_STATUS: &str = "Failed";
 
 pub const PATIENTS_LIST_HEADER: &[&str] =
     &["ID", "PatientID", "PatientName", "Number of Studies"];
@@ -80,5 +84,130 @@ pub const INSTANCE_DICOM_TAGS: &[&str] = &[
     "InstanceCreationTime",
 ];
 
+pub const TREE_PATIENT_DICOM_TAGS: &[&str] = &["PatientName", "PatientID"];
+pub const TREE_STUDY_DICOM_TAGS: &[&str] = &["StudyDate", "StudyDescription"];
+pub const TREE_SERIES_DICOM_TAGS: &[&str] = &["Modality", "SeriesDescription"];
+pub const TREE_INSTANCE_DICOM_TAGS: &[&str] = &["InstanceNumber"];
+
 pub const MODALITIES_LIST_HEADER: &[&str] =
     &["Name", "AET", "Host", "Port", "Manufacturer"];
+
+pub const PEERS_LIST_HEADER: &[&str] = &["Name", "URL", "Username"];
+
+pub const QUERY_ANSWER_INDEX_COLUMN: &str = "Index";
+pub const RETRIEVE_UID_TAGS: &[&str] =
+    &["StudyInstanceUID", "SeriesInstanceUID", "SOPInstanceUID"];
+
+pub const UPLOAD_RESULTS_HEADER: &[&str] = &[
+    "File",
+    "Status",
+    "ID",
+    "Series ID",
+    "Study ID",
+    "Patient ID",
+    "Error",
+];
+
+pub const DELETE_RESULTS_HEADER: &[&str] = &["ID", "Status", "Error"];
+pub const DELETED_STATUS: &str = "Deleted";
+pub const DELETE_FAILED_STATUS: &str = "Failed";
+
+pub const JOBS_LIST_HEADER: &[&str] = &["ID", "Type", "State", "Progress", "Creation time"];
+pub const JOB_STATE_SUCCESS: &str = "Success";
+pub const JOB_STATE_FAILURE: &str = "Failure";
+pub const JOB_STATE_PAUSED: &str = "Paused";
+
+pub const PARTIAL_FILE_SUFFIX: &str = ".part";
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+pub const NAME_TEMPLATE_FIELDS: &[&str] = &["ID"];
+pub const DOWNLOAD_RESULTS_HEADER: &[&str] =
+    &["ID", "File", "Status", "Size", "Duration", "Error"];
+pub const DOWNLOADED_STATUS: &str = "Downloaded";
+pub const DOWNLOAD_FAILED_STATUS: &str = "Failed";
+pub const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+/// How reqwest describes the errors of the connection or the transfer
+pub const TRANSPORT_ERRORS: &[&str] = &[
+    "error sending request",
+    "request or response body error",
+    "error decoding response body",
+];
+pub const STREAM_BUFFER_SIZE: usize = 64 * 1024;
+
+pub const ANONYMIZATION_PROFILES: &[&str] = &[
+    "basic",
+    "retain-dates",
+    "retain-device-identity",
+    "retain-uids",
+    "clean-descriptions",
+];
+/// Retain Longitudinal Temporal Information With Full Dates Option of DICOM PS3.15
+pub const RETAIN_DATES_KEEP: &[&str] = &[
+    "StudyDate",
+    "StudyTime",
+    "SeriesDate",
+    "SeriesTime",
+    "AcquisitionDate",
+    "AcquisitionTime",
+    "AcquisitionDateTime",
+    "ContentDate",
+    "ContentTime",
+    "InstanceCreationDate",
+    "InstanceCreationTime",
+    "PerformedProcedureStepStartDate",
+    "PerformedProcedureStepStartTime",
+];
+/// Retain Device Identity Option of DICOM PS3.15
+pub const RETAIN_DEVICE_IDENTITY_KEEP: &[&str] = &[
+    "StationName",
+    "DeviceSerialNumber",
+    "DeviceUID",
+    "PlateID",
+    "GeneratorID",
+    "CassetteID",
+    "GantryID",
+    "DetectorID",
+];
+/// Retain UIDs Option of DICOM PS3.15
+pub const RETAIN_UIDS_KEEP: &[&str] = &[
+    "StudyInstanceUID",
+    "SeriesInstanceUID",
+    "SOPInstanceUID",
+    "FrameOfReferenceUID",
+];
+/// Clean Descriptors Option of DICOM PS3.15. The descriptions, which the basic profile removes,
+/// are kept, with their values replaced with [`CLEANED_DESCRIPTION`]
+pub const CLEAN_DESCRIPTIONS_REPLACE: &[&str] = &[
+    "StudyDescription",
+    "SeriesDescription",
+    "ProtocolName",
+    "ImageComments",
+    "PatientComments",
+    "AdditionalPatientHistory",
+    "AdmittingDiagnosesDescription",
+    "DerivationDescription",
+    "RequestedProcedureDescription",
+    "PerformedProcedureStepDescription",
+];
+/// A description, that is known not to identify the patient, and is valid for any of the VRs of
+/// the descriptions (SH, LO, ST and LT)
+pub const CLEANED_DESCRIPTION: &str = "CLEANED";
+
+pub const PSEUDONYM_LENGTH: usize = 16;
+pub const MAPPING_FILE_HEADER: &[&str] = &[
+    "Timestamp",
+    "Level",
+    "Original ID",
+    "New ID",
+    "Original PatientID",
+    "New PatientID",
+    "Original PatientName",
+    "New PatientName",
+    "Original StudyInstanceUID",
+    "New StudyInstanceUID",
+    "Original AccessionNumber",
+    "New AccessionNumber",
+];
+
+/// Number of entities to fetch the `RequestedTags` of with a single `/tools/find` request
+pub const REQUESTED_TAGS_BATCH_SIZE: usize = 100;
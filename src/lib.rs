@@ -1,16 +1,27 @@
-use comfy_table::Table;
+use chrono::{SecondsFormat, Utc};
+use client::{Client, TransportOptions};
 use constants::*;
-use orthanc::client::Client;
+use models::*;
 use orthanc::entity::*;
 use orthanc::error::Error;
-use orthanc::models::Modality;
+use orthanc::models::{ModalityStoreResult, ModificationResult, PeerStoreResult};
 use serde_json::Value;
-use serde_yaml;
-use std::{fs, io, result};
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering::SeqCst};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use std::{fs, io, result, thread};
 use utils::*;
+use zip::result::ZipError;
+use zip::ZipArchive;
 
 pub mod cli;
+pub mod client;
+pub mod config;
 mod constants;
+pub mod models;
 pub mod utils;
 
 pub type Result<T> = result::Result<T, CliError>;
@@ -35,20 +46,37 @@ impl CliError {
             details: details.map(String::from),
         }
     }
+
+    /// Whether the error is likely to go away, if the request is retried: a server error, or an
+    /// error of the connection or the transfer, as opposed to a client error (e.g. 404) or a
+    /// local error (e.g. a full disk)
+    pub fn is_transient(&self) -> bool {
+        match self.error.strip_prefix("API error: ") {
+            Some(status) => {
+                status.starts_with('5')
+                    || status.starts_with("408")
+                    || status.starts_with("429")
+            }
+            None => TRANSPORT_ERRORS.iter().any(|e| self.error.starts_with(e)),
+        }
+    }
+
+    /// Names the entity, that the error is about, in the details, unless these are set already
+    pub fn with_entity(mut self, kind: &EntityKind, id: &str) -> CliError {
+        self.details
+            .get_or_insert_with(|| format!("{:?} {}", kind, id));
+        self
+    }
 }
 
 impl From<Error> for CliError {
     fn from(e: Error) -> Self {
         let mut err = CliError::new(&e.message.to_string(), None, None);
-        match e.details {
-            Some(d) => {
-                err.message = Some(d.message);
-                match d.details {
-                    Some(d) => err.details = Some(d),
-                    None => (),
-                }
+        if let Some(d) = e.details {
+            err.message = Some(d.message);
+            if let Some(d) = d.details {
+                err.details = Some(d)
             }
-            None => (),
         };
         err
     }
@@ -60,6 +88,24 @@ impl From<serde_yaml::Error> for CliError {
     }
 }
 
+impl From<serde_json::Error> for CliError {
+    fn from(e: serde_json::Error) -> Self {
+        CliError::new(&e.to_string(), None, None)
+    }
+}
+
+impl From<csv::Error> for CliError {
+    fn from(e: csv::Error) -> Self {
+        CliError::new(&e.to_string(), None, None)
+    }
+}
+
+impl From<ZipError> for CliError {
+    fn from(e: ZipError) -> Self {
+        CliError::new(&e.to_string(), None, None)
+    }
+}
+
 impl From<io::Error> for CliError {
     fn from(e: io::Error) -> Self {
         CliError::new(&e.to_string(), None, None)
@@ -71,56 +117,226 @@ impl Orthanc {
         server_address: String,
         username: Option<String>,
         password: Option<String>,
+        token: Option<String>,
+        headers: Vec<(String, String)>,
+        transport: TransportOptions,
     ) -> Result<Orthanc> {
-        let mut client = Client::new(server_address);
+        let mut client = Client::with_transport(server_address, &transport)?;
         client = match (username, password) {
             (Some(u), Some(p)) => client.auth(u, p),
             _ => client,
         };
+        if let Some(t) = token {
+            client = client.token(t);
+        }
+        for (name, value) in headers {
+            client = client.header(name, value);
+        }
         Ok(Orthanc { client })
     }
 
+    /// Builds a list of entities. DICOM tags, that are not among the entities' main DICOM tags,
+    /// are fetched from the server
+    fn entity_list<T: Entity>(
+        &self,
+        entities: Vec<T>,
+        header: &[&str],
+        dicom_tags: &[&str],
+        default_dicom_tags: &[&str],
+    ) -> Result<EntityList> {
+        let extra_tags: Vec<&str> = dicom_tags
+            .iter()
+            .filter(|t| !default_dicom_tags.contains(t))
+            .copied()
+            .collect();
+        let missing_tags: Vec<Vec<&str>> = entities
+            .iter()
+            .map(|e| {
+                extra_tags
+                    .iter()
+                    .filter(|t| e.main_dicom_tag(t).is_none())
+                    .copied()
+                    .collect()
+            })
+            .collect();
+        let requested_tags = self.requested_tags(&entities, &missing_tags)?;
+
+        let mut extra_values = vec![];
+        for (entity, missing_tags) in entities.iter().zip(missing_tags) {
+            let mut values = Row::new();
+            if !missing_tags.is_empty() {
+                match requested_tags.get(entity.id()) {
+                    Some(tags) => {
+                        for t in missing_tags {
+                            values.insert(t.to_string(), requested_tag_value(tags, t));
+                        }
+                    }
+                    None => {
+                        let tags = self.entity_tags::<T>(entity.id())?;
+                        for t in missing_tags {
+                            values.insert(t.to_string(), find_tag_value(&tags, t));
+                        }
+                    }
+                }
+            }
+            extra_values.push(values);
+        }
+
+        let mut list = EntityList::new(entities, header, dicom_tags);
+        for (row, values) in list.rows.iter_mut().zip(extra_values) {
+            row.extend(values);
+        }
+        Ok(list)
+    }
+
+    /// Fetches the DICOM tags, that are missing from the main DICOM tags of the entities, with
+    /// `/tools/find`, looking the entities up by their identifying tag (e.g. `StudyInstanceUID`)
+    /// in batches of [`REQUESTED_TAGS_BATCH_SIZE`]. Returns the `RequestedTags` by entity ID.
+    /// Entities, that can not be looked up this way, are left out, and so are all of them, if
+    /// the server does not support `RequestedTags`
+    fn requested_tags<T: Entity>(
+        &self,
+        entities: &[T],
+        missing_tags: &[Vec<&str>],
+    ) -> Result<HashMap<String, Value>> {
+        let mut requested_tags: Vec<String> = vec![];
+        for tag in missing_tags.iter().flatten().map(|t| tag_keyword(t)) {
+            if !requested_tags.contains(&tag) {
+                requested_tags.push(tag);
+            }
+        }
+        let mut result = HashMap::new();
+        if requested_tags.is_empty() {
+            return Ok(result);
+        }
+
+        let identifier_tag = entity_identifier_tag(&T::kind());
+        let mut identifiers: Vec<&str> = vec![];
+        for (entity, missing_tags) in entities.iter().zip(missing_tags) {
+            match entity.main_dicom_tag(identifier_tag) {
+                // Orthanc would treat these as a list of values or as wildcards
+                Some(i)
+                    if !missing_tags.is_empty()
+                        && !i.is_empty()
+                        && !i.contains(&['\\', '*', '?'][..])
+                        && !identifiers.contains(&i) =>
+                {
+                    identifiers.push(i)
+                }
+                _ => (),
+            }
+        }
+
+        for batch in identifiers.chunks(REQUESTED_TAGS_BATCH_SIZE) {
+            let mut query = HashMap::new();
+            query.insert(identifier_tag.to_string(), batch.join("\\"));
+            let found =
+                self.client
+                    .search_requested_tags(T::kind(), query, &requested_tags)?;
+            if !found.is_empty() && found.iter().all(|e| e.get("RequestedTags").is_none()) {
+                return Ok(HashMap::new());
+            }
+            for entity in found {
+                if let (Some(id), Some(tags)) =
+                    (entity["ID"].as_str(), entity.get("RequestedTags"))
+                {
+                    result.insert(id.to_string(), tags.clone());
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Builds a list of entities, sorted and narrowed down to the range requested in `options`.
+    /// An unsorted list is narrowed down first, so that the extra DICOM tags are only fetched for
+    /// the entities shown
+    fn sorted_entity_list<T: Entity>(
+        &self,
+        entities: Vec<T>,
+        header: &[&str],
+        dicom_tags: &[&str],
+        default_dicom_tags: &[&str],
+        options: &ListOptions,
+    ) -> Result<EntityList> {
+        match &options.sort_by {
+            Some(sort_by) => {
+                let mut list =
+                    self.entity_list(entities, header, dicom_tags, default_dicom_tags)?;
+                sort_rows(&mut list.rows, sort_by);
+                list.rows = options.page(list.rows);
+                Ok(list)
+            }
+            None => self.entity_list(
+                options.page(entities),
+                header,
+                dicom_tags,
+                default_dicom_tags,
+            ),
+        }
+    }
+
+    fn entity_tags<T: Entity>(&self, id: &str) -> Result<Value> {
+        match T::kind() {
+            EntityKind::Patient => self.client.patient_shared_tags(id),
+            EntityKind::Study => self.client.study_shared_tags(id),
+            EntityKind::Series => self.client.series_shared_tags(id),
+            EntityKind::Instance => self.client.instance_tags_expanded(id),
+        }
+        .map_err(Into::<_>::into)
+    }
+
     ////////// PATIENT //////////
 
     pub fn list_patients(
         &self,
         columns: Option<Vec<&str>>,
-        no_header: bool,
-    ) -> Result<Table> {
+        options: ListOptions,
+    ) -> Result<EntityList> {
         let header = &mut PATIENTS_LIST_HEADER.to_vec();
         let dicom_tags = &mut PATIENTS_LIST_DICOM_TAGS.to_vec();
         get_header_and_dicom_tags(header, dicom_tags, columns)?;
-
-        Ok(utils::create_list_table(
-            self.client.patients_expanded()?,
-            header,
-            dicom_tags,
-            no_header,
-        ))
+        check_sort_column(header, &options)?;
+
+        match options.server_range() {
+            Some((since, limit)) => self.entity_list(
+                self.client.patients_expanded_range(since, limit)?,
+                header,
+                dicom_tags,
+                PATIENTS_LIST_DICOM_TAGS,
+            ),
+            None => self.sorted_entity_list(
+                self.client.patients_expanded()?,
+                header,
+                dicom_tags,
+                PATIENTS_LIST_DICOM_TAGS,
+                &options,
+            ),
+        }
     }
 
-    pub fn show_patient(&self, patient_id: &str) -> Result<Table> {
-        Ok(create_show_table(
+    pub fn show_patient(&self, patient_id: &str) -> Result<EntityDetails> {
+        Ok(EntityDetails::new(
             self.client.patient(patient_id)?,
-            &PATIENT_DICOM_TAGS,
+            PATIENT_DICOM_TAGS,
         ))
     }
 
+    /// Anonymizes a patient with `anonymization` (see [`get_anonymization_config`]), or with the
+    /// defaults of Orthanc if it is `None`
     pub fn anonymize_patient(
         &self,
         id: &str,
-        replace: Option<Vec<&str>>,
-        keep: Option<Vec<&str>>,
-        keep_private_tags: Option<bool>,
-        config_file: Option<&str>,
-    ) -> Result<Table> {
-        match self.client.anonymize_patient(
-            id,
-            get_anonymization_config(replace, keep, keep_private_tags, config_file)?,
-        ) {
-            Ok(r) => Ok(create_new_entity_table(r)),
-            Err(e) => Err(e.into()),
+        anonymization: Option<Anonymization>,
+        asynchronous: bool,
+    ) -> Result<Outcome<ModificationResult>> {
+        if asynchronous {
+            return Ok(Outcome::Submitted(
+                self.client.anonymize_patient_async(id, anonymization)?,
+            ));
         }
+        Ok(Outcome::Completed(
+            self.client.anonymize_patient(id, anonymization)?,
+        ))
     }
 
     pub fn modify_patient(
@@ -129,21 +345,21 @@ impl Orthanc {
         replace: Option<Vec<&str>>,
         remove: Option<Vec<&str>>,
         config_file: Option<&str>,
-    ) -> Result<Table> {
-        match self
-            .client
-            .modify_patient(id, get_modification_config(replace, remove, config_file)?)
-        {
-            Ok(r) => Ok(create_new_entity_table(r)),
-            Err(e) => Err(e.into()),
+        asynchronous: bool,
+    ) -> Result<Outcome<ModificationResult>> {
+        let modification = get_modification_config(replace, remove, config_file)?;
+        if asynchronous {
+            return Ok(Outcome::Submitted(
+                self.client.modify_patient_async(id, modification)?,
+            ));
         }
+        Ok(Outcome::Completed(
+            self.client.modify_patient(id, modification)?,
+        ))
     }
 
     pub fn download_patient(&self, id: &str, output_file: &str) -> Result<()> {
-        let mut file = fs::File::create(output_file)?;
-        self.client
-            .patient_dicom(id, &mut file)
-            .map_err(Into::<_>::into)
+        self.download_archive(&EntityKind::Patient, id, output_file, false, |_| ())
     }
 
     pub fn delete_patient(&self, id: &str) -> Result<()> {
@@ -159,49 +375,62 @@ impl Orthanc {
         &self,
         patient_id: Option<&str>,
         columns: Option<Vec<&str>>,
-        no_header: bool,
-    ) -> Result<Table> {
+        options: ListOptions,
+    ) -> Result<EntityList> {
         let header = &mut STUDIES_LIST_HEADER.to_vec();
         let dicom_tags = &mut STUDIES_LIST_DICOM_TAGS.to_vec();
         get_header_and_dicom_tags(header, dicom_tags, columns)?;
-
-        if let Some(pid) = patient_id {
-            self.client.patient(pid)?; // Check if the patient exists
+        check_sort_column(header, &options)?;
+
+        // Children are fetched from their parent, which, unlike filtering all the entities of
+        // the server, scales with the size of the parent. An unknown parent results in 404
+        match (patient_id, options.server_range()) {
+            (Some(id), _) => self.sorted_entity_list(
+                self.client.patient_studies(id)?,
+                header,
+                dicom_tags,
+                STUDIES_LIST_DICOM_TAGS,
+                &options,
+            ),
+            (None, Some((since, limit))) => self.entity_list(
+                self.client.studies_expanded_range(since, limit)?,
+                header,
+                dicom_tags,
+                STUDIES_LIST_DICOM_TAGS,
+            ),
+            (None, None) => self.sorted_entity_list(
+                self.client.studies_expanded()?,
+                header,
+                dicom_tags,
+                STUDIES_LIST_DICOM_TAGS,
+                &options,
+            ),
         }
-
-        let mut studies = self.client.studies_expanded()?;
-
-        if let Some(pid) = patient_id {
-            studies.retain(|s| s.parent_id().unwrap() == pid);
-        };
-
-        Ok(utils::create_list_table(
-            studies, header, dicom_tags, no_header,
-        ))
     }
 
-    pub fn show_study(&self, study_id: &str) -> Result<Table> {
-        Ok(create_show_table(
+    pub fn show_study(&self, study_id: &str) -> Result<EntityDetails> {
+        Ok(EntityDetails::new(
             self.client.study(study_id)?,
-            &STUDY_DICOM_TAGS,
+            STUDY_DICOM_TAGS,
         ))
     }
 
+    /// Anonymizes a study with `anonymization` (see [`get_anonymization_config`]), or with the
+    /// defaults of Orthanc if it is `None`
     pub fn anonymize_study(
         &self,
         id: &str,
-        replace: Option<Vec<&str>>,
-        keep: Option<Vec<&str>>,
-        keep_private_tags: Option<bool>,
-        config_file: Option<&str>,
-    ) -> Result<Table> {
-        match self.client.anonymize_study(
-            id,
-            get_anonymization_config(replace, keep, keep_private_tags, config_file)?,
-        ) {
-            Ok(r) => Ok(create_new_entity_table(r)),
-            Err(e) => Err(e.into()),
+        anonymization: Option<Anonymization>,
+        asynchronous: bool,
+    ) -> Result<Outcome<ModificationResult>> {
+        if asynchronous {
+            return Ok(Outcome::Submitted(
+                self.client.anonymize_study_async(id, anonymization)?,
+            ));
         }
+        Ok(Outcome::Completed(
+            self.client.anonymize_study(id, anonymization)?,
+        ))
     }
 
     pub fn modify_study(
@@ -210,21 +439,21 @@ impl Orthanc {
         replace: Option<Vec<&str>>,
         remove: Option<Vec<&str>>,
         config_file: Option<&str>,
-    ) -> Result<Table> {
-        match self
-            .client
-            .modify_study(id, get_modification_config(replace, remove, config_file)?)
-        {
-            Ok(r) => Ok(create_new_entity_table(r)),
-            Err(e) => Err(e.into()),
+        asynchronous: bool,
+    ) -> Result<Outcome<ModificationResult>> {
+        let modification = get_modification_config(replace, remove, config_file)?;
+        if asynchronous {
+            return Ok(Outcome::Submitted(
+                self.client.modify_study_async(id, modification)?,
+            ));
         }
+        Ok(Outcome::Completed(
+            self.client.modify_study(id, modification)?,
+        ))
     }
 
     pub fn download_study(&self, id: &str, output_file: &str) -> Result<()> {
-        let mut file = fs::File::create(output_file)?;
-        self.client
-            .study_dicom(id, &mut file)
-            .map_err(Into::<_>::into)
+        self.download_archive(&EntityKind::Study, id, output_file, false, |_| ())
     }
 
     pub fn delete_study(&self, id: &str) -> Result<()> {
@@ -240,49 +469,60 @@ impl Orthanc {
         &self,
         study_id: Option<&str>,
         columns: Option<Vec<&str>>,
-        no_header: bool,
-    ) -> Result<Table> {
+        options: ListOptions,
+    ) -> Result<EntityList> {
         let header = &mut SERIES_LIST_HEADER.to_vec();
         let dicom_tags = &mut SERIES_LIST_DICOM_TAGS.to_vec();
         get_header_and_dicom_tags(header, dicom_tags, columns)?;
-
-        if let Some(pid) = study_id {
-            self.client.study(pid)?; // Check if the study exists
+        check_sort_column(header, &options)?;
+
+        match (study_id, options.server_range()) {
+            (Some(id), _) => self.sorted_entity_list(
+                self.client.study_series(id)?,
+                header,
+                dicom_tags,
+                SERIES_LIST_DICOM_TAGS,
+                &options,
+            ),
+            (None, Some((since, limit))) => self.entity_list(
+                self.client.series_expanded_range(since, limit)?,
+                header,
+                dicom_tags,
+                SERIES_LIST_DICOM_TAGS,
+            ),
+            (None, None) => self.sorted_entity_list(
+                self.client.series_expanded()?,
+                header,
+                dicom_tags,
+                SERIES_LIST_DICOM_TAGS,
+                &options,
+            ),
         }
-
-        let mut series = self.client.series_expanded()?;
-
-        if let Some(sid) = study_id {
-            series.retain(|s| s.parent_id().unwrap() == sid);
-        };
-
-        Ok(utils::create_list_table(
-            series, header, dicom_tags, no_header,
-        ))
     }
 
-    pub fn show_series(&self, series_id: &str) -> Result<Table> {
-        Ok(create_show_table(
+    pub fn show_series(&self, series_id: &str) -> Result<EntityDetails> {
+        Ok(EntityDetails::new(
             self.client.series(series_id)?,
-            &SERIES_DICOM_TAGS,
+            SERIES_DICOM_TAGS,
         ))
     }
 
+    /// Anonymizes a series with `anonymization` (see [`get_anonymization_config`]), or with the
+    /// defaults of Orthanc if it is `None`
     pub fn anonymize_series(
         &self,
         id: &str,
-        replace: Option<Vec<&str>>,
-        keep: Option<Vec<&str>>,
-        keep_private_tags: Option<bool>,
-        config_file: Option<&str>,
-    ) -> Result<Table> {
-        match self.client.anonymize_series(
-            id,
-            get_anonymization_config(replace, keep, keep_private_tags, config_file)?,
-        ) {
-            Ok(r) => Ok(create_new_entity_table(r)),
-            Err(e) => Err(e.into()),
+        anonymization: Option<Anonymization>,
+        asynchronous: bool,
+    ) -> Result<Outcome<ModificationResult>> {
+        if asynchronous {
+            return Ok(Outcome::Submitted(
+                self.client.anonymize_series_async(id, anonymization)?,
+            ));
         }
+        Ok(Outcome::Completed(
+            self.client.anonymize_series(id, anonymization)?,
+        ))
     }
 
     pub fn modify_series(
@@ -291,21 +531,21 @@ impl Orthanc {
         replace: Option<Vec<&str>>,
         remove: Option<Vec<&str>>,
         config_file: Option<&str>,
-    ) -> Result<Table> {
-        match self
-            .client
-            .modify_series(id, get_modification_config(replace, remove, config_file)?)
-        {
-            Ok(r) => Ok(create_new_entity_table(r)),
-            Err(e) => Err(e.into()),
+        asynchronous: bool,
+    ) -> Result<Outcome<ModificationResult>> {
+        let modification = get_modification_config(replace, remove, config_file)?;
+        if asynchronous {
+            return Ok(Outcome::Submitted(
+                self.client.modify_series_async(id, modification)?,
+            ));
         }
+        Ok(Outcome::Completed(
+            self.client.modify_series(id, modification)?,
+        ))
     }
 
     pub fn download_series(&self, id: &str, output_file: &str) -> Result<()> {
-        let mut file = fs::File::create(output_file)?;
-        self.client
-            .series_dicom(id, &mut file)
-            .map_err(Into::<_>::into)
+        self.download_archive(&EntityKind::Series, id, output_file, false, |_| ())
     }
 
     pub fn delete_series(&self, id: &str) -> Result<()> {
@@ -321,51 +561,57 @@ impl Orthanc {
         &self,
         series_id: Option<&str>,
         columns: Option<Vec<&str>>,
-        no_header: bool,
-    ) -> Result<Table> {
+        options: ListOptions,
+    ) -> Result<EntityList> {
         let header = &mut INSTANCES_LIST_HEADER.to_vec();
         let dicom_tags = &mut INSTANCES_LIST_DICOM_TAGS.to_vec();
         get_header_and_dicom_tags(header, dicom_tags, columns)?;
-
-        if let Some(pid) = series_id {
-            self.client.series(pid)?; // Check if the series exists
+        check_sort_column(header, &options)?;
+
+        match (series_id, options.server_range()) {
+            (Some(id), _) => self.sorted_entity_list(
+                self.client.series_instances(id)?,
+                header,
+                dicom_tags,
+                INSTANCES_LIST_DICOM_TAGS,
+                &options,
+            ),
+            (None, Some((since, limit))) => self.entity_list(
+                self.client.instances_expanded_range(since, limit)?,
+                header,
+                dicom_tags,
+                INSTANCES_LIST_DICOM_TAGS,
+            ),
+            (None, None) => self.sorted_entity_list(
+                self.client.instances_expanded()?,
+                header,
+                dicom_tags,
+                INSTANCES_LIST_DICOM_TAGS,
+                &options,
+            ),
         }
-
-        let mut instances = self.client.instances_expanded()?;
-
-        if let Some(sid) = series_id {
-            instances.retain(|s| s.parent_id().unwrap() == sid);
-        };
-
-        Ok(utils::create_list_table(
-            instances, header, dicom_tags, no_header,
-        ))
     }
 
-    pub fn show_instance(&self, instance_id: &str) -> Result<Table> {
-        Ok(create_show_table(
+    pub fn show_instance(&self, instance_id: &str) -> Result<EntityDetails> {
+        Ok(EntityDetails::new(
             self.client.instance(instance_id)?,
-            &INSTANCE_DICOM_TAGS,
+            INSTANCE_DICOM_TAGS,
         ))
     }
 
+    /// Anonymizes an instance with `anonymization` (see [`get_anonymization_config`]), and
+    /// writes the anonymized DICOM file to `path` (see [`write_output`])
     pub fn anonymize_instance(
         &self,
         id: &str,
-        replace: Option<Vec<&str>>,
-        keep: Option<Vec<&str>>,
-        keep_private_tags: Option<bool>,
-        config_file: Option<&str>,
+        anonymization: Option<Anonymization>,
         path: &str,
     ) -> Result<()> {
-        let mut file = fs::File::create(path)?;
-        self.client
-            .anonymize_instance(
-                id,
-                get_anonymization_config(replace, keep, keep_private_tags, config_file)?,
-                &mut file,
-            )
-            .map_err(Into::<_>::into)
+        write_output(path, |w| {
+            self.client
+                .anonymize_instance(id, anonymization, w)
+                .map_err(Into::<_>::into)
+        })
     }
 
     pub fn modify_instance(
@@ -376,21 +622,27 @@ impl Orthanc {
         config_file: Option<&str>,
         path: &str,
     ) -> Result<()> {
-        let mut file = fs::File::create(path)?;
-        self.client
-            .modify_instance(
-                id,
-                get_modification_config(replace, remove, config_file)?,
-                &mut file,
-            )
-            .map_err(Into::<_>::into)
+        let config = get_modification_config(replace, remove, config_file)?;
+        write_output(path, |w| {
+            self.client
+                .modify_instance(id, config, w)
+                .map_err(Into::<_>::into)
+        })
     }
 
-    pub fn download_instance(&self, id: &str, output_file: &str) -> Result<()> {
-        let mut file = fs::File::create(output_file)?;
-        self.client
-            .instance_dicom(id, &mut file)
-            .map_err(Into::<_>::into)
+    /// Downloads an instance to `output_file` (see [`write_output`]), reporting the number of
+    /// bytes received so far to `on_progress`
+    pub fn download_instance<F: FnMut(u64)>(
+        &self,
+        id: &str,
+        output_file: &str,
+        on_progress: F,
+    ) -> Result<()> {
+        write_output(output_file, |w| {
+            self.client
+                .instance_dicom(id, Progress::new(w, on_progress))
+                .map_err(Into::<_>::into)
+        })
     }
 
     pub fn delete_instance(&self, id: &str) -> Result<()> {
@@ -400,292 +652,1078 @@ impl Orthanc {
         }
     }
 
-    pub fn show_instance_tags(&self, id: &str) -> Result<Table> {
-        match self.client.instance_tags_expanded(id) {
-            Ok(tags) => {
-                let mut table = create_table(None);
-                match tags {
-                    Value::Object(map) => {
-                        for (k, v) in map.iter() {
-                            match v {
-                                Value::Object(map1) => match map1.get("Value").unwrap() {
-                                    // Only one-level (String) values are supported
-                                    Value::String(value) => {
-                                        match map1.get("Name").unwrap() {
-                                            Value::String(name) => {
-                                                table.add_row(vec![k, name, value].iter())
-                                            }
-                                            _ => &table,
-                                        };
-                                    }
-                                    _ => (),
-                                },
-                                _ => (),
-                            }
-                        }
+    /// Uploads DICOM files to Orthanc. `paths` can be files, directories or ZIP archives. A
+    /// failure to upload a file does not stop the upload, instead it is reported in the results
+    pub fn upload(&self, paths: Vec<&str>) -> Result<EntityList> {
+        let mut rows = vec![];
+        for path in paths {
+            for (file, found) in find_files(Path::new(path)) {
+                let file_name = file.to_string_lossy().to_string();
+                match found.and_then(|_| fs::read(&file)) {
+                    Ok(data) if is_zip_archive(&data) => {
+                        rows.extend(self.upload_zip_archive(&file_name, data));
                     }
-                    _ => (),
+                    Ok(data) => rows.push(create_upload_row(
+                        &file_name,
+                        self.client.upload(data).map_err(Into::<_>::into),
+                    )),
+                    Err(e) => rows.push(create_upload_row(&file_name, Err(e.into()))),
+                };
+            }
+        }
+        Ok(EntityList {
+            columns: UPLOAD_RESULTS_HEADER
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            rows,
+        })
+    }
+
+    fn upload_zip_archive(&self, file_name: &str, data: Vec<u8>) -> Vec<Row> {
+        let mut archive = match ZipArchive::new(Cursor::new(data)) {
+            Ok(a) => a,
+            Err(e) => return vec![create_upload_row(file_name, Err(e.into()))],
+        };
+        let mut rows = vec![];
+        for i in 0..archive.len() {
+            let mut entry = match archive.by_index(i) {
+                Ok(e) => e,
+                Err(e) => {
+                    rows.push(create_upload_row(file_name, Err(e.into())));
+                    continue;
                 }
-                Ok(table)
+            };
+            if entry.is_dir() {
+                continue;
             }
-            Err(e) => Err(e.into()),
+            let entry_name = format!("{}/{}", file_name, entry.name());
+            let mut data = vec![];
+            let result = match entry.read_to_end(&mut data) {
+                Ok(_) => self.client.upload(data).map_err(Into::<_>::into),
+                Err(e) => Err(e.into()),
+            };
+            rows.push(create_upload_row(&entry_name, result));
         }
+        rows
     }
 
-    ////////// MODALITY //////////
+    pub fn show_instance_tags(
+        &self,
+        id: &str,
+        groups: Option<Vec<&str>>,
+        tags: Option<Vec<&str>>,
+        meta: bool,
+    ) -> Result<Vec<InstanceTag>> {
+        check_tag_filters(&groups, &tags)?;
+        let mut result = vec![];
+        if meta {
+            result.extend(InstanceTag::from_expanded_tags(
+                &self.client.instance_header(id)?,
+            ));
+        }
+        result.extend(InstanceTag::from_expanded_tags(
+            &self.client.instance_tags_expanded(id)?,
+        ));
+        Ok(filter_instance_tags(result, groups, tags))
+    }
 
-    pub fn do_store(&self, modality: &str, ids: &Vec<&str>) -> Result<Table> {
-        match self.client.modality_store(modality, ids) {
-            Ok(r) => {
-                let mut table = create_table(None);
-                table.add_row(["Remote AET", &r.remote_aet].iter());
-                table.add_row(["Instances sent", &format!("{}", r.instances_count)].iter());
-                table.add_row(
-                    ["Instances failed", &format!("{}", r.failed_instances_count)].iter(),
-                );
-                Ok(table)
+    ////////// TREE //////////
+
+    /// The hierarchy of the whole server (if `root` is `None`), or of a single patient, study or
+    /// series. `max_depth` is the number of levels to descend to, counting the top one
+    pub fn tree(
+        &self,
+        root: Option<(EntityKind, &str)>,
+        max_depth: Option<usize>,
+    ) -> Result<Vec<TreeNode>> {
+        let depth = max_depth.unwrap_or(usize::MAX);
+        match root {
+            None => self
+                .client
+                .patients_expanded()?
+                .iter()
+                .map(|p| self.tree_node(p, depth))
+                .collect(),
+            Some((EntityKind::Patient, id)) => {
+                Ok(vec![self.tree_node(&self.client.patient(id)?, depth)?])
+            }
+            Some((EntityKind::Study, id)) => {
+                Ok(vec![self.tree_node(&self.client.study(id)?, depth)?])
+            }
+            Some((EntityKind::Series, id)) => {
+                Ok(vec![self.tree_node(&self.client.series(id)?, depth)?])
+            }
+            Some((EntityKind::Instance, id)) => {
+                Ok(vec![self.tree_node(&self.client.instance(id)?, depth)?])
+            }
+        }
+    }
+
+    /// A node of the tree, descending `depth - 1` levels below the entity
+    fn tree_node<T: Entity>(&self, entity: &T, depth: usize) -> Result<TreeNode> {
+        let id = entity.id();
+        let mut node = match T::kind() {
+            EntityKind::Patient => TreeNode::new(entity, TREE_PATIENT_DICOM_TAGS),
+            EntityKind::Study => TreeNode::new(entity, TREE_STUDY_DICOM_TAGS),
+            EntityKind::Series => TreeNode::new(entity, TREE_SERIES_DICOM_TAGS),
+            EntityKind::Instance => TreeNode::new(entity, TREE_INSTANCE_DICOM_TAGS),
+        };
+        if depth <= 1 {
+            return Ok(node);
+        }
+        node.children = match T::kind() {
+            EntityKind::Patient => self
+                .client
+                .patient_studies(id)?
+                .iter()
+                .map(|s| self.tree_node(s, depth - 1))
+                .collect::<Result<_>>()?,
+            EntityKind::Study => self
+                .client
+                .study_series(id)?
+                .iter()
+                .map(|s| self.tree_node(s, depth - 1))
+                .collect::<Result<_>>()?,
+            EntityKind::Series => self
+                .client
+                .series_instances(id)?
+                .iter()
+                .map(|i| self.tree_node(i, depth - 1))
+                .collect::<Result<_>>()?,
+            EntityKind::Instance => vec![],
+        };
+        Ok(node)
+    }
+
+    ////////// PSEUDONYMIZATION //////////
+
+    /// The identifiers of a patient, or of the study of a study or a series, that are recorded
+    /// in a pseudonymization mapping
+    pub fn entity_identifiers(&self, kind: &EntityKind, id: &str) -> Result<Identifiers> {
+        fn tag<T: Entity>(entity: &T, tag: &str) -> Option<String> {
+            entity.main_dicom_tag(tag).map(String::from)
+        }
+        match kind {
+            EntityKind::Patient => {
+                let patient: Patient = self.client.entity(id)?;
+                Ok(Identifiers {
+                    patient_id: tag(&patient, "PatientID"),
+                    patient_name: tag(&patient, "PatientName"),
+                    ..Default::default()
+                })
+            }
+            _ => {
+                let study: Study = self.client.ancestor(kind, id)?;
+                Ok(Identifiers {
+                    patient_id: tag(&study, "PatientID"),
+                    patient_name: tag(&study, "PatientName"),
+                    study_instance_uid: tag(&study, "StudyInstanceUID"),
+                    accession_number: tag(&study, "AccessionNumber"),
+                })
             }
-            Err(e) => Err(e.into()),
         }
     }
 
-    pub fn list_modalities(
+    /// Appends the original identifiers of an anonymized entity, and those of its anonymized
+    /// copy with `new_id`, to a CSV pseudonymization mapping file
+    pub fn record_mapping(
         &self,
-        columns: Option<Vec<&str>>,
-        no_header: bool,
-    ) -> Result<Table> {
-        let header = &mut MODALITIES_LIST_HEADER.to_vec();
-        let mut real_header = None;
+        mapping_file: &str,
+        kind: &EntityKind,
+        id: &str,
+        new_id: &str,
+        original: &Identifiers,
+    ) -> Result<()> {
+        let new = self.entity_identifiers(kind, new_id)?;
+        let timestamp = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+        append_mapping_record(
+            Path::new(mapping_file),
+            &mapping_record(&timestamp, kind, id, new_id, original, &new),
+        )
+    }
 
-        if let Some(c) = &columns {
-            check_columns_option(&header, &c)?;
+    ////////// ARCHIVE //////////
+
+    /// Downloads a patient, study or series to `output_file` (see [`write_output`]) as a ZIP
+    /// archive of DICOM files, or of DICOM media (with a DICOMDIR) if `media` is set. The number
+    /// of bytes received so far is reported to `on_progress`
+    pub fn download_archive<F: FnMut(u64)>(
+        &self,
+        kind: &EntityKind,
+        id: &str,
+        output_file: &str,
+        media: bool,
+        on_progress: F,
+    ) -> Result<()> {
+        write_output(output_file, |w| {
+            self.client
+                .archive(kind, id, media, Progress::new(w, on_progress))
+                .map_err(Into::<_>::into)
+        })
+    }
+
+    /// Downloads a patient, study or series and extracts its archive into `dir`, as it is being
+    /// downloaded. See [`extract_archive`] for how the files are named. Returns the number of
+    /// files extracted
+    pub fn extract<F: FnMut(u64)>(
+        &self,
+        kind: &EntityKind,
+        id: &str,
+        dir: &str,
+        path_template: Option<&str>,
+        media: bool,
+        on_progress: F,
+    ) -> Result<usize> {
+        if let Some(t) = path_template {
+            check_path_template(t)?;
+        }
+        let archive = self.client.archive_reader(kind, id, media)?;
+        extract_archive(
+            Progress::new(archive, on_progress),
+            Path::new(dir),
+            path_template,
+        )
+    }
+
+    ////////// BULK //////////
+
+    /// Lists the entities of `level`, that are selected for a bulk operation. Unlike search, a
+    /// query is matched at `level` itself. An unknown ID fails the whole selection
+    pub fn select_entities(
+        &self,
+        level: &EntityKind,
+        selection: &EntitySelection,
+        columns: Option<Vec<&str>>,
+    ) -> Result<EntityList> {
+        let (header, dicom_tags) = match level {
+            EntityKind::Patient => (PATIENTS_LIST_HEADER, PATIENTS_LIST_DICOM_TAGS),
+            EntityKind::Study => (STUDIES_LIST_HEADER, STUDIES_LIST_DICOM_TAGS),
+            EntityKind::Series => (SERIES_LIST_HEADER, SERIES_LIST_DICOM_TAGS),
+            EntityKind::Instance => (INSTANCES_LIST_HEADER, INSTANCES_LIST_DICOM_TAGS),
         };
+        let header = &mut header.to_vec();
+        let list_dicom_tags = &mut dicom_tags.to_vec();
+        get_header_and_dicom_tags(header, list_dicom_tags, columns)?;
+        match level {
+            EntityKind::Patient => self.entity_list(
+                self.selected_entities::<Patient>(selection)?,
+                header,
+                list_dicom_tags,
+                dicom_tags,
+            ),
+            EntityKind::Study => self.entity_list(
+                self.selected_entities::<Study>(selection)?,
+                header,
+                list_dicom_tags,
+                dicom_tags,
+            ),
+            EntityKind::Series => self.entity_list(
+                self.selected_entities::<Series>(selection)?,
+                header,
+                list_dicom_tags,
+                dicom_tags,
+            ),
+            EntityKind::Instance => self.entity_list(
+                self.selected_entities::<Instance>(selection)?,
+                header,
+                list_dicom_tags,
+                dicom_tags,
+            ),
+        }
+    }
+
+    fn selected_entities<T: Entity>(&self, selection: &EntitySelection) -> Result<Vec<T>> {
+        match selection {
+            EntitySelection::Query(q) => Ok(self.client.search(q)?),
+            EntitySelection::Ids(ids) => ids
+                .iter()
+                .map(|id| {
+                    self.client
+                        .entity(id)
+                        .map_err(|e| CliError::from(e).with_entity(&T::kind(), id))
+                })
+                .collect(),
+        }
+    }
+
+    /// Deletes entities one by one. A failure does not stop the deletion, instead it is reported
+    /// in the results
+    pub fn delete_entities(&self, level: &EntityKind, ids: &[&str]) -> EntityList {
+        let rows = ids
+            .iter()
+            .map(|id| {
+                let result = match level {
+                    EntityKind::Patient => self.client.delete_patient(id),
+                    EntityKind::Study => self.client.delete_study(id),
+                    EntityKind::Series => self.client.delete_series(id),
+                    EntityKind::Instance => self.client.delete_instance(id),
+                };
+                create_delete_row(id, result.map(|_| ()).map_err(Into::<_>::into))
+            })
+            .collect();
+        EntityList {
+            columns: DELETE_RESULTS_HEADER
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            rows,
+        }
+    }
 
-        let modalities = self.client.modalities_expanded()?;
+    /// Downloads the archives of the entities with `ids` of `level` to `paths`, `parallel` at a
+    /// time, retrying transient failures (see [`retry`]). A failure does not stop the other
+    /// downloads, instead it is reported in the results. The number of bytes received so far by
+    /// all the downloads is reported to `on_progress`
+    pub fn download_entities<F: Fn(u64) + Sync>(
+        &self,
+        level: &EntityKind,
+        downloads: &[(&str, PathBuf)],
+        media: bool,
+        parallel: usize,
+        retries: u32,
+        on_progress: F,
+    ) -> EntityList {
+        let next = AtomicUsize::new(0);
+        let received = AtomicU64::new(0);
+        let rows = Mutex::new(vec![Row::new(); downloads.len()]);
+        thread::scope(|scope| {
+            for _ in 0..parallel.max(1).min(downloads.len()) {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, SeqCst);
+                    let (id, path) = match downloads.get(index) {
+                        Some(d) => d,
+                        None => break,
+                    };
+                    let started = Instant::now();
+                    let mut size = 0;
+                    let result = retry(retries, RETRY_BACKOFF, || {
+                        // Take the bytes of the failed attempt back out of the total
+                        received.fetch_sub(size, SeqCst);
+                        size = 0;
+                        if let Some(parent) = path.parent() {
+                            fs::create_dir_all(parent)?;
+                        }
+                        let output = path.to_string_lossy();
+                        self.download_archive(level, id, &output, media, |b| {
+                            let total = received.fetch_add(b - size, SeqCst) + b - size;
+                            size = b;
+                            on_progress(total);
+                        })
+                    });
+                    rows.lock().unwrap()[index] =
+                        create_download_row(id, path, size, started.elapsed(), result);
+                });
+            }
+        });
+        EntityList {
+            columns: DOWNLOAD_RESULTS_HEADER
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            rows: rows.into_inner().unwrap(),
+        }
+    }
+
+    ////////// JOB //////////
 
-        if let Some(c) = columns {
+    pub fn list_jobs(&self, columns: Option<Vec<&str>>) -> Result<EntityList> {
+        let header = &mut JOBS_LIST_HEADER.to_vec();
+
+        if let Some(c) = &columns {
+            check_columns_option(header, c)?;
             // Make sure that the columns are sorted in the same way as the original header
             header.retain(|v| c.contains(v));
+        };
 
-            if !no_header {
-                real_header = Some(&header);
-            };
+        let mut rows = vec![];
+        for job in self.client.jobs_expanded()? {
+            let mut row = job_row(job);
+            row.retain(|k, _| header.contains(&k.as_str()));
+            rows.push(row);
+        }
+        Ok(EntityList {
+            columns: header.iter().map(ToString::to_string).collect(),
+            rows,
+        })
+    }
 
-            let mut table = create_table(real_header.map(|v| v.as_ref()));
-            for (m_name, m_config) in modalities {
-                let mut row = vec![];
-                if header.contains(&"Name") {
-                    row.push(m_name);
-                };
-                if header.contains(&"AET") {
-                    row.push(m_config.aet);
-                };
-                if header.contains(&"Host") {
-                    row.push(m_config.host);
-                };
-                if header.contains(&"Port") {
-                    row.push(format!("{}", m_config.port));
-                };
-                if header.contains(&"Manufacturer") {
-                    row.push(m_config.manufacturer.unwrap());
-                };
-                table.add_row(row.iter());
+    pub fn show_job(&self, id: &str) -> Result<EntityDetails> {
+        Ok(EntityDetails {
+            fields: job_row(self.client.job(id)?),
+        })
+    }
+
+    pub fn cancel_job(&self, id: &str) -> Result<()> {
+        self.client.cancel_job(id).map_err(Into::<_>::into)
+    }
+
+    pub fn pause_job(&self, id: &str) -> Result<()> {
+        self.client.pause_job(id).map_err(Into::<_>::into)
+    }
+
+    pub fn resume_job(&self, id: &str) -> Result<()> {
+        self.client.resume_job(id).map_err(Into::<_>::into)
+    }
+
+    pub fn resubmit_job(&self, id: &str) -> Result<()> {
+        self.client.resubmit_job(id).map_err(Into::<_>::into)
+    }
+
+    /// Polls the job every `interval` until it either succeeds or fails, passing its state to
+    /// `progress` on every poll. A failed or a paused job is an error, and so is a job, that does
+    /// not complete within `timeout`
+    pub fn wait_for_job<F: FnMut(&Job)>(
+        &self,
+        id: &str,
+        interval: Duration,
+        timeout: Option<Duration>,
+        mut progress: F,
+    ) -> Result<EntityDetails> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        loop {
+            let job = self.client.job(id)?;
+            progress(&job);
+            match job.state.as_str() {
+                JOB_STATE_SUCCESS => {
+                    return Ok(EntityDetails {
+                        fields: job_row(job),
+                    })
+                }
+                JOB_STATE_FAILURE => {
+                    return Err(CliError::new(
+                        "Job failed",
+                        Some(&job.error_description),
+                        Some(&format!("Error code: {}", job.error_code)),
+                    ))
+                }
+                // A paused job does not make progress, until it is resumed
+                JOB_STATE_PAUSED => {
+                    return Err(CliError::new(
+                        "Job paused",
+                        Some(&format!("Job {} is paused", id)),
+                        Some(&format!("Resume it with: orthanc job resume {}", id)),
+                    ))
+                }
+                _ => (),
             }
-            Ok(table)
-        } else {
-            if !no_header {
-                real_header = Some(&header);
+            let wait = match deadline {
+                Some(d) => {
+                    let remaining = d.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        return Err(CliError::new(
+                            "Job timed out",
+                            Some(&format!(
+                                "Job {} did not complete in {} seconds",
+                                id,
+                                timeout.unwrap().as_secs()
+                            )),
+                            Some(&format!("Job state: {}", job.state)),
+                        ));
+                    }
+                    interval.min(remaining)
+                }
+                None => interval,
             };
-            let mut table = create_table(real_header.map(|v| v.as_ref()));
-            for (m_name, m_config) in modalities {
-                let row = vec![
-                    m_name,
-                    m_config.aet,
-                    m_config.host,
-                    format!("{}", m_config.port),
-                    m_config.manufacturer.unwrap(),
-                ];
-                table.add_row(row.iter());
-            }
-            Ok(table)
+            thread::sleep(wait);
         }
     }
 
-    pub fn show_modality(&self, name: &str) -> Result<Table> {
+    ////////// MODALITY //////////
+
+    pub fn do_store(
+        &self,
+        modality: &str,
+        ids: &Vec<&str>,
+        asynchronous: bool,
+    ) -> Result<Outcome<ModalityStoreResult>> {
+        if asynchronous {
+            return Ok(Outcome::Submitted(
+                self.client.modality_store_async(modality, ids)?,
+            ));
+        }
+        Ok(Outcome::Completed(
+            self.client.modality_store(modality, ids)?,
+        ))
+    }
+
+    pub fn list_modalities(&self, columns: Option<Vec<&str>>) -> Result<EntityList> {
+        let header = &mut MODALITIES_LIST_HEADER.to_vec();
+
+        if let Some(c) = &columns {
+            check_columns_option(header, c)?;
+            // Make sure that the columns are sorted in the same way as the original header
+            header.retain(|v| c.contains(v));
+        };
+
+        let mut rows = vec![];
         for (m_name, m_config) in self.client.modalities_expanded()? {
-            if m_name == name {
-                let mut table = create_table(None);
-                table.add_row(vec!["Name", &m_name].iter());
-                table.add_row(vec!["AET", &m_config.aet].iter());
-                table.add_row(vec!["Host", &m_config.host].iter());
-                table.add_row(vec!["Port", &format!("{}", m_config.port)].iter());
-                table.add_row(vec!["Manufacturer", &m_config.manufacturer.unwrap()].iter());
-                if let Some(_) = m_config.allow_transcoding {
-                    table.add_row(
-                        vec![
-                            "Transcoding",
-                            &format!("{}", m_config.allow_transcoding.unwrap()),
-                        ]
-                        .iter(),
-                    );
-                };
-                table.add_row(
-                    vec!["C-ECHO", &format!("{}", m_config.allow_c_echo.unwrap())].iter(),
-                );
-                table.add_row(
-                    vec!["C-FIND", &format!("{}", m_config.allow_c_find.unwrap())].iter(),
-                );
-                table.add_row(
-                    vec!["C-GET", &format!("{}", m_config.allow_c_get.unwrap())].iter(),
-                );
-                table.add_row(
-                    vec!["C-MOVE", &format!("{}", m_config.allow_c_move.unwrap())].iter(),
-                );
-                table.add_row(
-                    vec!["C-STORE", &format!("{}", m_config.allow_c_store.unwrap())].iter(),
-                );
-                table.add_row(
-                    vec!["N-ACTION", &format!("{}", m_config.allow_n_action.unwrap())]
-                        .iter(),
-                );
-                table.add_row(
-                    vec![
-                        "N-EVENT-REPORT",
-                        &format!("{}", m_config.allow_n_event_report.unwrap()),
-                    ]
-                    .iter(),
-                );
-                return Ok(table);
-            }
+            let mut row = Row::new();
+            row.insert("Name".to_string(), Value::from(m_name));
+            row.insert("AET".to_string(), Value::from(m_config.aet));
+            row.insert("Host".to_string(), Value::from(m_config.host));
+            row.insert("Port".to_string(), Value::from(m_config.port));
+            row.insert(
+                "Manufacturer".to_string(),
+                Value::from(m_config.manufacturer),
+            );
+            row.retain(|k, _| header.contains(&k.as_str()));
+            rows.push(row);
         }
-        return Err(CliError::new(
-            &format!("Modality {} not found", name),
-            None,
-            None,
-        ));
+        Ok(EntityList {
+            columns: header.iter().map(ToString::to_string).collect(),
+            rows,
+        })
     }
 
-    pub fn create_modality(
-        &self,
-        name: &str,
-        aet: &str,
-        host: &str,
-        port: i32,
-    ) -> Result<()> {
-        let config = Modality {
-            aet: aet.to_string(),
-            host: host.to_string(),
-            port,
-            manufacturer: None,
-            allow_transcoding: None,
-            allow_c_echo: None,
-            allow_c_find: None,
-            allow_c_get: None,
-            allow_c_move: None,
-            allow_c_store: None,
-            allow_n_action: None,
-            allow_n_event_report: None,
+    pub fn show_modality(&self, name: &str) -> Result<EntityDetails> {
+        let m_config = self.modality_config(name)?;
+        let mut fields = Row::new();
+        fields.insert("Name".to_string(), Value::from(name));
+        fields.insert("AET".to_string(), Value::from(m_config.aet));
+        fields.insert("Host".to_string(), Value::from(m_config.host));
+        fields.insert("Port".to_string(), Value::from(m_config.port));
+        fields.insert(
+            "Manufacturer".to_string(),
+            Value::from(m_config.manufacturer),
+        );
+        if let Some(t) = m_config.allow_transcoding {
+            fields.insert("Transcoding".to_string(), Value::from(t));
+        };
+        if let Some(a) = m_config.local_aet {
+            fields.insert("Local AET".to_string(), Value::from(a));
         };
+        if let Some(t) = m_config.timeout {
+            fields.insert("Timeout".to_string(), Value::from(t));
+        };
+        if let Some(t) = m_config.use_dicom_tls {
+            fields.insert("DICOM TLS".to_string(), Value::from(t));
+        };
+        fields.insert("C-ECHO".to_string(), Value::from(m_config.allow_c_echo));
+        fields.insert("C-FIND".to_string(), Value::from(m_config.allow_c_find));
+        fields.insert("C-GET".to_string(), Value::from(m_config.allow_c_get));
+        fields.insert("C-MOVE".to_string(), Value::from(m_config.allow_c_move));
+        fields.insert("C-STORE".to_string(), Value::from(m_config.allow_c_store));
+        fields.insert("N-ACTION".to_string(), Value::from(m_config.allow_n_action));
+        fields.insert(
+            "N-EVENT-REPORT".to_string(),
+            Value::from(m_config.allow_n_event_report),
+        );
+        Ok(EntityDetails { fields })
+    }
+
+    pub fn create_modality(&self, name: &str, config: ModalityConfig) -> Result<()> {
         self.client
             .create_modality(name, config)
             .map_err(Into::<_>::into)
     }
 
-    pub fn modify_modality(
-        &self,
-        name: &str,
-        aet: &str,
-        host: &str,
-        port: i32,
-    ) -> Result<()> {
-        let config = Modality {
-            aet: aet.to_string(),
-            host: host.to_string(),
-            port,
-            manufacturer: None,
-            allow_transcoding: None,
-            allow_c_echo: None,
-            allow_c_find: None,
-            allow_c_get: None,
-            allow_c_move: None,
-            allow_c_store: None,
-            allow_n_action: None,
-            allow_n_event_report: None,
-        };
+    /// Update the settings of a modality, that are set in `update`, keeping the rest as is
+    pub fn modify_modality(&self, name: &str, update: ModalityConfig) -> Result<()> {
+        let mut config = self.modality_config(name)?;
+        config.merge(update);
         self.client
             .modify_modality(name, config)
             .map_err(Into::<_>::into)
     }
 
+    /// Create (or replace) the modalities defined in a YAML file, that maps modality names to
+    /// their settings
+    pub fn import_modalities(&self, file: &Path) -> Result<()> {
+        let modalities = read_modalities_file(file)?;
+        for (name, config) in modalities {
+            self.client.create_modality(&name, config)?;
+        }
+        Ok(())
+    }
+
+    fn modality_config(&self, name: &str) -> Result<ModalityConfig> {
+        match self.client.modalities_expanded()?.remove(name) {
+            Some(c) => Ok(c),
+            None => Err(CliError::new(
+                &format!("Modality {} not found", name),
+                None,
+                None,
+            )),
+        }
+    }
+
+    pub fn echo_modality(&self, name: &str, timeout: Option<u32>) -> Result<EntityDetails> {
+        self.client.modality_echo(name, timeout)?;
+        let mut fields = Row::new();
+        fields.insert("Modality".to_string(), Value::from(name));
+        fields.insert("C-ECHO".to_string(), Value::from("Success"));
+        Ok(EntityDetails { fields })
+    }
+
+    /// Send a C-FIND request to a remote modality. `level` is `patient`, `study`, `series` or
+    /// `instance`, `query` is a list of `TagName=Value` pairs. Each of the tags displayed is added
+    /// to the query with an empty value (unless it is filtered on), so that the modality returns
+    /// it
+    pub fn query_modality(
+        &self,
+        name: &str,
+        level: &str,
+        query: Vec<&str>,
+        columns: Option<Vec<&str>>,
+    ) -> Result<QueryAnswers> {
+        let (level, default_tags) = match level {
+            "patient" => ("Patient", PATIENTS_LIST_DICOM_TAGS),
+            "study" => ("Study", STUDIES_LIST_DICOM_TAGS),
+            "series" => ("Series", SERIES_LIST_DICOM_TAGS),
+            "instance" => ("Instance", INSTANCES_LIST_DICOM_TAGS),
+            _ => {
+                return Err(CliError::new(
+                    "Command error",
+                    Some(&format!("Invalid query level: {}", level)),
+                    None,
+                ))
+            }
+        };
+        let header = &mut vec![QUERY_ANSWER_INDEX_COLUMN];
+        header.extend(default_tags);
+        let dicom_tags = &mut default_tags.to_vec();
+        get_header_and_dicom_tags(header, dicom_tags, columns)?;
+
+        let mut query = parse_search_query(query, false)?.tags;
+        for t in dicom_tags.iter() {
+            query.entry(t.to_string()).or_default();
+        }
+        let query_id = self.client.modality_query(name, level, query)?.id;
+
+        let rows = self
+            .client
+            .query_answers(&query_id)?
+            .iter()
+            .enumerate()
+            .map(|(i, answer)| query_answer_row(i, answer, dicom_tags))
+            .collect();
+        Ok(QueryAnswers {
+            query_id,
+            answers: EntityList {
+                columns: header.iter().map(ToString::to_string).collect(),
+                rows,
+            },
+        })
+    }
+
+    /// Retrieve resources from a remote modality with C-MOVE (or C-GET, if `c_get` is set).
+    /// Unless they are moved to another `target_aet`, the number of instances, that have arrived
+    /// in Orthanc, is reported
+    pub fn retrieve_from_modality(
+        &self,
+        name: &str,
+        source: &RetrieveSource,
+        target_aet: Option<&str>,
+        c_get: bool,
+        asynchronous: bool,
+    ) -> Result<Outcome<EntityDetails>> {
+        if c_get && target_aet.is_some() {
+            return Err(CliError::new(
+                "Command error",
+                Some("Conflicting options"),
+                Some("A target AET can only be used with C-MOVE"),
+            ));
+        }
+
+        if asynchronous {
+            let job = match source {
+                RetrieveSource::Uids { study, series } => {
+                    let (level, uids) = retrieve_uids(study, series.as_deref());
+                    self.client.modality_retrieve_async(
+                        name,
+                        level,
+                        &[uids],
+                        target_aet,
+                        c_get,
+                    )?
+                }
+                RetrieveSource::Query { id, index } => self
+                    .client
+                    .query_retrieve_async(id, *index, target_aet, c_get)?,
+            };
+            return Ok(Outcome::Submitted(job));
+        }
+
+        // The instances of the resources are counted before and after retrieving them
+        let resources = match source {
+            RetrieveSource::Uids { study, series } => {
+                vec![retrieve_uids(study, series.as_deref()).1]
+            }
+            RetrieveSource::Query { id, index } => self
+                .client
+                .query_answers(id)?
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| index.map_or(true, |idx| idx == *i))
+                .map(|(_, a)| query_answer_uids(a))
+                .collect(),
+        };
+        let count_instances = || -> Result<usize> {
+            let mut count = 0;
+            for r in resources.iter() {
+                count += self.client.search_instance_ids(r.clone())?.len();
+            }
+            Ok(count)
+        };
+        let before = match target_aet {
+            Some(_) => 0,
+            None => count_instances()?,
+        };
+
+        match source {
+            RetrieveSource::Uids { series, .. } => {
+                let level = if series.is_some() { "Series" } else { "Study" };
+                self.client
+                    .modality_retrieve(name, level, &resources, target_aet, c_get)?
+            }
+            RetrieveSource::Query { id, index } => {
+                self.client.query_retrieve(id, *index, target_aet, c_get)?
+            }
+        };
+
+        let mut fields = Row::new();
+        fields.insert("Modality".to_string(), Value::from(name));
+        fields.insert(
+            "Method".to_string(),
+            Value::from(if c_get { "C-GET" } else { "C-MOVE" }),
+        );
+        match target_aet {
+            Some(t) => {
+                fields.insert("Target AET".to_string(), Value::from(t));
+            }
+            None => {
+                let after = count_instances()?;
+                fields.insert(
+                    "Instances received".to_string(),
+                    Value::from(after.saturating_sub(before)),
+                );
+            }
+        };
+        Ok(Outcome::Completed(EntityDetails { fields }))
+    }
+
     pub fn delete_modality(&self, name: &str) -> Result<()> {
         self.client.delete_modality(name).map_err(Into::<_>::into)
     }
 
+    ////////// PEER //////////
+
+    pub fn list_peers(&self, columns: Option<Vec<&str>>) -> Result<EntityList> {
+        let header = &mut PEERS_LIST_HEADER.to_vec();
+
+        if let Some(c) = &columns {
+            check_columns_option(header, c)?;
+            // Make sure that the columns are sorted in the same way as the original header
+            header.retain(|v| c.contains(v));
+        };
+
+        let mut rows = vec![];
+        for (p_name, p_config) in self.client.peers_expanded()? {
+            let mut row = Row::new();
+            row.insert("Name".to_string(), Value::from(p_name));
+            row.insert("URL".to_string(), Value::from(p_config.url));
+            row.insert("Username".to_string(), Value::from(p_config.username));
+            row.retain(|k, _| header.contains(&k.as_str()));
+            rows.push(row);
+        }
+        Ok(EntityList {
+            columns: header.iter().map(ToString::to_string).collect(),
+            rows,
+        })
+    }
+
+    pub fn show_peer(&self, name: &str) -> Result<EntityDetails> {
+        let p_config = self.peer_config(name)?;
+        let header_names = p_config.http_header_names();
+        let mut fields = Row::new();
+        fields.insert("Name".to_string(), Value::from(name));
+        fields.insert("URL".to_string(), Value::from(p_config.url));
+        fields.insert("Username".to_string(), Value::from(p_config.username));
+        fields.insert(
+            "HTTP headers".to_string(),
+            if header_names.is_empty() {
+                Value::Null
+            } else {
+                Value::from(header_names.join(", "))
+            },
+        );
+        fields.insert(
+            "Certificate file".to_string(),
+            Value::from(p_config.certificate_file),
+        );
+        fields.insert(
+            "Certificate key file".to_string(),
+            Value::from(p_config.certificate_key_file),
+        );
+        if let Some(p) = p_config.pkcs11 {
+            fields.insert("PKCS#11".to_string(), Value::from(p));
+        };
+        if let Some(t) = p_config.timeout {
+            fields.insert("Timeout".to_string(), Value::from(t));
+        };
+        Ok(EntityDetails { fields })
+    }
+
+    pub fn create_peer(&self, name: &str, config: PeerConfig) -> Result<()> {
+        self.client
+            .create_peer(name, config)
+            .map_err(Into::<_>::into)
+    }
+
+    /// Update the settings of a peer, that are set in `update`, keeping the rest as is. Orthanc
+    /// does not disclose the password and the HTTP header values of a peer, so if the peer has
+    /// them, they have to be part of `update`
+    pub fn modify_peer(&self, name: &str, update: PeerConfig) -> Result<()> {
+        let mut config = self.peer_config(name)?;
+        if config.username.is_some() && update.password.is_none() {
+            return Err(CliError::new(
+                "Command error",
+                Some(&format!("The password of peer {} must be specified", name)),
+                Some("Orthanc does not disclose the password, so it has to be set again"),
+            ));
+        }
+        if !config.http_header_names().is_empty() && update.http_headers.is_none() {
+            return Err(CliError::new(
+                "Command error",
+                Some(&format!("The HTTP headers of peer {} must be specified", name)),
+                Some("Orthanc does not disclose the header values, so they have to be set again"),
+            ));
+        }
+        // Secrets are returned as nulls or names only, so they are not sent back as such
+        config.password = None;
+        config.certificate_key_password = None;
+        config.http_headers = None;
+        config.merge(update);
+        self.client
+            .modify_peer(name, config)
+            .map_err(Into::<_>::into)
+    }
+
+    pub fn delete_peer(&self, name: &str) -> Result<()> {
+        self.client.delete_peer(name).map_err(Into::<_>::into)
+    }
+
+    pub fn store_peer(
+        &self,
+        peer: &str,
+        ids: &[&str],
+        transcode: Option<&str>,
+        compress: bool,
+        asynchronous: bool,
+    ) -> Result<Outcome<PeerStoreResult>> {
+        if asynchronous {
+            return Ok(Outcome::Submitted(
+                self.client
+                    .peer_store_async(peer, ids, transcode, compress)?,
+            ));
+        }
+        Ok(Outcome::Completed(
+            self.client.peer_store(peer, ids, transcode, compress)?,
+        ))
+    }
+
+    /// System information of a peer, as reported by the peer itself
+    pub fn peer_system(&self, name: &str) -> Result<EntityDetails> {
+        match self.client.peer_system(name)? {
+            Value::Object(fields) => Ok(EntityDetails { fields }),
+            v => Err(CliError::new(
+                "Command error",
+                Some("Unexpected system information"),
+                Some(&v.to_string()),
+            )),
+        }
+    }
+
+    fn peer_config(&self, name: &str) -> Result<PeerConfig> {
+        match self.client.peers_expanded()?.remove(name) {
+            Some(c) => Ok(c),
+            None => Err(CliError::new(
+                &format!("Peer {} not found", name),
+                None,
+                None,
+            )),
+        }
+    }
+
+    /// Searches for entities at `match_level` and returns their ancestors of kind `T`, e.g. the
+    /// patients, that have a series matching the query. Orthanc itself only matches the tags of
+    /// the entities searched for and of their ancestors
+    fn search_ancestors<T: Entity>(
+        &self,
+        query: &SearchQuery,
+        match_level: &EntityKind,
+    ) -> Result<Vec<T>> {
+        let (parent_kind, parent_ids) = match match_level {
+            EntityKind::Instance => (
+                EntityKind::Series,
+                parent_ids(self.client.search::<Instance>(query)?),
+            ),
+            EntityKind::Series => (
+                EntityKind::Study,
+                parent_ids(self.client.search::<Series>(query)?),
+            ),
+            _ => (
+                EntityKind::Patient,
+                parent_ids(self.client.search::<Study>(query)?),
+            ),
+        };
+        let mut ids = HashSet::new();
+        let mut ancestors = vec![];
+        for id in parent_ids {
+            let ancestor: T = self.client.ancestor(&parent_kind, &id)?;
+            if ids.insert(ancestor.id().to_string()) {
+                ancestors.push(ancestor);
+            }
+        }
+        Ok(ancestors)
+    }
+
     pub fn search_patients(
         &self,
-        query: Vec<&str>,
+        query: SearchQuery,
+        match_level: EntityKind,
         columns: Option<Vec<&str>>,
-        no_header: bool,
-    ) -> Result<Table> {
+        options: ListOptions,
+    ) -> Result<EntityList> {
         let header = &mut PATIENTS_LIST_HEADER.to_vec();
         let dicom_tags = &mut PATIENTS_LIST_DICOM_TAGS.to_vec();
         get_header_and_dicom_tags(header, dicom_tags, columns)?;
-
-        let patients: Vec<Patient> = self.client.search(parse_tag_kv_pairs(query)?)?;
-
-        Ok(utils::create_list_table(
-            patients, header, dicom_tags, no_header,
-        ))
+        check_sort_column(header, &options)?;
+
+        let ancestors = entity_depth(&match_level) > entity_depth(&EntityKind::Patient);
+        match (ancestors, options.server_range()) {
+            (true, _) => self.sorted_entity_list(
+                self.search_ancestors::<Patient>(&query, &match_level)?,
+                header,
+                dicom_tags,
+                PATIENTS_LIST_DICOM_TAGS,
+                &options,
+            ),
+            (false, Some((since, limit))) => self.entity_list(
+                self.client.search_range::<Patient>(&query, since, limit)?,
+                header,
+                dicom_tags,
+                PATIENTS_LIST_DICOM_TAGS,
+            ),
+            (false, None) => self.sorted_entity_list(
+                self.client.search::<Patient>(&query)?,
+                header,
+                dicom_tags,
+                PATIENTS_LIST_DICOM_TAGS,
+                &options,
+            ),
+        }
     }
 
     pub fn search_studies(
         &self,
-        query: Vec<&str>,
+        query: SearchQuery,
+        match_level: EntityKind,
         columns: Option<Vec<&str>>,
-        no_header: bool,
-    ) -> Result<Table> {
+        options: ListOptions,
+    ) -> Result<EntityList> {
         let header = &mut STUDIES_LIST_HEADER.to_vec();
         let dicom_tags = &mut STUDIES_LIST_DICOM_TAGS.to_vec();
         get_header_and_dicom_tags(header, dicom_tags, columns)?;
-
-        let studies: Vec<Study> = self.client.search(parse_tag_kv_pairs(query)?)?;
-
-        Ok(utils::create_list_table(
-            studies, header, dicom_tags, no_header,
-        ))
+        check_sort_column(header, &options)?;
+
+        let ancestors = entity_depth(&match_level) > entity_depth(&EntityKind::Study);
+        match (ancestors, options.server_range()) {
+            (true, _) => self.sorted_entity_list(
+                self.search_ancestors::<Study>(&query, &match_level)?,
+                header,
+                dicom_tags,
+                STUDIES_LIST_DICOM_TAGS,
+                &options,
+            ),
+            (false, Some((since, limit))) => self.entity_list(
+                self.client.search_range::<Study>(&query, since, limit)?,
+                header,
+                dicom_tags,
+                STUDIES_LIST_DICOM_TAGS,
+            ),
+            (false, None) => self.sorted_entity_list(
+                self.client.search::<Study>(&query)?,
+                header,
+                dicom_tags,
+                STUDIES_LIST_DICOM_TAGS,
+                &options,
+            ),
+        }
     }
 
     pub fn search_series(
         &self,
-        query: Vec<&str>,
+        query: SearchQuery,
+        match_level: EntityKind,
         columns: Option<Vec<&str>>,
-        no_header: bool,
-    ) -> Result<Table> {
+        options: ListOptions,
+    ) -> Result<EntityList> {
         let header = &mut SERIES_LIST_HEADER.to_vec();
         let dicom_tags = &mut SERIES_LIST_DICOM_TAGS.to_vec();
         get_header_and_dicom_tags(header, dicom_tags, columns)?;
-
-        let series: Vec<Series> = self.client.search(parse_tag_kv_pairs(query)?)?;
-
-        Ok(utils::create_list_table(
-            series, header, dicom_tags, no_header,
-        ))
+        check_sort_column(header, &options)?;
+
+        let ancestors = entity_depth(&match_level) > entity_depth(&EntityKind::Series);
+        match (ancestors, options.server_range()) {
+            (true, _) => self.sorted_entity_list(
+                self.search_ancestors::<Series>(&query, &match_level)?,
+                header,
+                dicom_tags,
+                SERIES_LIST_DICOM_TAGS,
+                &options,
+            ),
+            (false, Some((since, limit))) => self.entity_list(
+                self.client.search_range::<Series>(&query, since, limit)?,
+                header,
+                dicom_tags,
+                SERIES_LIST_DICOM_TAGS,
+            ),
+            (false, None) => self.sorted_entity_list(
+                self.client.search::<Series>(&query)?,
+                header,
+                dicom_tags,
+                SERIES_LIST_DICOM_TAGS,
+                &options,
+            ),
+        }
     }
 
     pub fn search_instances(
         &self,
-        query: Vec<&str>,
+        query: SearchQuery,
+        match_level: EntityKind,
         columns: Option<Vec<&str>>,
-        no_header: bool,
-    ) -> Result<Table> {
+        options: ListOptions,
+    ) -> Result<EntityList> {
         let header = &mut INSTANCES_LIST_HEADER.to_vec();
         let dicom_tags = &mut INSTANCES_LIST_DICOM_TAGS.to_vec();
         get_header_and_dicom_tags(header, dicom_tags, columns)?;
-
-        let instances: Vec<Instance> = self.client.search(parse_tag_kv_pairs(query)?)?;
-
-        Ok(utils::create_list_table(
-            instances, header, dicom_tags, no_header,
-        ))
+        check_sort_column(header, &options)?;
+
+        let ancestors = entity_depth(&match_level) > entity_depth(&EntityKind::Instance);
+        match (ancestors, options.server_range()) {
+            (true, _) => self.sorted_entity_list(
+                self.search_ancestors::<Instance>(&query, &match_level)?,
+                header,
+                dicom_tags,
+                INSTANCES_LIST_DICOM_TAGS,
+                &options,
+            ),
+            (false, Some((since, limit))) => self.entity_list(
+                self.client.search_range::<Instance>(&query, since, limit)?,
+                header,
+                dicom_tags,
+                INSTANCES_LIST_DICOM_TAGS,
+            ),
+            (false, None) => self.sorted_entity_list(
+                self.client.search::<Instance>(&query)?,
+                header,
+                dicom_tags,
+                INSTANCES_LIST_DICOM_TAGS,
+                &options,
+            ),
+        }
     }
 }
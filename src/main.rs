@@ -1,19 +1,81 @@
+use clap::ArgMatches;
 use cli::*;
+use config::*;
+use models::{
+    Anonymization, EntityDetails, EntityList, EntitySelection, Identifiers, ListOptions,
+    ModalityConfig, Outcome, PeerConfig, RetrieveSource,
+};
+use orthanc::entity::EntityKind;
 use orthanc_cli::*;
+use serde_json::Value;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
 use utils::*;
 
 fn main() {
     let matches = build_cli().get_matches();
 
+    let output = match get_output_format(matches.value_of("format")) {
+        Ok(f) => f,
+        Err(e) => return exit_with_error(e),
+    };
+
+    // Profiles are managed without connecting to a server
+    if let Some(("config", config)) = matches.subcommand() {
+        if let Err(e) = run_config_command(config, &output) {
+            exit_with_error(e)
+        }
+        return;
+    }
+
+    let profile = match get_profile(matches.value_of("profile")) {
+        Ok(p) => p,
+        Err(e) => return exit_with_error(e),
+    };
     let mut server_address = "".to_string();
-    match get_server_address(matches.value_of("server")) {
+    match get_server_address(matches.value_of("server"), profile.as_ref()) {
         Ok(s) => server_address = s,
         Err(e) => exit_with_error(e),
     };
+    let client_identity = match get_client_identity(
+        matches.value_of("client_cert"),
+        matches.value_of("client_key"),
+        matches.value_of("client_pkcs12"),
+        matches.value_of("client_pkcs12_password"),
+        profile.as_ref(),
+    ) {
+        Ok(i) => i,
+        Err(e) => return exit_with_error(e),
+    };
+    let transport = match get_transport_options(
+        matches.value_of("ca_cert"),
+        client_identity,
+        matches.is_present("insecure"),
+        matches.value_of("timeout"),
+        matches.value_of("proxy"),
+        profile.as_ref(),
+    ) {
+        Ok(t) => t,
+        Err(e) => return exit_with_error(e),
+    };
+    let headers = match get_headers(
+        matches.values_of("header").map(|h| h.collect()),
+        profile.as_ref(),
+    ) {
+        Ok(h) => h,
+        Err(e) => return exit_with_error(e),
+    };
     let o = match Orthanc::new(
         server_address,
-        get_username(matches.value_of("username")),
-        get_password(matches.value_of("password")),
+        get_username(matches.value_of("username"), profile.as_ref()),
+        get_password(matches.value_of("password"), profile.as_ref()),
+        get_token(matches.value_of("token"), profile.as_ref()),
+        headers,
+        transport,
     ) {
         Ok(o) => o,
         Err(e) => return exit_with_error(e),
@@ -21,345 +83,1051 @@ fn main() {
 
     match matches.subcommand() {
         Some(("patient", patient)) => match patient.subcommand() {
-            Some(("list", list)) => match o.list_patients(
-                list.values_of("columns").map(|c| c.collect()),
-                list.is_present("no_header"),
-            ) {
-                Ok(t) => print_table(t),
-                Err(e) => exit_with_error(e),
-            },
-            Some(("show", show)) => match o.show_patient(show.value_of("id").unwrap()) {
-                Ok(t) => print_table(t),
-                Err(e) => exit_with_error(e),
-            },
+            Some(("list", list)) => {
+                match list_options(list).and_then(|options| {
+                    o.list_patients(list.values_of("columns").map(|c| c.collect()), options)
+                }) {
+                    Ok(r) => print_output(&r, list.is_present("no_header"), &output),
+                    Err(e) => exit_with_error(e),
+                }
+            }
+            Some(("show", show)) => {
+                if let Err(e) =
+                    run_entity_command(show, &EntityKind::Patient, &output, |id| {
+                        o.show_patient(id)
+                    })
+                {
+                    exit_with_error(e)
+                }
+            }
             Some(("list-studies", list_studies)) => {
-                match o.list_studies(
-                    list_studies.value_of("id"),
-                    list_studies.values_of("columns").map(|c| c.collect()),
-                    list_studies.is_present("no_header"),
-                ) {
-                    Ok(t) => print_table(t),
+                match list_options(list_studies).and_then(|options| {
+                    o.list_studies(
+                        list_studies.value_of("id"),
+                        list_studies.values_of("columns").map(|c| c.collect()),
+                        options,
+                    )
+                }) {
+                    Ok(r) => {
+                        print_output(&r, list_studies.is_present("no_header"), &output)
+                    }
                     Err(e) => exit_with_error(e),
                 }
             }
             Some(("anonymize", anonymize)) => {
-                let mut keep_private_tags = None;
-                if anonymize.is_present("keep_private_tags") {
-                    keep_private_tags = Some(true);
-                }
-                match o.anonymize_patient(
-                    anonymize.value_of("id").unwrap(),
-                    anonymize.values_of("replace").map(|r| r.collect()),
-                    anonymize.values_of("keep").map(|k| k.collect()),
-                    keep_private_tags,
-                    anonymize.value_of("config"),
-                ) {
-                    Ok(t) => print_table(t),
-                    Err(e) => exit_with_error(e),
+                if let Err(e) =
+                    anonymize_entities(&o, anonymize, EntityKind::Patient, &output)
+                {
+                    exit_with_error(e)
                 }
             }
             Some(("search", search)) => {
-                match o.search_patients(
-                    search.values_of("query").unwrap().collect(),
-                    search.values_of("columns").map(|c| c.collect()),
-                    search.is_present("no_header"),
-                ) {
-                    Ok(t) => print_table(t),
+                match search_entities(&o, search, EntityKind::Patient) {
+                    Ok(r) => print_output(&r, search.is_present("no_header"), &output),
                     Err(e) => exit_with_error(e),
                 }
             }
-            Some(("modify", modify)) => match o.modify_patient(
-                modify.value_of("id").unwrap(),
-                modify.values_of("replace").map(|r| r.collect()),
-                modify.values_of("remove").map(|r| r.collect()),
-                modify.value_of("config"),
-            ) {
-                Ok(t) => print_table(t),
-                Err(e) => exit_with_error(e),
-            },
-            Some(("download", download)) => match o.download_patient(
-                download.value_of("id").unwrap(),
-                download.value_of("output").unwrap(),
-            ) {
-                Ok(_) => (),
-                Err(e) => exit_with_error(e),
-            },
+            Some(("modify", modify)) => {
+                if let Err(e) =
+                    run_entity_command(modify, &EntityKind::Patient, &output, |id| {
+                        o.modify_patient(
+                            id,
+                            modify.values_of("replace").map(|r| r.collect()),
+                            modify.values_of("remove").map(|r| r.collect()),
+                            modify.value_of("config"),
+                            modify.is_present("async"),
+                        )
+                    })
+                {
+                    exit_with_error(e)
+                }
+            }
+            Some(("download", download)) => {
+                if let Err(e) = download_archive(&o, download, EntityKind::Patient) {
+                    exit_with_error(e)
+                }
+            }
             Some(("delete", delete)) => {
-                match o.delete_patient(delete.value_of("id").unwrap()) {
-                    Ok(_) => (),
-                    Err(e) => exit_with_error(e),
+                let result = match delete.value_of("id").unwrap() {
+                    "-" => {
+                        delete_entities_from_stdin(&o, EntityKind::Patient, delete, &output)
+                    }
+                    id => o.delete_patient(id),
+                };
+                if let Err(e) = result {
+                    exit_with_error(e)
                 }
             }
             _ => {}
         },
         Some(("study", study)) => match study.subcommand() {
             Some(("list", list)) => {
-                match o.list_studies(
-                    None,
-                    list.values_of("columns").map(|c| c.collect()),
-                    list.is_present("no_header"),
-                ) {
-                    Ok(t) => print_table(t),
+                match list_options(list).and_then(|options| {
+                    o.list_studies(
+                        None,
+                        list.values_of("columns").map(|c| c.collect()),
+                        options,
+                    )
+                }) {
+                    Ok(r) => print_output(&r, list.is_present("no_header"), &output),
                     Err(e) => exit_with_error(e),
                 }
             }
-            Some(("show", show)) => match o.show_study(show.value_of("id").unwrap()) {
-                Ok(t) => print_table(t),
-                Err(e) => exit_with_error(e),
-            },
+            Some(("show", show)) => {
+                if let Err(e) =
+                    run_entity_command(show, &EntityKind::Study, &output, |id| {
+                        o.show_study(id)
+                    })
+                {
+                    exit_with_error(e)
+                }
+            }
             Some(("list-series", list_series)) => {
-                match o.list_series(
-                    list_series.value_of("id"),
-                    list_series.values_of("columns").map(|c| c.collect()),
-                    list_series.is_present("no_header"),
-                ) {
-                    Ok(t) => print_table(t),
+                match list_options(list_series).and_then(|options| {
+                    o.list_series(
+                        list_series.value_of("id"),
+                        list_series.values_of("columns").map(|c| c.collect()),
+                        options,
+                    )
+                }) {
+                    Ok(r) => print_output(&r, list_series.is_present("no_header"), &output),
                     Err(e) => exit_with_error(e),
                 }
             }
             Some(("search", search)) => {
-                match o.search_studies(
-                    search.values_of("query").unwrap().collect(),
-                    search.values_of("columns").map(|c| c.collect()),
-                    search.is_present("no_header"),
-                ) {
-                    Ok(t) => print_table(t),
+                match search_entities(&o, search, EntityKind::Study) {
+                    Ok(r) => print_output(&r, search.is_present("no_header"), &output),
                     Err(e) => exit_with_error(e),
                 }
             }
             Some(("anonymize", anonymize)) => {
-                let mut keep_private_tags = None;
-                if anonymize.is_present("keep_private_tags") {
-                    keep_private_tags = Some(true);
-                }
-                match o.anonymize_study(
-                    anonymize.value_of("id").unwrap(),
-                    anonymize.values_of("replace").map(|r| r.collect()),
-                    anonymize.values_of("keep").map(|k| k.collect()),
-                    keep_private_tags,
-                    anonymize.value_of("config"),
-                ) {
-                    Ok(t) => print_table(t),
-                    Err(e) => exit_with_error(e),
+                if let Err(e) =
+                    anonymize_entities(&o, anonymize, EntityKind::Study, &output)
+                {
+                    exit_with_error(e)
+                }
+            }
+            Some(("modify", modify)) => {
+                if let Err(e) =
+                    run_entity_command(modify, &EntityKind::Study, &output, |id| {
+                        o.modify_study(
+                            id,
+                            modify.values_of("replace").map(|r| r.collect()),
+                            modify.values_of("remove").map(|r| r.collect()),
+                            modify.value_of("config"),
+                            modify.is_present("async"),
+                        )
+                    })
+                {
+                    exit_with_error(e)
+                }
+            }
+            Some(("download", download)) => {
+                if let Err(e) = download_archive(&o, download, EntityKind::Study) {
+                    exit_with_error(e)
                 }
             }
-            Some(("modify", modify)) => match o.modify_study(
-                modify.value_of("id").unwrap(),
-                modify.values_of("replace").map(|r| r.collect()),
-                modify.values_of("remove").map(|r| r.collect()),
-                modify.value_of("config"),
-            ) {
-                Ok(t) => print_table(t),
-                Err(e) => exit_with_error(e),
-            },
-            Some(("download", download)) => match o.download_study(
-                download.value_of("id").unwrap(),
-                download.value_of("output").unwrap(),
-            ) {
-                Ok(_) => (),
-                Err(e) => exit_with_error(e),
-            },
             Some(("delete", delete)) => {
-                match o.delete_study(delete.value_of("id").unwrap()) {
-                    Ok(_) => (),
-                    Err(e) => exit_with_error(e),
+                let result = match delete.value_of("id").unwrap() {
+                    "-" => {
+                        delete_entities_from_stdin(&o, EntityKind::Study, delete, &output)
+                    }
+                    id => o.delete_study(id),
+                };
+                if let Err(e) = result {
+                    exit_with_error(e)
                 }
             }
             _ => {}
         },
         Some(("series", series)) => match series.subcommand() {
             Some(("list", list)) => {
-                match o.list_series(
-                    None,
-                    list.values_of("columns").map(|c| c.collect()),
-                    list.is_present("no_header"),
-                ) {
-                    Ok(t) => print_table(t),
+                match list_options(list).and_then(|options| {
+                    o.list_series(
+                        None,
+                        list.values_of("columns").map(|c| c.collect()),
+                        options,
+                    )
+                }) {
+                    Ok(r) => print_output(&r, list.is_present("no_header"), &output),
                     Err(e) => exit_with_error(e),
                 }
             }
-            Some(("show", show)) => match o.show_series(show.value_of("id").unwrap()) {
-                Ok(t) => print_table(t),
-                Err(e) => exit_with_error(e),
-            },
+            Some(("show", show)) => {
+                if let Err(e) =
+                    run_entity_command(show, &EntityKind::Series, &output, |id| {
+                        o.show_series(id)
+                    })
+                {
+                    exit_with_error(e)
+                }
+            }
             Some(("list-instances", list_instances)) => {
-                match o.list_instances(
-                    list_instances.value_of("id"),
-                    list_instances.values_of("columns").map(|c| c.collect()),
-                    list_instances.is_present("no_header"),
-                ) {
-                    Ok(t) => print_table(t),
+                match list_options(list_instances).and_then(|options| {
+                    o.list_instances(
+                        list_instances.value_of("id"),
+                        list_instances.values_of("columns").map(|c| c.collect()),
+                        options,
+                    )
+                }) {
+                    Ok(r) => {
+                        print_output(&r, list_instances.is_present("no_header"), &output)
+                    }
                     Err(e) => exit_with_error(e),
                 }
             }
             Some(("search", search)) => {
-                match o.search_series(
-                    search.values_of("query").unwrap().collect(),
-                    search.values_of("columns").map(|c| c.collect()),
-                    search.is_present("no_header"),
-                ) {
-                    Ok(t) => print_table(t),
+                match search_entities(&o, search, EntityKind::Series) {
+                    Ok(r) => print_output(&r, search.is_present("no_header"), &output),
                     Err(e) => exit_with_error(e),
                 }
             }
             Some(("anonymize", anonymize)) => {
-                let mut keep_private_tags = None;
-                if anonymize.is_present("keep_private_tags") {
-                    keep_private_tags = Some(true);
-                }
-                match o.anonymize_series(
-                    anonymize.value_of("id").unwrap(),
-                    anonymize.values_of("replace").map(|r| r.collect()),
-                    anonymize.values_of("keep").map(|k| k.collect()),
-                    keep_private_tags,
-                    anonymize.value_of("config"),
-                ) {
-                    Ok(t) => print_table(t),
-                    Err(e) => exit_with_error(e),
+                if let Err(e) =
+                    anonymize_entities(&o, anonymize, EntityKind::Series, &output)
+                {
+                    exit_with_error(e)
+                }
+            }
+            Some(("modify", modify)) => {
+                if let Err(e) =
+                    run_entity_command(modify, &EntityKind::Series, &output, |id| {
+                        o.modify_series(
+                            id,
+                            modify.values_of("replace").map(|r| r.collect()),
+                            modify.values_of("remove").map(|r| r.collect()),
+                            modify.value_of("config"),
+                            modify.is_present("async"),
+                        )
+                    })
+                {
+                    exit_with_error(e)
+                }
+            }
+            Some(("download", download)) => {
+                if let Err(e) = download_archive(&o, download, EntityKind::Series) {
+                    exit_with_error(e)
                 }
             }
-            Some(("modify", modify)) => match o.modify_series(
-                modify.value_of("id").unwrap(),
-                modify.values_of("replace").map(|r| r.collect()),
-                modify.values_of("remove").map(|r| r.collect()),
-                modify.value_of("config"),
-            ) {
-                Ok(t) => print_table(t),
-                Err(e) => exit_with_error(e),
-            },
-            Some(("download", download)) => match o.download_series(
-                download.value_of("id").unwrap(),
-                download.value_of("output").unwrap(),
-            ) {
-                Ok(_) => (),
-                Err(e) => exit_with_error(e),
-            },
             Some(("delete", delete)) => {
-                match o.delete_series(delete.value_of("id").unwrap()) {
-                    Ok(_) => (),
-                    Err(e) => exit_with_error(e),
+                let result = match delete.value_of("id").unwrap() {
+                    "-" => {
+                        delete_entities_from_stdin(&o, EntityKind::Series, delete, &output)
+                    }
+                    id => o.delete_series(id),
+                };
+                if let Err(e) = result {
+                    exit_with_error(e)
                 }
             }
             _ => {}
         },
         Some(("instance", instance)) => match instance.subcommand() {
             Some(("list", list)) => {
-                match o.list_instances(
-                    None,
-                    list.values_of("columns").map(|c| c.collect()),
-                    list.is_present("no_header"),
-                ) {
-                    Ok(t) => print_table(t),
+                match list_options(list).and_then(|options| {
+                    o.list_instances(
+                        None,
+                        list.values_of("columns").map(|c| c.collect()),
+                        options,
+                    )
+                }) {
+                    Ok(r) => print_output(&r, list.is_present("no_header"), &output),
                     Err(e) => exit_with_error(e),
                 }
             }
-            Some(("show", show)) => match o.show_instance(show.value_of("id").unwrap()) {
-                Ok(t) => print_table(t),
-                Err(e) => exit_with_error(e),
-            },
+            Some(("show", show)) => {
+                if let Err(e) =
+                    run_entity_command(show, &EntityKind::Instance, &output, |id| {
+                        o.show_instance(id)
+                    })
+                {
+                    exit_with_error(e)
+                }
+            }
             Some(("search", search)) => {
-                match o.search_instances(
-                    search.values_of("query").unwrap().collect(),
-                    search.values_of("columns").map(|c| c.collect()),
-                    search.is_present("no_header"),
-                ) {
-                    Ok(t) => print_table(t),
+                match search_entities(&o, search, EntityKind::Instance) {
+                    Ok(r) => print_output(&r, search.is_present("no_header"), &output),
                     Err(e) => exit_with_error(e),
                 }
             }
             Some(("anonymize", anonymize)) => {
-                let mut keep_private_tags = None;
-                if anonymize.is_present("keep_private_tags") {
-                    keep_private_tags = Some(true);
-                }
-                match o.anonymize_instance(
-                    anonymize.value_of("id").unwrap(),
-                    anonymize.values_of("replace").map(|r| r.collect()),
-                    anonymize.values_of("keep").map(|k| k.collect()),
-                    keep_private_tags,
-                    anonymize.value_of("config"),
-                    anonymize.value_of("output").unwrap(),
+                if let Err(e) = anonymize_instances(&o, anonymize) {
+                    exit_with_error(e)
+                }
+            }
+            Some(("modify", modify)) => {
+                if let Err(e) =
+                    run_file_command(modify, &EntityKind::Instance, "dcm", |id, path| {
+                        o.modify_instance(
+                            id,
+                            modify.values_of("replace").map(|r| r.collect()),
+                            modify.values_of("remove").map(|r| r.collect()),
+                            modify.value_of("config"),
+                            path,
+                        )
+                    })
+                {
+                    exit_with_error(e)
+                }
+            }
+            Some(("upload", upload)) => {
+                match o.upload(upload.values_of("path").unwrap().collect()) {
+                    Ok(r) => {
+                        print_output(&r, upload.is_present("no_header"), &output);
+                        if let Err(e) = check_upload_results(&r) {
+                            exit_with_error(e)
+                        }
+                    }
+                    Err(e) => exit_with_error(e),
+                }
+            }
+            Some(("tags", tags)) => {
+                match o.show_instance_tags(
+                    tags.value_of("id").unwrap(),
+                    tags.values_of("group").map(|v| v.collect()),
+                    tags.values_of("tag").map(|v| v.collect()),
+                    tags.is_present("meta"),
                 ) {
-                    Ok(_) => (),
+                    Ok(r) => print_output(&r, false, &output),
                     Err(e) => exit_with_error(e),
                 }
             }
-            Some(("modify", modify)) => match o.modify_instance(
-                modify.value_of("id").unwrap(),
-                modify.values_of("replace").map(|r| r.collect()),
-                modify.values_of("remove").map(|r| r.collect()),
-                modify.value_of("config"),
-                modify.value_of("output").unwrap(),
-            ) {
-                Ok(_) => (),
+            Some(("download", download)) => {
+                if let Err(e) =
+                    run_file_command(download, &EntityKind::Instance, "dcm", |id, path| {
+                        with_progress(|p| o.download_instance(id, path, |b| p.update(b)))
+                    })
+                {
+                    exit_with_error(e)
+                }
+            }
+            Some(("delete", delete)) => {
+                let result = match delete.value_of("id").unwrap() {
+                    "-" => delete_entities_from_stdin(
+                        &o,
+                        EntityKind::Instance,
+                        delete,
+                        &output,
+                    ),
+                    id => o.delete_instance(id),
+                };
+                if let Err(e) = result {
+                    exit_with_error(e)
+                }
+            }
+            _ => {}
+        },
+        Some(("modality", modality)) => match modality.subcommand() {
+            Some(("list", list)) => {
+                match o.list_modalities(list.values_of("columns").map(|c| c.collect())) {
+                    Ok(r) => print_output(&r, list.is_present("no_header"), &output),
+                    Err(e) => exit_with_error(e),
+                }
+            }
+            Some(("show", show)) => match o.show_modality(show.value_of("name").unwrap()) {
+                Ok(r) => print_output(&r, false, &output),
                 Err(e) => exit_with_error(e),
             },
-            Some(("tags", tags)) => {
-                match o.show_instance_tags(tags.value_of("id").unwrap()) {
-                    Ok(t) => print_table(t),
+            Some(("create", create)) => {
+                let result = match create.value_of("from_file") {
+                    Some(f) => o.import_modalities(Path::new(f)),
+                    None => modality_config(create).and_then(|c| {
+                        o.create_modality(create.value_of("name").unwrap(), c)
+                    }),
+                };
+                match result {
+                    Ok(_) => (),
                     Err(e) => exit_with_error(e),
                 }
             }
-            Some(("download", download)) => match o.download_instance(
-                download.value_of("id").unwrap(),
-                download.value_of("output").unwrap(),
-            ) {
+            Some(("modify", modify)) => match modality_config(modify)
+                .and_then(|c| o.modify_modality(modify.value_of("name").unwrap(), c))
+            {
                 Ok(_) => (),
                 Err(e) => exit_with_error(e),
             },
+            Some(("echo", echo)) => match number_option(echo, "timeout")
+                .and_then(|t| o.echo_modality(echo.value_of("name").unwrap(), t))
+            {
+                Ok(r) => print_output(&r, false, &output),
+                Err(e) => exit_with_error(e),
+            },
+            Some(("query", query)) => match o.query_modality(
+                query.value_of("name").unwrap(),
+                query.value_of("level").unwrap(),
+                query.values_of("query").map_or(vec![], |q| q.collect()),
+                query.values_of("columns").map(|c| c.collect()),
+            ) {
+                Ok(r) => {
+                    eprintln!("Query ID: {}", r.query_id);
+                    print_output(&r.answers, query.is_present("no_header"), &output)
+                }
+                Err(e) => exit_with_error(e),
+            },
+            Some(("retrieve", retrieve)) => {
+                let source = match retrieve.value_of("query") {
+                    Some(q) => RetrieveSource::Query {
+                        id: q.to_string(),
+                        index: match number_option(retrieve, "index") {
+                            Ok(i) => i,
+                            Err(e) => return exit_with_error(e),
+                        },
+                    },
+                    None => RetrieveSource::Uids {
+                        study: retrieve.value_of("study").unwrap().to_string(),
+                        series: retrieve.value_of("series").map(String::from),
+                    },
+                };
+                match o.retrieve_from_modality(
+                    retrieve.value_of("name").unwrap(),
+                    &source,
+                    retrieve.value_of("target"),
+                    retrieve.is_present("get"),
+                    retrieve.is_present("async"),
+                ) {
+                    Ok(r) => print_output(&r, false, &output),
+                    Err(e) => exit_with_error(e),
+                }
+            }
+            Some(("store", store)) => {
+                let ids = match store_ids(store) {
+                    Ok(i) => i,
+                    Err(e) => return exit_with_error(e),
+                };
+                let ids: Vec<&str> = ids.iter().map(AsRef::as_ref).collect();
+                match o.do_store(
+                    store.value_of("name").unwrap(),
+                    &ids,
+                    store.is_present("async"),
+                ) {
+                    Ok(r) => print_output(&r, false, &output),
+                    Err(e) => exit_with_error(e),
+                }
+            }
             Some(("delete", delete)) => {
-                match o.delete_instance(delete.value_of("id").unwrap()) {
+                match o.delete_modality(delete.value_of("name").unwrap()) {
                     Ok(_) => (),
                     Err(e) => exit_with_error(e),
                 }
             }
             _ => {}
         },
-        Some(("modality", modality)) => match modality.subcommand() {
-            Some(("list", list)) => match o.list_modalities(
-                list.values_of("columns").map(|c| c.collect()),
-                list.is_present("no_header"),
+        Some(("delete", delete)) => {
+            if let Err(e) = run_delete_command(&o, delete, &output) {
+                exit_with_error(e)
+            }
+        }
+        Some(("download", download)) => {
+            if let Err(e) = run_download_command(&o, download, &output) {
+                exit_with_error(e)
+            }
+        }
+        Some(("tree", tree)) => {
+            let root = match (
+                tree.value_of("patient"),
+                tree.value_of("study"),
+                tree.value_of("series"),
             ) {
-                Ok(t) => print_table(t),
+                (Some(id), _, _) => Some((EntityKind::Patient, id)),
+                (_, Some(id), _) => Some((EntityKind::Study, id)),
+                (_, _, Some(id)) => Some((EntityKind::Series, id)),
+                _ => None,
+            };
+            match number_option(tree, "max_depth").and_then(|d| o.tree(root, d)) {
+                Ok(r) => print_output(&r, false, &output),
                 Err(e) => exit_with_error(e),
-            },
-            Some(("show", show)) => match o.show_modality(show.value_of("name").unwrap()) {
-                Ok(t) => print_table(t),
+            }
+        }
+        Some(("peer", peer)) => match peer.subcommand() {
+            Some(("list", list)) => {
+                match o.list_peers(list.values_of("columns").map(|c| c.collect())) {
+                    Ok(r) => print_output(&r, list.is_present("no_header"), &output),
+                    Err(e) => exit_with_error(e),
+                }
+            }
+            Some(("show", show)) => match o.show_peer(show.value_of("name").unwrap()) {
+                Ok(r) => print_output(&r, false, &output),
                 Err(e) => exit_with_error(e),
             },
-            Some(("create", create)) => match o.create_modality(
-                create.value_of("name").unwrap(),
-                create.value_of("aet").unwrap(),
-                create.value_of("host").unwrap(),
-                create.value_of("port").unwrap().parse::<i32>().unwrap(),
-            ) {
+            Some(("create", create)) => match peer_config(create)
+                .and_then(|c| o.create_peer(create.value_of("name").unwrap(), c))
+            {
                 Ok(_) => (),
                 Err(e) => exit_with_error(e),
             },
-            Some(("modify", modify)) => match o.modify_modality(
-                modify.value_of("name").unwrap(),
-                modify.value_of("aet").unwrap(),
-                modify.value_of("host").unwrap(),
-                modify.value_of("port").unwrap().parse::<i32>().unwrap(),
-            ) {
+            Some(("modify", modify)) => match peer_config(modify)
+                .and_then(|c| o.modify_peer(modify.value_of("name").unwrap(), c))
+            {
                 Ok(_) => (),
                 Err(e) => exit_with_error(e),
             },
+            Some(("system", system)) => {
+                match o.peer_system(system.value_of("name").unwrap()) {
+                    Ok(r) => print_output(&r, false, &output),
+                    Err(e) => exit_with_error(e),
+                }
+            }
             Some(("store", store)) => {
-                let ids: Vec<&str> = store.values_of("ids").unwrap().collect();
-                match o.do_store(store.value_of("name").unwrap(), &ids) {
-                    Ok(t) => print_table(t),
+                let ids = match store_ids(store) {
+                    Ok(i) => i,
+                    Err(e) => return exit_with_error(e),
+                };
+                let ids: Vec<&str> = ids.iter().map(AsRef::as_ref).collect();
+                match o.store_peer(
+                    store.value_of("name").unwrap(),
+                    &ids,
+                    store.value_of("transcode"),
+                    store.is_present("compress"),
+                    store.is_present("async"),
+                ) {
+                    Ok(r) => print_output(&r, false, &output),
                     Err(e) => exit_with_error(e),
                 }
             }
             Some(("delete", delete)) => {
-                match o.delete_modality(delete.value_of("name").unwrap()) {
+                match o.delete_peer(delete.value_of("name").unwrap()) {
                     Ok(_) => (),
                     Err(e) => exit_with_error(e),
                 }
             }
             _ => {}
         },
+        Some(("job", job)) => match job.subcommand() {
+            Some(("list", list)) => {
+                match o.list_jobs(list.values_of("columns").map(|c| c.collect())) {
+                    Ok(r) => print_output(&r, list.is_present("no_header"), &output),
+                    Err(e) => exit_with_error(e),
+                }
+            }
+            Some(("show", show)) => match o.show_job(show.value_of("id").unwrap()) {
+                Ok(r) => print_output(&r, false, &output),
+                Err(e) => exit_with_error(e),
+            },
+            Some(("cancel", cancel)) => {
+                if let Err(e) = o.cancel_job(cancel.value_of("id").unwrap()) {
+                    exit_with_error(e)
+                }
+            }
+            Some(("pause", pause)) => {
+                if let Err(e) = o.pause_job(pause.value_of("id").unwrap()) {
+                    exit_with_error(e)
+                }
+            }
+            Some(("resume", resume)) => {
+                if let Err(e) = o.resume_job(resume.value_of("id").unwrap()) {
+                    exit_with_error(e)
+                }
+            }
+            Some(("resubmit", resubmit)) => {
+                if let Err(e) = o.resubmit_job(resubmit.value_of("id").unwrap()) {
+                    exit_with_error(e)
+                }
+            }
+            Some(("wait", wait)) => match wait_for_job(&o, wait) {
+                Ok(r) => print_output(&r, false, &output),
+                Err(e) => exit_with_error(e),
+            },
+            _ => {}
+        },
+        _ => {}
+    }
+}
+
+fn run_config_command(config: &ArgMatches, output: &OutputFormat) -> Result<()> {
+    let path = config_path()?;
+    let mut c = Config::load(&path)?;
+    match config.subcommand() {
+        Some(("list", list)) => {
+            print_output(&c.list_profiles(), list.is_present("no_header"), output)
+        }
+        Some(("show", show)) => print_output(
+            &c.show_profile(show.value_of("name").unwrap())?,
+            false,
+            output,
+        ),
+        Some(("add", add)) => {
+            c.add_profile(add.value_of("name").unwrap(), profile_from_args(add)?);
+            c.save(&path)?
+        }
+        Some(("remove", remove)) => {
+            c.remove_profile(remove.value_of("name").unwrap())?;
+            c.save(&path)?
+        }
         _ => {}
     }
+    Ok(())
+}
+
+/// Builds the profile of `config add`. The secrets are asked for (see [`read_secret`]), and the
+/// paths of the certificates are stored as absolute paths
+fn profile_from_args(args: &ArgMatches) -> Result<Profile> {
+    let secret = |name: &str, what: &str| -> Result<Option<String>> {
+        match args.is_present(name) {
+            true => read_secret(what).map(Some),
+            false => Ok(None),
+        }
+    };
+    let path = |name: &str, what: &str| -> Result<Option<String>> {
+        args.value_of(name)
+            .map(|p| setting_path(p, what))
+            .transpose()
+    };
+    let headers: Option<Vec<&str>> = args.values_of("header").map(|h| h.collect());
+    // Only validated, the headers are stored as they are given
+    get_headers(headers.clone(), None)?;
+    Ok(Profile {
+        server: args.value_of("server").unwrap().to_string(),
+        username: args.value_of("username").map(String::from),
+        password: secret("password", "Password")?,
+        token: secret("token", "Token")?,
+        headers: headers.map(|h| h.iter().map(ToString::to_string).collect()),
+        ca_cert: path("ca_cert", "CA certificate")?,
+        client_cert: path("client_cert", "client certificate")?,
+        client_key: path("client_key", "client key")?,
+        client_pkcs12: path("client_pkcs12", "client certificate archive")?,
+        client_pkcs12_password: secret("client_pkcs12_password", "PKCS#12 password")?,
+        insecure: args.is_present("insecure").then_some(true),
+        timeout: number_option(args, "timeout")?,
+        proxy: args.value_of("proxy").map(String::from),
+    })
+}
+
+/// Deletes the entities selected by `--query` or `--ids-from`, after showing them and asking for
+/// confirmation
+fn run_delete_command(o: &Orthanc, args: &ArgMatches, output: &OutputFormat) -> Result<()> {
+    let level = entity_kind(args.value_of("level").unwrap())?;
+    let selection = match args.values_of("query") {
+        Some(q) => EntitySelection::Query(parse_search_query(
+            q.collect(),
+            args.is_present("case_sensitive"),
+        )?),
+        None => EntitySelection::Ids(read_ids(args.value_of("ids_from").unwrap())?),
+    };
+    let selected = o.select_entities(&level, &selection, None)?;
+    if args.is_present("dry_run") {
+        print_output(&selected, false, output);
+        return Ok(());
+    }
+    delete_selected(
+        o,
+        &level,
+        selected,
+        args.is_present("yes"),
+        args.value_of("ids_from") == Some("-"),
+        output,
+    )
+}
+
+/// Deletes the `selected` entities, after showing them and asking for confirmation, unless `yes`
+/// is set. When the IDs are read from the standard input, it can not be used to confirm
+fn delete_selected(
+    o: &Orthanc,
+    level: &EntityKind,
+    selected: EntityList,
+    yes: bool,
+    ids_from_stdin: bool,
+    output: &OutputFormat,
+) -> Result<()> {
+    if selected.rows.is_empty() {
+        eprintln!("No {} to delete", entity_kind_plural(level));
+        return Ok(());
+    }
+
+    if !yes {
+        // The standard input is either taken by the IDs, or is not there to answer
+        if ids_from_stdin || !io::stdin().is_terminal() {
+            return Err(CliError::new(
+                "Command error",
+                Some("Deletion has to be confirmed"),
+                Some("Use --dry-run to see what would be deleted, and --yes to delete it"),
+            ));
+        }
+        eprintln!("{}", render(&selected, false, &OutputFormat::Table)?);
+        eprint!(
+            "Delete {} {}? [y/N] ",
+            selected.rows.len(),
+            entity_kind_plural(level)
+        );
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+            eprintln!("Nothing deleted");
+            return Ok(());
+        }
+    }
+
+    let ids: Vec<&str> = selected
+        .rows
+        .iter()
+        .filter_map(|r| r["ID"].as_str())
+        .collect();
+    let results = o.delete_entities(level, &ids);
+    print_output(&results, false, output);
+    check_delete_results(&results, level)
+}
+
+fn run_download_command(
+    o: &Orthanc,
+    args: &ArgMatches,
+    output: &OutputFormat,
+) -> Result<()> {
+    let level = entity_kind(args.value_of("level").unwrap())?;
+    let template = args.value_of("name_template").unwrap();
+    let mut columns = vec!["ID"];
+    columns.extend(name_template_tags(template)?);
+    let selection = match args.values_of("query") {
+        Some(q) => EntitySelection::Query(parse_search_query(
+            q.collect(),
+            args.is_present("case_sensitive"),
+        )?),
+        None => EntitySelection::Ids(read_ids(args.value_of("ids_from").unwrap())?),
+    };
+    let selected = o.select_entities(&level, &selection, Some(columns))?;
+    if selected.rows.is_empty() {
+        eprintln!("No {} to download", entity_kind_plural(&level));
+        return Ok(());
+    }
+
+    let paths = download_paths(
+        &selected,
+        template,
+        Path::new(args.value_of("output_dir").unwrap()),
+    )?;
+    let downloads: Vec<(&str, PathBuf)> = selected
+        .rows
+        .iter()
+        .filter_map(|r| r["ID"].as_str())
+        .zip(paths)
+        .collect();
+    let progress = Mutex::new(TransferProgress::new(io::stderr().is_terminal()));
+    let results = o.download_entities(
+        &level,
+        &downloads,
+        args.is_present("media"),
+        number_option(args, "parallel")?.unwrap(),
+        number_option(args, "retries")?.unwrap(),
+        |b| progress.lock().unwrap().update(b),
+    );
+    progress.into_inner().unwrap().finish();
+    print_output(&results, false, output);
+    check_download_results(&results, &level)
+}
+
+/// The anonymization configuration of an `anonymize` command
+fn anonymization_config(args: &ArgMatches) -> Result<Option<Anonymization>> {
+    get_anonymization_config(
+        args.values_of("replace").map(|r| r.collect()),
+        args.values_of("keep").map(|k| k.collect()),
+        args.is_present("keep_private_tags").then_some(true),
+        args.values_of("anonymization_profile").map(|p| p.collect()),
+        args.value_of("dicom_version"),
+        args.value_of("config"),
+    )
+}
+
+/// Prints an anonymization configuration as the JSON request body, that is sent to Orthanc
+fn print_anonymization_config(config: Option<Anonymization>) -> Result<()> {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&config.unwrap_or_default())?
+    );
+    Ok(())
+}
+
+/// Runs `anonymize` of patients, studies and series. With a pseudonym salt the patients get
+/// deterministic pseudonyms, and with `--mapping-file` the original and the new identifiers
+/// of every anonymized entity are recorded. With `--print-config` the anonymization
+/// configuration is printed instead
+fn anonymize_entities(
+    o: &Orthanc,
+    args: &ArgMatches,
+    kind: EntityKind,
+    output: &OutputFormat,
+) -> Result<()> {
+    let config = anonymization_config(args)?;
+    let salt = get_pseudonym_salt(args.value_of("pseudonym_salt_file"))?;
+    let mapping_file = args.value_of("mapping_file");
+
+    // The configuration of an entity, and its original identifiers, if they are needed
+    let entity_config = |id: &str| -> Result<(Option<Anonymization>, Option<Identifiers>)> {
+        if salt.is_none() && mapping_file.is_none() {
+            return Ok((config.clone(), None));
+        }
+        let original = o.entity_identifiers(&kind, id)?;
+        let config = match &salt {
+            Some(s) => Some(add_pseudonyms(config.clone(), s, &original)?),
+            None => config.clone(),
+        };
+        Ok((config, Some(original)))
+    };
+
+    if args.is_present("print_config") {
+        return match (&salt, args.value_of("id").unwrap()) {
+            (None, _) => print_anonymization_config(config.clone()),
+            (Some(_), "-") => Err(CliError::new(
+                "Command error",
+                Some("Cannot print the configuration of many entities with pseudonyms"),
+                Some("The pseudonyms depend on the entity. Print the configuration of a single ID"),
+            )),
+            (Some(_), id) => print_anonymization_config(entity_config(id)?.0),
+        };
+    }
+
+    let asynchronous = args.is_present("async");
+    run_entity_command(args, &kind, output, |id| {
+        let (config, original) = entity_config(id)?;
+        let result = match kind {
+            EntityKind::Patient => o.anonymize_patient(id, config, asynchronous)?,
+            EntityKind::Study => o.anonymize_study(id, config, asynchronous)?,
+            _ => o.anonymize_series(id, config, asynchronous)?,
+        };
+        if let (Some(file), Some(original), Outcome::Completed(r)) =
+            (mapping_file, &original, &result)
+        {
+            o.record_mapping(file, &kind, id, &r.id, original)?;
+        }
+        Ok(result)
+    })
+}
+
+/// Runs `anonymize` of instances. With `--print-config` the anonymization configuration is
+/// printed instead
+fn anonymize_instances(o: &Orthanc, args: &ArgMatches) -> Result<()> {
+    let config = anonymization_config(args)?;
+    if args.is_present("print_config") {
+        return print_anonymization_config(config);
+    }
+    run_file_command(args, &EntityKind::Instance, "dcm", |id, path| {
+        o.anonymize_instance(id, config.clone(), path)
+    })
+}
+
+/// Runs a per-entity command for its `id` argument, or for every ID read from the standard input
+/// if it is `-`. The results for many IDs are printed as a single list, also when the command
+/// fails for one of the IDs, which stops the processing of the IDs, that follow
+fn run_entity_command<T, F>(
+    args: &ArgMatches,
+    kind: &EntityKind,
+    output: &OutputFormat,
+    command: F,
+) -> Result<()>
+where
+    T: Output + ToRow,
+    F: Fn(&str) -> Result<T>,
+{
+    let id = args.value_of("id").unwrap();
+    if id != "-" {
+        print_output(&command(id)?, false, output);
+        return Ok(());
+    }
+    let mut results = vec![];
+    let mut error = None;
+    for id in read_ids("-")? {
+        match command(&id) {
+            Ok(r) => results.push((id, r)),
+            Err(e) => {
+                error = Some(e.with_entity(kind, &id));
+                break;
+            }
+        }
+    }
+    if !results.is_empty() {
+        print_output(&create_results_list(results), false, output);
+    }
+    error.map_or(Ok(()), Err)
+}
+
+/// Runs a per-entity command, that writes a file to `--output`. When the IDs are read from the
+/// standard input, `--output` is a directory, where a file named after each ID is written
+fn run_file_command<F>(
+    args: &ArgMatches,
+    kind: &EntityKind,
+    extension: &str,
+    command: F,
+) -> Result<()>
+where
+    F: Fn(&str, &str) -> Result<()>,
+{
+    let id = args.value_of("id").unwrap();
+    let output = args.value_of("output").unwrap();
+    if id != "-" {
+        return command(id, output);
+    }
+    if output == "-" {
+        return Err(CliError::new(
+            "Command error",
+            Some("Cannot write the files of many IDs to the standard output"),
+            Some("Use a directory as -o/--output, when the IDs are read from the standard input"),
+        ));
+    }
+    fs::create_dir_all(output)?;
+    for id in read_ids("-")? {
+        let path = Path::new(output).join(format!("{}.{}", id, extension));
+        command(&id, &path.to_string_lossy()).map_err(|e| e.with_entity(kind, &id))?;
+    }
+    Ok(())
+}
+
+/// Runs a transfer, showing its progress on the standard error, if it is a terminal
+fn with_progress<T, F: FnOnce(&mut TransferProgress) -> Result<T>>(
+    transfer: F,
+) -> Result<T> {
+    let mut progress = TransferProgress::new(io::stderr().is_terminal());
+    let result = transfer(&mut progress);
+    progress.finish();
+    result
+}
+
+/// Runs `download` of patients, studies and series, which either write the archive of an entity
+/// to a file, or extract it into a directory
+fn download_archive(o: &Orthanc, args: &ArgMatches, kind: EntityKind) -> Result<()> {
+    let media = args.is_present("media");
+    let dir = match args.value_of("extract") {
+        Some(d) => d,
+        None => {
+            return run_file_command(args, &kind, "zip", |id, path| {
+                with_progress(|p| {
+                    o.download_archive(&kind, id, path, media, |b| p.update(b))
+                })
+            })
+        }
+    };
+    let template = args.value_of("path_template");
+    match args.value_of("id").unwrap() {
+        "-" => {
+            for id in read_ids("-")? {
+                with_progress(|p| {
+                    o.extract(&kind, &id, dir, template, media, |b| p.update(b))
+                })
+                .map_err(|e| e.with_entity(&kind, &id))?;
+            }
+        }
+        id => {
+            with_progress(|p| o.extract(&kind, id, dir, template, media, |b| p.update(b)))?;
+        }
+    }
+    Ok(())
+}
+
+/// Deletes the entities, whose IDs are read from the standard input, once `--yes` confirms it
+fn delete_entities_from_stdin(
+    o: &Orthanc,
+    kind: EntityKind,
+    args: &ArgMatches,
+    output: &OutputFormat,
+) -> Result<()> {
+    let selected = o.select_entities(&kind, &EntitySelection::Ids(read_ids("-")?), None)?;
+    delete_selected(o, &kind, selected, args.is_present("yes"), true, output)
+}
+
+/// `--entity-ids` of `store` commands, where `-` stands for the IDs read from the standard input
+fn store_ids(args: &ArgMatches) -> Result<Vec<String>> {
+    let mut ids = vec![];
+    for id in args.values_of("ids").unwrap() {
+        match id {
+            "-" => ids.extend(read_ids("-")?),
+            _ => ids.push(id.to_string()),
+        }
+    }
+    Ok(ids)
+}
+
+/// Runs `job wait`, showing the progress of the job on stderr
+fn wait_for_job(o: &Orthanc, args: &ArgMatches) -> Result<EntityDetails> {
+    let interval = number_option(args, "interval")?.unwrap();
+    let timeout = number_option(args, "job_timeout")?;
+    let res = o.wait_for_job(
+        args.value_of("id").unwrap(),
+        Duration::from_secs(interval),
+        timeout.map(Duration::from_secs),
+        |job| eprint!("\r{}", format_job_progress(job)),
+    );
+    eprintln!();
+    res
+}
+
+/// The value of a numeric option, if it is set. Invalid values are an error, rather than a panic,
+/// should they get past the validator of the option
+fn number_option<T: FromStr>(args: &ArgMatches, name: &str) -> Result<Option<T>> {
+    args.value_of(name)
+        .map(|v| parse_number(v, &name.replace('_', " ")))
+        .transpose()
+}
+
+/// `--limit`, `--offset` and `--sort-by` of list and search commands
+fn list_options(args: &ArgMatches) -> Result<ListOptions> {
+    Ok(ListOptions {
+        limit: number_option(args, "limit")?,
+        offset: number_option(args, "offset")?.unwrap_or(0),
+        sort_by: args.value_of("sort_by").map(parse_sort_by).transpose()?,
+    })
+}
+
+/// Runs `<ENTITY> search`: matches the query at the `level` of the command, and lists the
+/// entities of the level requested with `--level`
+fn search_entities(
+    o: &Orthanc,
+    args: &ArgMatches,
+    level: EntityKind,
+) -> Result<EntityList> {
+    let query = parse_search_query(
+        args.values_of("query").unwrap().collect(),
+        args.is_present("case_sensitive"),
+    )?;
+    let columns = args.values_of("columns").map(|c| c.collect());
+    let options = list_options(args)?;
+    match args.value_of("level").unwrap() {
+        "patient" => o.search_patients(query, level, columns, options),
+        "study" => o.search_studies(query, level, columns, options),
+        "series" => o.search_series(query, level, columns, options),
+        _ => o.search_instances(query, level, columns, options),
+    }
+}
+
+/// Modality settings, that are specified on the command line of `modality create` or
+/// `modality modify`
+fn modality_config(args: &ArgMatches) -> Result<ModalityConfig> {
+    let mut config = ModalityConfig {
+        aet: args.value_of("aet").map(String::from),
+        host: args.value_of("host").map(String::from),
+        port: number_option(args, "port")?,
+        manufacturer: args.value_of("manufacturer").map(String::from),
+        allow_transcoding: args.value_of("transcoding").map(|t| t == "true"),
+        local_aet: args.value_of("local_aet").map(String::from),
+        timeout: number_option(args, "timeout")?,
+        use_dicom_tls: args.value_of("tls").map(|t| t == "true"),
+        ..Default::default()
+    };
+    set_dimse_permissions(
+        &mut config,
+        args.values_of("allow").map(|a| a.collect()),
+        args.values_of("deny").map(|d| d.collect()),
+    )?;
+    Ok(config)
+}
+
+/// Peer settings, that are specified on the command line of `peer create` or `peer modify`
+fn peer_config(args: &ArgMatches) -> Result<PeerConfig> {
+    let http_headers = match args.values_of("header") {
+        Some(h) => Some(Value::Object(
+            get_headers(Some(h.collect()), None)?
+                .into_iter()
+                .map(|(name, value)| (name, Value::from(value)))
+                .collect(),
+        )),
+        None => None,
+    };
+    Ok(PeerConfig {
+        url: args.value_of("url").map(String::from),
+        username: args.value_of("username").map(String::from),
+        password: args.value_of("password").map(String::from),
+        http_headers,
+        certificate_file: args.value_of("certificate_file").map(String::from),
+        certificate_key_file: args.value_of("certificate_key_file").map(String::from),
+        certificate_key_password: args
+            .value_of("certificate_key_password")
+            .map(String::from),
+        timeout: number_option(args, "timeout")?,
+        ..Default::default()
+    })
 }
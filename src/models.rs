@@ -0,0 +1,769 @@
+use orthanc::entity::*;
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+
+/// A single row of a result, mapping column names to values. DICOM tags that are absent from an
+/// entity are represented as [`Value::Null`]
+pub type Row = Map<String, Value>;
+
+/// A list of entities (patients, studies, modalities etc.), one row per entity
+#[derive(Debug, PartialEq)]
+pub struct EntityList {
+    pub columns: Vec<String>,
+    pub rows: Vec<Row>,
+}
+
+/// Details of a single entity
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(transparent)]
+pub struct EntityDetails {
+    pub fields: Row,
+}
+
+/// A DICOM tag of an instance
+#[derive(Serialize, Debug, Eq, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct InstanceTag {
+    pub tag: String,
+    pub name: String,
+    /// The type of the value, as reported by Orthanc: `String`, `Sequence`, `Null`, `Binary` or
+    /// `TooLong`
+    #[serde(rename = "Type")]
+    pub kind: String,
+    pub value: TagValue,
+}
+
+/// The value of a DICOM tag. Multiple values are kept backslash-separated, as in DICOM
+#[derive(Serialize, Debug, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum TagValue {
+    None,
+    String(String),
+    /// Sequence items, each of which is a list of tags
+    Sequence(Vec<Vec<InstanceTag>>),
+}
+
+/// An Orthanc job, as returned by `/jobs/{id}`
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct Job {
+    #[serde(rename = "ID")]
+    pub id: String,
+    #[serde(rename = "Type")]
+    pub kind: String,
+    /// `Pending`, `Running`, `Success`, `Failure`, `Paused` or `Retry`
+    pub state: String,
+    /// Percentage of completion
+    pub progress: u64,
+    #[serde(default)]
+    pub priority: i64,
+    pub creation_time: String,
+    #[serde(default)]
+    pub completion_time: Option<String>,
+    #[serde(default)]
+    pub effective_runtime: f64,
+    #[serde(default)]
+    pub error_code: i64,
+    #[serde(default)]
+    pub error_description: String,
+    #[serde(default)]
+    pub content: Value,
+}
+
+/// An entity in the patient/study/series/instance hierarchy, along with its descendants
+#[derive(Serialize, Debug, PartialEq)]
+pub struct TreeNode {
+    #[serde(rename = "Type")]
+    pub kind: EntityKind,
+    #[serde(rename = "ID")]
+    pub id: String,
+    /// Key DICOM tags of the entity and the number of its children
+    #[serde(flatten)]
+    pub fields: Row,
+    #[serde(rename = "Children", skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<TreeNode>,
+}
+
+/// Response to an asynchronous request, that has been submitted as a job
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub struct JobSubmission {
+    #[serde(rename = "ID")]
+    pub id: String,
+    #[serde(rename = "Path")]
+    pub path: String,
+}
+
+/// Configuration of an Orthanc peer. As with [`ModalityConfig`], every field is optional.
+/// Orthanc does not disclose secrets: passwords are returned as `null`, and only the names of
+/// the HTTP headers are returned
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct PeerConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// A map of header names to values when sent to Orthanc, a list of header names when
+    /// returned by Orthanc
+    #[serde(
+        rename = "HttpHeaders",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub http_headers: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub certificate_file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub certificate_key_file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub certificate_key_password: Option<String>,
+    #[serde(rename = "Pkcs11", default, skip_serializing_if = "Option::is_none")]
+    pub pkcs11: Option<bool>,
+    /// HTTP timeout in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u32>,
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+fn serialize_sorted<S: Serializer>(
+    map: &Option<HashMap<String, String>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    map.as_ref()
+        .map(|m| m.iter().collect::<BTreeMap<_, _>>())
+        .serialize(serializer)
+}
+
+/// Anonymization request body. Mirrors `orthanc::models::Anonymization`, and adds `Remove`. The
+/// fields are read in snake case from configuration files, and sent to Orthanc in Pascal case,
+/// with the replaced tags sorted, so that `--print-config` prints them in a stable order
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Eq, PartialEq)]
+pub struct Anonymization {
+    #[serde(rename(serialize = "Replace"))]
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_sorted"
+    )]
+    pub replace: Option<HashMap<String, String>>,
+    #[serde(rename(serialize = "Keep"))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep: Option<Vec<String>>,
+    #[serde(rename(serialize = "Remove"))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remove: Option<Vec<String>>,
+    #[serde(rename(serialize = "KeepPrivateTags"))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_private_tags: Option<bool>,
+    #[serde(rename(serialize = "DicomVersion"))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dicom_version: Option<String>,
+    #[serde(rename(serialize = "Force"))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub force: Option<bool>,
+}
+
+/// Identifiers of a patient, or of a study, that are recorded in a pseudonymization mapping
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct Identifiers {
+    pub patient_id: Option<String>,
+    pub patient_name: Option<String>,
+    pub study_instance_uid: Option<String>,
+    pub accession_number: Option<String>,
+}
+
+/// A C-FIND query, that Orthanc has sent to a remote modality. Its answers are kept by Orthanc
+/// under `/queries/{id}`, so that they can be retrieved later on
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub struct ModalityQuery {
+    #[serde(rename = "ID")]
+    pub id: String,
+    #[serde(rename = "Path")]
+    pub path: String,
+}
+
+/// Answers to a C-FIND query, one row per answer
+#[derive(Debug, PartialEq)]
+pub struct QueryAnswers {
+    pub query_id: String,
+    pub answers: EntityList,
+}
+
+/// Resources to retrieve from a remote modality
+#[derive(Debug, Eq, PartialEq)]
+pub enum RetrieveSource {
+    /// A study, or one of its series, identified by their UIDs
+    Uids {
+        study: String,
+        series: Option<String>,
+    },
+    /// The answers to a C-FIND query, all of them or only the one at `index`
+    Query { id: String, index: Option<usize> },
+}
+
+/// A query of `/tools/find`: the values of DICOM tags to match
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct SearchQuery {
+    pub tags: HashMap<String, String>,
+    /// Match person names case-sensitively. Orthanc's `CaseSensitivePN` setting applies otherwise
+    pub case_sensitive: bool,
+}
+
+/// Entities to operate on in bulk: either those matching a search query, or those with the IDs
+/// given
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum EntitySelection {
+    Query(SearchQuery),
+    Ids(Vec<String>),
+}
+
+/// Which part of a list to show, and in which order
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct ListOptions {
+    pub limit: Option<usize>,
+    pub offset: usize,
+    pub sort_by: Option<SortBy>,
+}
+
+/// A column to sort a list by
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SortBy {
+    pub column: String,
+    pub descending: bool,
+}
+
+impl ListOptions {
+    /// `since` and `limit` to request from Orthanc. The server can only pick the range, when the
+    /// list does not have to be sorted first. Orthanc requires a limit along with `since`
+    pub fn server_range(&self) -> Option<(usize, usize)> {
+        match (&self.sort_by, self.limit) {
+            (None, Some(limit)) => Some((self.offset, limit)),
+            _ => None,
+        }
+    }
+
+    /// Skips `offset` items and keeps at most `limit` of the rest
+    pub fn page<T>(&self, items: Vec<T>) -> Vec<T> {
+        items
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+}
+
+/// Outcome of an operation, that either runs to completion, or is submitted as a job
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Outcome<T> {
+    Completed(T),
+    Submitted(JobSubmission),
+}
+
+/// Configuration of a DICOM modality, as stored by Orthanc. Every field is optional, so that the
+/// same structure can describe a partial update. Settings unknown to orthanc-cli are kept in
+/// `other`, so that they survive a round-trip through `modality modify`
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct ModalityConfig {
+    #[serde(rename = "AET", default, skip_serializing_if = "Option::is_none")]
+    pub aet: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manufacturer: Option<String>,
+    #[serde(rename = "AllowEcho", default, skip_serializing_if = "Option::is_none")]
+    pub allow_c_echo: Option<bool>,
+    #[serde(rename = "AllowFind", default, skip_serializing_if = "Option::is_none")]
+    pub allow_c_find: Option<bool>,
+    #[serde(rename = "AllowGet", default, skip_serializing_if = "Option::is_none")]
+    pub allow_c_get: Option<bool>,
+    #[serde(rename = "AllowMove", default, skip_serializing_if = "Option::is_none")]
+    pub allow_c_move: Option<bool>,
+    #[serde(
+        rename = "AllowStore",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub allow_c_store: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allow_n_action: Option<bool>,
+    #[serde(
+        rename = "AllowEventReport",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub allow_n_event_report: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allow_transcoding: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_aet: Option<String>,
+    /// DIMSE timeout in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub use_dicom_tls: Option<bool>,
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+impl Serialize for EntityList {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.rows.serialize(serializer)
+    }
+}
+
+impl EntityList {
+    /// Builds a list out of entities, keeping only the `columns` requested. `dicom_tags` are the
+    /// columns, that are looked up among the entities' main DICOM tags
+    pub fn new<T: Entity>(entities: Vec<T>, columns: &[&str], dicom_tags: &[&str]) -> Self {
+        let mut rows = vec![];
+        for entity in entities {
+            let mut row = Row::new();
+
+            if columns.contains(&"ID") {
+                row.insert("ID".to_string(), Value::from(entity.id()));
+            };
+
+            for t in dicom_tags.iter() {
+                row.insert(t.to_string(), dicom_tag_value(&entity, t));
+            }
+
+            match T::kind() {
+                EntityKind::Instance => {
+                    if columns.contains(&"Index in series") {
+                        row.insert(
+                            "Index in series".to_string(),
+                            Value::from(entity.index()),
+                        );
+                    };
+                    if columns.contains(&"File size") {
+                        row.insert("File size".to_string(), Value::from(entity.size()));
+                    };
+                }
+                _ => {
+                    let num_children =
+                        format!("Number of {}", entity.children_kind_name().unwrap());
+                    if columns.contains(&num_children.as_str()) {
+                        row.insert(num_children, Value::from(entity.children_len()));
+                    }
+                }
+            }
+            rows.push(row);
+        }
+        EntityList {
+            columns: columns.iter().map(ToString::to_string).collect(),
+            rows,
+        }
+    }
+}
+
+impl EntityDetails {
+    pub fn new<T: Entity>(entity: T, dicom_tags: &[&str]) -> Self {
+        let mut fields = Row::new();
+        fields.insert("ID".to_string(), Value::from(entity.id()));
+        if T::kind() != EntityKind::Patient {
+            fields.insert(
+                format!("{} ID", entity.parent_kind_name().unwrap()),
+                Value::from(entity.parent_id().unwrap()),
+            );
+        }
+
+        for t in dicom_tags.iter() {
+            fields.insert(t.to_string(), dicom_tag_value(&entity, t));
+        }
+
+        match T::kind() {
+            EntityKind::Instance => {
+                fields.insert("Index in series".to_string(), Value::from(entity.index()));
+                fields.insert("File size".to_string(), Value::from(entity.size()));
+            }
+            _ => {
+                fields.insert(
+                    format!("Number of {}", entity.children_kind_name().unwrap()),
+                    Value::from(entity.children_len()),
+                );
+            }
+        }
+        EntityDetails { fields }
+    }
+}
+
+impl TreeNode {
+    /// A node without children, holding the `dicom_tags` of the entity and the number of its
+    /// children
+    pub fn new<T: Entity>(entity: &T, dicom_tags: &[&str]) -> Self {
+        let mut fields = Row::new();
+        for t in dicom_tags.iter() {
+            fields.insert(t.to_string(), dicom_tag_value(entity, t));
+        }
+        if let Some(children) = entity.children_kind_name() {
+            fields.insert(
+                format!("Number of {}", children),
+                Value::from(entity.children_len()),
+            );
+        }
+        TreeNode {
+            kind: T::kind(),
+            id: entity.id().to_string(),
+            fields,
+            children: vec![],
+        }
+    }
+}
+
+impl InstanceTag {
+    /// Parses DICOM tags in Orthanc's expanded format (as returned by `/instances/{id}/tags`),
+    /// descending into sequences
+    pub fn from_expanded_tags(tags: &Value) -> Vec<InstanceTag> {
+        let map = match tags {
+            Value::Object(m) => m,
+            _ => return vec![],
+        };
+        map.iter()
+            .map(|(tag, v)| InstanceTag {
+                tag: tag.to_string(),
+                name: v["Name"].as_str().unwrap_or_default().to_string(),
+                kind: v["Type"].as_str().unwrap_or_default().to_string(),
+                value: match &v["Value"] {
+                    Value::String(s) => TagValue::String(s.to_string()),
+                    Value::Array(items) => TagValue::Sequence(
+                        items.iter().map(InstanceTag::from_expanded_tags).collect(),
+                    ),
+                    _ => TagValue::None,
+                },
+            })
+            .collect()
+    }
+}
+
+/// Sets the optional fields of `$target`, that are set in `$update`
+macro_rules! merge_fields {
+    ($target:ident, $update:ident, $($f:ident),*) => {
+        $(if $update.$f.is_some() {
+            $target.$f = $update.$f;
+        })*
+    };
+}
+
+impl ModalityConfig {
+    /// Overrides the settings with those, that are set in `update`
+    pub fn merge(&mut self, update: ModalityConfig) {
+        merge_fields!(
+            self,
+            update,
+            aet,
+            host,
+            port,
+            manufacturer,
+            allow_c_echo,
+            allow_c_find,
+            allow_c_get,
+            allow_c_move,
+            allow_c_store,
+            allow_n_action,
+            allow_n_event_report,
+            allow_transcoding,
+            local_aet,
+            timeout,
+            use_dicom_tls
+        );
+        self.other.extend(update.other);
+    }
+}
+
+impl PeerConfig {
+    /// Overrides the settings with those, that are set in `update`
+    pub fn merge(&mut self, update: PeerConfig) {
+        merge_fields!(
+            self,
+            update,
+            url,
+            username,
+            password,
+            http_headers,
+            certificate_file,
+            certificate_key_file,
+            certificate_key_password,
+            pkcs11,
+            timeout
+        );
+        self.other.extend(update.other);
+    }
+
+    /// Names of the HTTP headers, whether they are given as a map or as a list of names
+    pub fn http_header_names(&self) -> Vec<String> {
+        match &self.http_headers {
+            Some(Value::Object(m)) => m.keys().cloned().collect(),
+            Some(Value::Array(a)) => a
+                .iter()
+                .filter_map(|n| n.as_str().map(String::from))
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+fn dicom_tag_value<T: Entity>(entity: &T, tag: &str) -> Value {
+    entity.main_dicom_tag(tag).map_or(Value::Null, Value::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use maplit::hashmap;
+    use serde_json::json;
+
+    fn instance() -> Instance {
+        Instance {
+            id: "foo".to_string(),
+            main_dicom_tags: hashmap! {
+                "SOPInstanceUID".to_string() => "suid_1".to_string(),
+            },
+            parent_series: "foo_series".to_string(),
+            index_in_series: Some(13),
+            file_uuid: "file_uuid".to_string(),
+            file_size: 139402,
+            modified_from: None,
+            entity: EntityKind::Instance,
+            anonymized_from: None,
+        }
+    }
+
+    #[test]
+    fn test_entity_list() {
+        let patient = Patient {
+            id: "foo".to_string(),
+            is_stable: true,
+            last_update: NaiveDate::from_ymd(2020, 1, 1).and_hms(15, 46, 17),
+            main_dicom_tags: hashmap! {
+                "PatientName".to_string() => "Rick Sanchez".to_string(),
+            },
+            studies: ["study_1".to_string()].to_vec(),
+            entity: EntityKind::Patient,
+            anonymized_from: None,
+        };
+        let list = EntityList::new(
+            vec![patient],
+            &["ID", "PatientID", "PatientName", "Number of Studies"],
+            &["PatientID", "PatientName"],
+        );
+        assert_eq!(
+            list.columns,
+            vec!["ID", "PatientID", "PatientName", "Number of Studies"]
+        );
+        assert_eq!(
+            serde_json::to_value(&list).unwrap(),
+            json!([{
+                "ID": "foo",
+                "PatientID": null,
+                "PatientName": "Rick Sanchez",
+                "Number of Studies": 1
+            }])
+        );
+    }
+
+    #[test]
+    fn test_entity_list_columns() {
+        let list = EntityList::new(
+            vec![instance()],
+            &["SOPInstanceUID", "File size"],
+            &["SOPInstanceUID"],
+        );
+        assert_eq!(
+            serde_json::to_value(&list).unwrap(),
+            json!([{"SOPInstanceUID": "suid_1", "File size": 139402}])
+        );
+    }
+
+    #[test]
+    fn test_instance_tags_from_expanded_tags() {
+        let tags = InstanceTag::from_expanded_tags(&json!({
+            "0008,1140": {
+                "Name": "ReferencedImageSequence",
+                "Type": "Sequence",
+                "Value": [
+                    {"0008,1155": {"Name": "ReferencedSOPInstanceUID", "Type": "String", "Value": "1.2.3"}}
+                ]
+            },
+            "0010,0010": {"Name": "PatientName", "Type": "String", "Value": "Rick\\Morty"},
+            "0010,0030": {"Name": "PatientBirthDate", "Type": "Null", "Value": null},
+            "7fe0,0010": {"Name": "PixelData", "Type": "TooLong"}
+        }));
+        assert_eq!(
+            tags,
+            vec![
+                InstanceTag {
+                    tag: "0008,1140".to_string(),
+                    name: "ReferencedImageSequence".to_string(),
+                    kind: "Sequence".to_string(),
+                    value: TagValue::Sequence(vec![vec![InstanceTag {
+                        tag: "0008,1155".to_string(),
+                        name: "ReferencedSOPInstanceUID".to_string(),
+                        kind: "String".to_string(),
+                        value: TagValue::String("1.2.3".to_string()),
+                    }]]),
+                },
+                InstanceTag {
+                    tag: "0010,0010".to_string(),
+                    name: "PatientName".to_string(),
+                    kind: "String".to_string(),
+                    value: TagValue::String("Rick\\Morty".to_string()),
+                },
+                InstanceTag {
+                    tag: "0010,0030".to_string(),
+                    name: "PatientBirthDate".to_string(),
+                    kind: "Null".to_string(),
+                    value: TagValue::None,
+                },
+                InstanceTag {
+                    tag: "7fe0,0010".to_string(),
+                    name: "PixelData".to_string(),
+                    kind: "TooLong".to_string(),
+                    value: TagValue::None,
+                },
+            ]
+        );
+        assert_eq!(
+            serde_json::to_value(&tags[0]).unwrap(),
+            json!({
+                "Tag": "0008,1140",
+                "Name": "ReferencedImageSequence",
+                "Type": "Sequence",
+                "Value": [[{
+                    "Tag": "0008,1155",
+                    "Name": "ReferencedSOPInstanceUID",
+                    "Type": "String",
+                    "Value": "1.2.3"
+                }]]
+            })
+        );
+    }
+
+    #[test]
+    fn test_entity_details() {
+        let details = EntityDetails::new(instance(), &["SOPInstanceUID", "InstanceNumber"]);
+        assert_eq!(
+            serde_json::to_value(&details).unwrap(),
+            json!({
+                "ID": "foo",
+                "Series ID": "foo_series",
+                "SOPInstanceUID": "suid_1",
+                "InstanceNumber": null,
+                "Index in series": 13,
+                "File size": 139402
+            })
+        );
+        assert_eq!(
+            details.fields.keys().collect::<Vec<_>>(),
+            vec![
+                "ID",
+                "Series ID",
+                "SOPInstanceUID",
+                "InstanceNumber",
+                "Index in series",
+                "File size"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_modality_config_merge() {
+        let mut config: ModalityConfig = serde_json::from_value(json!({
+            "AET": "FOO",
+            "Host": "1.2.3.4",
+            "Port": 104,
+            "Manufacturer": "Generic",
+            "AllowEcho": true,
+            "AllowGet": true,
+            "Properties": {"foo": "bar"}
+        }))
+        .unwrap();
+        config.merge(ModalityConfig {
+            port: Some(4242),
+            allow_c_get: Some(false),
+            timeout: Some(30),
+            ..Default::default()
+        });
+        assert_eq!(
+            serde_json::to_value(&config).unwrap(),
+            json!({
+                "AET": "FOO",
+                "Host": "1.2.3.4",
+                "Port": 4242,
+                "Manufacturer": "Generic",
+                "AllowEcho": true,
+                "AllowGet": false,
+                "Timeout": 30,
+                "Properties": {"foo": "bar"}
+            })
+        );
+    }
+
+    #[test]
+    fn test_peer_config() {
+        let mut config: PeerConfig = serde_json::from_value(json!({
+            "Url": "http://peer:8042/",
+            "Username": "orthanc",
+            "Password": null,
+            "HttpHeaders": ["X-Api-Key", "X-Foo"],
+            "Pkcs11": false
+        }))
+        .unwrap();
+        assert_eq!(config.password, None);
+        assert_eq!(config.http_header_names(), vec!["X-Api-Key", "X-Foo"]);
+
+        config.http_headers = None;
+        config.merge(PeerConfig {
+            password: Some("secret".to_string()),
+            http_headers: Some(json!({"X-Api-Key": "foo"})),
+            timeout: Some(30),
+            ..Default::default()
+        });
+        assert_eq!(config.http_header_names(), vec!["X-Api-Key"]);
+        assert_eq!(
+            serde_json::to_value(&config).unwrap(),
+            json!({
+                "Url": "http://peer:8042/",
+                "Username": "orthanc",
+                "Password": "secret",
+                "HttpHeaders": {"X-Api-Key": "foo"},
+                "Pkcs11": false,
+                "Timeout": 30
+            })
+        );
+    }
+
+    #[test]
+    fn test_list_options() {
+        let options = ListOptions {
+            limit: Some(2),
+            offset: 1,
+            sort_by: None,
+        };
+        assert_eq!(options.server_range(), Some((1, 2)));
+        assert_eq!(options.page(vec![1, 2, 3, 4]), vec![2, 3]);
+
+        let options = ListOptions {
+            sort_by: Some(SortBy {
+                column: "ID".to_string(),
+                descending: false,
+            }),
+            ..options
+        };
+        assert_eq!(options.server_range(), None);
+
+        let options = ListOptions {
+            offset: 3,
+            ..Default::default()
+        };
+        assert_eq!(options.server_range(), None);
+        assert_eq!(options.page(vec![1, 2, 3, 4]), vec![4]);
+    }
+}
@@ -1,11 +1,135 @@
+use crate::client::{ClientIdentity, TransportOptions};
+use crate::config::Profile;
 use crate::constants::*;
+use crate::models::Anonymization;
+use crate::models::*;
 use crate::{CliError, Result};
-use comfy_table::{ColumnConstraint, ContentArrangement, Table, Width};
+use comfy_table::{Cell, ColumnConstraint, ContentArrangement, Table, Width};
+use dicom_core::dictionary::{DataDictionary, DictionaryEntry};
+use dicom_object::{DefaultDicomObject, StandardDataDictionary, Tag};
+use hmac::{Hmac, Mac};
 use orthanc::entity::*;
 use orthanc::models::*;
-use serde_yaml;
-use std::collections::HashMap;
-use std::{env, fs, process, result};
+use reqwest::header::{HeaderName, HeaderValue};
+use serde::Serialize;
+use serde_json::Value;
+use sha2::Sha256;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use std::{env, fs, io, process, result, thread};
+use zip::read::read_zipfile_from_stream;
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Yaml,
+    Csv,
+    Tsv,
+}
+
+/// A command result, that can be rendered as a table or serialized
+pub trait Output: Serialize {
+    fn to_table(&self, no_header: bool) -> Table;
+}
+
+impl Output for EntityList {
+    fn to_table(&self, no_header: bool) -> Table {
+        create_list_table(self, no_header)
+    }
+}
+
+impl Output for EntityDetails {
+    fn to_table(&self, _no_header: bool) -> Table {
+        create_show_table(self)
+    }
+}
+
+impl Output for Vec<InstanceTag> {
+    fn to_table(&self, _no_header: bool) -> Table {
+        create_instance_tags_table(self)
+    }
+}
+
+impl Output for Vec<TreeNode> {
+    fn to_table(&self, _no_header: bool) -> Table {
+        create_tree_table(self)
+    }
+}
+
+impl Output for ModificationResult {
+    fn to_table(&self, _no_header: bool) -> Table {
+        create_new_entity_table(self)
+    }
+}
+
+impl Output for ModalityStoreResult {
+    fn to_table(&self, _no_header: bool) -> Table {
+        create_store_result_table(self)
+    }
+}
+
+impl Output for PeerStoreResult {
+    fn to_table(&self, _no_header: bool) -> Table {
+        create_peer_store_result_table(self)
+    }
+}
+
+impl<T: Output> Output for Outcome<T> {
+    fn to_table(&self, no_header: bool) -> Table {
+        match self {
+            Outcome::Completed(r) => r.to_table(no_header),
+            Outcome::Submitted(j) => {
+                let mut table = create_table(None);
+                table.add_row(["Job ID", &j.id].iter());
+                table
+            }
+        }
+    }
+}
+
+/// A command result for a single entity, that can be a row of the results of the command run
+/// for many entities
+pub trait ToRow {
+    fn to_row(&self) -> Row;
+}
+
+impl ToRow for EntityDetails {
+    fn to_row(&self) -> Row {
+        self.fields.clone()
+    }
+}
+
+impl ToRow for ModificationResult {
+    fn to_row(&self) -> Row {
+        let mut row = Row::new();
+        row.insert("New ID".to_string(), Value::from(self.id.as_str()));
+        if self.entity != EntityKind::Patient {
+            row.insert(
+                "Patient ID".to_string(),
+                Value::from(self.patient_id.as_str()),
+            );
+        }
+        row
+    }
+}
+
+impl<T: ToRow> ToRow for Outcome<T> {
+    fn to_row(&self) -> Row {
+        match self {
+            Outcome::Completed(r) => r.to_row(),
+            Outcome::Submitted(j) => {
+                let mut row = Row::new();
+                row.insert("Job ID".to_string(), Value::from(j.id.as_str()));
+                row
+            }
+        }
+    }
+}
 
 pub fn create_table(header: Option<&[&str]>) -> Table {
     let mut table = Table::new();
@@ -18,58 +142,21 @@ pub fn create_table(header: Option<&[&str]>) -> Table {
     table
 }
 
-pub fn create_list_table<T: Entity>(
-    entities: Vec<T>,
-    columns: &[&str],
-    dicom_tags: &[&str],
-    no_header: bool,
-) -> Table {
-    let header = if no_header { None } else { Some(columns) };
+pub fn create_list_table(list: &EntityList, no_header: bool) -> Table {
+    let columns: Vec<&str> = list.columns.iter().map(AsRef::as_ref).collect();
+    let header = if no_header { None } else { Some(&columns[..]) };
     let mut table = create_table(header);
-    for entity in entities {
-        let mut row: Vec<String> = vec![];
-
-        if columns.contains(&"ID") {
-            row.push(entity.id().to_string());
-        };
-
-        for t in dicom_tags.iter() {
-            let val = entity
-                .main_dicom_tag(t)
-                .unwrap_or(ABSENT_DICOM_TAG_PLACEHOLDER);
-            row.push(val.to_string());
-        }
-
-        match T::kind() {
-            EntityKind::Instance => {
-                if columns.contains(&"Index in series") {
-                    let index_in_series = match entity.index() {
-                        Some(i) => format!("{}", i),
-                        None => "".to_string(),
-                    };
-                    row.push(index_in_series);
-                };
-                if columns.contains(&"File size") {
-                    let file_size = format!("{}", entity.size());
-                    row.push(file_size);
-                };
-            }
-            _ => {
-                if columns.contains(&"Number of Studies")
-                    || columns.contains(&"Number of Series")
-                    || columns.contains(&"Number of Instances")
-                {
-                    let num_children = format!("{}", entity.children_len());
-                    row.push(num_children);
-                }
-            }
-        }
-        table.add_row(row.iter());
+    for row in list.rows.iter() {
+        table.add_row(
+            columns
+                .iter()
+                .filter_map(|c| row.get(*c))
+                .map(value_to_cell),
+        );
     }
 
-    // This assumes the ID is always the first column
-    if columns.contains(&"ID") {
-        if let Some(id_column) = table.column_mut(0) {
+    if let Some(i) = columns.iter().position(|c| *c == "ID") {
+        if let Some(id_column) = table.column_mut(i) {
             id_column.set_constraint(ColumnConstraint::LowerBoundary(Width::Fixed(
                 ID_COLUMN_WIDTH,
             )));
@@ -78,57 +165,173 @@ pub fn create_list_table<T: Entity>(
     table
 }
 
-pub fn create_show_table<T: Entity>(entity: T, dicom_tags: &[&str]) -> Table {
+pub fn create_show_table(details: &EntityDetails) -> Table {
     let mut table = create_table(None);
-    table.add_row(["ID", entity.id()].iter());
-    if T::kind() != EntityKind::Patient {
-        table.add_row(
-            [
-                &format!("{} ID", entity.parent_kind_name().unwrap()),
-                entity.parent_id().unwrap(),
-            ]
-            .iter(),
-        );
+    for (k, v) in details.fields.iter() {
+        table.add_row([k.to_string(), value_to_cell(v)].iter());
     }
+    table
+}
+
+pub fn create_instance_tags_table(tags: &[InstanceTag]) -> Table {
+    let mut table = create_table(None);
+    add_instance_tag_rows(&mut table, tags, 0);
+    table
+}
+
+/// Adds a row per tag. Sequence items follow their sequence, indented by `level`
+fn add_instance_tag_rows(table: &mut Table, tags: &[InstanceTag], level: usize) {
+    let indent = "  ".repeat(level);
+    for t in tags {
+        let value = match (t.kind.as_str(), &t.value) {
+            ("Binary", _) => "<binary>".to_string(),
+            ("TooLong", _) => "<too long>".to_string(),
+            (_, TagValue::String(v)) => v.to_string(),
+            (_, TagValue::Sequence(items)) => format!("<sequence: {} items>", items.len()),
+            (_, TagValue::None) => "".to_string(),
+        };
+        table.add_row([format!("{}{}", indent, t.tag), t.name.to_string(), value].iter());
+        if let TagValue::Sequence(items) = &t.value {
+            for (i, item) in items.iter().enumerate() {
+                table.add_row(
+                    [
+                        format!("{}  [{}]", indent, i),
+                        "".to_string(),
+                        "".to_string(),
+                    ]
+                    .iter(),
+                );
+                add_instance_tag_rows(table, item, level + 2);
+            }
+        }
+    }
+}
+
+pub fn create_tree_table(nodes: &[TreeNode]) -> Table {
+    let mut table = create_table(None);
+    add_tree_rows(&mut table, nodes, 0);
+    table
+}
 
-    for t in dicom_tags.iter() {
+/// Adds a row per entity, followed by the rows of its children, indented by `level`
+fn add_tree_rows(table: &mut Table, nodes: &[TreeNode], level: usize) {
+    let indent = "  ".repeat(level);
+    for n in nodes {
         table.add_row(
             [
-                t,
-                entity
-                    .main_dicom_tag(t)
-                    .unwrap_or(ABSENT_DICOM_TAG_PLACEHOLDER),
+                format!("{}{:?}", indent, n.kind),
+                tree_node_summary(n),
+                n.id.to_string(),
             ]
             .iter(),
         );
+        add_tree_rows(table, &n.children, level + 1);
     }
-    match T::kind() {
-        EntityKind::Instance => {
-            let index_in_series = match entity.index() {
-                Some(i) => format!("{}", i),
-                None => "".to_string(),
-            };
-            let file_size = format!("{}", entity.size());
-            table.add_row(["Index in series", &index_in_series].iter());
-            table.add_row(["File size", &file_size].iter());
-        }
-        _ => {
-            let num_children = format!("{}", entity.children_len());
-            table.add_row(
-                [
-                    &format!("Number of {}", entity.children_kind_name().unwrap()),
-                    &num_children,
-                ]
-                .iter(),
-            );
-        }
+}
+
+/// The key tags of a tree node, separated by commas, followed by the number of children, e.g.
+/// `MR, T1 SE, 25 instances`. Absent tags are left out
+fn tree_node_summary(node: &TreeNode) -> String {
+    node.fields
+        .iter()
+        .filter_map(|(k, v)| match (k.strip_prefix("Number of "), v) {
+            (Some(children), Value::Number(n)) => {
+                let children = children.to_lowercase();
+                match (n.as_u64(), children.as_str()) {
+                    (Some(1), "studies") => Some("1 study".to_string()),
+                    (Some(1), "instances") => Some("1 instance".to_string()),
+                    _ => Some(format!("{} {}", n, children)),
+                }
+            }
+            (_, Value::String(s)) if !s.is_empty() => Some(s.to_string()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Makes sure, that the tag filters are valid: groups as `gggg`, tags either as keywords or as
+/// `gggg,eeee`
+pub fn check_tag_filters(
+    groups: &Option<Vec<&str>>,
+    tags: &Option<Vec<&str>>,
+) -> Result<()> {
+    let hex = |s: &str| s.len() == 4 && s.chars().all(|c| c.is_ascii_hexdigit());
+    if let Some(g) = groups.iter().flatten().find(|g| !hex(g)) {
+        return Err(CliError::new(
+            "Command error",
+            Some(&format!("Invalid tag group: {}", g)),
+            Some("A tag group consists of 4 hexadecimal digits (e.g. 0010)"),
+        ));
+    }
+    if let Some(t) = tags.iter().flatten().find(|t| !is_dicom_tag(t)) {
+        return Err(CliError::new(
+            "Command error",
+            Some(&format!("Invalid tag: {}", t)),
+            Some("Tags can be specified either by keyword (e.g. PatientBirthDate) or as gggg,eeee (e.g. 0010,0030)"),
+        ));
+    }
+    Ok(())
+}
+
+/// Keeps only the top-level tags, that belong to one of the `groups` or match one of the `tags`.
+/// Without any filters all tags are kept
+pub fn filter_instance_tags(
+    instance_tags: Vec<InstanceTag>,
+    groups: Option<Vec<&str>>,
+    tags: Option<Vec<&str>>,
+) -> Vec<InstanceTag> {
+    if groups.is_none() && tags.is_none() {
+        return instance_tags;
     }
+    let groups: Vec<String> = groups
+        .unwrap_or_default()
+        .iter()
+        .map(|g| format!("{},", g.to_lowercase()))
+        .collect();
+    let tags = tags.unwrap_or_default();
+    instance_tags
+        .into_iter()
+        .filter(|t| {
+            groups.iter().any(|g| t.tag.starts_with(g.as_str()))
+                || tags
+                    .iter()
+                    .any(|n| t.name == *n || t.tag == n.to_lowercase())
+        })
+        .collect()
+}
+
+pub fn create_store_result_table(result: &ModalityStoreResult) -> Table {
+    let mut table = create_table(None);
+    table.add_row(["Remote AET", &result.remote_aet].iter());
+    table.add_row(["Instances sent", &format!("{}", result.instances_count)].iter());
+    table.add_row(
+        [
+            "Instances failed",
+            &format!("{}", result.failed_instances_count),
+        ]
+        .iter(),
+    );
+    table
+}
+
+pub fn create_peer_store_result_table(result: &PeerStoreResult) -> Table {
+    let mut table = create_table(None);
+    table.add_row(["Peer", &result.peer.join(", ")].iter());
+    table.add_row(["Instances sent", &format!("{}", result.instances_count)].iter());
+    table.add_row(
+        [
+            "Instances failed",
+            &format!("{}", result.failed_instances_count),
+        ]
+        .iter(),
+    );
     table
 }
 
-pub fn create_new_entity_table(result: ModificationResult) -> Table {
+pub fn create_new_entity_table(result: &ModificationResult) -> Table {
     let mut table = create_table(None);
-    table.add_row([format!("New {:?} ID", result.entity), result.id].iter());
+    table.add_row([format!("New {:?} ID", result.entity), result.id.to_string()].iter());
     match result.entity {
         EntityKind::Patient => &table,
         _ => table.add_row(["Patient ID", &result.patient_id].iter()),
@@ -136,20 +339,22 @@ pub fn create_new_entity_table(result: ModificationResult) -> Table {
     table
 }
 
+fn value_to_cell(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.to_string(),
+        Value::Null => ABSENT_DICOM_TAG_PLACEHOLDER.to_string(),
+        v => v.to_string(),
+    }
+}
+
 pub fn create_error_table(error: CliError) -> Table {
     let mut table = create_table(None);
     table.add_row(["Error", &error.error].iter());
-    match error.message {
-        Some(m) => {
-            table.add_row(["Message", &m].iter());
-        }
-        None => (),
+    if let Some(m) = error.message {
+        table.add_row(["Message", &m].iter());
     };
-    match error.details {
-        Some(d) => {
-            table.add_row(["Details", &d].iter());
-        }
-        None => (),
+    if let Some(d) = error.details {
+        table.add_row(["Details", &d].iter());
     };
     table
 }
@@ -171,14 +376,158 @@ pub fn parse_tag_kv_pairs(cmd_values: Vec<&str>) -> Result<HashMap<String, Strin
     Ok(map)
 }
 
+/// Parses search query terms `TagName=TagValue` into a query of `/tools/find`. Values may contain
+/// DICOM wildcards (`*` and `?`), dates and times may be ranges (`20200101-20201231`, `-20201231`
+/// or `20200101-`). A tag, that is repeated, matches any of its values
+pub fn parse_search_query(terms: Vec<&str>, case_sensitive: bool) -> Result<SearchQuery> {
+    let mut values: Vec<(&str, Vec<&str>)> = vec![];
+    for term in terms {
+        let (tag, value) = match term.split_once('=') {
+            Some((t, v)) if is_dicom_tag(t) => (t, v),
+            Some(_) => {
+                return Err(CliError::new(
+                    "Command error",
+                    Some(&format!("Invalid tag in query term '{}'", term)),
+                    Some("A tag is either a keyword (e.g. StudyDate) or gggg,eeee (e.g. 0008,0020)"),
+                ))
+            }
+            None => {
+                return Err(CliError::new(
+                    "Command error",
+                    Some(&format!("Wrong query term '{}'", term)),
+                    Some("Must be of format 'TagName=TagValue'"),
+                ))
+            }
+        };
+        check_query_value(tag, value)?;
+        match values.iter_mut().find(|(t, _)| *t == tag) {
+            Some((_, v)) => v.push(value),
+            None => values.push((tag, vec![value])),
+        }
+    }
+
+    let mut tags = HashMap::new();
+    for (tag, v) in values {
+        // Orthanc matches a list of values exactly, without wildcards or ranges
+        if v.len() > 1
+            && v.iter()
+                .any(|v| v.contains(&['*', '?'][..]) || is_range(tag, v))
+        {
+            return Err(CliError::new(
+                "Command error",
+                Some(&format!(
+                    "Multiple values of {} with wildcards or ranges",
+                    tag
+                )),
+                Some("A tag with multiple values matches any of them exactly"),
+            ));
+        }
+        tags.insert(tag.to_string(), v.join("\\"));
+    }
+    Ok(SearchQuery {
+        tags,
+        case_sensitive,
+    })
+}
+
+/// Makes sure, that the value of a date (`...Date`) or time (`...Time`) tag in a search query is
+/// either a single date or time, or a range of them
+fn check_query_value(tag: &str, value: &str) -> Result<()> {
+    let (kind, is_valid, format): (_, fn(&str) -> bool, _) = if tag.ends_with("Date") {
+        ("date", is_dicom_date, "YYYYMMDD")
+    } else if tag.ends_with("Time") && !tag.ends_with("DateTime") {
+        ("time", is_dicom_time, "HHMMSS")
+    } else {
+        return Ok(());
+    };
+    if value.is_empty() {
+        return Ok(());
+    }
+    let (start, end) = value.split_once('-').unwrap_or((value, value));
+    if start.is_empty() && end.is_empty()
+        || [start, end].iter().any(|v| !v.is_empty() && !is_valid(v))
+    {
+        return Err(CliError::new(
+            "Command error",
+            Some(&format!(
+                "Invalid {} in query term '{}={}'",
+                kind, tag, value
+            )),
+            Some(&format!(
+                "Must be either {0}, or a range: {0}-{0}, -{0} or {0}-",
+                format
+            )),
+        ));
+    }
+    if !start.is_empty() && !end.is_empty() && start > end {
+        return Err(CliError::new(
+            "Command error",
+            Some(&format!(
+                "Invalid {} range in query term '{}={}'",
+                kind, tag, value
+            )),
+            Some("The start of the range is after its end"),
+        ));
+    }
+    Ok(())
+}
+
+fn is_range(tag: &str, value: &str) -> bool {
+    (tag.ends_with("Date") || tag.ends_with("Time")) && value.contains('-')
+}
+
+fn is_dicom_date(value: &str) -> bool {
+    value.len() == 8 && value.chars().all(|c| c.is_ascii_digit())
+}
+
+/// `HH`, `HHMM`, `HHMMSS`, or `HHMMSS.F` with up to 6 digits of fraction
+fn is_dicom_time(value: &str) -> bool {
+    let (hms, fraction) = match value.split_once('.') {
+        Some((hms, f)) if hms.len() == 6 => (hms, f),
+        Some(_) => return false,
+        None => (value, ""),
+    };
+    [2, 4, 6].contains(&hms.len())
+        && hms.chars().all(|c| c.is_ascii_digit())
+        && fraction.len() <= 6
+        && fraction.chars().all(|c| c.is_ascii_digit())
+}
+
+/// IDs of the parents of entities, without duplicates, in the order they are first encountered
+pub fn parent_ids<T: Entity>(entities: Vec<T>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    entities
+        .iter()
+        .filter_map(|e| e.parent_id())
+        .filter(|id| seen.insert(id.to_string()))
+        .map(String::from)
+        .collect()
+}
+
+/// Depth of an entity kind in the hierarchy: 0 for patients, 3 for instances
+pub fn entity_depth(kind: &EntityKind) -> usize {
+    match kind {
+        EntityKind::Patient => 0,
+        EntityKind::Study => 1,
+        EntityKind::Series => 2,
+        EntityKind::Instance => 3,
+    }
+}
+
 pub fn get_anonymization_config(
     replace: Option<Vec<&str>>,
     keep: Option<Vec<&str>>,
     keep_private_tags: Option<bool>,
+    profiles: Option<Vec<&str>>,
+    dicom_version: Option<&str>,
     config_file: Option<&str>,
 ) -> Result<Option<Anonymization>> {
     // This should never happen, but double-checking anyway
-    if (replace.is_some() || keep.is_some() || keep_private_tags.is_some())
+    if (replace.is_some()
+        || keep.is_some()
+        || keep_private_tags.is_some()
+        || profiles.is_some()
+        || dicom_version.is_some())
         && config_file.is_some()
     {
         return Err(CliError::new(
@@ -190,14 +539,22 @@ pub fn get_anonymization_config(
 
     match config_file {
         Some(c) => Ok(Some(get_anonymization_config_from_file(c)?)),
-        None => match (&replace, &keep, &keep_private_tags) {
-            (None, None, None) => Ok(None),
+        None => match (
+            &replace,
+            &keep,
+            &keep_private_tags,
+            &profiles,
+            &dicom_version,
+        ) {
+            (None, None, None, None, None) => Ok(None),
             // TODO: This assumes that there is always either a config file
             // or at least one of the options
             _ => Ok(Some(get_anonymization_config_from_cmd_options(
                 replace,
                 keep,
                 keep_private_tags,
+                profiles,
+                dicom_version,
             )?)),
         },
     }
@@ -226,11 +583,7 @@ pub fn get_modification_config(
 
     match config_file {
         Some(c) => Ok(get_modification_config_from_file(c)?),
-        None => match (&replace, &remove) {
-            // TODO: This assumes that there is always either a config file
-            // or at least one of the options
-            _ => Ok(get_modification_config_from_cmd_options(replace, remove)?),
-        },
+        None => Ok(get_modification_config_from_cmd_options(replace, remove)?),
     }
 }
 
@@ -252,19 +605,65 @@ fn get_anonymization_config_from_cmd_options(
     replace: Option<Vec<&str>>,
     keep: Option<Vec<&str>>,
     keep_private_tags: Option<bool>,
+    profiles: Option<Vec<&str>>,
+    dicom_version: Option<&str>,
 ) -> Result<Anonymization> {
+    let mut keep_tags: Vec<&str> = vec![];
+    let mut clean_tags: Vec<&str> = vec![];
+    for profile in profiles.iter().flatten() {
+        let (profile_keep, profile_clean) = anonymization_profile(profile)?;
+        keep_tags.extend(profile_keep);
+        clean_tags.extend(profile_clean);
+    }
+    keep_tags.extend(keep.iter().flatten());
+    let mut seen = HashSet::new();
+    keep_tags.retain(|t| seen.insert(*t));
+    // Tags, that are kept, are not cleaned by a profile, and tags, that are replaced explicitly,
+    // get the explicit value
+    let mut replace_tags: HashMap<String, String> = clean_tags
+        .iter()
+        .filter(|t| !keep_tags.contains(t))
+        .map(|t| (t.to_string(), CLEANED_DESCRIPTION.to_string()))
+        .collect();
+    if let Some(r) = replace {
+        replace_tags.extend(parse_tag_kv_pairs(r)?);
+    }
+    let to_list = |tags: Vec<&str>| {
+        (!tags.is_empty()).then(|| tags.iter().map(ToString::to_string).collect())
+    };
     Ok(Anonymization {
-        replace: match replace {
-            Some(r) => Some(parse_tag_kv_pairs(r)?),
-            None => None,
-        },
-        keep: keep.map(|vec| vec.iter().map(ToString::to_string).collect()),
+        replace: (!replace_tags.is_empty()).then_some(replace_tags),
+        keep: to_list(keep_tags),
+        remove: None,
         keep_private_tags,
-        dicom_version: None,
+        dicom_version: dicom_version.map(String::from),
         force: Some(true),
     })
 }
 
+/// The tags, that an anonymization profile keeps and cleans (see [`CLEANED_DESCRIPTION`]), on top
+/// of the Basic Application Level Confidentiality Profile of DICOM PS3.15, which Orthanc applies
+/// by default. The name is case-insensitive (e.g. `retain-UIDs`)
+pub fn anonymization_profile(
+    name: &str,
+) -> Result<(&'static [&'static str], &'static [&'static str])> {
+    match name.to_lowercase().as_str() {
+        "basic" => Ok((&[], &[])),
+        "retain-dates" => Ok((RETAIN_DATES_KEEP, &[])),
+        "retain-device-identity" => Ok((RETAIN_DEVICE_IDENTITY_KEEP, &[])),
+        "retain-uids" => Ok((RETAIN_UIDS_KEEP, &[])),
+        "clean-descriptions" => Ok((&[], CLEAN_DESCRIPTIONS_REPLACE)),
+        _ => Err(CliError::new(
+            "Command error",
+            Some(&format!("Unknown anonymization profile: {}", name)),
+            Some(&format!(
+                "Available profiles: {}",
+                ANONYMIZATION_PROFILES.join(", ")
+            )),
+        )),
+    }
+}
+
 fn get_modification_config_from_cmd_options(
     replace: Option<Vec<&str>>,
     remove: Option<Vec<&str>>,
@@ -279,855 +678,4312 @@ fn get_modification_config_from_cmd_options(
     })
 }
 
-pub fn get_server_address(cmd_option: Option<&str>) -> result::Result<String, CliError> {
-    match cmd_option {
-        Some(s) => Ok(s.to_string()),
-        None => match env::var("ORC_ORTHANC_SERVER") {
-            Ok(s) => Ok(s),
-            Err(e) => Err(CliError::new(
-                "Command error",
-                Some("Neither --server nor ORC_ORTHANC_SERVER are set"),
-                Some(&format!("{}", e)),
-            )),
+/// The secret salt of deterministic pseudonyms, read from `salt_file` or `ORC_PSEUDONYM_SALT`.
+/// It is never taken from the command line, where it would show up in the process list and the
+/// shell history. A trailing line break of the file is ignored
+pub fn get_pseudonym_salt(salt_file: Option<&str>) -> Result<Option<String>> {
+    let salt = match salt_file {
+        Some(path) => {
+            let salt = read_setting_file(path.to_string(), "pseudonym salt file")?;
+            String::from_utf8_lossy(&salt)
+                .trim_end_matches(&['\r', '\n'][..])
+                .to_string()
+        }
+        None => match env::var("ORC_PSEUDONYM_SALT") {
+            Ok(salt) => salt,
+            Err(_) => return Ok(None),
         },
+    };
+    if salt.is_empty() {
+        return Err(CliError::new(
+            "Command error",
+            Some("Empty pseudonym salt"),
+            None,
+        ));
     }
+    Ok(Some(salt))
 }
 
-pub fn get_username(cmd_option: Option<&str>) -> Option<String> {
-    match cmd_option {
-        Some(s) => Some(s.to_string()),
-        None => match env::var("ORC_ORTHANC_USERNAME") {
-            Ok(s) => Some(s),
-            Err(_) => None, // TODO: This will hide the error
-        },
+/// A pseudonym of `value`, derived from it and `salt` with HMAC-SHA256, so that the same value
+/// always gets the same pseudonym, which cannot be traced back to it without the salt
+pub fn pseudonym(salt: &str, value: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(salt.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(value.as_bytes());
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<String>()[..PSEUDONYM_LENGTH]
+        .to_string()
+}
+
+/// Adds the replacement of PatientID and PatientName with the pseudonym of the original
+/// PatientID to an anonymization configuration, unless these tags are replaced or kept
+/// explicitly
+pub fn add_pseudonyms(
+    config: Option<Anonymization>,
+    salt: &str,
+    original: &Identifiers,
+) -> Result<Anonymization> {
+    let patient_id = original.patient_id.as_deref().ok_or_else(|| {
+        CliError::new(
+            "Command error",
+            Some("Cannot derive a pseudonym: the patient has no PatientID"),
+            None,
+        )
+    })?;
+    let value = pseudonym(salt, patient_id);
+    let mut config = config.unwrap_or(Anonymization {
+        force: Some(true),
+        ..Default::default()
+    });
+    let keep = config.keep.clone().unwrap_or_default();
+    let replace = config.replace.get_or_insert_with(HashMap::new);
+    for tag in ["PatientID", "PatientName"] {
+        if !keep.iter().any(|k| k == tag) {
+            replace
+                .entry(tag.to_string())
+                .or_insert_with(|| value.clone());
+        }
     }
+    Ok(config)
 }
 
-pub fn get_password(cmd_option: Option<&str>) -> Option<String> {
-    match cmd_option {
-        Some(s) => Some(s.to_string()),
-        None => match env::var("ORC_ORTHANC_PASSWORD") {
-            Ok(s) => Some(s),
-            Err(_) => None, // TODO: This will hide the error
-        },
+/// A row of a pseudonymization mapping file
+pub fn mapping_record(
+    timestamp: &str,
+    kind: &EntityKind,
+    id: &str,
+    new_id: &str,
+    original: &Identifiers,
+    new: &Identifiers,
+) -> Vec<String> {
+    let mut record = vec![
+        timestamp.to_string(),
+        format!("{:?}", kind),
+        id.to_string(),
+        new_id.to_string(),
+    ];
+    for (o, n) in [
+        (&original.patient_id, &new.patient_id),
+        (&original.patient_name, &new.patient_name),
+        (&original.study_instance_uid, &new.study_instance_uid),
+        (&original.accession_number, &new.accession_number),
+    ] {
+        record.push(o.clone().unwrap_or_default());
+        record.push(n.clone().unwrap_or_default());
     }
+    record
 }
 
-pub fn check_columns_option(
-    original_header: &[&str],
-    requested_columns: &[&str],
+/// Appends a row to a CSV pseudonymization mapping file, creating the file with a header row, if
+/// it does not exist yet
+pub fn append_mapping_record(path: &Path, record: &[String]) -> Result<()> {
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    let is_new = file.metadata()?.len() == 0;
+    let mut writer = csv::Writer::from_writer(file);
+    if is_new {
+        writer.write_record(MAPPING_FILE_HEADER)?;
+    }
+    writer.write_record(record)?;
+    writer.flush().map_err(Into::into)
+}
+
+/// Sets the DIMSE permissions of a modality. `allow` and `deny` are lists of services, as
+/// accepted by `--allow` and `--deny` (e.g. `C-ECHO`, `N-ACTION`)
+pub fn set_dimse_permissions(
+    config: &mut ModalityConfig,
+    allow: Option<Vec<&str>>,
+    deny: Option<Vec<&str>>,
 ) -> Result<()> {
-    for c in requested_columns {
-        if !original_header.contains(c) {
+    let allow = allow.unwrap_or_default();
+    let deny = deny.unwrap_or_default();
+    if let Some(s) = allow.iter().find(|s| deny.contains(s)) {
+        return Err(CliError::new(
+            "Command error",
+            Some(&format!("Service both allowed and denied: {}", s)),
+            None,
+        ));
+    }
+    for (services, permission) in [(allow, true), (deny, false)] {
+        for s in services {
+            let field = match s.to_uppercase().as_str() {
+                "C-ECHO" => &mut config.allow_c_echo,
+                "C-FIND" => &mut config.allow_c_find,
+                "C-GET" => &mut config.allow_c_get,
+                "C-MOVE" => &mut config.allow_c_move,
+                "C-STORE" => &mut config.allow_c_store,
+                "N-ACTION" => &mut config.allow_n_action,
+                "N-EVENT-REPORT" => &mut config.allow_n_event_report,
+                _ => {
+                    return Err(CliError::new(
+                        "Command error",
+                        Some(&format!("Unknown DIMSE service: {}", s)),
+                        None,
+                    ))
+                }
+            };
+            *field = Some(permission);
+        }
+    }
+    Ok(())
+}
+
+/// Reads a YAML file, that maps modality names to their settings. The settings are named as in
+/// the `DicomModalities` section of the Orthanc configuration, and AET, Host and Port are
+/// required
+pub fn read_modalities_file(file: &Path) -> Result<BTreeMap<String, ModalityConfig>> {
+    let modalities: BTreeMap<String, ModalityConfig> =
+        serde_yaml::from_slice(&fs::read(file)?)?;
+    for (name, config) in modalities.iter() {
+        if let Some(k) = config.other.keys().next() {
             return Err(CliError::new(
                 "Command error",
-                Some(&format!(
-                    "Invalid column name: {}. Available columns: {}",
-                    c,
-                    original_header.join(", ")
-                )),
+                Some(&format!("Unknown setting of modality {}: {}", name, k)),
                 None,
             ));
         }
+        if config.aet.is_none() || config.host.is_none() || config.port.is_none() {
+            return Err(CliError::new(
+                "Command error",
+                Some(&format!("Incomplete settings of modality {}", name)),
+                Some("AET, Host and Port are required"),
+            ));
+        }
     }
-    Ok(())
+    Ok(modalities)
 }
 
-pub fn get_header_and_dicom_tags(
-    header: &mut Vec<&str>,
-    dicom_tags: &mut Vec<&str>,
-    columns: Option<Vec<&str>>,
-) -> Result<()> {
-    if let Some(c) = columns {
-        check_columns_option(header, &c)?;
-        header.retain(|v| c.contains(v));
-        dicom_tags.retain(|v| c.contains(v));
-    };
-    Ok(())
+/// The server address, taken from `--server`, `ORC_ORTHANC_SERVER` or the selected profile, in
+/// this order of precedence. Same goes for the username and the password
+pub fn get_server_address(
+    cmd_option: Option<&str>,
+    profile: Option<&Profile>,
+) -> result::Result<String, CliError> {
+    match cmd_option {
+        Some(s) => Ok(s.to_string()),
+        None => match env::var("ORC_ORTHANC_SERVER") {
+            Ok(s) => Ok(s),
+            Err(e) => match profile {
+                Some(p) => Ok(p.server.to_string()),
+                None => Err(CliError::new(
+                    "Command error",
+                    Some("Neither --server nor ORC_ORTHANC_SERVER are set"),
+                    Some(&format!("{}", e)),
+                )),
+            },
+        },
+    }
 }
 
-pub fn print_table(table: Table) {
-    println!("{}", table);
+pub fn get_username(cmd_option: Option<&str>, profile: Option<&Profile>) -> Option<String> {
+    get_setting(
+        cmd_option,
+        "ORC_ORTHANC_USERNAME",
+        profile.and_then(|p| p.username.as_ref()),
+    )
 }
 
-pub fn exit_with_error(error: CliError) {
-    let output = create_error_table(error);
-    eprintln!("{}", output);
-    process::exit(1);
+pub fn get_password(cmd_option: Option<&str>, profile: Option<&Profile>) -> Option<String> {
+    get_setting(
+        cmd_option,
+        "ORC_ORTHANC_PASSWORD",
+        profile.and_then(|p| p.password.as_ref()),
+    )
 }
 
-#[cfg(test)]
-mod tests {
+pub fn get_token(cmd_option: Option<&str>, profile: Option<&Profile>) -> Option<String> {
+    get_setting(
+        cmd_option,
+        "ORC_ORTHANC_TOKEN",
+        profile.and_then(|p| p.token.as_ref()),
+    )
+}
+
+/// Parses custom headers given as `Name:Value`. Headers from the command line replace those of
+/// the profile
+pub fn get_headers(
+    cmd_option: Option<Vec<&str>>,
+    profile: Option<&Profile>,
+) -> Result<Vec<(String, String)>> {
+    let headers = match (cmd_option, profile.and_then(|p| p.headers.as_ref())) {
+        (Some(h), _) => h.iter().map(ToString::to_string).collect(),
+        (None, Some(h)) => h.clone(),
+        (None, None) => vec![],
+    };
+    headers
+        .iter()
+        .map(|h| match h.split_once(':') {
+            Some((name, value))
+                if HeaderName::from_bytes(name.trim().as_bytes()).is_ok()
+                    && HeaderValue::from_str(value.trim()).is_ok() =>
+            {
+                Ok((name.trim().to_string(), value.trim().to_string()))
+            }
+            _ => Err(CliError::new(
+                "Command error",
+                Some(&format!("Invalid header: {}", h)),
+                Some("Headers must be specified as Name:Value, e.g. X-Api-Key:secret"),
+            )),
+        })
+        .collect()
+}
+
+/// Takes the value of a setting from the command-line option, the environment variable or the
+/// profile, in this order of precedence
+fn get_setting(
+    cmd_option: Option<&str>,
+    env_var: &str,
+    profile_value: Option<&String>,
+) -> Option<String> {
+    match cmd_option {
+        Some(s) => Some(s.to_string()),
+        None => env::var(env_var) // TODO: This will hide the error
+            .ok()
+            .or_else(|| profile_value.cloned()),
+    }
+}
+
+/// The absolute path of a file, that a setting points to (e.g. a certificate), so that the
+/// setting can be stored in a profile and used from any directory
+pub fn setting_path(path: &str, what: &str) -> Result<String> {
+    fs::canonicalize(path)
+        .map(|p| p.to_string_lossy().to_string())
+        .map_err(|e| {
+            CliError::new(
+                "Command error",
+                Some(&format!("Could not read {}: {}", what, path)),
+                Some(&e.to_string()),
+            )
+        })
+}
+
+/// Reads a secret (e.g. a password) from the standard input. If it is a terminal, the secret is
+/// prompted for and not echoed, otherwise a line of the input is read. Secrets are never taken
+/// from the command line, where they would show up in the process list and the shell history
+pub fn read_secret(what: &str) -> Result<String> {
+    let stdin = io::stdin();
+    let mut secret = String::new();
+    if stdin.is_terminal() {
+        eprint!("{}: ", what);
+        let _echo_off = EchoOff::new();
+        stdin.read_line(&mut secret)?;
+    } else {
+        stdin.read_line(&mut secret)?;
+    }
+    let secret = secret.trim_end_matches(&['\r', '\n'][..]);
+    if secret.is_empty() {
+        return Err(CliError::new(
+            "Command error",
+            Some(&format!("Empty {}", what.to_lowercase())),
+            None,
+        ));
+    }
+    Ok(secret.to_string())
+}
+
+/// Turns off the echo of the terminal on the standard input, except for the line break, until it
+/// is dropped
+struct EchoOff {
+    #[cfg(unix)]
+    original: Option<libc::termios>,
+}
+
+impl EchoOff {
+    #[cfg(unix)]
+    fn new() -> EchoOff {
+        let mut termios = std::mem::MaybeUninit::<libc::termios>::uninit();
+        // SAFETY: tcgetattr initializes `termios`, if it succeeds
+        let original = unsafe {
+            match libc::tcgetattr(libc::STDIN_FILENO, termios.as_mut_ptr()) {
+                0 => Some(termios.assume_init()),
+                _ => None,
+            }
+        };
+        if let Some(o) = original {
+            let mut silent = o;
+            silent.c_lflag &= !libc::ECHO;
+            silent.c_lflag |= libc::ECHONL;
+            // SAFETY: `silent` is a valid termios, read from the same terminal
+            unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &silent) };
+        }
+        EchoOff { original }
+    }
+
+    #[cfg(not(unix))]
+    fn new() -> EchoOff {
+        EchoOff {}
+    }
+}
+
+impl Drop for EchoOff {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(o) = &self.original {
+            // SAFETY: `o` is the termios, that was read from the terminal before
+            unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, o) };
+        }
+    }
+}
+
+/// Reads a file, that a setting points to (e.g. a certificate)
+fn read_setting_file(path: String, what: &str) -> Result<Vec<u8>> {
+    fs::read(&path).map_err(|e| {
+        CliError::new(
+            "Command error",
+            Some(&format!("Could not read {}: {}", what, path)),
+            Some(&e.to_string()),
+        )
+    })
+}
+
+/// Collects the client certificate for mutual TLS: either a PEM-encoded certificate and private
+/// key, or a PKCS#12 archive with both. The files are read from the paths specified
+pub fn get_client_identity(
+    client_cert: Option<&str>,
+    client_key: Option<&str>,
+    client_pkcs12: Option<&str>,
+    client_pkcs12_password: Option<&str>,
+    profile: Option<&Profile>,
+) -> Result<Option<ClientIdentity>> {
+    let client_cert = get_setting(
+        client_cert,
+        "ORC_CLIENT_CERT",
+        profile.and_then(|p| p.client_cert.as_ref()),
+    );
+    let client_key = get_setting(
+        client_key,
+        "ORC_CLIENT_KEY",
+        profile.and_then(|p| p.client_key.as_ref()),
+    );
+    let client_pkcs12 = get_setting(
+        client_pkcs12,
+        "ORC_CLIENT_PKCS12",
+        profile.and_then(|p| p.client_pkcs12.as_ref()),
+    );
+    let client_pkcs12_password = get_setting(
+        client_pkcs12_password,
+        "ORC_CLIENT_PKCS12_PASSWORD",
+        profile.and_then(|p| p.client_pkcs12_password.as_ref()),
+    );
+    match (client_cert, client_key, client_pkcs12) {
+        (None, None, None) => Ok(None),
+        (Some(cert), Some(key), None) => Ok(Some(ClientIdentity::Pem {
+            cert: read_setting_file(cert, "client certificate")?,
+            key: read_setting_file(key, "client key")?,
+        })),
+        (None, None, Some(archive)) => Ok(Some(ClientIdentity::Pkcs12 {
+            archive: read_setting_file(archive, "client certificate archive")?,
+            password: client_pkcs12_password.unwrap_or_default(),
+        })),
+        (_, _, Some(_)) => Err(CliError::new(
+            "Command error",
+            Some("Both a client certificate and a PKCS#12 archive are specified"),
+            Some("Use either --client-cert and --client-key, or --client-pkcs12"),
+        )),
+        (cert, _, None) => Err(CliError::new(
+            "Command error",
+            Some(match cert {
+                Some(_) => "Client certificate without a private key",
+                None => "Client private key without a certificate",
+            }),
+            Some("--client-cert and --client-key must be specified together"),
+        )),
+    }
+}
+
+/// Collects TLS, timeout and proxy settings. The CA certificate is read from the file specified
+pub fn get_transport_options(
+    ca_cert: Option<&str>,
+    client_identity: Option<ClientIdentity>,
+    insecure: bool,
+    timeout: Option<&str>,
+    proxy: Option<&str>,
+    profile: Option<&Profile>,
+) -> Result<TransportOptions> {
+    let ca_cert = get_setting(
+        ca_cert,
+        "ORC_CA_CERT",
+        profile.and_then(|p| p.ca_cert.as_ref()),
+    );
+    let insecure = insecure
+        || match env::var("ORC_INSECURE") {
+            Ok(v) => ["1", "true", "yes"].contains(&v.to_lowercase().as_str()),
+            Err(_) => profile.and_then(|p| p.insecure).unwrap_or(false),
+        };
+    let timeout = match get_setting(
+        timeout,
+        "ORC_TIMEOUT",
+        profile
+            .and_then(|p| p.timeout.map(|t| t.to_string()))
+            .as_ref(),
+    ) {
+        Some(t) => Some(t.parse::<u64>().map_err(|_| {
+            CliError::new(
+                "Command error",
+                Some(&format!("Invalid timeout: {}", t)),
+                Some("The timeout must be a number of seconds"),
+            )
+        })?),
+        None => None,
+    };
+    Ok(TransportOptions {
+        ca_cert: ca_cert
+            .map(|c| read_setting_file(c, "CA certificate"))
+            .transpose()?,
+        client_identity,
+        insecure,
+        timeout,
+        proxy: get_setting(proxy, "ORC_PROXY", profile.and_then(|p| p.proxy.as_ref())),
+    })
+}
+
+pub fn check_columns_option(
+    original_header: &[&str],
+    requested_columns: &[&str],
+) -> Result<()> {
+    for c in requested_columns {
+        if !original_header.contains(c) {
+            return Err(CliError::new(
+                "Command error",
+                Some(&format!(
+                    "Invalid column name: {}. Available columns: {}",
+                    c,
+                    original_header.join(", ")
+                )),
+                None,
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Parses the value of a numeric command-line option, e.g. the `limit` of `--limit`
+pub fn parse_number<T: FromStr>(value: &str, what: &str) -> Result<T> {
+    value.parse().map_err(|_| {
+        CliError::new(
+            "Command error",
+            Some(&format!("Invalid {}: {}", what, value)),
+            Some("Must be a number"),
+        )
+    })
+}
+
+/// Parses the value of `--sort-by`: a column, optionally followed by `:asc` or `:desc`
+pub fn parse_sort_by(value: &str) -> Result<SortBy> {
+    let (column, order) = match value.rsplit_once(':') {
+        Some((c, o)) => (c, Some(o)),
+        None => (value, None),
+    };
+    let descending = match order {
+        None | Some("asc") => false,
+        Some("desc") => true,
+        Some(o) => {
+            return Err(CliError::new(
+                "Command error",
+                Some(&format!("Invalid sort order: {}", o)),
+                Some("The order is either asc or desc, e.g. StudyDate:desc"),
+            ))
+        }
+    };
+    Ok(SortBy {
+        column: column.to_string(),
+        descending,
+    })
+}
+
+/// Makes sure, that the list is sorted by one of its columns
+pub fn check_sort_column(header: &[&str], options: &ListOptions) -> Result<()> {
+    match &options.sort_by {
+        Some(s) if !header.contains(&s.column.as_str()) => Err(CliError::new(
+            "Command error",
+            Some(&format!(
+                "Invalid sort column: {}. Available columns: {}",
+                s.column,
+                header.join(", ")
+            )),
+            Some("A DICOM tag, that is not displayed, can be added with -c/--columns"),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Sorts the rows of a list by a column. Values, that are numbers, or look like ones (e.g.
+/// DICOM dates and times), are compared as numbers, the rest as strings. Missing values come
+/// last in either order
+pub fn sort_rows(rows: &mut [Row], sort_by: &SortBy) {
+    let key = |row: &Row| {
+        row.get(&sort_by.column)
+            .and_then(|v| sort_key(&sort_by.column, v))
+    };
+    rows.sort_by(|a, b| match (key(a), key(b)) {
+        (Some(a), Some(b)) => {
+            let ordering = a.partial_cmp(&b).unwrap_or(Ordering::Equal);
+            if sort_by.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        }
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    });
+}
+
+/// A value of a list column, as it is compared when sorting. Numbers come before strings
+#[derive(Debug, PartialEq, PartialOrd)]
+enum SortKey {
+    Number(f64),
+    Text(String),
+}
+
+fn sort_key(column: &str, value: &Value) -> Option<SortKey> {
+    let s = match value {
+        Value::Number(n) => return n.as_f64().map(SortKey::Number),
+        Value::String(s) if !s.trim().is_empty() => s.trim(),
+        _ => return None,
+    };
+    // Old-style dates (yyyy.mm.dd) and times (hh:mm:ss) are still around, and times may be
+    // truncated (hhmm), so they are brought to yyyymmdd and hhmmss.ffffff before comparing
+    let normalized = if column.ends_with("Date") {
+        s.replace('.', "")
+    } else if column.ends_with("Time") {
+        let s = s.replace(':', "");
+        match s.split_once('.') {
+            Some((hms, fraction)) => format!("{:0<6}.{}", hms, fraction),
+            None => format!("{:0<6}", s),
+        }
+    } else {
+        s.to_string()
+    };
+    match normalized.parse::<f64>() {
+        Ok(n) if n.is_finite() => Some(SortKey::Number(n)),
+        _ => Some(SortKey::Text(s.to_string())),
+    }
+}
+
+/// Checks whether `name` looks like a DICOM tag: either a keyword (e.g. `PatientBirthDate`) or
+/// a `gggg,eeee` tag number (e.g. `0010,0030`)
+pub fn is_dicom_tag(name: &str) -> bool {
+    let hex = |s: &str| s.len() == 4 && s.chars().all(|c| c.is_ascii_hexdigit());
+    match name.split_once(',') {
+        Some((group, element)) => hex(group) && hex(element),
+        None => {
+            name.starts_with(|c: char| c.is_ascii_uppercase())
+                && name.chars().all(|c| c.is_ascii_alphanumeric())
+        }
+    }
+}
+
+/// Looks up the value of a DICOM tag in the expanded tags of an entity (as returned by
+/// `/instances/{id}/tags` or `/{level}/{id}/shared-tags`) either by keyword or by `gggg,eeee`.
+/// Only string values are supported, anything else is treated as absent
+pub fn find_tag_value(tags: &Value, tag: &str) -> Value {
+    let map = match tags {
+        Value::Object(m) => m,
+        _ => return Value::Null,
+    };
+    let entry = match map.get(&tag.to_lowercase()) {
+        Some(e) => Some(e),
+        None => map.values().find(|v| v["Name"] == tag),
+    };
+    match entry.map(|e| &e["Value"]) {
+        Some(Value::String(v)) => Value::from(v.as_str()),
+        _ => Value::Null,
+    }
+}
+
+/// Looks up the value of a DICOM tag in the `RequestedTags` of an entity, either by keyword or
+/// by `gggg,eeee`. Only string values are supported, anything else is treated as absent
+pub fn requested_tag_value(tags: &Value, tag: &str) -> Value {
+    match &tags[tag_keyword(tag).as_str()] {
+        Value::String(v) => Value::from(v.as_str()),
+        _ => Value::Null,
+    }
+}
+
+/// Narrows down the default `header` and `dicom_tags` to the requested `columns`. Columns, that
+/// are not part of the default header, are accepted as long as they are DICOM tags, and are
+/// appended in the order they were requested
+pub fn get_header_and_dicom_tags<'a>(
+    header: &mut Vec<&'a str>,
+    dicom_tags: &mut Vec<&'a str>,
+    columns: Option<Vec<&'a str>>,
+) -> Result<()> {
+    if let Some(c) = columns {
+        let mut extra_tags = vec![];
+        for column in c.iter() {
+            if header.contains(column) || extra_tags.contains(column) {
+                continue;
+            }
+            if !is_dicom_tag(column) {
+                return Err(CliError::new(
+                    "Command error",
+                    Some(&format!(
+                        "Invalid column name: {}. Available columns: {}",
+                        column,
+                        header.join(", ")
+                    )),
+                    Some("Any DICOM tag can be used as a column, either by keyword (e.g. PatientBirthDate) or as gggg,eeee (e.g. 0010,0030)"),
+                ));
+            }
+            extra_tags.push(*column);
+        }
+        header.retain(|v| c.contains(v));
+        dicom_tags.retain(|v| c.contains(v));
+        header.extend(extra_tags.iter());
+        dicom_tags.extend(extra_tags.iter());
+    };
+    Ok(())
+}
+
+/// A row of the answers to a C-FIND query. `answer` holds DICOM tags in the expanded format
+pub fn query_answer_row(index: usize, answer: &Value, dicom_tags: &[&str]) -> Row {
+    let mut row = Row::new();
+    row.insert(QUERY_ANSWER_INDEX_COLUMN.to_string(), Value::from(index));
+    for t in dicom_tags {
+        row.insert(t.to_string(), find_tag_value(answer, t));
+    }
+    row
+}
+
+/// The level and the UIDs of a study, or of one of its series, to retrieve from a modality
+pub fn retrieve_uids(
+    study: &str,
+    series: Option<&str>,
+) -> (&'static str, HashMap<String, String>) {
+    let mut uids = HashMap::new();
+    uids.insert("StudyInstanceUID".to_string(), study.to_string());
+    match series {
+        Some(s) => {
+            uids.insert("SeriesInstanceUID".to_string(), s.to_string());
+            ("Series", uids)
+        }
+        None => ("Study", uids),
+    }
+}
+
+/// The UIDs, that identify the resource described by an answer to a C-FIND query, as a query for
+/// the matching instances. Patient-level answers carry no UIDs, so they are identified by
+/// `PatientID`
+pub fn query_answer_uids(answer: &Value) -> HashMap<String, String> {
+    let mut uids = HashMap::new();
+    for t in RETRIEVE_UID_TAGS {
+        if let Value::String(v) = find_tag_value(answer, t) {
+            uids.insert(t.to_string(), v);
+        }
+    }
+    if uids.is_empty() {
+        if let Value::String(v) = find_tag_value(answer, "PatientID") {
+            uids.insert("PatientID".to_string(), v);
+        }
+    }
+    uids
+}
+
+/// Collects the files to upload. Directories are traversed recursively, their entries are sorted
+/// by name. Symbolic links are followed, but every directory is traversed only once, so that a
+/// link loop ends. An entry, that can not be read, is returned with its error, and the traversal
+/// goes on with the other entries
+pub fn find_files(path: &Path) -> Vec<(PathBuf, io::Result<()>)> {
+    let mut files = vec![];
+    find_files_in(path, &mut HashSet::new(), &mut files);
+    files
+}
+
+fn find_files_in(
+    path: &Path,
+    visited: &mut HashSet<PathBuf>,
+    files: &mut Vec<(PathBuf, io::Result<()>)>,
+) {
+    let failed = |e| (path.to_path_buf(), Err(e));
+    match fs::metadata(path) {
+        Ok(m) if m.is_dir() => (),
+        Ok(_) => return files.push((path.to_path_buf(), Ok(()))),
+        Err(e) => return files.push(failed(e)),
+    };
+    match fs::canonicalize(path) {
+        Ok(p) => {
+            if !visited.insert(p) {
+                return;
+            }
+        }
+        Err(e) => return files.push(failed(e)),
+    };
+    let mut entries = vec![];
+    match fs::read_dir(path) {
+        Ok(dir) => {
+            for e in dir {
+                match e {
+                    Ok(e) => entries.push(e.path()),
+                    Err(e) => files.push(failed(e)),
+                }
+            }
+        }
+        Err(e) => return files.push(failed(e)),
+    };
+    entries.sort();
+    for e in entries {
+        find_files_in(&e, visited, files);
+    }
+}
+
+pub fn is_zip_archive(data: &[u8]) -> bool {
+    data.starts_with(b"PK\x03\x04")
+}
+
+/// Turns a job into a row of the jobs list (or job details)
+pub fn job_row(job: Job) -> Row {
+    let mut row = Row::new();
+    row.insert("ID".to_string(), Value::from(job.id));
+    row.insert("Type".to_string(), Value::from(job.kind));
+    row.insert("State".to_string(), Value::from(job.state));
+    row.insert("Progress".to_string(), Value::from(job.progress));
+    row.insert("Priority".to_string(), Value::from(job.priority));
+    row.insert("Creation time".to_string(), Value::from(job.creation_time));
+    row.insert(
+        "Completion time".to_string(),
+        Value::from(job.completion_time),
+    );
+    row.insert(
+        "Effective runtime".to_string(),
+        Value::from(job.effective_runtime),
+    );
+    row.insert("Error code".to_string(), Value::from(job.error_code));
+    row.insert(
+        "Error description".to_string(),
+        Value::from(job.error_description),
+    );
+    row.insert("Content".to_string(), job.content);
+    row
+}
+
+/// A one-line progress indicator of a job, e.g. `[#####---------------]  25% Running`
+pub fn format_job_progress(job: &Job) -> String {
+    let width = 20;
+    let filled = job.progress.min(100) as usize * width / 100;
+    format!(
+        "[{}{}] {:>3}% {:<7}",
+        "#".repeat(filled),
+        "-".repeat(width - filled),
+        job.progress,
+        job.state
+    )
+}
+
+/// Turns the result of uploading a single file into a row of the upload results list
+pub fn create_upload_row(file: &str, result: Result<UploadResult>) -> Row {
+    let mut row = Row::new();
+    row.insert("File".to_string(), Value::from(file));
+    match result {
+        Ok(r) => {
+            let status = match r.status.as_str() {
+                "Success" => "New",
+                "AlreadyStored" => "Already stored",
+                s => s,
+            };
+            row.insert("Status".to_string(), Value::from(status));
+            row.insert("ID".to_string(), Value::from(r.id));
+            row.insert("Series ID".to_string(), Value::from(r.parent_series));
+            row.insert("Study ID".to_string(), Value::from(r.parent_study));
+            row.insert("Patient ID".to_string(), Value::from(r.parent_patient));
+            row.insert("Error".to_string(), Value::Null);
+        }
+        Err(e) => {
+            row.insert("Status".to_string(), Value::from(UPLOAD_FAILED_STATUS));
+            row.insert("ID".to_string(), Value::Null);
+            row.insert("Series ID".to_string(), Value::Null);
+            row.insert("Study ID".to_string(), Value::Null);
+            row.insert("Patient ID".to_string(), Value::Null);
+            row.insert(
+                "Error".to_string(),
+                Value::from(e.message.unwrap_or(e.error)),
+            );
+        }
+    }
+    row
+}
+
+/// Fails if any of the files could not be uploaded
+pub fn check_upload_results(results: &EntityList) -> Result<()> {
+    let failed = results
+        .rows
+        .iter()
+        .filter(|r| r["Status"] == UPLOAD_FAILED_STATUS)
+        .count();
+    if failed > 0 {
+        return Err(CliError::new(
+            "Upload error",
+            Some(&format!(
+                "{} of {} files failed to upload",
+                failed,
+                results.rows.len()
+            )),
+            None,
+        ));
+    }
+    Ok(())
+}
+
+pub fn create_delete_row(id: &str, result: Result<()>) -> Row {
+    let mut row = Row::new();
+    row.insert("ID".to_string(), Value::from(id));
+    match result {
+        Ok(_) => {
+            row.insert("Status".to_string(), Value::from(DELETED_STATUS));
+            row.insert("Error".to_string(), Value::from(""));
+        }
+        Err(e) => {
+            row.insert("Status".to_string(), Value::from(DELETE_FAILED_STATUS));
+            row.insert(
+                "Error".to_string(),
+                Value::from(e.message.unwrap_or(e.error)),
+            );
+        }
+    }
+    row
+}
+
+/// Builds a list out of the results of a command run for many entities, one row per entity ID.
+/// The columns are those of all the results, in the order of their appearance
+pub fn create_results_list<T: ToRow>(results: Vec<(String, T)>) -> EntityList {
+    let mut columns = vec!["ID".to_string()];
+    let mut rows = vec![];
+    for (id, result) in results {
+        let mut row = Row::new();
+        row.insert("ID".to_string(), Value::from(id));
+        for (column, value) in result.to_row() {
+            if !columns.contains(&column) {
+                columns.push(column.clone());
+            }
+            row.entry(column).or_insert(value);
+        }
+        rows.push(row);
+    }
+    for row in rows.iter_mut() {
+        for column in columns.iter() {
+            row.entry(column.as_str()).or_insert(Value::Null);
+        }
+    }
+    EntityList { columns, rows }
+}
+
+/// Returns an error, if any of the entities failed to be deleted
+pub fn check_delete_results(results: &EntityList, level: &EntityKind) -> Result<()> {
+    let failed = results
+        .rows
+        .iter()
+        .filter(|r| r["Status"] == DELETE_FAILED_STATUS)
+        .count();
+    if failed > 0 {
+        return Err(CliError::new(
+            "Delete error",
+            Some(&format!(
+                "{} of {} {} failed to be deleted",
+                failed,
+                results.rows.len(),
+                entity_kind_plural(level)
+            )),
+            None,
+        ));
+    }
+    Ok(())
+}
+
+/// A row of the results of a bulk download. The size and the duration are those of the last
+/// attempt to download the entity
+pub fn create_download_row(
+    id: &str,
+    path: &Path,
+    size: u64,
+    duration: Duration,
+    result: Result<()>,
+) -> Row {
+    let mut row = Row::new();
+    row.insert("ID".to_string(), Value::from(id));
+    row.insert("File".to_string(), Value::from(path.to_string_lossy()));
+    let (status, error) = match result {
+        Ok(_) => (DOWNLOADED_STATUS, "".to_string()),
+        Err(e) => (DOWNLOAD_FAILED_STATUS, e.message.unwrap_or(e.error)),
+    };
+    row.insert("Status".to_string(), Value::from(status));
+    row.insert("Size".to_string(), Value::from(format_bytes(size)));
+    row.insert(
+        "Duration".to_string(),
+        Value::from(format!("{:.1}s", duration.as_secs_f64())),
+    );
+    row.insert("Error".to_string(), Value::from(error));
+    row
+}
+
+/// Returns an error, if any of the entities failed to be downloaded
+pub fn check_download_results(results: &EntityList, level: &EntityKind) -> Result<()> {
+    let failed = results
+        .rows
+        .iter()
+        .filter(|r| r["Status"] == DOWNLOAD_FAILED_STATUS)
+        .count();
+    if failed > 0 {
+        return Err(CliError::new(
+            "Download error",
+            Some(&format!(
+                "{} of {} {} failed to be downloaded",
+                failed,
+                results.rows.len(),
+                entity_kind_plural(level)
+            )),
+            None,
+        ));
+    }
+    Ok(())
+}
+
+/// Runs `f` until it succeeds, fails with an error, that is not transient (see
+/// [`CliError::is_transient`]), or has been retried `retries` times. The delay before a retry
+/// starts at `backoff`, and doubles with every retry
+pub fn retry<T, F: FnMut() -> Result<T>>(
+    retries: u32,
+    backoff: Duration,
+    mut f: F,
+) -> Result<T> {
+    let mut delay = backoff;
+    for _ in 0..retries {
+        match f() {
+            Err(e) if e.is_transient() => {
+                thread::sleep(delay);
+                delay *= 2;
+            }
+            result => return result,
+        }
+    }
+    f()
+}
+
+/// The paths of the files of a bulk download, named after `template` (see
+/// [`render_path_template`]) with the values of `entities`. Files, that end up with the same
+/// name, are told apart by a numeric suffix
+pub fn download_paths(
+    entities: &EntityList,
+    template: &str,
+    dir: &Path,
+) -> Result<Vec<PathBuf>> {
+    let mut paths = HashSet::new();
+    let mut result = vec![];
+    for row in entities.rows.iter() {
+        let path = render_path_template(template, |tag| match row.get(tag) {
+            Some(Value::String(v)) => Some(v.to_string()),
+            Some(Value::Null) | None => None,
+            Some(v) => Some(v.to_string()),
+        })?;
+        let path = unique_path(dir.join(path), &paths);
+        paths.insert(path.clone());
+        result.push(path);
+    }
+    Ok(result)
+}
+
+/// Reads IDs, one per line, from a file, or from the standard input if `source` is `-`. Blank
+/// lines are skipped
+pub fn read_ids(source: &str) -> Result<Vec<String>> {
+    let content = if source == "-" {
+        io::read_to_string(io::stdin())?
+    } else {
+        fs::read_to_string(source)?
+    };
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// The entity kind of a `--level` value
+pub fn entity_kind(level: &str) -> Result<EntityKind> {
+    match level {
+        "patient" => Ok(EntityKind::Patient),
+        "study" => Ok(EntityKind::Study),
+        "series" => Ok(EntityKind::Series),
+        "instance" => Ok(EntityKind::Instance),
+        _ => Err(CliError::new(
+            "Command error",
+            Some(&format!("Invalid level: {}", level)),
+            Some("Must be one of patient, study, series, instance"),
+        )),
+    }
+}
+
+/// The name of many entities of a kind, e.g. `studies`
+pub fn entity_kind_plural(kind: &EntityKind) -> &'static str {
+    match kind {
+        EntityKind::Patient => "patients",
+        EntityKind::Study => "studies",
+        EntityKind::Series => "series",
+        EntityKind::Instance => "instances",
+    }
+}
+
+/// The main DICOM tag, that identifies an entity of `kind`
+pub fn entity_identifier_tag(kind: &EntityKind) -> &'static str {
+    match kind {
+        EntityKind::Patient => "PatientID",
+        EntityKind::Study => "StudyInstanceUID",
+        EntityKind::Series => "SeriesInstanceUID",
+        EntityKind::Instance => "SOPInstanceUID",
+    }
+}
+
+/// The keyword of a `gggg,eeee` DICOM tag (e.g. `PatientBirthDate` for `0010,0030`), the way
+/// Orthanc names the `RequestedTags` of an entity. Keywords and unknown tags are returned as is
+pub fn tag_keyword(tag: &str) -> String {
+    let number = |s: &str| u16::from_str_radix(s, 16).ok();
+    let entry = match tag.split_once(',') {
+        Some((group, element)) => match (number(group), number(element)) {
+            (Some(g), Some(e)) => StandardDataDictionary.by_tag(Tag(g, e)),
+            _ => None,
+        },
+        None => None,
+    };
+    match entry {
+        Some(e) => e.alias().to_string(),
+        None => tag.to_string(),
+    }
+}
+
+/// A part of a `--path-template`: either text, or a `{Tag}` placeholder
+#[derive(Debug, Eq, PartialEq)]
+enum TemplatePart<'a> {
+    Text(&'a str),
+    Tag(&'a str),
+}
+
+/// Parses a template, whose placeholders are DICOM tags, or one of `fields`
+fn parse_path_template<'a>(
+    template: &'a str,
+    fields: &[&str],
+) -> Result<Vec<TemplatePart<'a>>> {
+    let error = |details: &str| {
+        Err(CliError::new(
+            "Command error",
+            Some(&format!("Invalid path template: {}", template)),
+            Some(details),
+        ))
+    };
+    if template.starts_with('/') || template.split('/').any(|c| c == "..") {
+        return error("The path must be relative to the output directory");
+    }
+    let mut parts = vec![];
+    let mut rest = template;
+    while !rest.is_empty() {
+        match rest.find(['{', '}']) {
+            Some(i) if rest[i..].starts_with('{') => {
+                let end = match rest[i..].find('}') {
+                    Some(e) => i + e,
+                    None => return error("Unclosed {"),
+                };
+                let tag = &rest[i + 1..end];
+                if !is_dicom_tag(tag) && !fields.contains(&tag) {
+                    let mut details = format!(
+                        "Invalid tag: {}. A tag is either a keyword (e.g. StudyDate) or gggg,eeee (e.g. 0008,0020)",
+                        tag
+                    );
+                    if !fields.is_empty() {
+                        details.push_str(&format!(", or one of: {}", fields.join(", ")));
+                    }
+                    return error(&details);
+                }
+                if i > 0 {
+                    parts.push(TemplatePart::Text(&rest[..i]));
+                }
+                parts.push(TemplatePart::Tag(tag));
+                rest = &rest[end + 1..];
+            }
+            Some(_) => return error("Unopened }"),
+            None => {
+                parts.push(TemplatePart::Text(rest));
+                rest = "";
+            }
+        }
+    }
+    Ok(parts)
+}
+
+/// Checks, that a `--path-template` is a relative path with valid `{Tag}` placeholders
+pub fn check_path_template(template: &str) -> Result<()> {
+    parse_path_template(template, &[]).map(|_| ())
+}
+
+/// Checks a `--name-template` of bulk downloads, and returns the DICOM tags it refers to
+pub fn name_template_tags(template: &str) -> Result<Vec<&str>> {
+    Ok(parse_path_template(template, NAME_TEMPLATE_FIELDS)?
+        .into_iter()
+        .filter_map(|p| match p {
+            TemplatePart::Tag(t) if !NAME_TEMPLATE_FIELDS.contains(&t) => Some(t),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Builds a path out of a `--path-template` or a `--name-template`, replacing each `{Tag}` with
+/// the value of the tag. Absent and empty values are replaced with a placeholder, and
+/// characters, that are not allowed in file names, with `_`
+pub fn render_path_template<F: Fn(&str) -> Option<String>>(
+    template: &str,
+    tag_value: F,
+) -> Result<PathBuf> {
+    let mut path = String::new();
+    for part in parse_path_template(template, NAME_TEMPLATE_FIELDS)? {
+        match part {
+            TemplatePart::Text(t) => path.push_str(t),
+            TemplatePart::Tag(t) => {
+                let value = tag_value(t).unwrap_or_default();
+                let value: String = value
+                    .trim()
+                    .chars()
+                    .map(|c| match c {
+                        '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+                        c if c.is_control() => '_',
+                        c => c,
+                    })
+                    .collect();
+                path.push_str(match value.as_str() {
+                    "" => ABSENT_DICOM_TAG_PLACEHOLDER,
+                    "." | ".." => "_",
+                    v => v,
+                });
+            }
+        }
+    }
+    Ok(PathBuf::from(path))
+}
+
+/// The value of a DICOM tag (a keyword or `gggg,eeee`) of a DICOM file, as a string
+pub fn dicom_tag_value(object: &DefaultDicomObject, tag: &str) -> Option<String> {
+    let element = match tag.split_once(',') {
+        Some((group, element)) => object.element(Tag(
+            u16::from_str_radix(group, 16).ok()?,
+            u16::from_str_radix(element, 16).ok()?,
+        )),
+        None => object.element_by_name(tag),
+    };
+    let value = element.ok()?.to_str().ok()?;
+    // Values are padded to an even length
+    Some(value.trim_end_matches([' ', '\0']).to_string())
+}
+
+/// Extracts a ZIP archive into `dir` as the archive is being read, so that it never has to be
+/// held in memory as a whole. The files keep their paths in the archive, unless they are
+/// DICOM files named after `template` (see [`render_path_template`]). Files, that end up with
+/// the same name, are told apart by a numeric suffix. Returns the number of files extracted
+pub fn extract_archive<R: Read>(
+    mut reader: R,
+    dir: &Path,
+    template: Option<&str>,
+) -> Result<usize> {
+    let mut paths = HashSet::new();
+    while let Some(mut file) = read_zipfile_from_stream(&mut reader)? {
+        if file.is_dir() {
+            continue;
+        }
+        let name = match file.enclosed_name() {
+            Some(n) => n.to_path_buf(),
+            None => {
+                return Err(CliError::new(
+                    "Command error",
+                    Some(&format!("Invalid path in archive: {}", file.name())),
+                    None,
+                ))
+            }
+        };
+        let path = match template {
+            Some(t) => {
+                let mut data = Vec::with_capacity(file.size() as usize);
+                file.read_to_end(&mut data)?;
+                let object = data
+                    .get(DICOM_PREAMBLE_LENGTH..)
+                    .and_then(|d| dicom_object::from_reader(d).ok())
+                    .ok_or_else(|| {
+                        CliError::new(
+                            "Command error",
+                            Some(&format!("Not a DICOM file: {}", name.display())),
+                            Some("A path template can only be applied to DICOM files"),
+                        )
+                    })?;
+                let path = unique_path(
+                    dir.join(render_path_template(t, |tag| {
+                        dicom_tag_value(&object, tag)
+                    })?),
+                    &paths,
+                );
+                write_file(&path, &mut data.as_slice())?;
+                path
+            }
+            None => {
+                let path = unique_path(dir.join(name), &paths);
+                write_file(&path, &mut file)?;
+                path
+            }
+        };
+        paths.insert(path);
+    }
+    Ok(paths.len())
+}
+
+/// Adds a numeric suffix to the file name of `path`, if it is one of `taken`
+fn unique_path(path: PathBuf, taken: &HashSet<PathBuf>) -> PathBuf {
+    if !taken.contains(&path) {
+        return path;
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map_or("".to_string(), |e| format!(".{}", e.to_string_lossy()));
+    (1..)
+        .map(|i| path.with_file_name(format!("{}_{}{}", stem, i, extension)))
+        .find(|p| !taken.contains(p))
+        .unwrap()
+}
+
+fn write_file<R: Read>(path: &Path, content: &mut R) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    io::copy(content, &mut fs::File::create(path)?)?;
+    Ok(())
+}
+
+/// Writes to `output` with `write`, or to the standard output if `output` is `-`. A file is
+/// first written under a temporary name next to it, and only renamed to `output` once it has
+/// been written completely, so that a failed transfer never leaves a truncated file behind
+pub fn write_output<F: FnOnce(&mut dyn Write) -> Result<()>>(
+    output: &str,
+    write: F,
+) -> Result<()> {
+    if output == "-" {
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        write(&mut stdout)?;
+        return stdout.flush().map_err(Into::into);
+    }
+    let path = Path::new(output);
+    let file_name = path.file_name().filter(|_| !path.is_dir()).ok_or_else(|| {
+        CliError::new(
+            "Command error",
+            Some(&format!("Not a file path: {}", output)),
+            None,
+        )
+    })?;
+    let temp_path = path.with_file_name(format!(
+        ".{}{}",
+        file_name.to_string_lossy(),
+        PARTIAL_FILE_SUFFIX
+    ));
+    let result = fs::File::create(&temp_path)
+        .map_err(Into::into)
+        .and_then(|mut file| {
+            write(&mut file)?;
+            file.sync_all()?;
+            fs::rename(&temp_path, path).map_err(Into::into)
+        });
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// Wraps a reader or a writer, and reports the number of bytes transferred so far to
+/// `on_progress`
+pub struct Progress<T, F: FnMut(u64)> {
+    inner: T,
+    bytes: u64,
+    on_progress: F,
+}
+
+impl<T, F: FnMut(u64)> Progress<T, F> {
+    pub fn new(inner: T, on_progress: F) -> Progress<T, F> {
+        Progress {
+            inner,
+            bytes: 0,
+            on_progress,
+        }
+    }
+
+    fn add(&mut self, bytes: usize) {
+        if bytes > 0 {
+            self.bytes += bytes as u64;
+            (self.on_progress)(self.bytes);
+        }
+    }
+}
+
+impl<T: Read, F: FnMut(u64)> Read for Progress<T, F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.add(n);
+        Ok(n)
+    }
+}
+
+impl<T: Write, F: FnMut(u64)> Write for Progress<T, F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.add(n);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Shows the progress of a transfer on a single, repeatedly overwritten line of the standard
+/// error, if `enabled`
+pub struct TransferProgress {
+    enabled: bool,
+    started: Instant,
+    reported: Option<Instant>,
+    width: usize,
+}
+
+impl TransferProgress {
+    pub fn new(enabled: bool) -> TransferProgress {
+        TransferProgress {
+            enabled,
+            started: Instant::now(),
+            reported: None,
+            width: 0,
+        }
+    }
+
+    pub fn update(&mut self, bytes: u64) {
+        if !self.enabled {
+            return;
+        }
+        let now = Instant::now();
+        if matches!(self.reported, Some(r) if now.duration_since(r) < PROGRESS_INTERVAL) {
+            return;
+        }
+        self.reported = Some(now);
+        let line = format_transfer_progress(bytes, now.duration_since(self.started));
+        eprint!("\r{:width$}", line, width = self.width);
+        self.width = self.width.max(line.len());
+    }
+
+    pub fn finish(&mut self) {
+        if self.reported.is_some() {
+            eprintln!();
+        }
+    }
+}
+
+/// Formats the number of bytes transferred and the average rate, e.g.
+/// "1.5 MiB received (512.0 KiB/s)"
+pub fn format_transfer_progress(bytes: u64, elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs_f64();
+    let rate = if seconds > 0.0 {
+        (bytes as f64 / seconds) as u64
+    } else {
+        0
+    };
+    format!(
+        "{} received ({}/s)",
+        format_bytes(bytes),
+        format_bytes(rate)
+    )
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    let units = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, units[unit])
+}
+
+pub fn get_output_format(cmd_option: Option<&str>) -> Result<OutputFormat> {
+    match cmd_option {
+        None | Some("table") => Ok(OutputFormat::Table),
+        Some("json") => Ok(OutputFormat::Json),
+        Some("yaml") => Ok(OutputFormat::Yaml),
+        Some("csv") => Ok(OutputFormat::Csv),
+        Some("tsv") => Ok(OutputFormat::Tsv),
+        Some(f) => Err(CliError::new(
+            "Command error",
+            Some(&format!("Unknown output format: {}", f)),
+            Some("Must be one of table, json, yaml, csv, tsv"),
+        )),
+    }
+}
+
+fn table_to_delimited(mut table: Table, delimiter: u8) -> Result<String> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_writer(vec![]);
+    if let Some(h) = table.header() {
+        writer.write_record(h.cell_iter().map(Cell::content))?;
+    }
+    for r in table.row_iter() {
+        writer.write_record(r.cell_iter().map(Cell::content))?;
+    }
+    let data = writer
+        .into_inner()
+        .map_err(|e| CliError::new(&e.to_string(), None, None))?;
+    Ok(String::from_utf8_lossy(&data).to_string())
+}
+
+/// Renders a command result in the requested format. JSON and YAML are serialized from the
+/// result itself, the other formats are rendered from its table
+pub fn render<T: Output>(
+    result: &T,
+    no_header: bool,
+    format: &OutputFormat,
+) -> Result<String> {
+    match format {
+        OutputFormat::Table => Ok(format!("{}", result.to_table(no_header))),
+        OutputFormat::Json => Ok(serde_json::to_string_pretty(result)?),
+        OutputFormat::Yaml => Ok(serde_yaml::to_string(result)?),
+        OutputFormat::Csv => table_to_delimited(result.to_table(no_header), b','),
+        OutputFormat::Tsv => table_to_delimited(result.to_table(no_header), b'\t'),
+    }
+}
+
+pub fn print_table(table: Table) {
+    println!("{}", table);
+}
+
+pub fn print_output<T: Output>(result: &T, no_header: bool, format: &OutputFormat) {
+    match format {
+        OutputFormat::Table => print_table(result.to_table(no_header)),
+        _ => match render(result, no_header, format) {
+            Ok(o) => println!("{}", o.trim_end()),
+            Err(e) => exit_with_error(e),
+        },
+    }
+}
+
+pub fn exit_with_error(error: CliError) {
+    let output = create_error_table(error);
+    eprintln!("{}", output);
+    process::exit(1);
+}
+
+#[cfg(test)]
+mod tests {
     use super::*;
     use chrono::NaiveDate;
+    use dicom_core::{DataElement, PrimitiveValue, VR};
+    use dicom_object::{FileMetaTableBuilder, InMemDicomObject};
     use maplit::hashmap;
     use regex::RegexBuilder;
+    use serde_json::json;
     use std::env::{remove_var, set_var};
     use std::io::Write;
     use tempfile::NamedTempFile;
 
-    fn format_table(table: Table) -> String {
-        let trailing_whitespace_regex = RegexBuilder::new(r"([ ]+$)")
-            .multi_line(true)
-            .build()
-            .unwrap();
-        trailing_whitespace_regex
-            .replace_all(&format!("{}", table), "")
-            .to_string()
+    fn format_table(table: Table) -> String {
+        let trailing_whitespace_regex = RegexBuilder::new(r"([ ]+$)")
+            .multi_line(true)
+            .build()
+            .unwrap();
+        trailing_whitespace_regex
+            .replace_all(&format!("{}", table), "")
+            .to_string()
+    }
+
+    #[test]
+    fn test_create_table_with_header() {
+        let table = create_table(Some(&["Foo", "bar"]));
+        assert_eq!(&format!("{}", table), " Foo   bar \n-----------");
+    }
+
+    #[test]
+    fn test_create_table_no_header() {
+        let table = create_table(None);
+        assert_eq!(&format!("{}", table), "");
+    }
+
+    fn test_list_table_patient(
+        columns: &[&str],
+        dicom_tags: &[&str],
+        no_header: bool,
+        expected_output: &str,
+    ) {
+        let patient_1 = Patient {
+            id: "foo".to_string(),
+            is_stable: true,
+            last_update: NaiveDate::from_ymd(2020, 1, 1).and_hms(15, 46, 17),
+            main_dicom_tags: hashmap! {
+                "PatientID".to_string() => "foo_id".to_string(),
+                "PatientName".to_string() => "Rick Sanchez".to_string(),
+            },
+            studies: ["study_1".to_string()].to_vec(),
+            entity: EntityKind::Patient,
+            anonymized_from: None,
+        };
+        let patient_2 = Patient {
+            id: "bar".to_string(),
+            is_stable: true,
+            last_update: NaiveDate::from_ymd(2020, 1, 1).and_hms(15, 46, 17),
+            main_dicom_tags: hashmap! {
+                "PatientName".to_string() => "Morty Smith".to_string(),
+            },
+            studies: ["study_1".to_string(), "study_2".to_string()].to_vec(),
+            entity: EntityKind::Patient,
+            anonymized_from: None,
+        };
+        assert_eq!(
+            format_table(create_list_table(
+                &EntityList::new(vec![patient_1, patient_2], columns, dicom_tags),
+                no_header,
+            )),
+            expected_output
+        );
+    }
+
+    fn test_list_table_study(
+        columns: &[&str],
+        dicom_tags: &[&str],
+        no_header: bool,
+        expected_output: &str,
+    ) {
+        let study_1 = Study {
+            id: "foo".to_string(),
+            is_stable: true,
+            last_update: NaiveDate::from_ymd(2020, 8, 30).and_hms(19, 11, 9),
+            main_dicom_tags: hashmap! {
+                "AccessionNumber".to_string() => "foo_an".to_string(),
+                "StudyInstanceUID".to_string() => "foo_suid".to_string(),
+                "StudyDescription".to_string() => "foo_sd".to_string(),
+            },
+            parent_patient: "patient_foo".to_string(),
+            patient_main_dicom_tags: hashmap! {
+                "PatientName".to_string() => "Rick Sanchez".to_string(),
+            },
+            series: ["foo_series_1".to_string(), "foo_series_2".to_string()].to_vec(),
+            entity: EntityKind::Study,
+            anonymized_from: None,
+        };
+        let study_2 = Study {
+            id: "bar".to_string(),
+            is_stable: true,
+            last_update: NaiveDate::from_ymd(2020, 12, 30).and_hms(10, 10, 0),
+            main_dicom_tags: hashmap! {
+                "PatientID".to_string() => "bar_pid".to_string(),
+                "StudyDate".to_string() => "20200101".to_string(),
+                "StudyTime".to_string() => "190001".to_string(),
+            },
+            parent_patient: "patient_bar".to_string(),
+            patient_main_dicom_tags: hashmap! {
+                "PatientName".to_string() => "Rick Sanchez".to_string(),
+            },
+            series: ["bar_series_1".to_string()].to_vec(),
+            entity: EntityKind::Study,
+            anonymized_from: None,
+        };
+        assert_eq!(
+            format_table(create_list_table(
+                &EntityList::new(vec![study_1, study_2], columns, dicom_tags),
+                no_header,
+            )),
+            expected_output
+        );
+    }
+
+    fn test_list_table_series(
+        columns: &[&str],
+        dicom_tags: &[&str],
+        no_header: bool,
+        expected_output: &str,
+    ) {
+        let series_1 = Series {
+            id: "foo".to_string(),
+            status: "Unknown".to_string(),
+            is_stable: true,
+            last_update: NaiveDate::from_ymd(2020, 8, 30).and_hms(19, 11, 9),
+            main_dicom_tags: hashmap! {
+                "BodyPartExamined".to_string() => "ABDOMEN".to_string(),
+                "SeriesDescription".to_string() => "Foo".to_string(),
+            },
+            parent_study: "study_1".to_string(),
+            expected_number_of_instances: Some(17),
+            instances: ["i1".to_string(), "i2".to_string()].to_vec(),
+            entity: EntityKind::Series,
+            anonymized_from: None,
+        };
+        let series_2 = Series {
+            id: "bar".to_string(),
+            status: "Known".to_string(),
+            is_stable: true,
+            last_update: NaiveDate::from_ymd(2021, 8, 30).and_hms(19, 12, 9),
+            main_dicom_tags: hashmap! {
+                "BodyPartExamined".to_string() => "KNEE".to_string(),
+                "Modality".to_string() => "CT".to_string(),
+            },
+            parent_study: "study_2".to_string(),
+            expected_number_of_instances: Some(17),
+            instances: ["i3".to_string(), "i4".to_string()].to_vec(),
+            entity: EntityKind::Series,
+            anonymized_from: None,
+        };
+        assert_eq!(
+            format_table(create_list_table(
+                &EntityList::new(vec![series_1, series_2], columns, dicom_tags),
+                no_header,
+            )),
+            expected_output
+        );
+    }
+    fn test_list_table_instance(
+        columns: &[&str],
+        dicom_tags: &[&str],
+        no_header: bool,
+        expected_output: &str,
+    ) {
+        let instance_1 = Instance {
+            id: "foo".to_string(),
+            main_dicom_tags: hashmap! {
+                "SOPInstanceUID".to_string() => "suid_1".to_string(),
+                "InstanceNumber".to_string() => "in_1".to_string(),
+            },
+            parent_series: "foo_series".to_string(),
+            index_in_series: Some(13),
+            file_uuid: "file_uuid".to_string(),
+            file_size: 139402,
+            modified_from: None,
+            entity: EntityKind::Instance,
+            anonymized_from: None,
+        };
+        let instance_2 = Instance {
+            id: "bar".to_string(),
+            main_dicom_tags: hashmap! {
+                "SOPInstanceUID".to_string() => "suid_2".to_string(),
+                "InstanceCreationDate".to_string() => "19000101".to_string(),
+                "InstanceCreationTime".to_string() => "000000".to_string(),
+            },
+            parent_series: "foo_series".to_string(),
+            index_in_series: Some(13),
+            file_uuid: "file_uuid".to_string(),
+            file_size: 139402,
+            modified_from: None,
+            entity: EntityKind::Instance,
+            anonymized_from: None,
+        };
+        assert_eq!(
+            format_table(create_list_table(
+                &EntityList::new(vec![instance_1, instance_2], columns, dicom_tags),
+                no_header,
+            )),
+            expected_output
+        );
+    }
+
+    #[test]
+    fn test_create_list_table_patient() {
+        test_list_table_patient(
+            PATIENTS_LIST_HEADER,
+            PATIENTS_LIST_DICOM_TAGS,
+            false,
+            include_str!("../tests/data/unit/list_patients").trim_end(),
+        );
+    }
+
+    #[test]
+    fn test_create_list_table_patient_no_header() {
+        test_list_table_patient(
+            PATIENTS_LIST_HEADER,
+            PATIENTS_LIST_DICOM_TAGS,
+            true,
+            include_str!("../tests/data/unit/list_patients_no_header").trim_end(),
+        );
+    }
+
+    #[test]
+    fn test_create_list_table_patient_columns() {
+        test_list_table_patient(
+            &["PatientID", "Number of Studies"],
+            &["PatientID"],
+            false,
+            include_str!("../tests/data/unit/list_patients_columns").trim_end(),
+        );
+    }
+
+    #[test]
+    fn test_create_list_table_patient_columns_no_header() {
+        test_list_table_patient(
+            &["PatientID", "Number of Studies"],
+            &["PatientID"],
+            true,
+            include_str!("../tests/data/unit/list_patients_columns_no_header").trim_end(),
+        );
+    }
+
+    #[test]
+    fn test_create_list_table_study() {
+        test_list_table_study(
+            STUDIES_LIST_HEADER,
+            STUDIES_LIST_DICOM_TAGS,
+            false,
+            include_str!("../tests/data/unit/list_studies").trim_end(),
+        );
+    }
+
+    #[test]
+    fn test_create_list_table_study_no_header() {
+        test_list_table_study(
+            STUDIES_LIST_HEADER,
+            STUDIES_LIST_DICOM_TAGS,
+            true,
+            include_str!("../tests/data/unit/list_studies_no_header").trim_end(),
+        );
+    }
+
+    #[test]
+    fn test_create_list_table_series() {
+        test_list_table_series(
+            SERIES_LIST_HEADER,
+            SERIES_LIST_DICOM_TAGS,
+            false,
+            include_str!("../tests/data/unit/list_series").trim_end(),
+        );
+    }
+
+    #[test]
+    fn test_create_list_table_series_no_header() {
+        test_list_table_series(
+            SERIES_LIST_HEADER,
+            SERIES_LIST_DICOM_TAGS,
+            true,
+            include_str!("../tests/data/unit/list_series_no_header").trim_end(),
+        );
+    }
+
+    #[test]
+    fn test_create_list_table_instance() {
+        test_list_table_instance(
+            INSTANCES_LIST_HEADER,
+            INSTANCES_LIST_DICOM_TAGS,
+            false,
+            include_str!("../tests/data/unit/list_instances").trim_end(),
+        );
+    }
+
+    #[test]
+    fn test_create_list_table_instance_no_header() {
+        test_list_table_instance(
+            INSTANCES_LIST_HEADER,
+            INSTANCES_LIST_DICOM_TAGS,
+            true,
+            include_str!("../tests/data/unit/list_instances_no_header").trim_end(),
+        );
+    }
+
+    #[test]
+    fn test_create_list_table_instance_columns() {
+        test_list_table_instance(
+            &["SOPInstanceUID", "Index in series", "File size"],
+            &["SOPInstanceUID"],
+            false,
+            include_str!("../tests/data/unit/list_instances_columns").trim_end(),
+        );
+    }
+
+    #[test]
+    fn test_create_list_table_instance_columns_no_header() {
+        test_list_table_instance(
+            &["SOPInstanceUID", "Index in series", "File size"],
+            &["SOPInstanceUID"],
+            true,
+            include_str!("../tests/data/unit/list_instances_columns_no_header").trim_end(),
+        );
+    }
+
+    #[test]
+    fn test_create_list_table_no_header_no_data() {
+        let data: Vec<Patient> = vec![];
+        assert_eq!(
+            format_table(create_list_table(
+                &EntityList::new(data, PATIENTS_LIST_HEADER, PATIENTS_LIST_DICOM_TAGS),
+                true,
+            )),
+            ""
+        );
+    }
+
+    #[test]
+    fn test_create_show_table_patient() {
+        let patient = Patient {
+            id: "foo".to_string(),
+            is_stable: true,
+            last_update: NaiveDate::from_ymd(2020, 1, 1).and_hms(15, 46, 17),
+            main_dicom_tags: hashmap! {
+                "PatientID".to_string() => "foo_id".to_string(),
+                "PatientName".to_string() => "Rick Sanchez".to_string(),
+            },
+            studies: ["study_1".to_string()].to_vec(),
+            entity: EntityKind::Patient,
+            anonymized_from: None,
+        };
+
+        assert_eq!(
+            format_table(create_show_table(&EntityDetails::new(
+                patient,
+                PATIENT_DICOM_TAGS
+            ))),
+            include_str!("../tests/data/unit/show_patient").trim_end()
+        );
+    }
+
+    #[test]
+    fn test_create_show_table_study() {
+        let study = Study {
+            id: "foo".to_string(),
+            is_stable: true,
+            last_update: NaiveDate::from_ymd(2020, 8, 30).and_hms(19, 11, 9),
+            main_dicom_tags: hashmap! {
+                "AccessionNumber".to_string() => "foo_an".to_string(),
+                "StudyInstanceUID".to_string() => "foo_suid".to_string(),
+                "StudyDescription".to_string() => "foo_sd".to_string(),
+            },
+            parent_patient: "patient_foo".to_string(),
+            patient_main_dicom_tags: hashmap! {
+                "PatientName".to_string() => "Rick Sanchez".to_string(),
+            },
+            series: ["foo_series_1".to_string(), "foo_series_2".to_string()].to_vec(),
+            entity: EntityKind::Study,
+            anonymized_from: None,
+        };
+
+        assert_eq!(
+            format_table(create_show_table(&EntityDetails::new(
+                study,
+                STUDY_DICOM_TAGS
+            ))),
+            include_str!("../tests/data/unit/show_study").trim_end()
+        );
+    }
+
+    #[test]
+    fn test_create_show_table_series() {
+        let series = Series {
+            id: "foo".to_string(),
+            status: "Unknown".to_string(),
+            is_stable: true,
+            last_update: NaiveDate::from_ymd(2020, 8, 30).and_hms(19, 11, 9),
+            main_dicom_tags: hashmap! {
+                "BodyPartExamined".to_string() => "ABDOMEN".to_string(),
+                "SeriesDescription".to_string() => "Foo".to_string(),
+            },
+            parent_study: "study_1".to_string(),
+            expected_number_of_instances: Some(17),
+            instances: ["i1".to_string(), "i2".to_string()].to_vec(),
+            entity: EntityKind::Series,
+            anonymized_from: None,
+        };
+        assert_eq!(
+            format_table(create_show_table(&EntityDetails::new(
+                series,
+                SERIES_DICOM_TAGS
+            ))),
+            include_str!("../tests/data/unit/show_series").trim_end()
+        );
+    }
+
+    #[test]
+    fn test_create_show_table_instance() {
+        let instance = Instance {
+            id: "foo".to_string(),
+            main_dicom_tags: hashmap! {
+                "SOPInstanceUID".to_string() => "suid_1".to_string(),
+                "InstanceNumber".to_string() => "in_1".to_string(),
+            },
+            parent_series: "foo_series".to_string(),
+            index_in_series: Some(13),
+            file_uuid: "file_uuid".to_string(),
+            file_size: 139402,
+            modified_from: None,
+            entity: EntityKind::Instance,
+            anonymized_from: None,
+        };
+        assert_eq!(
+            format_table(create_show_table(&EntityDetails::new(
+                instance,
+                INSTANCE_DICOM_TAGS
+            ))),
+            include_str!("../tests/data/unit/show_instance").trim_end()
+        );
+    }
+
+    #[test]
+    fn test_create_new_entity_table() {
+        let res = ModificationResult {
+            id: "foobar".to_string(),
+            patient_id: "bazqux".to_string(),
+            path: "long_and_rocky".to_string(),
+            entity: EntityKind::Study,
+        };
+        let expected_table = " New Study ID   foobar \n Patient ID     bazqux ";
+        assert_eq!(format!("{}", create_new_entity_table(&res)), expected_table)
+    }
+
+    #[test]
+    fn test_create_new_entity_table_patient() {
+        let res = ModificationResult {
+            id: "foobar".to_string(),
+            patient_id: "bazqux".to_string(),
+            path: "long_and_rocky".to_string(),
+            entity: EntityKind::Patient,
+        };
+        let expected_table = " New Patient ID   foobar ";
+        assert_eq!(format!("{}", create_new_entity_table(&res)), expected_table)
+    }
+
+    #[test]
+    fn test_create_results_list() {
+        let result = |id: &str| {
+            Outcome::Completed(ModificationResult {
+                id: id.to_string(),
+                patient_id: "bazqux".to_string(),
+                path: "long_and_rocky".to_string(),
+                entity: EntityKind::Study,
+            })
+        };
+        let list = create_results_list(vec![
+            ("foo".to_string(), result("new_foo")),
+            ("bar".to_string(), result("new_bar")),
+        ]);
+        assert_eq!(list.columns, vec!["ID", "New ID", "Patient ID"]);
+        assert_eq!(list.rows[1]["ID"], "bar");
+        assert_eq!(list.rows[1]["New ID"], "new_bar");
+        assert_eq!(list.rows[1]["Patient ID"], "bazqux");
+
+        let list = create_results_list(vec![(
+            "foo".to_string(),
+            Outcome::<ModificationResult>::Submitted(JobSubmission {
+                id: "job".to_string(),
+                path: "/jobs/job".to_string(),
+            }),
+        )]);
+        assert_eq!(list.columns, vec!["ID", "Job ID"]);
+        assert_eq!(list.rows[0]["Job ID"], "job");
+    }
+
+    #[test]
+    fn test_create_results_list_missing_columns() {
+        let details = |fields: Value| EntityDetails {
+            fields: fields.as_object().unwrap().clone(),
+        };
+        let list = create_results_list(vec![
+            ("foo".to_string(), details(json!({"ID": "foo", "Foo": 1}))),
+            ("bar".to_string(), details(json!({"ID": "bar", "Bar": 2}))),
+        ]);
+        assert_eq!(list.columns, vec!["ID", "Foo", "Bar"]);
+        assert_eq!(list.rows[0]["Bar"], Value::Null);
+        assert_eq!(list.rows[1]["Foo"], Value::Null);
+        assert_eq!(list.rows[1]["Bar"], 2);
+    }
+
+    #[test]
+    fn test_create_error_table() {
+        assert_eq!(
+            format!(
+                "{}",
+                create_error_table(CliError::new(
+                    "error",
+                    Some("message"),
+                    Some("details")
+                ))
+            ),
+            " Error     error   \n Message   message \n Details   details "
+        );
+        assert_eq!(
+            format!(
+                "{}",
+                create_error_table(CliError::new("error", None, Some("details")))
+            ),
+            " Error     error   \n Details   details "
+        );
+        assert_eq!(
+            format!(
+                "{}",
+                create_error_table(CliError::new("error", Some("message"), None))
+            ),
+            " Error     error   \n Message   message "
+        );
+        assert_eq!(
+            format!("{}", create_error_table(CliError::new("error", None, None))),
+            " Error   error "
+        );
+    }
+
+    #[test]
+    fn test_parse_tag_kv_pairs() {
+        assert_eq!(
+            parse_tag_kv_pairs(vec!["Foo=Bar", "Baz=42"]).unwrap(),
+            hashmap! {"Foo".to_string() => "Bar".to_string(), "Baz".to_string() => "42".to_string()}
+        )
+    }
+
+    #[test]
+    fn test_parse_tag_kv_pairs_error() {
+        assert_eq!(
+            parse_tag_kv_pairs(vec!["Foo=Bar", "Baz"]).unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Wrong option value 'Baz'"),
+                Some("Must be of format 'TagName=TagValue'"),
+            )
+        )
+    }
+
+    #[test]
+    fn test_parse_search_query() {
+        assert_eq!(
+            parse_search_query(
+                vec![
+                    "StudyDate=20200101-20201231",
+                    "StudyTime=-1200",
+                    "StudyDescription=*a=b*",
+                    "Modality=CT",
+                    "0008,0060=MR",
+                    "Modality=MR",
+                ],
+                true
+            )
+            .unwrap(),
+            SearchQuery {
+                tags: hashmap! {
+                    "StudyDate".to_string() => "20200101-20201231".to_string(),
+                    "StudyTime".to_string() => "-1200".to_string(),
+                    "StudyDescription".to_string() => "*a=b*".to_string(),
+                    "Modality".to_string() => "CT\\MR".to_string(),
+                    "0008,0060".to_string() => "MR".to_string(),
+                },
+                case_sensitive: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_search_query_error() {
+        let error = |terms: Vec<&str>| parse_search_query(terms, false).unwrap_err();
+        assert_eq!(
+            error(vec!["Modality"]),
+            CliError::new(
+                "Command error",
+                Some("Wrong query term 'Modality'"),
+                Some("Must be of format 'TagName=TagValue'"),
+            )
+        );
+        assert_eq!(
+            error(vec!["modality=MR"]),
+            CliError::new(
+                "Command error",
+                Some("Invalid tag in query term 'modality=MR'"),
+                Some("A tag is either a keyword (e.g. StudyDate) or gggg,eeee (e.g. 0008,0020)"),
+            )
+        );
+        for term in [
+            "StudyDate=2020",
+            "StudyDate=2020-01-01",
+            "StudyDate=-",
+            "StudyTime=12h",
+        ] {
+            assert_eq!(
+                error(vec![term]).message,
+                Some(format!(
+                    "Invalid {} in query term '{}'",
+                    if term.contains("Date") {
+                        "date"
+                    } else {
+                        "time"
+                    },
+                    term
+                ))
+            );
+        }
+        assert_eq!(
+            error(vec!["StudyDate=20201231-20200101"]),
+            CliError::new(
+                "Command error",
+                Some("Invalid date range in query term 'StudyDate=20201231-20200101'"),
+                Some("The start of the range is after its end"),
+            )
+        );
+        assert_eq!(
+            error(vec!["PatientName=Rick*", "PatientName=Morty"]),
+            CliError::new(
+                "Command error",
+                Some("Multiple values of PatientName with wildcards or ranges"),
+                Some("A tag with multiple values matches any of them exactly"),
+            )
+        );
+    }
+
+    #[test]
+    fn test_is_dicom_time() {
+        for t in ["12", "1230", "123059", "123059.5", "123059.123456"] {
+            assert!(is_dicom_time(t), "{}", t);
+        }
+        for t in ["1", "123", "12:30", "1230.5", "123059.1234567"] {
+            assert!(!is_dicom_time(t), "{}", t);
+        }
+    }
+
+    #[test]
+    fn test_get_anonymization_config_from_file() {
+        let mut file = fs::File::create("/tmp/anon_config.yml").unwrap();
+        file.write_all(b"{}").unwrap();
+        assert_eq!(
+            get_anonymization_config_from_file("/tmp/anon_config.yml").unwrap(),
+            Anonymization {
+                replace: None,
+                keep: None,
+                remove: None,
+                keep_private_tags: None,
+                force: Some(true),
+                dicom_version: None
+            }
+        )
+    }
+
+    #[test]
+    fn test_get_anonymization_config_from_file_file_not_found() {
+        assert_eq!(
+            get_anonymization_config_from_file("/tmp/anon_garble.yml").unwrap_err(),
+            CliError {
+                error: "No such file or directory (os error 2)".to_string(),
+                message: None,
+                details: None
+            }
+        )
+    }
+
+    #[test]
+    fn test_get_anonymization_config_from_file_yaml_parse_error() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "garble").unwrap();
+        assert_eq!(
+            get_anonymization_config_from_file(file.path().to_str().unwrap()).unwrap_err(),
+            CliError {
+                error: "invalid type: string \"garble\", expected struct Anonymization at line 1 column 1".to_string(),
+                message: None,
+                details: None
+            }
+        )
+    }
+
+    #[test]
+    fn test_get_anonymization_config_from_cmd_options() {
+        assert_eq!(
+            get_anonymization_config_from_cmd_options(
+                Some(vec!["Foo=Bar", "Baz=qux"]),
+                Some(vec!["Qux", "Quuz"]),
+                Some(true),
+                None,
+                Some("2017c")
+            )
+            .unwrap(),
+            Anonymization {
+                replace: Some(
+                    hashmap! {"Foo".to_string() => "Bar".to_string(), "Baz".to_string() => "qux".to_string()}
+                ),
+                keep: Some(vec!["Qux".to_string(), "Quuz".to_string()]),
+                remove: None,
+                keep_private_tags: Some(true),
+                dicom_version: Some("2017c".to_string()),
+                force: Some(true)
+            }
+        );
+
+        assert_eq!(
+            get_anonymization_config_from_cmd_options(None, None, None, None, None)
+                .unwrap(),
+            Anonymization {
+                replace: None,
+                keep: None,
+                remove: None,
+                keep_private_tags: None,
+                dicom_version: None,
+                force: Some(true)
+            }
+        )
+    }
+
+    #[test]
+    fn test_get_modification_config_from_cmd_options() {
+        assert_eq!(
+            get_modification_config_from_cmd_options(
+                Some(vec!["Foo=Bar", "Baz=qux"]),
+                Some(vec!["Qux", "Quuz"]),
+            )
+            .unwrap(),
+            Modification {
+                replace: Some(
+                    hashmap! {"Foo".to_string() => "Bar".to_string(), "Baz".to_string() => "qux".to_string()}
+                ),
+                remove: Some(vec!["Qux".to_string(), "Quuz".to_string()]),
+                force: Some(true)
+            }
+        );
+
+        assert_eq!(
+            get_modification_config_from_cmd_options(None, None).unwrap(),
+            Modification {
+                replace: None,
+                remove: None,
+                force: Some(true)
+            }
+        );
+    }
+
+    #[test]
+    fn test_get_modification_config_from_file() {
+        let mut file = fs::File::create("/tmp/mod_config.yml").unwrap();
+        file.write_all(b"{}").unwrap();
+        assert_eq!(
+            get_modification_config_from_file("/tmp/mod_config.yml").unwrap(),
+            Modification {
+                replace: None,
+                remove: None,
+                force: Some(true)
+            }
+        )
+    }
+
+    #[test]
+    fn test_get_modification_config_from_file_file_not_found() {
+        assert_eq!(
+            get_modification_config_from_file("/tmp/anon_garble.yml").unwrap_err(),
+            CliError {
+                error: "No such file or directory (os error 2)".to_string(),
+                message: None,
+                details: None
+            }
+        )
+    }
+
+    #[test]
+    fn test_get_modification_config_from_file_yaml_parse_error() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "garble").unwrap();
+        assert_eq!(
+            get_modification_config_from_file(file.path().to_str().unwrap()).unwrap_err(),
+            CliError {
+                error: "invalid type: string \"garble\", expected struct Modification at line 1 column 1".to_string(),
+                message: None,
+                details: None
+            }
+        )
+    }
+
+    #[test]
+    fn test_get_anonymization_config_conflicting_options() {
+        assert_eq!(
+            get_anonymization_config(
+                Some(vec!["Foo=Bar", "Baz=qux"]),
+                Some(vec!["Qux", "Quuz"]),
+                Some(true),
+                None,
+                None,
+                Some("/tmp/foo.yml")
+            )
+            .unwrap_err(),
+            CliError::new("Command error", Some("Conflicting options"), None)
+        );
+        assert_eq!(
+            get_anonymization_config(
+                None,
+                None,
+                None,
+                Some(vec!["retain-dates"]),
+                None,
+                Some("/tmp/foo.yml")
+            )
+            .unwrap_err(),
+            CliError::new("Command error", Some("Conflicting options"), None)
+        )
+    }
+
+    #[test]
+    fn test_get_anonymization_config_profiles() {
+        let config = get_anonymization_config(
+            Some(vec!["StudyDescription=Research"]),
+            Some(vec!["PatientSex", "StudyDate", "SeriesDescription"]),
+            None,
+            Some(vec![
+                "basic",
+                "retain-dates",
+                "retain-UIDs",
+                "clean-descriptions",
+            ]),
+            None,
+            None,
+        )
+        .unwrap()
+        .unwrap();
+        let keep = config.keep.unwrap();
+        assert_eq!(
+            keep.len(),
+            RETAIN_DATES_KEEP.len() + RETAIN_UIDS_KEEP.len() + 2
+        );
+        assert_eq!(keep[0], "StudyDate");
+        assert_eq!(keep[keep.len() - 2..], ["PatientSex", "SeriesDescription"]);
+        // Cleaned, unless kept (SeriesDescription) or replaced (StudyDescription) explicitly
+        let replace = config.replace.unwrap();
+        assert_eq!(replace.len(), CLEAN_DESCRIPTIONS_REPLACE.len() - 1);
+        assert_eq!(replace["StudyDescription"], "Research");
+        assert_eq!(replace["ProtocolName"], CLEANED_DESCRIPTION);
+        assert!(!replace.contains_key("SeriesDescription"));
+        assert_eq!(config.remove, None);
+        assert_eq!(config.force, Some(true));
+
+        // The descriptions, that the basic profile removes, are kept with a cleaned value
+        let config = get_anonymization_config(
+            None,
+            None,
+            None,
+            Some(vec!["clean-descriptions"]),
+            None,
+            None,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            config.replace.unwrap(),
+            CLEAN_DESCRIPTIONS_REPLACE
+                .iter()
+                .map(|t| (t.to_string(), CLEANED_DESCRIPTION.to_string()))
+                .collect()
+        );
+        assert_eq!(config.keep, None);
+
+        assert_eq!(
+            get_anonymization_config(None, None, None, Some(vec!["basic"]), None, None)
+                .unwrap(),
+            Some(Anonymization {
+                force: Some(true),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            get_anonymization_config(None, None, None, Some(vec!["foo"]), None, None)
+                .unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Unknown anonymization profile: foo"),
+                Some("Available profiles: basic, retain-dates, retain-device-identity, retain-uids, clean-descriptions"),
+            )
+        );
+    }
+
+    #[test]
+    fn test_anonymization_serialization() {
+        let config = Anonymization {
+            keep: Some(vec!["StudyDate".to_string()]),
+            remove: Some(vec!["StudyDescription".to_string()]),
+            dicom_version: Some("2021b".to_string()),
+            force: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(&config).unwrap(),
+            json!({
+                "Keep": ["StudyDate"],
+                "Remove": ["StudyDescription"],
+                "DicomVersion": "2021b",
+                "Force": true,
+            })
+        );
+    }
+
+    #[test]
+    fn test_get_modification_config_not_enough_options() {
+        assert_eq!(
+            get_modification_config(None, None, None).unwrap_err(),
+            CliError::new("Command error", Some("Not enough options"), None)
+        )
+    }
+
+    #[test]
+    fn test_get_modification_config_conflicting_options() {
+        assert_eq!(
+            get_modification_config(
+                Some(vec!["Foo=Bar", "Baz=qux"]),
+                Some(vec!["Qux", "Quuz"]),
+                Some("/tmp/foo.yml")
+            )
+            .unwrap_err(),
+            CliError::new("Command error", Some("Conflicting options"), None)
+        )
+    }
+
+    #[test]
+    fn test_get_server() {
+        let profile = Profile {
+            server: "qux".to_string(),
+            ..Default::default()
+        };
+        remove_var("ORC_ORTHANC_SERVER");
+        assert_eq!(
+            get_server_address(Some("foo"), None).unwrap(),
+            "foo".to_string()
+        );
+        assert_eq!(
+            get_server_address(None, None).unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Neither --server nor ORC_ORTHANC_SERVER are set"),
+                Some("environment variable not found"),
+            )
+        );
+        assert_eq!(
+            get_server_address(None, Some(&profile)).unwrap(),
+            "qux".to_string()
+        );
+        set_var("ORC_ORTHANC_SERVER", "bar");
+        assert_eq!(get_server_address(None, None).unwrap(), "bar".to_string());
+        assert_eq!(
+            get_server_address(None, Some(&profile)).unwrap(),
+            "bar".to_string()
+        );
+        assert_eq!(
+            get_server_address(Some("baz"), Some(&profile)).unwrap(),
+            "baz".to_string()
+        )
+    }
+
+    #[test]
+    fn test_get_username() {
+        let profile = Profile {
+            username: Some("qux".to_string()),
+            ..Default::default()
+        };
+        remove_var("ORC_ORTHANC_USERNAME");
+        assert_eq!(get_username(Some("foo"), None).unwrap(), "foo".to_string());
+        assert_eq!(get_username(None, None), None);
+        assert_eq!(
+            get_username(None, Some(&profile)).unwrap(),
+            "qux".to_string()
+        );
+        set_var("ORC_ORTHANC_USERNAME", "bar");
+        assert_eq!(
+            get_username(Some("foo"), Some(&profile)).unwrap(),
+            "foo".to_string()
+        );
+        assert_eq!(
+            get_username(None, Some(&profile)).unwrap(),
+            "bar".to_string()
+        );
+    }
+
+    #[test]
+    fn test_get_password() {
+        let profile = Profile {
+            password: Some("qux".to_string()),
+            ..Default::default()
+        };
+        remove_var("ORC_ORTHANC_PASSWORD");
+        assert_eq!(get_password(Some("foo"), None).unwrap(), "foo".to_string());
+        assert_eq!(get_password(None, None), None);
+        assert_eq!(
+            get_password(None, Some(&profile)).unwrap(),
+            "qux".to_string()
+        );
+        set_var("ORC_ORTHANC_PASSWORD", "bar");
+        assert_eq!(
+            get_password(Some("foo"), Some(&profile)).unwrap(),
+            "foo".to_string()
+        );
+        assert_eq!(
+            get_password(None, Some(&profile)).unwrap(),
+            "bar".to_string()
+        );
+    }
+
+    #[test]
+    fn test_get_token() {
+        let profile = Profile {
+            token: Some("qux".to_string()),
+            ..Default::default()
+        };
+        remove_var("ORC_ORTHANC_TOKEN");
+        assert_eq!(get_token(None, None), None);
+        assert_eq!(get_token(None, Some(&profile)).unwrap(), "qux".to_string());
+        set_var("ORC_ORTHANC_TOKEN", "bar");
+        assert_eq!(get_token(None, Some(&profile)).unwrap(), "bar".to_string());
+        assert_eq!(
+            get_token(Some("foo"), Some(&profile)).unwrap(),
+            "foo".to_string()
+        );
+    }
+
+    #[test]
+    fn test_get_headers() {
+        let profile = Profile {
+            headers: Some(vec!["X-Api-Key: qux".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(get_headers(None, None).unwrap(), vec![]);
+        assert_eq!(
+            get_headers(None, Some(&profile)).unwrap(),
+            vec![("X-Api-Key".to_string(), "qux".to_string())]
+        );
+        assert_eq!(
+            get_headers(Some(vec!["X-Foo:bar", "X-Baz: a:b "]), Some(&profile)).unwrap(),
+            vec![
+                ("X-Foo".to_string(), "bar".to_string()),
+                ("X-Baz".to_string(), "a:b".to_string())
+            ]
+        );
+        assert_eq!(
+            get_headers(Some(vec!["X-Foo"]), None).unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Invalid header: X-Foo"),
+                Some("Headers must be specified as Name:Value, e.g. X-Api-Key:secret"),
+            )
+        );
+        assert!(get_headers(Some(vec!["X Foo:bar"]), None).is_err());
+    }
+
+    #[test]
+    fn test_get_client_identity() {
+        for v in [
+            "ORC_CLIENT_CERT",
+            "ORC_CLIENT_KEY",
+            "ORC_CLIENT_PKCS12",
+            "ORC_CLIENT_PKCS12_PASSWORD",
+        ] {
+            remove_var(v);
+        }
+        assert_eq!(
+            get_client_identity(None, None, None, None, None).unwrap(),
+            None
+        );
+
+        let mut cert = NamedTempFile::new().unwrap();
+        cert.write_all(b"foo").unwrap();
+        let cert_path = cert.path().to_str().unwrap().to_string();
+        let mut key = NamedTempFile::new().unwrap();
+        key.write_all(b"bar").unwrap();
+        let key_path = key.path().to_str().unwrap().to_string();
+
+        let profile = Profile {
+            client_key: Some(key_path.clone()),
+            client_pkcs12_password: Some("baz".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            get_client_identity(Some(&cert_path), None, None, None, Some(&profile))
+                .unwrap(),
+            Some(ClientIdentity::Pem {
+                cert: b"foo".to_vec(),
+                key: b"bar".to_vec()
+            })
+        );
+        assert_eq!(
+            get_client_identity(None, None, Some(&cert_path), None, None).unwrap(),
+            Some(ClientIdentity::Pkcs12 {
+                archive: b"foo".to_vec(),
+                password: "".to_string()
+            })
+        );
+        set_var("ORC_CLIENT_PKCS12", &cert_path);
+        assert_eq!(
+            get_client_identity(None, None, None, None, Some(&Profile::default())).unwrap(),
+            Some(ClientIdentity::Pkcs12 {
+                archive: b"foo".to_vec(),
+                password: "".to_string()
+            })
+        );
+        assert_eq!(
+            get_client_identity(Some(&cert_path), None, None, None, Some(&profile))
+                .unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Both a client certificate and a PKCS#12 archive are specified"),
+                Some("Use either --client-cert and --client-key, or --client-pkcs12"),
+            )
+        );
+        remove_var("ORC_CLIENT_PKCS12");
+
+        assert_eq!(
+            get_client_identity(Some(&cert_path), None, None, None, None).unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Client certificate without a private key"),
+                Some("--client-cert and --client-key must be specified together"),
+            )
+        );
+        assert_eq!(
+            get_client_identity(None, Some(&key_path), None, None, None).unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Client private key without a certificate"),
+                Some("--client-cert and --client-key must be specified together"),
+            )
+        );
+        assert_eq!(
+            get_client_identity(
+                Some(&cert_path),
+                Some("/nonexistent/key.pem"),
+                None,
+                None,
+                None
+            )
+            .unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Could not read client key: /nonexistent/key.pem"),
+                Some("No such file or directory (os error 2)"),
+            )
+        );
+    }
+
+    #[test]
+    fn test_get_transport_options() {
+        for v in ["ORC_CA_CERT", "ORC_INSECURE", "ORC_TIMEOUT", "ORC_PROXY"] {
+            remove_var(v);
+        }
+        assert_eq!(
+            get_transport_options(None, None, false, None, None, None).unwrap(),
+            TransportOptions::default()
+        );
+
+        let mut ca_cert = NamedTempFile::new().unwrap();
+        ca_cert.write_all(b"foo").unwrap();
+        let ca_cert_path = ca_cert.path().to_str().unwrap().to_string();
+        let profile = Profile {
+            insecure: Some(true),
+            timeout: Some(30),
+            proxy: Some("http://proxy:3128".to_string()),
+            ..Default::default()
+        };
+        let identity = ClientIdentity::Pkcs12 {
+            archive: b"bar".to_vec(),
+            password: "baz".to_string(),
+        };
+        assert_eq!(
+            get_transport_options(
+                Some(&ca_cert_path),
+                Some(identity.clone()),
+                false,
+                Some("10"),
+                None,
+                Some(&profile)
+            )
+            .unwrap(),
+            TransportOptions {
+                ca_cert: Some(b"foo".to_vec()),
+                client_identity: Some(identity),
+                insecure: true,
+                timeout: Some(10),
+                proxy: Some("http://proxy:3128".to_string()),
+            }
+        );
+
+        set_var("ORC_INSECURE", "false");
+        set_var("ORC_TIMEOUT", "20");
+        let options =
+            get_transport_options(None, None, false, None, None, Some(&profile)).unwrap();
+        assert!(!options.insecure);
+        assert_eq!(options.timeout, Some(20));
+        remove_var("ORC_INSECURE");
+
+        set_var("ORC_TIMEOUT", "soon");
+        assert_eq!(
+            get_transport_options(None, None, false, None, None, None).unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Invalid timeout: soon"),
+                Some("The timeout must be a number of seconds"),
+            )
+        );
+        remove_var("ORC_TIMEOUT");
+
+        assert_eq!(
+            get_transport_options(
+                Some("/nonexistent/ca.pem"),
+                None,
+                false,
+                None,
+                None,
+                None
+            )
+            .unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Could not read CA certificate: /nonexistent/ca.pem"),
+                Some("No such file or directory (os error 2)"),
+            )
+        );
+    }
+
+    #[test]
+    fn test_check_columns_options() {
+        assert_eq!(
+            check_columns_option(&["foo", "bar", "baz"], &["foo", "baz"]).unwrap(),
+            ()
+        );
+
+        assert_eq!(
+            check_columns_option(&["foo", "bar", "baz"], &["bar", "qux"]).unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Invalid column name: qux. Available columns: foo, bar, baz"),
+                None
+            )
+        )
+    }
+
+    #[test]
+    fn test_get_header_and_dicom_tags() {
+        let mut header = vec!["foo", "bar", "baz", "qux", "quux", "quuz"];
+        let mut dicom_tags = vec!["qux", "quux", "quuz"];
+
+        get_header_and_dicom_tags(
+            &mut header,
+            &mut dicom_tags,
+            Some(vec!["bar", "quux", "quuz"]),
+        )
+        .unwrap();
+
+        assert_eq!(header, vec!["bar", "quux", "quuz"]);
+        assert_eq!(dicom_tags, vec!["quux", "quuz"]);
+    }
+
+    #[test]
+    fn test_get_header_and_dicom_tags_extra_tags() {
+        let mut header = vec!["ID", "PatientID", "PatientName", "Number of Studies"];
+        let mut dicom_tags = vec!["PatientID", "PatientName"];
+
+        get_header_and_dicom_tags(
+            &mut header,
+            &mut dicom_tags,
+            Some(vec!["InstitutionName", "PatientName", "ID", "0010,0030"]),
+        )
+        .unwrap();
+
+        assert_eq!(
+            header,
+            vec!["ID", "PatientName", "InstitutionName", "0010,0030"]
+        );
+        assert_eq!(
+            dicom_tags,
+            vec!["PatientName", "InstitutionName", "0010,0030"]
+        );
+    }
+
+    #[test]
+    fn test_get_header_and_dicom_tags_invalid_column() {
+        let mut header = vec!["ID", "PatientID"];
+        let mut dicom_tags = vec!["PatientID"];
+
+        assert_eq!(
+            get_header_and_dicom_tags(
+                &mut header,
+                &mut dicom_tags,
+                Some(vec!["ID", "Number of foos"])
+            )
+            .unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Invalid column name: Number of foos. Available columns: ID, PatientID"),
+                Some("Any DICOM tag can be used as a column, either by keyword (e.g. PatientBirthDate) or as gggg,eeee (e.g. 0010,0030)"),
+            )
+        );
+    }
+
+    #[test]
+    fn test_is_dicom_tag() {
+        assert!(is_dicom_tag("PatientBirthDate"));
+        assert!(is_dicom_tag("0010,0030"));
+        assert!(is_dicom_tag("7FE0,0010"));
+        assert!(!is_dicom_tag("patientBirthDate"));
+        assert!(!is_dicom_tag("Patient Birth Date"));
+        assert!(!is_dicom_tag("0010,030"));
+        assert!(!is_dicom_tag("0010,003X"));
+        assert!(!is_dicom_tag(""));
+    }
+
+    #[test]
+    fn test_find_tag_value() {
+        let tags = serde_json::json!({
+            "0008,0080": {"Name": "InstitutionName", "Type": "String", "Value": "Hospital"},
+            "0010,1002": {"Name": "OtherPatientIDsSequence", "Type": "Sequence", "Value": []},
+        });
+        assert_eq!(
+            find_tag_value(&tags, "InstitutionName"),
+            Value::from("Hospital")
+        );
+        assert_eq!(find_tag_value(&tags, "0008,0080"), Value::from("Hospital"));
+        assert_eq!(
+            find_tag_value(&tags, "OtherPatientIDsSequence"),
+            Value::Null
+        );
+        assert_eq!(find_tag_value(&tags, "PatientBirthDate"), Value::Null);
+        assert_eq!(
+            find_tag_value(&Value::Null, "PatientBirthDate"),
+            Value::Null
+        );
+    }
+
+    #[test]
+    fn test_requested_tag_value() {
+        let tags = serde_json::json!({
+            "InstitutionName": "Hospital",
+            "OtherPatientIDsSequence": [],
+            "0009,0010": "Private",
+        });
+        assert_eq!(
+            requested_tag_value(&tags, "InstitutionName"),
+            Value::from("Hospital")
+        );
+        assert_eq!(
+            requested_tag_value(&tags, "0008,0080"),
+            Value::from("Hospital")
+        );
+        assert_eq!(
+            requested_tag_value(&tags, "0009,0010"),
+            Value::from("Private")
+        );
+        assert_eq!(
+            requested_tag_value(&tags, "OtherPatientIDsSequence"),
+            Value::Null
+        );
+        assert_eq!(requested_tag_value(&tags, "PatientBirthDate"), Value::Null);
+    }
+
+    #[test]
+    fn test_tag_keyword() {
+        assert_eq!(tag_keyword("0010,0030"), "PatientBirthDate");
+        assert_eq!(tag_keyword("0010,0030"), tag_keyword("PatientBirthDate"));
+        assert_eq!(tag_keyword("0009,0010"), "0009,0010");
+        assert_eq!(tag_keyword("zzzz,0010"), "zzzz,0010");
+    }
+
+    #[test]
+    fn test_find_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("b/c")).unwrap();
+        fs::write(dir.path().join("b/c/3.dcm"), b"").unwrap();
+        fs::write(dir.path().join("b/2.dcm"), b"").unwrap();
+        fs::write(dir.path().join("a.dcm"), b"").unwrap();
+        let found = |path: &Path| -> Vec<(PathBuf, bool)> {
+            find_files(path)
+                .into_iter()
+                .map(|(p, r)| (p, r.is_ok()))
+                .collect()
+        };
+
+        assert_eq!(
+            found(dir.path()),
+            vec![
+                (dir.path().join("a.dcm"), true),
+                (dir.path().join("b/2.dcm"), true),
+                (dir.path().join("b/c/3.dcm"), true),
+            ]
+        );
+        assert_eq!(
+            found(&dir.path().join("a.dcm")),
+            vec![(dir.path().join("a.dcm"), true)]
+        );
+        assert_eq!(
+            found(&dir.path().join("garble.dcm")),
+            vec![(dir.path().join("garble.dcm"), false)]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_files_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("b")).unwrap();
+        fs::write(dir.path().join("b/2.dcm"), b"").unwrap();
+        // A loop, a dangling link, that fails on its own, and a file after them
+        symlink(dir.path(), dir.path().join("b/loop")).unwrap();
+        symlink(dir.path().join("garble"), dir.path().join("b/broken")).unwrap();
+        fs::write(dir.path().join("c.dcm"), b"").unwrap();
+
+        let found: Vec<(PathBuf, bool)> = find_files(dir.path())
+            .into_iter()
+            .map(|(p, r)| (p, r.is_ok()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (dir.path().join("b/2.dcm"), true),
+                (dir.path().join("b/broken"), false),
+                (dir.path().join("c.dcm"), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_zip_archive() {
+        assert!(is_zip_archive(b"PK\x03\x04foo"));
+        assert!(!is_zip_archive(b"DICM"));
+        assert!(!is_zip_archive(b""));
+    }
+
+    #[test]
+    fn test_create_upload_row() {
+        let row = create_upload_row(
+            "foo.dcm",
+            Ok(UploadResult {
+                id: "foo".to_string(),
+                status: "AlreadyStored".to_string(),
+                path: "/instances/foo".to_string(),
+                parent_patient: "patient".to_string(),
+                parent_study: "study".to_string(),
+                parent_series: "series".to_string(),
+            }),
+        );
+        assert_eq!(
+            Value::Object(row),
+            serde_json::json!({
+                "File": "foo.dcm",
+                "Status": "Already stored",
+                "ID": "foo",
+                "Series ID": "series",
+                "Study ID": "study",
+                "Patient ID": "patient",
+                "Error": null
+            })
+        );
+
+        let row = create_upload_row(
+            "bar.dcm",
+            Err(CliError::new(
+                "API error: 400 Bad Request",
+                Some("Bad file format"),
+                None,
+            )),
+        );
+        assert_eq!(row["Status"], "Failed");
+        assert_eq!(row["ID"], Value::Null);
+        assert_eq!(row["Error"], "Bad file format");
+    }
+
+    #[test]
+    fn test_check_upload_results() {
+        let ok = create_upload_row(
+            "foo.dcm",
+            Ok(UploadResult {
+                id: "foo".to_string(),
+                status: "Success".to_string(),
+                path: "/instances/foo".to_string(),
+                parent_patient: "patient".to_string(),
+                parent_study: "study".to_string(),
+                parent_series: "series".to_string(),
+            }),
+        );
+        let failed = create_upload_row("bar.dcm", Err(CliError::new("error", None, None)));
+        let columns: Vec<String> = UPLOAD_RESULTS_HEADER
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+
+        assert_eq!(ok["Status"], "New");
+        assert_eq!(
+            check_upload_results(&EntityList {
+                columns: columns.clone(),
+                rows: vec![ok.clone()]
+            }),
+            Ok(())
+        );
+        assert_eq!(
+            check_upload_results(&EntityList {
+                columns,
+                rows: vec![ok, failed]
+            })
+            .unwrap_err(),
+            CliError::new("Upload error", Some("1 of 2 files failed to upload"), None)
+        );
+    }
+
+    #[test]
+    fn test_get_output_format() {
+        assert_eq!(get_output_format(None).unwrap(), OutputFormat::Table);
+        assert_eq!(
+            get_output_format(Some("table")).unwrap(),
+            OutputFormat::Table
+        );
+        assert_eq!(get_output_format(Some("json")).unwrap(), OutputFormat::Json);
+        assert_eq!(get_output_format(Some("yaml")).unwrap(), OutputFormat::Yaml);
+        assert_eq!(get_output_format(Some("csv")).unwrap(), OutputFormat::Csv);
+        assert_eq!(get_output_format(Some("tsv")).unwrap(), OutputFormat::Tsv);
+        assert_eq!(
+            get_output_format(Some("xml")).unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Unknown output format: xml"),
+                Some("Must be one of table, json, yaml, csv, tsv"),
+            )
+        );
+    }
+
+    fn entity_list() -> EntityList {
+        let mut foo = Row::new();
+        foo.insert("ID".to_string(), Value::from("foo"));
+        foo.insert("PatientName".to_string(), Value::from("Sanchez, Rick"));
+        foo.insert("Number of Studies".to_string(), Value::from(2));
+        let mut bar = Row::new();
+        bar.insert("ID".to_string(), Value::from("bar"));
+        bar.insert("PatientName".to_string(), Value::Null);
+        bar.insert("Number of Studies".to_string(), Value::from(1));
+        EntityList {
+            columns: vec![
+                "ID".to_string(),
+                "PatientName".to_string(),
+                "Number of Studies".to_string(),
+            ],
+            rows: vec![foo, bar],
+        }
+    }
+
+    fn entity_details() -> EntityDetails {
+        let mut fields = Row::new();
+        fields.insert("ID".to_string(), Value::from("foo"));
+        fields.insert("PatientName".to_string(), Value::from("Smith\tMorty"));
+        EntityDetails { fields }
+    }
+
+    #[test]
+    fn test_render_table() {
+        assert_eq!(
+            render(&entity_details(), false, &OutputFormat::Table).unwrap(),
+            " ID            foo        \n PatientName   Smith\tMorty "
+        );
+    }
+
+    #[test]
+    fn test_render_json() {
+        assert_eq!(
+            render(&entity_list(), false, &OutputFormat::Json).unwrap(),
+            r#"[
+  {
+    "ID": "foo",
+    "PatientName": "Sanchez, Rick",
+    "Number of Studies": 2
+  },
+  {
+    "ID": "bar",
+    "PatientName": null,
+    "Number of Studies": 1
+  }
+]"#
+        );
+        assert_eq!(
+            render(&entity_details(), false, &OutputFormat::Json).unwrap(),
+            r#"{
+  "ID": "foo",
+  "PatientName": "Smith\tMorty"
+}"#
+        );
+        assert_eq!(
+            render(
+                &EntityList {
+                    columns: vec!["ID".to_string()],
+                    rows: vec![]
+                },
+                true,
+                &OutputFormat::Json
+            )
+            .unwrap(),
+            "[]"
+        );
+    }
+
+    #[test]
+    fn test_render_json_instance_tags() {
+        let tags = vec![InstanceTag {
+            tag: "0010,0010".to_string(),
+            name: "PatientName".to_string(),
+            kind: "String".to_string(),
+            value: TagValue::String("Rick".to_string()),
+        }];
+        assert_eq!(
+            render(&tags, false, &OutputFormat::Json).unwrap(),
+            r#"[
+  {
+    "Tag": "0010,0010",
+    "Name": "PatientName",
+    "Type": "String",
+    "Value": "Rick"
+  }
+]"#
+        );
+    }
+
+    fn instance_tags() -> Vec<InstanceTag> {
+        InstanceTag::from_expanded_tags(&json!({
+            "0008,0016": {"Name": "SOPClassUID", "Type": "String", "Value": "1.2.840.10008.5.1.4.1.1.2"},
+            "0008,1140": {
+                "Name": "ReferencedImageSequence",
+                "Type": "Sequence",
+                "Value": [
+                    {"0008,1155": {"Name": "ReferencedSOPInstanceUID", "Type": "String", "Value": "1.2.3"}},
+                    {"0008,1155": {"Name": "ReferencedSOPInstanceUID", "Type": "String", "Value": "4.5.6"}}
+                ]
+            },
+            "0010,0010": {"Name": "PatientName", "Type": "String", "Value": "Rick"},
+            "0010,0030": {"Name": "PatientBirthDate", "Type": "Null", "Value": null},
+            "0028,1201": {"Name": "RedPaletteColorLookupTableData", "Type": "Binary", "Value": null},
+            "7fe0,0010": {"Name": "PixelData", "Type": "TooLong"}
+        }))
     }
 
     #[test]
-    fn test_create_table_with_header() {
-        let table = create_table(Some(&["Foo", "bar"]));
-        assert_eq!(&format!("{}", table), " Foo   bar \n-----------");
+    fn test_render_instance_tags_table() {
+        assert_eq!(
+            render(&instance_tags(), false, &OutputFormat::Csv).unwrap(),
+            concat!(
+                "\"0008,0016\",SOPClassUID,1.2.840.10008.5.1.4.1.1.2\n",
+                "\"0008,1140\",ReferencedImageSequence,<sequence: 2 items>\n",
+                "  [0],,\n",
+                "\"    0008,1155\",ReferencedSOPInstanceUID,1.2.3\n",
+                "  [1],,\n",
+                "\"    0008,1155\",ReferencedSOPInstanceUID,4.5.6\n",
+                "\"0010,0010\",PatientName,Rick\n",
+                "\"0010,0030\",PatientBirthDate,\n",
+                "\"0028,1201\",RedPaletteColorLookupTableData,<binary>\n",
+                "\"7fe0,0010\",PixelData,<too long>\n",
+            )
+        );
     }
 
     #[test]
-    fn test_create_table_no_header() {
-        let table = create_table(None);
-        assert_eq!(&format!("{}", table), "");
+    fn test_filter_instance_tags() {
+        let tags = |groups, names| {
+            filter_instance_tags(instance_tags(), groups, names)
+                .into_iter()
+                .map(|t| t.tag)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(tags(None, None).len(), 6);
+        assert_eq!(tags(Some(vec!["0010"]), None), ["0010,0010", "0010,0030"]);
+        assert_eq!(
+            tags(Some(vec!["7FE0"]), Some(vec!["SOPClassUID", "0008,1140"])),
+            ["0008,0016", "0008,1140", "7fe0,0010"]
+        );
+        assert_eq!(tags(None, Some(vec!["ReferencedSOPInstanceUID"])).len(), 0);
     }
 
-    fn test_list_table_patient(
-        columns: &[&str],
-        dicom_tags: &[&str],
-        no_header: bool,
-        expected_output: &str,
-    ) {
-        let patient_1 = Patient {
-            id: "foo".to_string(),
-            is_stable: true,
-            last_update: NaiveDate::from_ymd(2020, 1, 1).and_hms(15, 46, 17),
-            main_dicom_tags: hashmap! {
-                "PatientID".to_string() => "foo_id".to_string(),
-                "PatientName".to_string() => "Rick Sanchez".to_string(),
-            },
-            studies: ["study_1".to_string()].to_vec(),
-            entity: EntityKind::Patient,
-            anonymized_from: None,
-        };
-        let patient_2 = Patient {
-            id: "bar".to_string(),
-            is_stable: true,
-            last_update: NaiveDate::from_ymd(2020, 1, 1).and_hms(15, 46, 17),
-            main_dicom_tags: hashmap! {
-                "PatientName".to_string() => "Morty Smith".to_string(),
-            },
-            studies: ["study_1".to_string(), "study_2".to_string()].to_vec(),
-            entity: EntityKind::Patient,
-            anonymized_from: None,
-        };
+    #[test]
+    fn test_check_tag_filters() {
+        assert!(check_tag_filters(&None, &None).is_ok());
+        assert!(check_tag_filters(
+            &Some(vec!["7FE0"]),
+            &Some(vec!["PatientName", "0010,0010"])
+        )
+        .is_ok());
         assert_eq!(
-            format_table(create_list_table(
-                vec![patient_1, patient_2],
-                &columns,
-                &dicom_tags,
-                no_header,
-            )),
-            expected_output
+            check_tag_filters(&Some(vec!["0010", "10"]), &None).unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Invalid tag group: 10"),
+                Some("A tag group consists of 4 hexadecimal digits (e.g. 0010)"),
+            )
         );
+        assert!(check_tag_filters(&None, &Some(vec!["patient-name"])).is_err());
     }
 
-    fn test_list_table_study(
-        columns: &[&str],
-        dicom_tags: &[&str],
-        no_header: bool,
-        expected_output: &str,
-    ) {
-        let study_1 = Study {
-            id: "foo".to_string(),
-            is_stable: true,
-            last_update: NaiveDate::from_ymd(2020, 8, 30).and_hms(19, 11, 09),
-            main_dicom_tags: hashmap! {
-                "AccessionNumber".to_string() => "foo_an".to_string(),
-                "StudyInstanceUID".to_string() => "foo_suid".to_string(),
-                "StudyDescription".to_string() => "foo_sd".to_string(),
-            },
-            parent_patient: "patient_foo".to_string(),
-            patient_main_dicom_tags: hashmap! {
-                "PatientName".to_string() => "Rick Sanchez".to_string(),
-            },
-            series: ["foo_series_1".to_string(), "foo_series_2".to_string()].to_vec(),
-            entity: EntityKind::Study,
-            anonymized_from: None,
-        };
-        let study_2 = Study {
-            id: "bar".to_string(),
-            is_stable: true,
-            last_update: NaiveDate::from_ymd(2020, 12, 30).and_hms(10, 10, 0),
-            main_dicom_tags: hashmap! {
-                "PatientID".to_string() => "bar_pid".to_string(),
-                "StudyDate".to_string() => "20200101".to_string(),
-                "StudyTime".to_string() => "190001".to_string(),
-            },
-            parent_patient: "patient_bar".to_string(),
-            patient_main_dicom_tags: hashmap! {
-                "PatientName".to_string() => "Rick Sanchez".to_string(),
-            },
-            series: ["bar_series_1".to_string()].to_vec(),
-            entity: EntityKind::Study,
-            anonymized_from: None,
-        };
+    fn job() -> Job {
+        serde_json::from_value(json!({
+            "ID": "c2d8b0c1-2b7a-4a9e-8c3d-3f1e6b2a9d10",
+            "Type": "ResourceModification",
+            "State": "Running",
+            "Progress": 45,
+            "Priority": 0,
+            "CreationTime": "20210330T101516.123456",
+            "EffectiveRuntime": 1.5,
+            "ErrorCode": 0,
+            "ErrorDescription": "Success",
+            "Content": {"Description": "REST API"},
+            "Timestamp": "20210330T101517.654321"
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_job_row() {
         assert_eq!(
-            format_table(create_list_table(
-                vec![study_1, study_2],
-                &columns,
-                &dicom_tags,
-                no_header,
-            )),
-            expected_output
+            Value::Object(job_row(job())),
+            json!({
+                "ID": "c2d8b0c1-2b7a-4a9e-8c3d-3f1e6b2a9d10",
+                "Type": "ResourceModification",
+                "State": "Running",
+                "Progress": 45,
+                "Priority": 0,
+                "Creation time": "20210330T101516.123456",
+                "Completion time": null,
+                "Effective runtime": 1.5,
+                "Error code": 0,
+                "Error description": "Success",
+                "Content": {"Description": "REST API"}
+            })
         );
     }
 
-    fn test_list_table_series(
-        columns: &[&str],
-        dicom_tags: &[&str],
-        no_header: bool,
-        expected_output: &str,
-    ) {
-        let series_1 = Series {
-            id: "foo".to_string(),
-            status: "Unknown".to_string(),
-            is_stable: true,
-            last_update: NaiveDate::from_ymd(2020, 8, 30).and_hms(19, 11, 09),
-            main_dicom_tags: hashmap! {
-                "BodyPartExamined".to_string() => "ABDOMEN".to_string(),
-                "SeriesDescription".to_string() => "Foo".to_string(),
-            },
-            parent_study: "study_1".to_string(),
-            expected_number_of_instances: Some(17),
-            instances: ["i1".to_string(), "i2".to_string()].to_vec(),
-            entity: EntityKind::Series,
-            anonymized_from: None,
-        };
-        let series_2 = Series {
-            id: "bar".to_string(),
-            status: "Known".to_string(),
-            is_stable: true,
-            last_update: NaiveDate::from_ymd(2021, 8, 30).and_hms(19, 12, 09),
-            main_dicom_tags: hashmap! {
-                "BodyPartExamined".to_string() => "KNEE".to_string(),
-                "Modality".to_string() => "CT".to_string(),
-            },
-            parent_study: "study_2".to_string(),
-            expected_number_of_instances: Some(17),
-            instances: ["i3".to_string(), "i4".to_string()].to_vec(),
-            entity: EntityKind::Series,
-            anonymized_from: None,
-        };
+    #[test]
+    fn test_format_job_progress() {
+        let mut job = job();
         assert_eq!(
-            format_table(create_list_table(
-                vec![series_1, series_2],
-                &columns,
-                &dicom_tags,
-                no_header,
-            )),
-            expected_output
+            format_job_progress(&job),
+            "[#########-----------]  45% Running"
+        );
+        job.progress = 100;
+        job.state = "Success".to_string();
+        assert_eq!(
+            format_job_progress(&job),
+            "[####################] 100% Success"
+        );
+        job.progress = 0;
+        job.state = "Pending".to_string();
+        assert_eq!(
+            format_job_progress(&job),
+            "[--------------------]   0% Pending"
         );
     }
-    fn test_list_table_instance(
-        columns: &[&str],
-        dicom_tags: &[&str],
-        no_header: bool,
-        expected_output: &str,
-    ) {
-        let instance_1 = Instance {
+
+    #[test]
+    fn test_render_outcome() {
+        let submitted: Outcome<ModificationResult> = Outcome::Submitted(JobSubmission {
             id: "foo".to_string(),
-            main_dicom_tags: hashmap! {
-                "SOPInstanceUID".to_string() => "suid_1".to_string(),
-                "InstanceNumber".to_string() => "in_1".to_string(),
-            },
-            parent_series: "foo_series".to_string(),
-            index_in_series: Some(13),
-            file_uuid: "file_uuid".to_string(),
-            file_size: 139402,
-            modified_from: None,
-            entity: EntityKind::Instance,
-            anonymized_from: None,
-        };
-        let instance_2 = Instance {
-            id: "bar".to_string(),
-            main_dicom_tags: hashmap! {
-                "SOPInstanceUID".to_string() => "suid_2".to_string(),
-                "InstanceCreationDate".to_string() => "19000101".to_string(),
-                "InstanceCreationTime".to_string() => "000000".to_string(),
-            },
-            parent_series: "foo_series".to_string(),
-            index_in_series: Some(13),
-            file_uuid: "file_uuid".to_string(),
-            file_size: 139402,
-            modified_from: None,
-            entity: EntityKind::Instance,
-            anonymized_from: None,
-        };
+            path: "/jobs/foo".to_string(),
+        });
         assert_eq!(
-            format_table(create_list_table(
-                vec![instance_1, instance_2],
-                &columns,
-                &dicom_tags,
-                no_header,
-            )),
-            expected_output
+            render(&submitted, false, &OutputFormat::Csv).unwrap(),
+            "Job ID,foo\n"
+        );
+        assert_eq!(
+            render(&submitted, false, &OutputFormat::Json).unwrap(),
+            "{\n  \"ID\": \"foo\",\n  \"Path\": \"/jobs/foo\"\n}"
         );
     }
 
     #[test]
-    fn test_create_list_table_patient() {
-        test_list_table_patient(
-            PATIENTS_LIST_HEADER,
-            PATIENTS_LIST_DICOM_TAGS,
-            false,
-            include_str!("../tests/data/unit/list_patients").trim_end(),
+    fn test_render_yaml() {
+        assert_eq!(
+            render(&entity_list(), false, &OutputFormat::Yaml).unwrap(),
+            concat!(
+                "---\n",
+                "- ID: foo\n",
+                "  PatientName: \"Sanchez, Rick\"\n",
+                "  Number of Studies: 2\n",
+                "- ID: bar\n",
+                "  PatientName: ~\n",
+                "  Number of Studies: 1\n",
+            )
+        );
+        assert_eq!(
+            render(&entity_details(), false, &OutputFormat::Yaml).unwrap(),
+            "---\nID: foo\nPatientName: \"Smith\\tMorty\"\n"
         );
     }
 
     #[test]
-    fn test_create_list_table_patient_no_header() {
-        test_list_table_patient(
-            PATIENTS_LIST_HEADER,
-            PATIENTS_LIST_DICOM_TAGS,
-            true,
-            include_str!("../tests/data/unit/list_patients_no_header").trim_end(),
+    fn test_render_csv() {
+        assert_eq!(
+            render(&entity_list(), false, &OutputFormat::Csv).unwrap(),
+            "ID,PatientName,Number of Studies\nfoo,\"Sanchez, Rick\",2\nbar,undefined,1\n"
+        );
+        assert_eq!(
+            render(&entity_list(), true, &OutputFormat::Csv).unwrap(),
+            "foo,\"Sanchez, Rick\",2\nbar,undefined,1\n"
+        );
+        assert_eq!(
+            render(&entity_details(), false, &OutputFormat::Csv).unwrap(),
+            "ID,foo\nPatientName,Smith\tMorty\n"
         );
     }
 
     #[test]
-    fn test_create_list_table_patient_columns() {
-        test_list_table_patient(
-            &["PatientID", "Number of Studies"],
-            &["PatientID"],
-            false,
-            include_str!("../tests/data/unit/list_patients_columns").trim_end(),
+    fn test_render_tsv() {
+        assert_eq!(
+            render(&entity_details(), false, &OutputFormat::Tsv).unwrap(),
+            "ID\tfoo\nPatientName\t\"Smith\tMorty\"\n"
         );
     }
 
     #[test]
-    fn test_create_list_table_patient_columns_no_header() {
-        test_list_table_patient(
-            &["PatientID", "Number of Studies"],
-            &["PatientID"],
-            true,
-            include_str!("../tests/data/unit/list_patients_columns_no_header").trim_end(),
+    fn test_render_modification_result() {
+        let res = ModificationResult {
+            id: "foobar".to_string(),
+            patient_id: "bazqux".to_string(),
+            path: "long_and_rocky".to_string(),
+            entity: EntityKind::Study,
+        };
+        assert_eq!(
+            render(&res, false, &OutputFormat::Csv).unwrap(),
+            "New Study ID,foobar\nPatient ID,bazqux\n"
         );
     }
 
     #[test]
-    fn test_create_list_table_study() {
-        test_list_table_study(
-            STUDIES_LIST_HEADER,
-            STUDIES_LIST_DICOM_TAGS,
-            false,
-            include_str!("../tests/data/unit/list_studies").trim_end(),
+    fn test_get_header_and_dicom_tags_no_columns() {
+        let mut header = vec!["foo", "bar", "baz"];
+        let mut dicom_tags = vec!["qux", "quux", "quuz"];
+
+        get_header_and_dicom_tags(&mut header, &mut dicom_tags, None).unwrap();
+
+        assert_eq!(header, vec!["foo", "bar", "baz"]);
+        assert_eq!(dicom_tags, vec!["qux", "quux", "quuz"]);
+    }
+
+    #[test]
+    fn test_set_dimse_permissions() {
+        let mut config = ModalityConfig {
+            allow_c_echo: Some(false),
+            allow_c_get: Some(true),
+            ..Default::default()
+        };
+        set_dimse_permissions(
+            &mut config,
+            Some(vec!["C-ECHO", "N-EVENT-REPORT"]),
+            Some(vec!["C-GET"]),
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            ModalityConfig {
+                allow_c_echo: Some(true),
+                allow_c_get: Some(false),
+                allow_n_event_report: Some(true),
+                ..Default::default()
+            }
+        );
+        set_dimse_permissions(&mut config, None, None).unwrap();
+        assert_eq!(config.allow_c_find, None);
+    }
+
+    #[test]
+    fn test_set_dimse_permissions_conflict() {
+        assert_eq!(
+            set_dimse_permissions(
+                &mut ModalityConfig::default(),
+                Some(vec!["C-FIND", "C-MOVE"]),
+                Some(vec!["C-MOVE"]),
+            )
+            .unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Service both allowed and denied: C-MOVE"),
+                None,
+            )
+        );
+        assert_eq!(
+            set_dimse_permissions(
+                &mut ModalityConfig::default(),
+                Some(vec!["C-FOO"]),
+                None
+            )
+            .unwrap_err(),
+            CliError::new("Command error", Some("Unknown DIMSE service: C-FOO"), None)
         );
     }
 
     #[test]
-    fn test_create_list_table_study_no_header() {
-        test_list_table_study(
-            STUDIES_LIST_HEADER,
-            STUDIES_LIST_DICOM_TAGS,
-            true,
-            include_str!("../tests/data/unit/list_studies_no_header").trim_end(),
+    fn test_read_modalities_file() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            "foo:\n  AET: FOO\n  Host: 1.2.3.4\n  Port: 104\n  AllowGet: false\n  UseDicomTls: true"
+        )
+        .unwrap();
+        let modalities = read_modalities_file(file.path()).unwrap();
+        assert_eq!(modalities.len(), 1);
+        assert_eq!(
+            modalities["foo"],
+            ModalityConfig {
+                aet: Some("FOO".to_string()),
+                host: Some("1.2.3.4".to_string()),
+                port: Some(104),
+                allow_c_get: Some(false),
+                use_dicom_tls: Some(true),
+                ..Default::default()
+            }
         );
     }
 
     #[test]
-    fn test_create_list_table_series() {
-        test_list_table_series(
-            SERIES_LIST_HEADER,
-            SERIES_LIST_DICOM_TAGS,
-            false,
-            include_str!("../tests/data/unit/list_series").trim_end(),
+    fn test_read_modalities_file_invalid() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "foo:\n  AET: FOO\n  Host: 1.2.3.4\n  Prot: 104").unwrap();
+        assert_eq!(
+            read_modalities_file(file.path()).unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Unknown setting of modality foo: Prot"),
+                None,
+            )
+        );
+
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "foo:\n  AET: FOO\n  Host: 1.2.3.4").unwrap();
+        assert_eq!(
+            read_modalities_file(file.path()).unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Incomplete settings of modality foo"),
+                Some("AET, Host and Port are required"),
+            )
         );
     }
 
     #[test]
-    fn test_create_list_table_series_no_header() {
-        test_list_table_series(
-            SERIES_LIST_HEADER,
-            SERIES_LIST_DICOM_TAGS,
-            true,
-            include_str!("../tests/data/unit/list_series_no_header").trim_end(),
+    fn test_query_answer_row() {
+        let answer = json!({
+            "0010,0020": {"Name": "PatientID", "Type": "String", "Value": "P1"},
+            "0020,000d": {"Name": "StudyInstanceUID", "Type": "String", "Value": "1.2.3"}
+        });
+        assert_eq!(
+            Value::from(query_answer_row(
+                2,
+                &answer,
+                &["PatientID", "StudyInstanceUID", "StudyDate"]
+            )),
+            json!({
+                "Index": 2,
+                "PatientID": "P1",
+                "StudyInstanceUID": "1.2.3",
+                "StudyDate": null
+            })
         );
     }
 
     #[test]
-    fn test_create_list_table_instance() {
-        test_list_table_instance(
-            INSTANCES_LIST_HEADER,
-            INSTANCES_LIST_DICOM_TAGS,
-            false,
-            include_str!("../tests/data/unit/list_instances").trim_end(),
+    fn test_retrieve_uids() {
+        assert_eq!(
+            retrieve_uids("1.2.3", None),
+            (
+                "Study",
+                hashmap! {"StudyInstanceUID".to_string() => "1.2.3".to_string()}
+            )
+        );
+        assert_eq!(
+            retrieve_uids("1.2.3", Some("1.2.3.4")),
+            (
+                "Series",
+                hashmap! {
+                    "StudyInstanceUID".to_string() => "1.2.3".to_string(),
+                    "SeriesInstanceUID".to_string() => "1.2.3.4".to_string(),
+                }
+            )
         );
     }
 
     #[test]
-    fn test_create_list_table_instance_no_header() {
-        test_list_table_instance(
-            INSTANCES_LIST_HEADER,
-            INSTANCES_LIST_DICOM_TAGS,
-            true,
-            include_str!("../tests/data/unit/list_instances_no_header").trim_end(),
+    fn test_query_answer_uids() {
+        let answer = json!({
+            "0008,0052": {"Name": "QueryRetrieveLevel", "Type": "String", "Value": "SERIES"},
+            "0010,0020": {"Name": "PatientID", "Type": "String", "Value": "P1"},
+            "0020,000d": {"Name": "StudyInstanceUID", "Type": "String", "Value": "1.2.3"},
+            "0020,000e": {"Name": "SeriesInstanceUID", "Type": "String", "Value": "1.2.3.4"}
+        });
+        assert_eq!(
+            query_answer_uids(&answer),
+            hashmap! {
+                "StudyInstanceUID".to_string() => "1.2.3".to_string(),
+                "SeriesInstanceUID".to_string() => "1.2.3.4".to_string(),
+            }
+        );
+        let answer = json!({
+            "0008,0052": {"Name": "QueryRetrieveLevel", "Type": "String", "Value": "PATIENT"},
+            "0010,0020": {"Name": "PatientID", "Type": "String", "Value": "P1"}
+        });
+        assert_eq!(
+            query_answer_uids(&answer),
+            hashmap! {"PatientID".to_string() => "P1".to_string()}
         );
     }
 
     #[test]
-    fn test_create_list_table_instance_columns() {
-        test_list_table_instance(
-            &["SOPInstanceUID", "Index in series", "File size"],
-            &["SOPInstanceUID"],
-            false,
-            include_str!("../tests/data/unit/list_instances_columns").trim_end(),
+    fn test_render_tree() {
+        let instance = |id: &str, number: &str| TreeNode {
+            kind: EntityKind::Instance,
+            id: id.to_string(),
+            fields: json!({ "InstanceNumber": number })
+                .as_object()
+                .unwrap()
+                .clone(),
+            children: vec![],
+        };
+        let series = TreeNode {
+            kind: EntityKind::Series,
+            id: "se1".to_string(),
+            fields:
+                json!({"Modality": "MR", "SeriesDescription": "", "Number of Instances": 2})
+                    .as_object()
+                    .unwrap()
+                    .clone(),
+            children: vec![instance("i1", "1"), instance("i2", "2")],
+        };
+        let study = TreeNode {
+            kind: EntityKind::Study,
+            id: "st1".to_string(),
+            fields: json!({"StudyDate": "20200101", "StudyDescription": null, "Number of Series": 1})
+                .as_object()
+                .unwrap()
+                .clone(),
+            children: vec![series],
+        };
+        assert_eq!(
+            render(&vec![study], false, &OutputFormat::Table).unwrap(),
+            concat!(
+                " Study          20200101, 1 series   st1 \n",
+                "   Series       MR, 2 instances      se1 \n",
+                "     Instance   1                    i1  \n",
+                "     Instance   2                    i2  "
+            )
         );
     }
 
     #[test]
-    fn test_create_list_table_instance_columns_no_header() {
-        test_list_table_instance(
-            &["SOPInstanceUID", "Index in series", "File size"],
-            &["SOPInstanceUID"],
-            true,
-            include_str!("../tests/data/unit/list_instances_columns_no_header").trim_end(),
+    fn test_parse_number() {
+        assert_eq!(parse_number::<usize>("10", "limit").unwrap(), 10);
+        assert_eq!(parse_number::<i32>("-1", "port").unwrap(), -1);
+        assert_eq!(
+            parse_number::<u32>("-1", "timeout").unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Invalid timeout: -1"),
+                Some("Must be a number"),
+            )
         );
+        assert!(parse_number::<u8>("256", "retries").is_err());
     }
 
     #[test]
-    fn test_create_list_table_no_header_no_data() {
-        let data: Vec<Patient> = vec![];
+    fn test_parse_sort_by() {
         assert_eq!(
-            format_table(create_list_table(
-                data,
-                PATIENTS_LIST_HEADER,
-                PATIENTS_LIST_DICOM_TAGS,
-                true,
-            )),
-            ""
+            parse_sort_by("StudyDate").unwrap(),
+            SortBy {
+                column: "StudyDate".to_string(),
+                descending: false
+            }
+        );
+        assert_eq!(
+            parse_sort_by("0008,0020:desc").unwrap(),
+            SortBy {
+                column: "0008,0020".to_string(),
+                descending: true
+            }
+        );
+        assert_eq!(
+            parse_sort_by("StudyDate:foo").unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Invalid sort order: foo"),
+                Some("The order is either asc or desc, e.g. StudyDate:desc"),
+            )
         );
     }
 
     #[test]
-    fn test_create_show_table_patient() {
-        let patient = Patient {
-            id: "foo".to_string(),
-            is_stable: true,
-            last_update: NaiveDate::from_ymd(2020, 1, 1).and_hms(15, 46, 17),
-            main_dicom_tags: hashmap! {
-                "PatientID".to_string() => "foo_id".to_string(),
-                "PatientName".to_string() => "Rick Sanchez".to_string(),
-            },
-            studies: ["study_1".to_string()].to_vec(),
-            entity: EntityKind::Patient,
-            anonymized_from: None,
+    fn test_check_sort_column() {
+        let options = ListOptions {
+            sort_by: Some(parse_sort_by("PatientName").unwrap()),
+            ..Default::default()
         };
-
+        assert!(check_sort_column(&["ID", "PatientName"], &options).is_ok());
         assert_eq!(
-            format_table(create_show_table(patient, &PATIENT_DICOM_TAGS)),
-            include_str!("../tests/data/unit/show_patient").trim_end()
+            check_sort_column(&["ID", "PatientID"], &options).unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Invalid sort column: PatientName. Available columns: ID, PatientID"),
+                Some("A DICOM tag, that is not displayed, can be added with -c/--columns"),
+            )
         );
+        assert!(check_sort_column(&["ID"], &ListOptions::default()).is_ok());
     }
 
     #[test]
-    fn test_create_show_table_study() {
-        let study = Study {
-            id: "foo".to_string(),
-            is_stable: true,
-            last_update: NaiveDate::from_ymd(2020, 8, 30).and_hms(19, 11, 09),
-            main_dicom_tags: hashmap! {
-                "AccessionNumber".to_string() => "foo_an".to_string(),
-                "StudyInstanceUID".to_string() => "foo_suid".to_string(),
-                "StudyDescription".to_string() => "foo_sd".to_string(),
-            },
-            parent_patient: "patient_foo".to_string(),
-            patient_main_dicom_tags: hashmap! {
-                "PatientName".to_string() => "Rick Sanchez".to_string(),
-            },
-            series: ["foo_series_1".to_string(), "foo_series_2".to_string()].to_vec(),
-            entity: EntityKind::Study,
-            anonymized_from: None,
-        };
+    fn test_sort_rows() {
+        let mut rows: Vec<Row> = [
+            json!({"ID": "a", "InstanceNumber": "10", "StudyTime": "0930", "Number of Series": 2}),
+            json!({"ID": "b", "InstanceNumber": null, "StudyTime": "093000.5", "Number of Series": 10}),
+            json!({"ID": "c", "InstanceNumber": "9", "StudyTime": "08", "Number of Series": 1}),
+        ]
+        .iter()
+        .map(|r| r.as_object().unwrap().clone())
+        .collect();
+        let ids = |rows: &[Row]| rows.iter().map(|r| r["ID"].clone()).collect::<Vec<_>>();
 
-        assert_eq!(
-            format_table(create_show_table(study, &STUDY_DICOM_TAGS)),
-            include_str!("../tests/data/unit/show_study").trim_end()
-        );
+        sort_rows(&mut rows, &parse_sort_by("InstanceNumber").unwrap());
+        assert_eq!(ids(&rows), vec!["c", "a", "b"]);
+        sort_rows(&mut rows, &parse_sort_by("InstanceNumber:desc").unwrap());
+        assert_eq!(ids(&rows), vec!["a", "c", "b"]);
+        sort_rows(&mut rows, &parse_sort_by("StudyTime").unwrap());
+        assert_eq!(ids(&rows), vec!["c", "a", "b"]);
+        sort_rows(&mut rows, &parse_sort_by("Number of Series:desc").unwrap());
+        assert_eq!(ids(&rows), vec!["b", "a", "c"]);
+        sort_rows(&mut rows, &parse_sort_by("ID:desc").unwrap());
+        assert_eq!(ids(&rows), vec!["c", "b", "a"]);
     }
 
     #[test]
-    fn test_create_show_table_series() {
-        let series = Series {
-            id: "foo".to_string(),
-            status: "Unknown".to_string(),
-            is_stable: true,
-            last_update: NaiveDate::from_ymd(2020, 8, 30).and_hms(19, 11, 09),
-            main_dicom_tags: hashmap! {
-                "BodyPartExamined".to_string() => "ABDOMEN".to_string(),
-                "SeriesDescription".to_string() => "Foo".to_string(),
-            },
-            parent_study: "study_1".to_string(),
-            expected_number_of_instances: Some(17),
-            instances: ["i1".to_string(), "i2".to_string()].to_vec(),
-            entity: EntityKind::Series,
-            anonymized_from: None,
+    fn test_delete_results() {
+        let results = EntityList {
+            columns: DELETE_RESULTS_HEADER
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            rows: vec![
+                create_delete_row("a", Ok(())),
+                create_delete_row(
+                    "b",
+                    Err(CliError::new("API error", Some("Unknown resource"), None)),
+                ),
+            ],
         };
+        assert_eq!(results.rows[0]["Status"], "Deleted");
+        assert_eq!(results.rows[0]["Error"], "");
+        assert_eq!(results.rows[1]["Status"], "Failed");
+        assert_eq!(results.rows[1]["Error"], "Unknown resource");
         assert_eq!(
-            format_table(create_show_table(series, &SERIES_DICOM_TAGS)),
-            include_str!("../tests/data/unit/show_series").trim_end()
+            check_delete_results(&results, &EntityKind::Study).unwrap_err(),
+            CliError::new(
+                "Delete error",
+                Some("1 of 2 studies failed to be deleted"),
+                None
+            )
         );
+        assert!(check_delete_results(
+            &EntityList {
+                columns: vec![],
+                rows: vec![create_delete_row("a", Ok(()))]
+            },
+            &EntityKind::Study
+        )
+        .is_ok());
     }
 
     #[test]
-    fn test_create_show_table_instance() {
-        let instance = Instance {
-            id: "foo".to_string(),
-            main_dicom_tags: hashmap! {
-                "SOPInstanceUID".to_string() => "suid_1".to_string(),
-                "InstanceNumber".to_string() => "in_1".to_string(),
-            },
-            parent_series: "foo_series".to_string(),
-            index_in_series: Some(13),
-            file_uuid: "file_uuid".to_string(),
-            file_size: 139402,
-            modified_from: None,
-            entity: EntityKind::Instance,
-            anonymized_from: None,
-        };
+    fn test_read_ids() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "foo\n\n  bar  \n").unwrap();
         assert_eq!(
-            format_table(create_show_table(instance, &INSTANCE_DICOM_TAGS)),
-            include_str!("../tests/data/unit/show_instance").trim_end()
+            read_ids(file.path().to_str().unwrap()).unwrap(),
+            vec!["foo", "bar"]
         );
     }
 
     #[test]
-    fn test_create_new_entity_table() {
-        let res = ModificationResult {
-            id: "foobar".to_string(),
-            patient_id: "bazqux".to_string(),
-            path: "long_and_rocky".to_string(),
-            entity: EntityKind::Study,
+    fn test_entity_kind() {
+        assert_eq!(entity_kind("series").unwrap(), EntityKind::Series);
+        assert_eq!(
+            entity_kind("foo").unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Invalid level: foo"),
+                Some("Must be one of patient, study, series, instance"),
+            )
+        );
+        assert_eq!(entity_kind_plural(&EntityKind::Series), "series");
+    }
+
+    fn dicom_file(patient_id: &str, instance_number: Option<&str>) -> Vec<u8> {
+        // DICOM values have an even length
+        let pad = |v: &str| match v.len() % 2 {
+            0 => v.to_string(),
+            _ => format!("{} ", v),
         };
-        let expected_table = " New Study ID   foobar \n Patient ID     bazqux ";
-        assert_eq!(format!("{}", create_new_entity_table(res)), expected_table)
+        let mut object = InMemDicomObject::create_empty();
+        object.put(DataElement::new(
+            Tag(0x0010, 0x0020),
+            VR::LO,
+            PrimitiveValue::from(pad(patient_id)),
+        ));
+        if let Some(n) = instance_number {
+            object.put(DataElement::new(
+                Tag(0x0020, 0x0013),
+                VR::IS,
+                PrimitiveValue::from(pad(n)),
+            ));
+        }
+        let file = object
+            .with_meta(
+                FileMetaTableBuilder::new()
+                    .media_storage_sop_class_uid("1.2.840.10008.5.1.4.1.1.7")
+                    .media_storage_sop_instance_uid("1.2.3")
+                    .transfer_syntax("1.2.840.10008.1.2.1"),
+            )
+            .unwrap();
+        let mut data = vec![];
+        file.write_all(&mut data).unwrap();
+        data
+    }
+
+    fn zip_archive(files: Vec<(&str, Vec<u8>)>) -> Vec<u8> {
+        let mut archive = zip::ZipWriter::new(io::Cursor::new(vec![]));
+        for (name, content) in files {
+            archive
+                .start_file(name, zip::write::FileOptions::default())
+                .unwrap();
+            archive.write_all(&content).unwrap();
+        }
+        archive.finish().unwrap().into_inner()
+    }
+
+    fn list_files(dir: &Path) -> Vec<String> {
+        let mut files = vec![];
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                files.extend(list_files(&path));
+            } else {
+                files.push(path.to_string_lossy().to_string());
+            }
+        }
+        files.sort();
+        files
     }
 
     #[test]
-    fn test_create_new_entity_table_patient() {
-        let res = ModificationResult {
-            id: "foobar".to_string(),
-            patient_id: "bazqux".to_string(),
-            path: "long_and_rocky".to_string(),
-            entity: EntityKind::Patient,
+    fn test_render_path_template() {
+        let tags = hashmap! {
+            "PatientID" => " patient/1 ",
+            "StudyDate" => "20200101",
+            "SeriesNumber" => "",
+            "0020,0013" => "..",
         };
-        let expected_table = " New Patient ID   foobar ";
-        assert_eq!(format!("{}", create_new_entity_table(res)), expected_table)
+        let tag_value = |t: &str| tags.get(t).map(ToString::to_string);
+        assert_eq!(
+            render_path_template(
+                "{PatientID}/{StudyDate}_{StudyDescription}/{SeriesNumber}/{0020,0013}.dcm",
+                tag_value
+            )
+            .unwrap(),
+            PathBuf::from("patient_1/20200101_undefined/undefined/_.dcm")
+        );
+        assert_eq!(
+            render_path_template("no_tags.dcm", tag_value).unwrap(),
+            PathBuf::from("no_tags.dcm")
+        );
     }
 
     #[test]
-    fn test_create_error_table() {
+    fn test_check_path_template() {
+        assert!(check_path_template("{PatientID}/{0020,0013}.dcm").is_ok());
+        let error = |template: &str, details: &str| {
+            CliError::new(
+                "Command error",
+                Some(&format!("Invalid path template: {}", template)),
+                Some(details),
+            )
+        };
         assert_eq!(
-            format!(
-                "{}",
-                create_error_table(CliError::new(
-                    "error",
-                    Some("message"),
-                    Some("details")
-                ))
-            ),
-            " Error     error   \n Message   message \n Details   details "
+            check_path_template("/{PatientID}.dcm").unwrap_err(),
+            error(
+                "/{PatientID}.dcm",
+                "The path must be relative to the output directory"
+            )
         );
         assert_eq!(
-            format!(
-                "{}",
-                create_error_table(CliError::new("error", None, Some("details")))
-            ),
-            " Error     error   \n Details   details "
+            check_path_template("{PatientID}/../x.dcm").unwrap_err(),
+            error(
+                "{PatientID}/../x.dcm",
+                "The path must be relative to the output directory"
+            )
         );
         assert_eq!(
-            format!(
-                "{}",
-                create_error_table(CliError::new("error", Some("message"), None))
-            ),
-            " Error     error   \n Message   message "
+            check_path_template("{PatientID.dcm").unwrap_err(),
+            error("{PatientID.dcm", "Unclosed {")
+        );
+        assert_eq!(
+            check_path_template("PatientID}.dcm").unwrap_err(),
+            error("PatientID}.dcm", "Unopened }")
+        );
+        assert_eq!(
+            check_path_template("{foo}.dcm").unwrap_err(),
+            error(
+                "{foo}.dcm",
+                "Invalid tag: foo. A tag is either a keyword (e.g. StudyDate) or gggg,eeee (e.g. 0008,0020)"
+            )
+        );
+    }
+
+    #[test]
+    fn test_dicom_tag_value() {
+        let data = dicom_file("patient_1", Some("7"));
+        let object = dicom_object::from_reader(&data[DICOM_PREAMBLE_LENGTH..]).unwrap();
+        assert_eq!(
+            dicom_tag_value(&object, "PatientID"),
+            Some("patient_1".to_string())
+        );
+        assert_eq!(dicom_tag_value(&object, "0020,0013"), Some("7".to_string()));
+        assert_eq!(dicom_tag_value(&object, "StudyDate"), None);
+    }
+
+    #[test]
+    fn test_extract_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = zip_archive(vec![
+            ("p1/s1/IM1.dcm", dicom_file("p1", Some("1"))),
+            ("p1/s1/IM2.dcm", dicom_file("p1", Some("2"))),
+        ]);
+        assert_eq!(
+            extract_archive(archive.as_slice(), dir.path(), None).unwrap(),
+            2
+        );
+        let path = |p: &str| dir.path().join(p).to_string_lossy().to_string();
+        assert_eq!(
+            list_files(dir.path()),
+            vec![path("p1/s1/IM1.dcm"), path("p1/s1/IM2.dcm")]
         );
         assert_eq!(
-            format!("{}", create_error_table(CliError::new("error", None, None))),
-            " Error   error "
+            fs::read(dir.path().join("p1/s1/IM2.dcm")).unwrap(),
+            dicom_file("p1", Some("2"))
         );
     }
 
     #[test]
-    fn test_parse_tag_kv_pairs() {
+    fn test_extract_archive_path_template() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = zip_archive(vec![
+            ("IM1.dcm", dicom_file("p1", Some("1"))),
+            ("IM2.dcm", dicom_file("p1", None)),
+            ("IM3.dcm", dicom_file("p1", None)),
+            ("IM4.dcm", dicom_file("p2", Some("1"))),
+        ]);
         assert_eq!(
-            parse_tag_kv_pairs(vec!["Foo=Bar", "Baz=42"]).unwrap(),
-            hashmap! {"Foo".to_string() => "Bar".to_string(), "Baz".to_string() => "42".to_string()}
-        )
+            extract_archive(
+                archive.as_slice(),
+                dir.path(),
+                Some("{PatientID}/{InstanceNumber}.dcm")
+            )
+            .unwrap(),
+            4
+        );
+        let path = |p: &str| dir.path().join(p).to_string_lossy().to_string();
+        assert_eq!(
+            list_files(dir.path()),
+            vec![
+                path("p1/1.dcm"),
+                path("p1/undefined.dcm"),
+                path("p1/undefined_1.dcm"),
+                path("p2/1.dcm"),
+            ]
+        );
     }
 
     #[test]
-    fn test_parse_tag_kv_pairs_error() {
+    fn test_extract_archive_not_dicom() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = zip_archive(vec![("README", b"foo".to_vec())]);
         assert_eq!(
-            parse_tag_kv_pairs(vec!["Foo=Bar", "Baz"]).unwrap_err(),
+            extract_archive(archive.as_slice(), dir.path(), Some("{PatientID}.dcm"))
+                .unwrap_err(),
             CliError::new(
                 "Command error",
-                Some("Wrong option value 'Baz'"),
-                Some("Must be of format 'TagName=TagValue'"),
+                Some("Not a DICOM file: README"),
+                Some("A path template can only be applied to DICOM files"),
             )
-        )
+        );
     }
 
     #[test]
-    fn test_get_anonymization_config_from_file() {
-        let mut file = fs::File::create("/tmp/anon_config.yml").unwrap();
-        file.write(b"{}").unwrap();
+    fn test_write_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.zip");
+        write_output(&path.to_string_lossy(), |w| {
+            w.write_all(b"foo")?;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"foo");
         assert_eq!(
-            get_anonymization_config_from_file("/tmp/anon_config.yml").unwrap(),
-            Anonymization {
-                replace: None,
-                keep: None,
-                keep_private_tags: None,
-                force: Some(true),
-                dicom_version: None
-            }
-        )
+            list_files(dir.path()),
+            vec![path.to_string_lossy().to_string()]
+        );
     }
 
     #[test]
-    fn test_get_anonymization_config_from_file_file_not_found() {
+    fn test_write_output_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.zip");
         assert_eq!(
-            get_anonymization_config_from_file("/tmp/anon_garble.yml").unwrap_err(),
-            CliError {
-                error: "No such file or directory (os error 2)".to_string(),
-                message: None,
-                details: None
-            }
-        )
+            write_output(&path.to_string_lossy(), |w| {
+                w.write_all(b"foo")?;
+                Err(CliError::new(
+                    "Command error",
+                    Some("Transfer failed"),
+                    None,
+                ))
+            })
+            .unwrap_err(),
+            CliError::new("Command error", Some("Transfer failed"), None)
+        );
+        assert_eq!(list_files(dir.path()), Vec::<String>::new());
     }
 
     #[test]
-    fn test_get_anonymization_config_from_file_yaml_parse_error() {
-        let mut file = NamedTempFile::new().unwrap();
-        writeln!(file, "garble").unwrap();
-        assert_eq!(
-            get_anonymization_config_from_file(file.path().to_str().unwrap()).unwrap_err(),
-            CliError {
-                error: "invalid type: string \"garble\", expected struct Anonymization at line 1 column 1".to_string(),
-                message: None,
-                details: None
-            }
-        )
+    fn test_progress() {
+        let mut reported = vec![];
+        let mut reader = Progress::new(&b"foobar"[..], |b| reported.push(b));
+        let mut buf = [0; 4];
+        assert_eq!(reader.read(&mut buf).unwrap(), 4);
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+        assert_eq!(reported, vec![4, 6]);
+
+        let mut reported = vec![];
+        let mut data = vec![];
+        let mut writer = Progress::new(&mut data, |b| reported.push(b));
+        writer.write_all(b"foo").unwrap();
+        writer.write_all(b"bar").unwrap();
+        assert_eq!(data, b"foobar");
+        assert_eq!(reported, vec![3, 6]);
     }
 
     #[test]
-    fn test_get_anonymization_config_from_cmd_options() {
+    fn test_format_transfer_progress() {
         assert_eq!(
-            get_anonymization_config_from_cmd_options(
-                Some(vec!["Foo=Bar", "Baz=qux"]),
-                Some(vec!["Qux", "Quuz"]),
-                Some(true)
-            )
-            .unwrap(),
-            Anonymization {
-                replace: Some(
-                    hashmap! {"Foo".to_string() => "Bar".to_string(), "Baz".to_string() => "qux".to_string()}
-                ),
-                keep: Some(vec!["Qux".to_string(), "Quuz".to_string()]),
-                keep_private_tags: Some(true),
-                dicom_version: None,
-                force: Some(true)
-            }
+            format_transfer_progress(0, Duration::from_secs(0)),
+            "0 B received (0 B/s)"
         );
-
         assert_eq!(
-            get_anonymization_config_from_cmd_options(None, None, None).unwrap(),
-            Anonymization {
-                replace: None,
-                keep: None,
-                keep_private_tags: None,
-                dicom_version: None,
-                force: Some(true)
-            }
-        )
-    }
-
-    #[test]
-    fn test_get_modification_config_from_cmd_options() {
+            format_transfer_progress(1023, Duration::from_secs(1)),
+            "1023 B received (1023 B/s)"
+        );
         assert_eq!(
-            get_modification_config_from_cmd_options(
-                Some(vec!["Foo=Bar", "Baz=qux"]),
-                Some(vec!["Qux", "Quuz"]),
-            )
-            .unwrap(),
-            Modification {
-                replace: Some(
-                    hashmap! {"Foo".to_string() => "Bar".to_string(), "Baz".to_string() => "qux".to_string()}
-                ),
-                remove: Some(vec!["Qux".to_string(), "Quuz".to_string()]),
-                force: Some(true)
-            }
+            format_transfer_progress(3 * 1024 * 1024 / 2, Duration::from_secs(3)),
+            "1.5 MiB received (512.0 KiB/s)"
         );
-
         assert_eq!(
-            get_modification_config_from_cmd_options(None, None).unwrap(),
-            Modification {
-                replace: None,
-                remove: None,
-                force: Some(true)
-            }
+            format_transfer_progress(
+                5 * 1024 * 1024 * 1024 * 1024 * 1024,
+                Duration::from_secs(1)
+            ),
+            "5120.0 TiB received (5120.0 TiB/s)"
         );
     }
 
     #[test]
-    fn test_get_modification_config_from_file() {
-        let mut file = fs::File::create("/tmp/mod_config.yml").unwrap();
-        file.write(b"{}").unwrap();
+    fn test_name_template_tags() {
         assert_eq!(
-            get_modification_config_from_file("/tmp/mod_config.yml").unwrap(),
-            Modification {
-                replace: None,
-                remove: None,
-                force: Some(true)
-            }
-        )
+            name_template_tags("{PatientID}/{ID}_{0008,0020}.zip").unwrap(),
+            vec!["PatientID", "0008,0020"]
+        );
+        assert_eq!(
+            name_template_tags("{id}.zip").unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Invalid path template: {id}.zip"),
+                Some("Invalid tag: id. A tag is either a keyword (e.g. StudyDate) or gggg,eeee (e.g. 0008,0020), or one of: ID"),
+            )
+        );
     }
 
     #[test]
-    fn test_get_modification_config_from_file_file_not_found() {
+    fn test_download_paths() {
+        let row = |id: &str, patient_id: Value| {
+            let mut row = Row::new();
+            row.insert("ID".to_string(), Value::from(id));
+            row.insert("PatientID".to_string(), patient_id);
+            row
+        };
+        let entities = EntityList {
+            columns: vec!["ID".to_string(), "PatientID".to_string()],
+            rows: vec![
+                row("foo", Value::from("p1")),
+                row("bar", Value::from("p1")),
+                row("baz", Value::Null),
+            ],
+        };
         assert_eq!(
-            get_modification_config_from_file("/tmp/anon_garble.yml").unwrap_err(),
-            CliError {
-                error: "No such file or directory (os error 2)".to_string(),
-                message: None,
-                details: None
-            }
-        )
+            download_paths(&entities, "{PatientID}.zip", Path::new("out")).unwrap(),
+            vec![
+                PathBuf::from("out/p1.zip"),
+                PathBuf::from("out/p1_1.zip"),
+                PathBuf::from("out/undefined.zip"),
+            ]
+        );
+        assert_eq!(
+            download_paths(&entities, "{PatientID}/{ID}.zip", Path::new("out")).unwrap(),
+            vec![
+                PathBuf::from("out/p1/foo.zip"),
+                PathBuf::from("out/p1/bar.zip"),
+                PathBuf::from("out/undefined/baz.zip"),
+            ]
+        );
     }
 
     #[test]
-    fn test_get_modification_config_from_file_yaml_parse_error() {
-        let mut file = NamedTempFile::new().unwrap();
-        writeln!(file, "garble").unwrap();
+    fn test_create_download_row() {
+        let row = create_download_row(
+            "foo",
+            Path::new("out/foo.zip"),
+            1536,
+            Duration::from_millis(2250),
+            Ok(()),
+        );
         assert_eq!(
-            get_modification_config_from_file(file.path().to_str().unwrap()).unwrap_err(),
-            CliError {
-                error: "invalid type: string \"garble\", expected struct Modification at line 1 column 1".to_string(),
-                message: None,
-                details: None
-            }
-        )
+            row.keys().collect::<Vec<_>>(),
+            DOWNLOAD_RESULTS_HEADER.to_vec()
+        );
+        assert_eq!(row["File"], "out/foo.zip");
+        assert_eq!(row["Status"], DOWNLOADED_STATUS);
+        assert_eq!(row["Size"], "1.5 KiB");
+        assert_eq!(row["Duration"], "2.2s");
+        assert_eq!(row["Error"], "");
+
+        let row = create_download_row(
+            "foo",
+            Path::new("out/foo.zip"),
+            0,
+            Duration::from_millis(10),
+            Err(CliError::new(
+                "API error: 404 Not Found",
+                Some("Unknown resource"),
+                None,
+            )),
+        );
+        assert_eq!(row["Status"], DOWNLOAD_FAILED_STATUS);
+        assert_eq!(row["Error"], "Unknown resource");
     }
 
     #[test]
-    fn test_get_anonymization_config_conflicting_options() {
+    fn test_check_download_results() {
+        let mut results = EntityList {
+            columns: DOWNLOAD_RESULTS_HEADER
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            rows: vec![create_download_row(
+                "foo",
+                Path::new("foo.zip"),
+                0,
+                Duration::from_secs(0),
+                Ok(()),
+            )],
+        };
+        assert!(check_download_results(&results, &EntityKind::Study).is_ok());
+        results.rows.push(create_download_row(
+            "bar",
+            Path::new("bar.zip"),
+            0,
+            Duration::from_secs(0),
+            Err(CliError::new("error", None, None)),
+        ));
         assert_eq!(
-            get_anonymization_config(
-                Some(vec!["Foo=Bar", "Baz=qux"]),
-                Some(vec!["Qux", "Quuz"]),
-                Some(true),
-                Some("/tmp/foo.yml")
+            check_download_results(&results, &EntityKind::Study).unwrap_err(),
+            CliError::new(
+                "Download error",
+                Some("1 of 2 studies failed to be downloaded"),
+                None
             )
-            .unwrap_err(),
-            CliError::new("Command error", Some("Conflicting options"), None)
-        )
+        );
     }
 
     #[test]
-    fn test_get_modification_config_not_enough_options() {
+    fn test_retry() {
+        let attempts = |errors: Vec<&str>, retries: u32| {
+            let mut errors = errors.into_iter();
+            let mut attempts = 0;
+            let result = retry(retries, Duration::from_millis(1), || {
+                attempts += 1;
+                match errors.next() {
+                    Some(e) => Err(CliError::new(e, None, None)),
+                    None => Ok(()),
+                }
+            });
+            (attempts, result.is_ok())
+        };
+        assert_eq!(attempts(vec![], 3), (1, true));
         assert_eq!(
-            get_modification_config(None, None, None).unwrap_err(),
-            CliError::new("Command error", Some("Not enough options"), None)
-        )
+            attempts(
+                vec![
+                    "API error: 500 Internal Server Error",
+                    "error sending request"
+                ],
+                3
+            ),
+            (3, true)
+        );
+        assert_eq!(
+            attempts(vec!["API error: 503 Service Unavailable"; 4], 3),
+            (4, false)
+        );
+        assert_eq!(attempts(vec!["API error: 404 Not Found"], 3), (1, false));
+        assert_eq!(attempts(vec!["Command error"], 3), (1, false));
+        assert_eq!(
+            attempts(vec!["No space left on device (os error 28)"], 3),
+            (1, false)
+        );
+        assert_eq!(
+            attempts(vec!["error decoding response body: operation timed out"], 3),
+            (2, true)
+        );
+        assert_eq!(
+            attempts(vec!["API error: 429 Too Many Requests"], 0),
+            (1, false)
+        );
     }
 
     #[test]
-    fn test_get_modification_config_conflicting_options() {
+    fn test_get_pseudonym_salt() {
+        remove_var("ORC_PSEUDONYM_SALT");
+        assert_eq!(get_pseudonym_salt(None).unwrap(), None);
+
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(b"s3cret\n").unwrap();
+        let path = file.path().to_str().unwrap().to_string();
         assert_eq!(
-            get_modification_config(
-                Some(vec!["Foo=Bar", "Baz=qux"]),
-                Some(vec!["Qux", "Quuz"]),
-                Some("/tmp/foo.yml")
-            )
-            .unwrap_err(),
-            CliError::new("Command error", Some("Conflicting options"), None)
-        )
-    }
+            get_pseudonym_salt(Some(&path)).unwrap(),
+            Some("s3cret".to_string())
+        );
 
-    #[test]
-    fn test_get_server() {
-        remove_var("ORC_ORTHANC_SERVER");
-        assert_eq!(get_server_address(Some("foo")).unwrap(), "foo".to_string());
+        set_var("ORC_PSEUDONYM_SALT", "pepper");
+        assert_eq!(
+            get_pseudonym_salt(None).unwrap(),
+            Some("pepper".to_string())
+        );
+        assert_eq!(
+            get_pseudonym_salt(Some(&path)).unwrap(),
+            Some("s3cret".to_string())
+        );
+        set_var("ORC_PSEUDONYM_SALT", "");
         assert_eq!(
-            get_server_address(None).unwrap_err(),
+            get_pseudonym_salt(None).unwrap_err(),
+            CliError::new("Command error", Some("Empty pseudonym salt"), None)
+        );
+        remove_var("ORC_PSEUDONYM_SALT");
+
+        assert_eq!(
+            get_pseudonym_salt(Some("/nonexistent")).unwrap_err(),
             CliError::new(
                 "Command error",
-                Some("Neither --server nor ORC_ORTHANC_SERVER are set"),
-                Some("environment variable not found"),
+                Some("Could not read pseudonym salt file: /nonexistent"),
+                Some("No such file or directory (os error 2)"),
             )
         );
-        set_var("ORC_ORTHANC_SERVER", "bar");
-        assert_eq!(get_server_address(None).unwrap(), "bar".to_string());
-        assert_eq!(get_server_address(Some("baz")).unwrap(), "baz".to_string())
     }
 
     #[test]
-    fn test_get_username() {
-        remove_var("ORC_ORTHANC_USERNAME");
-        assert_eq!(get_username(Some("foo")).unwrap(), "foo".to_string());
-        assert_eq!(get_username(None), None);
-        set_var("ORC_ORTHANC_USERNAME", "bar");
-        assert_eq!(get_username(Some("foo")).unwrap(), "foo".to_string());
-        assert_eq!(get_username(None).unwrap(), "bar".to_string());
+    fn test_pseudonym() {
+        let p = pseudonym("salt", "patient_1");
+        assert_eq!(p.len(), PSEUDONYM_LENGTH);
+        assert!(p.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(p, pseudonym("salt", "patient_1"));
+        assert_ne!(p, pseudonym("salt", "patient_2"));
+        assert_ne!(p, pseudonym("pepper", "patient_1"));
     }
 
     #[test]
-    fn test_get_password() {
-        remove_var("ORC_ORTHANC_PASSWORD");
-        assert_eq!(get_password(Some("foo")).unwrap(), "foo".to_string());
-        assert_eq!(get_password(None), None);
-        set_var("ORC_ORTHANC_PASSWORD", "bar");
-        assert_eq!(get_password(Some("foo")).unwrap(), "foo".to_string());
-        assert_eq!(get_password(None).unwrap(), "bar".to_string());
-    }
+    fn test_add_pseudonyms() {
+        let original = Identifiers {
+            patient_id: Some("patient_1".to_string()),
+            ..Default::default()
+        };
+        let value = pseudonym("salt", "patient_1");
+        assert_eq!(
+            add_pseudonyms(None, "salt", &original).unwrap(),
+            Anonymization {
+                replace: Some(hashmap! {
+                    "PatientID".to_string() => value.clone(),
+                    "PatientName".to_string() => value.clone(),
+                }),
+                force: Some(true),
+                ..Default::default()
+            }
+        );
 
-    #[test]
-    fn test_check_columns_options() {
+        let config = Anonymization {
+            replace: Some(hashmap! {"PatientName".to_string() => "Rick".to_string()}),
+            keep: Some(vec!["PatientID".to_string()]),
+            force: Some(true),
+            ..Default::default()
+        };
         assert_eq!(
-            check_columns_option(&["foo", "bar", "baz"], &["foo", "baz"]).unwrap(),
-            ()
+            add_pseudonyms(Some(config.clone()), "salt", &original).unwrap(),
+            config
         );
 
         assert_eq!(
-            check_columns_option(&["foo", "bar", "baz"], &["bar", "qux"]).unwrap_err(),
+            add_pseudonyms(None, "salt", &Identifiers::default()).unwrap_err(),
             CliError::new(
                 "Command error",
-                Some("Invalid column name: qux. Available columns: foo, bar, baz"),
+                Some("Cannot derive a pseudonym: the patient has no PatientID"),
                 None
             )
-        )
+        );
     }
 
     #[test]
-    fn test_get_header_and_dicom_tags() {
-        let mut header = vec!["foo", "bar", "baz", "qux", "quux", "quuz"];
-        let mut dicom_tags = vec!["qux", "quux", "quuz"];
-
-        get_header_and_dicom_tags(
-            &mut header,
-            &mut dicom_tags,
-            Some(vec!["bar", "quux", "quuz"]),
-        )
-        .unwrap();
-
-        assert_eq!(header, vec!["bar", "quux", "quuz"]);
-        assert_eq!(dicom_tags, vec!["quux", "quuz"]);
+    fn test_mapping_record() {
+        let original = Identifiers {
+            patient_id: Some("patient_1".to_string()),
+            patient_name: Some("Rick".to_string()),
+            study_instance_uid: Some("1.2.3".to_string()),
+            accession_number: None,
+        };
+        let new = Identifiers {
+            patient_id: Some("ABC".to_string()),
+            patient_name: Some("ABC".to_string()),
+            study_instance_uid: Some("4.5.6".to_string()),
+            accession_number: None,
+        };
+        let record = mapping_record(
+            "2020-01-01T00:00:00Z",
+            &EntityKind::Study,
+            "foo",
+            "bar",
+            &original,
+            &new,
+        );
+        assert_eq!(record.len(), MAPPING_FILE_HEADER.len());
+        assert_eq!(
+            record,
+            vec![
+                "2020-01-01T00:00:00Z",
+                "Study",
+                "foo",
+                "bar",
+                "patient_1",
+                "ABC",
+                "Rick",
+                "ABC",
+                "1.2.3",
+                "4.5.6",
+                "",
+                "",
+            ]
+        );
     }
 
     #[test]
-    fn test_get_header_and_dicom_tags_no_columns() {
-        let mut header = vec!["foo", "bar", "baz"];
-        let mut dicom_tags = vec!["qux", "quux", "quuz"];
-
-        get_header_and_dicom_tags(&mut header, &mut dicom_tags, None).unwrap();
-
-        assert_eq!(header, vec!["foo", "bar", "baz"]);
-        assert_eq!(dicom_tags, vec!["qux", "quux", "quuz"]);
+    fn test_append_mapping_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mapping.csv");
+        let record = |id: &str| {
+            let mut record = vec![id.to_string(); MAPPING_FILE_HEADER.len() - 1];
+            record.push("Rick, Sanchez".to_string());
+            record
+        };
+        append_mapping_record(&path, &record("foo")).unwrap();
+        append_mapping_record(&path, &record("bar")).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], MAPPING_FILE_HEADER.join(","));
+        assert!(lines[1].starts_with("foo,foo,"));
+        assert!(lines[2].ends_with(",bar,\"Rick, Sanchez\""));
     }
 }
@@ -6,11 +6,10 @@ use regex::{Regex, RegexBuilder};
 use std::env;
 use std::fs;
 use std::io::prelude::*;
-use std::io::BufReader;
+use std::io::{BufReader, Cursor};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Stdio};
 use std::str;
-use zip;
 
 const DEFAULT_DINO_HOST: &str = "dino"; // docker-compose
 const DEFAULT_DINO_PORT: &str = "5252";
@@ -98,7 +97,7 @@ impl PartialEq for CommandResult {
                 return false;
             }
         }
-        return true;
+        true
     }
 }
 
@@ -110,43 +109,35 @@ fn client() -> Client {
 }
 
 fn find_instance_by_sop_instance_uid(sop_instance_uid: &str) -> Option<Instance> {
-    let instances = client().instances_expanded().unwrap();
-    for i in instances {
-        if i.main_dicom_tags["SOPInstanceUID"] == sop_instance_uid {
-            return Some(i);
-        }
-    }
-    return None;
+    client()
+        .instances_expanded()
+        .unwrap()
+        .into_iter()
+        .find(|i| i.main_dicom_tags["SOPInstanceUID"] == sop_instance_uid)
 }
 
 fn find_series_by_series_instance_uid(series_instance_uid: &str) -> Option<Series> {
-    let series = client().series_expanded().unwrap();
-    for s in series {
-        if s.main_dicom_tags["SeriesInstanceUID"] == series_instance_uid {
-            return Some(s);
-        }
-    }
-    return None;
+    client()
+        .series_expanded()
+        .unwrap()
+        .into_iter()
+        .find(|s| s.main_dicom_tags["SeriesInstanceUID"] == series_instance_uid)
 }
 
 fn find_study_by_study_instance_uid(study_instance_uid: &str) -> Option<Study> {
-    let studies = client().studies_expanded().unwrap();
-    for s in studies {
-        if s.main_dicom_tags["StudyInstanceUID"] == study_instance_uid {
-            return Some(s);
-        }
-    }
-    return None;
+    client()
+        .studies_expanded()
+        .unwrap()
+        .into_iter()
+        .find(|s| s.main_dicom_tags["StudyInstanceUID"] == study_instance_uid)
 }
 
 fn find_patient_by_patient_id(patient_id: &str) -> Option<Patient> {
-    let patients = client().patients_expanded().unwrap();
-    for p in patients {
-        if p.main_dicom_tags["PatientID"] == patient_id {
-            return Some(p);
-        }
-    }
-    return None;
+    client()
+        .patients_expanded()
+        .unwrap()
+        .into_iter()
+        .find(|p| p.main_dicom_tags["PatientID"] == patient_id)
 }
 
 fn fixup_output(output: &str) -> String {
@@ -225,6 +216,80 @@ fn run_command(args: Vec<&str>) -> CommandResult {
     )
 }
 
+fn run_command_with_config(args: Vec<&str>, config_path: &Path) -> CommandResult {
+    let res = Command::new(executable_path())
+        .args(&args)
+        .env("ORC_CONFIG", config_path)
+        .env_remove("ORC_PROFILE")
+        .env_remove("ORC_ORTHANC_SERVER")
+        .output()
+        .unwrap();
+    CommandResult::new(
+        res.status.code().unwrap(),
+        String::from_utf8(res.stdout).unwrap(),
+        String::from_utf8(res.stderr).unwrap(),
+    )
+}
+
+fn run_command_with_stdin(args: Vec<&str>, input: &str) -> CommandResult {
+    run_command_with_input(Command::new(executable_path()).args(&args), input)
+}
+
+fn run_command_with_config_and_stdin(
+    args: Vec<&str>,
+    config_path: &Path,
+    input: &str,
+) -> CommandResult {
+    run_command_with_input(
+        Command::new(executable_path())
+            .args(&args)
+            .env("ORC_CONFIG", config_path)
+            .env_remove("ORC_PROFILE")
+            .env_remove("ORC_ORTHANC_SERVER"),
+        input,
+    )
+}
+
+fn run_command_with_input(command: &mut Command, input: &str) -> CommandResult {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let res = child.wait_with_output().unwrap();
+    CommandResult::new(
+        res.status.code().unwrap(),
+        String::from_utf8(res.stdout).unwrap(),
+        String::from_utf8(res.stderr).unwrap(),
+    )
+}
+
+/// Paths of all the files in a directory and its subdirectories, relative to the directory
+fn list_files(dir: &Path) -> Vec<String> {
+    let mut files = vec![];
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            files.extend(
+                list_files(&path).into_iter().map(|f| {
+                    format!("{}/{}", path.file_name().unwrap().to_str().unwrap(), f)
+                }),
+            );
+        } else {
+            files.push(path.file_name().unwrap().to_str().unwrap().to_string());
+        }
+    }
+    files.sort();
+    files
+}
+
 fn assert_result(args: Vec<&str>, expected_result: CommandResult) {
     let res = run_command(args);
     assert!(res == expected_result);
@@ -242,6 +307,100 @@ fn test_server_option() {
     );
 }
 
+#[test]
+fn test_profile_option() {
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("config.yaml");
+    run_command_with_config(
+        vec!["config", "add", "dev", "-s", "http://localhost:8901"],
+        &config_path,
+    );
+    assert!(
+        run_command_with_config(vec!["--profile", "dev", "patient", "list"], &config_path)
+            == CommandResult::new(
+                1,
+                "".to_string(),
+                " Error   error sending request for url (http://localhost:8901/patients?expand): error trying to connect: tcp connect error: Connection refused (os error 111)\n".to_string(),
+            )
+    );
+}
+
+#[test]
+fn test_config_profiles() {
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("orthanc-cli").join("config.yaml");
+    run_command_with_config_and_stdin(
+        vec![
+            "config",
+            "add",
+            "pacs",
+            "-s",
+            "https://pacs",
+            "-u",
+            "rick",
+            "-p",
+        ],
+        &config_path,
+        "secret\n",
+    );
+    assert!(fs::read_to_string(&config_path)
+        .unwrap()
+        .contains("password: secret"));
+    run_command_with_config(
+        vec!["config", "add", "dev", "-s", "http://localhost:8042"],
+        &config_path,
+    );
+    assert!(
+        run_command_with_config(vec!["-F", "csv", "config", "list"], &config_path)
+            == CommandResult::new(
+                0,
+                "Name,Server,Username\ndev,http://localhost:8042,undefined\npacs,https://pacs,rick\n"
+                    .to_string(),
+                "".to_string(),
+            )
+    );
+    assert!(
+        run_command_with_config(vec!["-F", "csv", "config", "show", "pacs"], &config_path)
+            == CommandResult::new(
+                0,
+                "Name,pacs\nServer,https://pacs\nUsername,rick\nPassword,********\n"
+                    .to_string(),
+                "".to_string(),
+            )
+    );
+    run_command_with_config(vec!["config", "remove", "pacs"], &config_path);
+    assert!(
+        run_command_with_config(vec!["config", "show", "pacs"], &config_path)
+            == CommandResult::new(
+                1,
+                "".to_string(),
+                concat!(
+                    " Error     Command error\n",
+                    " Message   Profile not found: pacs\n",
+                    " Details   See `orthanc config list` for the available profiles\n",
+                )
+                .to_string(),
+            )
+    );
+}
+
+#[test]
+fn test_ca_cert_option_no_such_file() {
+    assert_result(
+        vec!["--ca-cert", "/tmp/nonexistent_ca.pem", "patient", "list"],
+        CommandResult::new(
+            1,
+            "".to_string(),
+            concat!(
+                " Error     Command error\n",
+                " Message   Could not read CA certificate: /tmp/nonexistent_ca.pem\n",
+                " Details   No such file or directory (os error 2)\n",
+            )
+            .to_string(),
+        ),
+    );
+}
+
 #[test]
 fn test_username_option() {
     assert_result(
@@ -323,6 +482,60 @@ fn _test_list_patients_columns() {
     );
 }
 
+#[test]
+fn _test_list_patients_columns_dicom_tag() {
+    assert_result(
+        vec![
+            "-F",
+            "csv",
+            "patient",
+            "list",
+            "-c",
+            "PatientID",
+            "0010,0020",
+            "-n",
+        ],
+        CommandResult::new(
+            0,
+            include_str!("data/patient_list_columns_dicom_tag.stdout").to_string(),
+            "".to_string(),
+        ),
+    );
+}
+
+#[test]
+fn _test_list_patients_json() {
+    assert_result(
+        vec!["-F", "json", "patient", "list", "-n"],
+        CommandResult::new(
+            0,
+            include_str!("data/patient_list_json.stdout").to_string(),
+            "".to_string(),
+        ),
+    );
+}
+
+#[test]
+fn _test_list_patients_columns_csv_no_header() {
+    assert_result(
+        vec![
+            "--format",
+            "csv",
+            "patient",
+            "list",
+            "-c",
+            "PatientID",
+            "PatientName",
+            "-n",
+        ],
+        CommandResult::new(
+            0,
+            include_str!("data/patient_list_columns_csv_no_header.stdout").to_string(),
+            "".to_string(),
+        ),
+    );
+}
+
 #[test]
 fn _test_list_patients_error() {
     assert_result(
@@ -371,6 +584,48 @@ fn _test_list_studies_columns() {
     );
 }
 
+#[test]
+fn test_list_studies_sorted() {
+    assert_result(
+        vec![
+            "study",
+            "list",
+            "-c",
+            "StudyInstanceUID",
+            "StudyTime",
+            "--sort-by",
+            "StudyTime:desc",
+            "--limit",
+            "2",
+        ],
+        CommandResult::new(
+            0,
+            include_str!("data/study_list_sorted.stdout").to_string(),
+            "".to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_list_studies_invalid_sort_column() {
+    assert_result(
+        vec![
+            "study",
+            "list",
+            "-c",
+            "ID",
+            "StudyDate",
+            "--sort-by",
+            "PatientID",
+        ],
+        CommandResult::new(
+            1,
+            "".to_string(),
+            include_str!("data/study_list_invalid_sort_column.stderr").to_string(),
+        ),
+    );
+}
+
 #[test]
 fn _test_list_studies_error() {
     assert_result(
@@ -492,6 +747,19 @@ fn _test_show_patient() {
     );
 }
 
+#[test]
+fn _test_show_patient_yaml() {
+    let patient = find_patient_by_patient_id(PATIENT_ID).unwrap();
+    assert_result(
+        vec!["-F", "yaml", "patient", "show", &patient.id],
+        CommandResult::new(
+            0,
+            include_str!("data/patient_show_yaml.stdout").to_string(),
+            "".to_string(),
+        ),
+    );
+}
+
 #[test]
 fn _test_show_patient_error() {
     assert_result(
@@ -529,6 +797,36 @@ fn _test_show_study_error() {
     );
 }
 
+#[test]
+fn test_show_study_stdin() {
+    let study = find_study_by_study_instance_uid(STUDY_INSTANCE_UID).unwrap();
+    assert!(
+        run_command_with_stdin(
+            vec!["study", "show", "-"],
+            &format!("{}\n\n{}\n", study.id, study.id)
+        ) == CommandResult::new(
+            0,
+            include_str!("data/study_show_stdin.stdout").to_string(),
+            "".to_string(),
+        )
+    );
+}
+
+#[test]
+fn test_show_study_stdin_error() {
+    let study = find_study_by_study_instance_uid(STUDY_INSTANCE_UID).unwrap();
+    assert!(
+        run_command_with_stdin(
+            vec!["study", "show", "-"],
+            &format!("{}\nfoobar\n{}\n", study.id, study.id)
+        ) == CommandResult::new(
+            1,
+            include_str!("data/study_show_stdin_error.stdout").to_string(),
+            include_str!("data/study_show_stdin_error.stderr").to_string(),
+        )
+    );
+}
+
 #[test]
 fn _test_show_series() {
     let series = find_series_by_series_instance_uid(SERIES_INSTANCE_UID).unwrap();
@@ -693,6 +991,31 @@ fn _test_list_series_instances_error() {
     );
 }
 
+#[test]
+fn test_tree_study() {
+    let study = find_study_by_study_instance_uid(STUDY_INSTANCE_UID).unwrap();
+    assert_result(
+        vec!["tree", "--study", &study.id, "-d", "2"],
+        CommandResult::new(
+            0,
+            include_str!("data/study_tree.stdout").to_string(),
+            "".to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_tree_error() {
+    assert_result(
+        vec!["tree", "--study", "foo"],
+        CommandResult::new(
+            1,
+            "".to_string(),
+            include_str!("data/not_found_error.stderr").to_string(),
+        ),
+    );
+}
+
 #[test]
 fn test_download_patient() {
     let patient = find_patient_by_patient_id(PATIENT_ID).unwrap();
@@ -761,6 +1084,97 @@ fn test_download_study_error() {
     );
 }
 
+#[test]
+fn test_download_study_extract() {
+    let study = find_study_by_study_instance_uid(STUDY_INSTANCE_UID).unwrap();
+    let _ = fs::remove_dir_all("/tmp/study_extracted");
+    assert_result(
+        vec!["study", "download", &study.id, "-x", "/tmp/study_extracted"],
+        CommandResult::new(0, "".to_string(), "".to_string()),
+    );
+    assert_eq!(
+        list_files(Path::new("/tmp/study_extracted")),
+        vec![
+            "patient_2 Patient 2/REMOVED Study 1/MR Series 1/MR000000.dcm",
+            "patient_2 Patient 2/REMOVED Study 1/PR/PR000000.dcm",
+        ]
+    );
+}
+
+#[test]
+fn test_download_study_extract_path_template() {
+    let study = find_study_by_study_instance_uid(STUDY_INSTANCE_UID).unwrap();
+    let _ = fs::remove_dir_all("/tmp/study_extracted_template");
+    assert_result(
+        vec![
+            "study",
+            "download",
+            &study.id,
+            "-x",
+            "/tmp/study_extracted_template",
+            "-t",
+            "{PatientID}/{StudyDescription}/{Modality}.dcm",
+        ],
+        CommandResult::new(0, "".to_string(), "".to_string()),
+    );
+    assert_eq!(
+        list_files(Path::new("/tmp/study_extracted_template")),
+        vec!["patient_2/Study 1/MR.dcm", "patient_2/Study 1/PR.dcm"]
+    );
+}
+
+#[test]
+fn test_download_study_extract_invalid_path_template() {
+    assert_result(
+        vec![
+            "study",
+            "download",
+            "foobar",
+            "-x",
+            "/tmp/study_extracted_template",
+            "-t",
+            "{PatientID}/{foo}.dcm",
+        ],
+        CommandResult::new(
+            1,
+            "".to_string(),
+            include_str!("data/download_invalid_path_template.stderr").to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_download_study_media() {
+    let study = find_study_by_study_instance_uid(STUDY_INSTANCE_UID).unwrap();
+    assert_result(
+        vec![
+            "study",
+            "download",
+            &study.id,
+            "-m",
+            "-o",
+            "/tmp/study_media.zip",
+        ],
+        CommandResult::new(0, "".to_string(), "".to_string()),
+    );
+    let file = fs::File::open("/tmp/study_media.zip").unwrap();
+    let zip = zip::ZipArchive::new(BufReader::new(file)).unwrap();
+    assert!(zip.file_names().any(|f| f == "DICOMDIR"));
+}
+
+#[test]
+fn test_download_study_stdout() {
+    let study = find_study_by_study_instance_uid(STUDY_INSTANCE_UID).unwrap();
+    let res = Command::new(executable_path())
+        .args(["study", "download", &study.id, "-o", "-"])
+        .output()
+        .unwrap();
+    assert_eq!(res.status.code().unwrap(), 0);
+    assert_eq!(String::from_utf8(res.stderr).unwrap(), "");
+    let zip = zip::ZipArchive::new(Cursor::new(res.stdout)).unwrap();
+    assert_eq!(zip.len(), 2);
+}
+
 #[test]
 fn test_download_series() {
     let series = find_series_by_series_instance_uid(SERIES_INSTANCE_UID).unwrap();
@@ -811,6 +1225,40 @@ fn test_download_instance() {
     // tags.
 }
 
+#[test]
+fn test_upload_instance() {
+    let instance = find_instance_by_sop_instance_uid(SOP_INSTANCE_UID).unwrap();
+    let mut file = fs::File::create("/tmp/instance_upload.dcm").unwrap();
+    client().instance_dicom(&instance.id, &mut file).unwrap();
+    assert_result(
+        vec![
+            "-F",
+            "csv",
+            "instance",
+            "upload",
+            "/tmp/instance_upload.dcm",
+            "-n",
+        ],
+        CommandResult::new(
+            0,
+            include_str!("data/instance_upload.stdout").to_string(),
+            "".to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_upload_instance_error() {
+    assert_result(
+        vec!["-F", "csv", "instance", "upload", "/tmp/garble.dcm", "-n"],
+        CommandResult::new(
+            1,
+            include_str!("data/instance_upload_error.stdout").to_string(),
+            include_str!("data/instance_upload_error.stderr").to_string(),
+        ),
+    );
+}
+
 #[test]
 fn test_download_instance_error() {
     assert_result(
@@ -1213,6 +1661,47 @@ fn test_anonymize_study_no_customization() {
     );
 }
 
+#[test]
+fn test_anonymize_study_async() {
+    let study = find_study_by_study_instance_uid(STUDY_INSTANCE_UID).unwrap();
+    let res = run_command(vec![
+        "-F",
+        "csv",
+        "study",
+        "anonymize",
+        &study.id,
+        "--async",
+    ]);
+    assert_eq!(res.exit_code, 0);
+    let job_id = res
+        .stdout
+        .trim()
+        .strip_prefix("Job ID,")
+        .unwrap()
+        .to_string();
+
+    let res = run_command(vec!["-F", "json", "job", "wait", &job_id]);
+    assert_eq!(res.exit_code, 0);
+    let job: serde_json::Value = serde_json::from_str(&res.stdout).unwrap();
+    assert_eq!(job["ID"], job_id.as_str());
+    assert_eq!(job["State"], "Success");
+    assert_eq!(job["Progress"], 100);
+    assert!(res.stderr.contains("[####################] 100% Success"));
+
+    let res = run_command(vec!["-F", "csv", "job", "list", "-c", "ID", "State", "-n"]);
+    assert!(res.stdout.contains(&format!("{},Success\n", job_id)));
+
+    let new_study_id = job["Content"]["ID"].as_str().unwrap();
+    assert_result(
+        vec!["study", "show", new_study_id],
+        CommandResult::new(
+            0,
+            include_str!("data/study_show_anonymized_no_config.stdout").to_string(),
+            "".to_string(),
+        ),
+    );
+}
+
 #[test]
 fn test_anonymize_series_no_customization() {
     let series = find_series_by_series_instance_uid(SERIES_INSTANCE_UID).unwrap();
@@ -1328,6 +1817,51 @@ fn test_anonymize_patient_with_cmd_options() {
     );
 }
 
+#[test]
+fn test_anonymize_study_mapping_file() {
+    let _ = fs::remove_file("/tmp/study_mapping.csv");
+    fs::write("/tmp/pseudonym_salt", "s3cret\n").unwrap();
+    let study = find_study_by_study_instance_uid(STUDY_INSTANCE_UID).unwrap();
+    let res = run_command(vec![
+        "study",
+        "anonymize",
+        &study.id,
+        "--mapping-file",
+        "/tmp/study_mapping.csv",
+        "--pseudonym-salt-file",
+        "/tmp/pseudonym_salt",
+    ]);
+    assert_eq!(res.exit_code, 0);
+    let new_study_id = res.new_entity_id();
+
+    let mapping = fs::read_to_string("/tmp/study_mapping.csv").unwrap();
+    let rows: Vec<Vec<&str>> = mapping.lines().map(|l| l.split(',').collect()).collect();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(
+        rows[0][..4],
+        ["Timestamp", "Level", "Original ID", "New ID"]
+    );
+    assert_eq!(rows[1][1..4], ["Study", &study.id, &new_study_id]);
+    assert_eq!(rows[1][4], PATIENT_ID);
+    assert_eq!(rows[1][8], STUDY_INSTANCE_UID);
+    assert_ne!(rows[1][9], STUDY_INSTANCE_UID);
+
+    // The same patient always gets the same pseudonym
+    let pseudonym = rows[1][5];
+    assert_eq!(rows[1][7], pseudonym);
+    let res = run_command(vec![
+        "study",
+        "anonymize",
+        &study.id,
+        "--pseudonym-salt-file",
+        "/tmp/pseudonym_salt",
+        "--print-config",
+    ]);
+    assert!(res
+        .stdout
+        .contains(&format!("\"PatientID\": \"{}\"", pseudonym)));
+}
+
 #[test]
 fn test_anonymize_study_with_config() {
     let mut file = fs::File::create("/tmp/study_anon_config.yml").unwrap();
@@ -1481,9 +2015,10 @@ fn test_anonymize_instance_with_config() {
             .unwrap(),
         "Patient 2 " // TODO: Why is there a trailing space?
     );
-    assert!(
-        matches!(obj.element(Tag::from((0x1235, 0x0042))).unwrap_err(), DicomError::NoSuchDataElementTag{..})
-    );
+    assert!(matches!(
+        obj.element(Tag::from((0x1235, 0x0042))).unwrap_err(),
+        DicomError::NoSuchDataElementTag { .. }
+    ));
 }
 
 #[test]
@@ -1550,7 +2085,7 @@ fn test_anonymize_patient_config_file_not_found() {
 #[test]
 fn test_anonymize_patient_yaml_parse_error() {
     let mut file = fs::File::create("/tmp/mod_config.yml").unwrap();
-    file.write(b"garble").unwrap();
+    file.write_all(b"garble").unwrap();
     assert_result(
         vec![
             "patient",
@@ -1594,7 +2129,7 @@ fn test_anonymize_study_config_file_not_found() {
 #[test]
 fn test_anonymize_study_yaml_parse_error() {
     let mut file = fs::File::create("/tmp/mod_config.yml").unwrap();
-    file.write(b"garble").unwrap();
+    file.write_all(b"garble").unwrap();
     assert_result(
         vec!["study", "anonymize", "foobar", "-c", "/tmp/mod_config.yml"],
         CommandResult::new(
@@ -1632,7 +2167,7 @@ fn test_anonymize_series_config_file_not_found() {
 #[test]
 fn test_anonymize_series_yaml_parse_error() {
     let mut file = fs::File::create("/tmp/mod_config.yml").unwrap();
-    file.write(b"garble").unwrap();
+    file.write_all(b"garble").unwrap();
     assert_result(
         vec!["series", "anonymize", "foobar", "-c", "/tmp/mod_config.yml"],
         CommandResult::new(
@@ -1678,7 +2213,7 @@ fn test_anonymize_instance_config_file_not_found() {
 #[test]
 fn test_anonymize_instance_yaml_parse_error() {
     let mut file = fs::File::create("/tmp/mod_config.yml").unwrap();
-    file.write(b"garble").unwrap();
+    file.write_all(b"garble").unwrap();
     assert_result(
         vec![
             "instance",
@@ -1959,7 +2494,7 @@ fn test_modify_instance_config_cmdline() {
 #[test]
 fn test_modify_patient_error() {
     let mut file = fs::File::create("/tmp/mod_config.yml").unwrap();
-    file.write(b"remove:\nreplace:").unwrap();
+    file.write_all(b"remove:\nreplace:").unwrap();
     assert_result(
         vec!["patient", "modify", "foobar", "-c", "/tmp/mod_config.yml"],
         CommandResult::new(
@@ -1985,7 +2520,7 @@ fn test_modify_patient_config_file_not_found() {
 #[test]
 fn test_modify_patient_yaml_parse_error() {
     let mut file = fs::File::create("/tmp/mod_config.yml").unwrap();
-    file.write(b"garble").unwrap();
+    file.write_all(b"garble").unwrap();
     assert_result(
         vec!["patient", "modify", "foobar", "-c", "/tmp/mod_config.yml"],
         CommandResult::new(
@@ -1999,7 +2534,7 @@ fn test_modify_patient_yaml_parse_error() {
 #[test]
 fn test_modify_study_error() {
     let mut file = fs::File::create("/tmp/mod_config.yml").unwrap();
-    file.write(b"remove:\nreplace:").unwrap();
+    file.write_all(b"remove:\nreplace:").unwrap();
     assert_result(
         vec!["study", "modify", "foobar", "-c", "/tmp/mod_config.yml"],
         CommandResult::new(
@@ -2025,7 +2560,7 @@ fn test_modify_study_config_file_not_found() {
 #[test]
 fn test_modify_study_yaml_parse_error() {
     let mut file = fs::File::create("/tmp/mod_config.yml").unwrap();
-    file.write(b"garble").unwrap();
+    file.write_all(b"garble").unwrap();
     assert_result(
         vec!["study", "modify", "foobar", "-c", "/tmp/mod_config.yml"],
         CommandResult::new(
@@ -2039,7 +2574,7 @@ fn test_modify_study_yaml_parse_error() {
 #[test]
 fn test_modify_series_error() {
     let mut file = fs::File::create("/tmp/mod_config.yml").unwrap();
-    file.write(b"remove:\nreplace:").unwrap();
+    file.write_all(b"remove:\nreplace:").unwrap();
     assert_result(
         vec!["series", "modify", "foobar", "-c", "/tmp/mod_config.yml"],
         CommandResult::new(
@@ -2065,7 +2600,7 @@ fn test_modify_series_config_file_not_found() {
 #[test]
 fn test_modify_series_yaml_parse_error() {
     let mut file = fs::File::create("/tmp/mod_config.yml").unwrap();
-    file.write(b"garble").unwrap();
+    file.write_all(b"garble").unwrap();
     assert_result(
         vec!["series", "modify", "foobar", "-c", "/tmp/mod_config.yml"],
         CommandResult::new(
@@ -2079,7 +2614,7 @@ fn test_modify_series_yaml_parse_error() {
 #[test]
 fn test_modify_instance_error() {
     let mut file = fs::File::create("/tmp/mod_config.yml").unwrap();
-    file.write(b"remove:\nreplace:").unwrap();
+    file.write_all(b"remove:\nreplace:").unwrap();
     assert_result(
         vec![
             "instance",
@@ -2121,7 +2656,7 @@ fn test_modify_instance_config_file_not_found() {
 #[test]
 fn test_modify_instance_yaml_parse_error() {
     let mut file = fs::File::create("/tmp/mod_config.yml").unwrap();
-    file.write(b"garble").unwrap();
+    file.write_all(b"garble").unwrap();
     assert_result(
         vec![
             "instance",
@@ -2252,6 +2787,128 @@ fn test_delete_instance_error() {
     );
 }
 
+#[test]
+fn test_bulk_delete_dry_run() {
+    assert_result(
+        vec![
+            "delete",
+            "-l",
+            "study",
+            "-q",
+            &format!("StudyInstanceUID={}", STUDY_INSTANCE_UID),
+            "--dry-run",
+        ],
+        CommandResult::new(
+            0,
+            include_str!("data/delete_dry_run.stdout").to_string(),
+            "".to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_bulk_delete_not_confirmed() {
+    assert_result(
+        vec![
+            "delete",
+            "-l",
+            "study",
+            "-q",
+            &format!("StudyInstanceUID={}", STUDY_INSTANCE_UID),
+        ],
+        CommandResult::new(
+            1,
+            "".to_string(),
+            include_str!("data/delete_not_confirmed.stderr").to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_delete_study_stdin_not_confirmed() {
+    let study = find_study_by_study_instance_uid(STUDY_INSTANCE_UID).unwrap();
+    assert!(
+        run_command_with_stdin(vec!["study", "delete", "-"], &format!("{}\n", study.id))
+            == CommandResult::new(
+                1,
+                "".to_string(),
+                include_str!("data/delete_not_confirmed.stderr").to_string(),
+            )
+    );
+    assert!(find_study_by_study_instance_uid(STUDY_INSTANCE_UID).is_some());
+}
+
+#[test]
+fn test_anonymize_print_config() {
+    let study = find_study_by_study_instance_uid(STUDY_INSTANCE_UID).unwrap();
+    assert_result(
+        vec![
+            "study",
+            "anonymize",
+            &study.id,
+            "-P",
+            "retain-device-identity",
+            "clean-descriptions",
+            "-k",
+            "StudyDescription",
+            "--dicom-version",
+            "2021b",
+            "--print-config",
+        ],
+        CommandResult::new(
+            0,
+            include_str!("data/anonymize_print_config.stdout").to_string(),
+            "".to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_bulk_download() {
+    let _ = fs::remove_dir_all("/tmp/bulk_download");
+    let res = run_command(vec![
+        "download",
+        "-l",
+        "study",
+        "-q",
+        &format!("StudyInstanceUID={}", STUDY_INSTANCE_UID),
+        "-o",
+        "/tmp/bulk_download",
+        "-t",
+        "{PatientID}/{StudyDescription}.zip",
+        "-p",
+        "2",
+    ]);
+    assert_eq!(res.exit_code, 0);
+    assert_eq!(res.stderr, "");
+    assert!(res.stdout.contains("Downloaded"));
+    assert_eq!(
+        list_files(Path::new("/tmp/bulk_download")),
+        vec!["patient_2/Study 1.zip"]
+    );
+}
+
+#[test]
+fn test_bulk_download_failure() {
+    let res = run_command_with_stdin(
+        vec![
+            "download",
+            "-l",
+            "study",
+            "--ids-from",
+            "-",
+            "-o",
+            "/tmp/bulk_download_failure",
+        ],
+        "foobar\n",
+    );
+    assert_eq!(res.exit_code, 1);
+    assert_eq!(
+        res.stderr,
+        include_str!("data/bulk_download_not_found.stderr")
+    );
+}
+
 #[test]
 fn test_modalities() {
     // Get system info
@@ -2311,9 +2968,25 @@ fn test_modalities() {
         ],
         CommandResult::new(0, "".to_string(), "".to_string()),
     );
+    assert_result(
+        vec!["modality", "modify", "bar", "-m", "GE", "--deny", "C-GET"],
+        CommandResult::new(0, "".to_string(), "".to_string()),
+    );
 
     // Show
     if sysinfo.api_version > 6 {
+        assert_result(
+            vec![
+                "modality",
+                "modify",
+                "bar",
+                "--local-aet",
+                "LOCAL",
+                "--timeout",
+                "30",
+            ],
+            CommandResult::new(0, "".to_string(), "".to_string()),
+        );
         assert_result(
             vec!["modality", "show", "bar"],
             CommandResult::new(
@@ -2346,6 +3019,36 @@ fn test_modalities() {
             " Error   Modality bar not found \n".to_string(),
         ),
     );
+
+    // Create from file
+    let dir = tempfile::tempdir().unwrap();
+    let modalities_file = dir.path().join("modalities.yaml");
+    fs::write(
+        &modalities_file,
+        "baz:\n  AET: BAZ\n  Host: 9.8.7.6\n  Port: 1717\n  Manufacturer: GE\n  AllowGet: false\n",
+    )
+    .unwrap();
+    assert_result(
+        vec![
+            "modality",
+            "create",
+            "--from-file",
+            modalities_file.to_str().unwrap(),
+        ],
+        CommandResult::new(0, "".to_string(), "".to_string()),
+    );
+    assert_result(
+        vec!["modality", "list", "-c", "Name", "Manufacturer"],
+        CommandResult::new(
+            0,
+            include_str!("data/modality_list_from_file.stdout").to_string(),
+            "".to_string(),
+        ),
+    );
+    assert_result(
+        vec!["modality", "delete", "baz"],
+        CommandResult::new(0, "".to_string(), "".to_string()),
+    );
 }
 
 #[test]
@@ -2492,20 +3195,212 @@ fn test_modality_store_error() {
     );
 }
 
+#[test]
+fn test_modality_echo() {
+    let modality = Modality {
+        aet: env::var("DINO_SCP_AET").unwrap_or(DEFAULT_DINO_AET.to_string()),
+        host: DEFAULT_DINO_HOST.to_string(),
+        port: env::var("DINO_SCP_PORT")
+            .unwrap_or(DEFAULT_DINO_PORT.to_string())
+            .parse::<i32>()
+            .unwrap(),
+        manufacturer: None,
+        allow_c_echo: None,
+        allow_c_find: None,
+        allow_c_get: None,
+        allow_c_move: None,
+        allow_c_store: None,
+        allow_n_action: None,
+        allow_n_event_report: None,
+        allow_transcoding: None,
+    };
+    client().create_modality("dino", modality).unwrap();
+
+    assert_result(
+        vec!["modality", "echo", "dino"],
+        CommandResult::new(
+            0,
+            include_str!("data/modality_echo.stdout").to_string(),
+            "".to_string(),
+        ),
+    );
+    assert_result(
+        vec!["modality", "echo", "dino", "-t", "5"],
+        CommandResult::new(
+            0,
+            include_str!("data/modality_echo.stdout").to_string(),
+            "".to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_modality_echo_error() {
+    assert_result(
+        vec!["modality", "echo", "garble"],
+        CommandResult::new(
+            1,
+            "".to_string(),
+            include_str!("data/modality_delete_not_found_error.stderr").to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_modality_query_error() {
+    assert_result(
+        vec!["modality", "query", "garble", "PatientID=foo"],
+        CommandResult::new(
+            1,
+            "".to_string(),
+            include_str!("data/modality_delete_not_found_error.stderr").to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_modality_query_invalid_date_range() {
+    assert_result(
+        vec!["modality", "query", "garble", "StudyDate=20201231-20200101"],
+        CommandResult::new(
+            1,
+            "".to_string(),
+            include_str!("data/studies_search_invalid_date_range.stderr").to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_modality_retrieve_error() {
+    assert_result(
+        vec![
+            "modality",
+            "retrieve",
+            "garble",
+            "--study",
+            STUDY_INSTANCE_UID,
+        ],
+        CommandResult::new(
+            1,
+            "".to_string(),
+            include_str!("data/modality_delete_not_found_error.stderr").to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_peers() {
+    // Create
+    assert_result(
+        vec![
+            "peer",
+            "create",
+            "self",
+            "--url",
+            "http://localhost:8042/",
+            "-u",
+            "orthanc",
+            "-p",
+            "orthanc",
+        ],
+        CommandResult::new(0, "".to_string(), "".to_string()),
+    );
+
+    // List
+    assert_result(
+        vec!["peer", "list", "-c", "Name", "URL"],
+        CommandResult::new(
+            0,
+            include_str!("data/peer_list_columns.stdout").to_string(),
+            "".to_string(),
+        ),
+    );
+
+    // Modify
+    assert_result(
+        vec!["peer", "modify", "self", "--timeout", "30"],
+        CommandResult::new(
+            1,
+            "".to_string(),
+            include_str!("data/peer_modify_password_error.stderr").to_string(),
+        ),
+    );
+    assert_result(
+        vec!["peer", "modify", "self", "--timeout", "30", "-p", "orthanc"],
+        CommandResult::new(0, "".to_string(), "".to_string()),
+    );
+
+    // System
+    let system = run_command(vec!["-F", "json", "peer", "system", "self"]);
+    assert_eq!(system.exit_code, 0);
+    assert!(system.stdout.contains("\"ApiVersion\""));
+
+    // Delete
+    assert_result(
+        vec!["peer", "delete", "self"],
+        CommandResult::new(0, "".to_string(), "".to_string()),
+    );
+    assert_result(
+        vec!["peer", "show", "self"],
+        CommandResult::new(
+            1,
+            "".to_string(),
+            " Error   Peer self not found \n".to_string(),
+        ),
+    );
+}
+
 #[test]
 fn test_instance_tags() {
+    // The fixture only lists string tags, so the output is narrowed down to them
+    let expected = include_str!("data/instance_tags.stdout");
+    let id = find_instance_by_sop_instance_uid(SOP_INSTANCE_UID)
+        .unwrap()
+        .id;
+    let mut args = vec!["instance", "tags", &id, "--tag"];
+    args.extend(
+        expected
+            .lines()
+            .map(|l| l.split_whitespace().next().unwrap()),
+    );
+    assert_result(
+        args,
+        CommandResult::new(0, expected.to_string(), "".to_string()),
+    );
+}
+
+#[test]
+fn test_instance_tags_filter() {
     assert_result(
         vec![
+            "-F",
+            "csv",
             "instance",
             "tags",
             &find_instance_by_sop_instance_uid(SOP_INSTANCE_UID)
                 .unwrap()
                 .id,
+            "-t",
+            "StudyDate",
+            "0010,0010",
+            "PatientID",
         ],
         CommandResult::new(
             0,
-            include_str!("data/instance_tags.stdout").to_string(),
+            include_str!("data/instance_tags_filter.stdout").to_string(),
+            "".to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_instance_tags_invalid_group() {
+    assert_result(
+        vec!["instance", "tags", "foobar", "-g", "10"],
+        CommandResult::new(
+            1,
             "".to_string(),
+            include_str!("data/instance_tags_invalid_group.stderr").to_string(),
         ),
     );
 }
@@ -2588,6 +3483,39 @@ fn test_search_series() {
     );
 }
 
+#[test]
+fn test_search_series_level_patient() {
+    assert_result(
+        vec![
+            "series",
+            "search",
+            "-q",
+            "SeriesInstanceUID=1.3.46.670589.11.1.5.0.3724.2011072815265926000",
+            "--level",
+            "patient",
+            "-c",
+            "PatientID",
+        ],
+        CommandResult::new(
+            0,
+            include_str!("data/series_search_level_patient.stdout").to_string(),
+            "".to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_search_studies_invalid_date_range() {
+    assert_result(
+        vec!["study", "search", "-q", "StudyDate=20201231-20200101"],
+        CommandResult::new(
+            1,
+            "".to_string(),
+            include_str!("data/studies_search_invalid_date_range.stderr").to_string(),
+        ),
+    );
+}
+
 #[test]
 fn test_search_series_no_header() {
     assert_result(